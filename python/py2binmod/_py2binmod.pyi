@@ -1,7 +1,33 @@
+from typing import Literal
+
+
+class Py2BinmodError(RuntimeError):
+    code: str
+
+
+class ParserError(ValueError):
+    code: str
+
+
+class CompilerError(RuntimeError):
+    code: str
+
+
+class TemplateError(TypeError):
+    code: str
+
+
 async def transpile_command(
     project_dir: str,
     out_dir: str | None = None,
     stdout: bool = False,
+    ignore: list[str] | None = None,
+    verbosity: Literal["quiet", "normal", "verbose"] = "normal",
+    metadata: str | None = None,
+    bundle: bool = False,
+    include: list[str] | None = None,
+    emit_cargo_lock: bool = False,
+    explain: bool = False,
 ) -> None:
     ...
 
@@ -10,5 +36,105 @@ async def build_command(
     project_dir: str,
     out_dir: str | None = None,
     release: bool = False,
+    ignore: list[str] | None = None,
+    offline: bool = False,
+    locked: bool = False,
+    profile: str | None = None,
+    jobs: int | None = None,
+    verbosity: Literal["quiet", "normal", "verbose"] = "normal",
+    force: bool = False,
+    metadata: str | None = None,
+    rustflags: list[str] | None = None,
+    dry_run: bool = False,
+    assume_target: bool = False,
+    include: list[str] | None = None,
+    retries: int = 0,
+    retry_backoff_ms: int = 1000,
+    explain: bool = False,
+) -> None:
+    ...
+
+
+async def watch_command(
+    project_dir: str,
+    out_dir: str | None = None,
+    release: bool = False,
+    debounce_ms: int = 300,
+    ignore: list[str] | None = None,
+    metadata: str | None = None,
+    include: list[str] | None = None,
+    verbosity: Literal["quiet", "normal", "verbose"] = "normal",
+) -> None:
+    ...
+
+
+async def doctor_command(
+    project_dir: str,
+    verbosity: Literal["quiet", "normal", "verbose"] = "normal",
+) -> list[tuple[str, str]]:
+    ...
+
+
+async def init_command(
+    project_dir: str,
+    verbosity: Literal["quiet", "normal", "verbose"] = "normal",
+) -> None:
+    ...
+
+
+def transpile_command_sync(
+    project_dir: str,
+    out_dir: str | None = None,
+    stdout: bool = False,
+    ignore: list[str] | None = None,
+    verbosity: Literal["quiet", "normal", "verbose"] = "normal",
+    metadata: str | None = None,
+    bundle: bool = False,
+    include: list[str] | None = None,
+    emit_cargo_lock: bool = False,
+    explain: bool = False,
+) -> None:
+    ...
+
+
+def build_command_sync(
+    project_dir: str,
+    out_dir: str | None = None,
+    release: bool = False,
+    ignore: list[str] | None = None,
+    offline: bool = False,
+    locked: bool = False,
+    profile: str | None = None,
+    jobs: int | None = None,
+    verbosity: Literal["quiet", "normal", "verbose"] = "normal",
+    force: bool = False,
+    metadata: str | None = None,
+    rustflags: list[str] | None = None,
+    dry_run: bool = False,
+    assume_target: bool = False,
+    include: list[str] | None = None,
+    retries: int = 0,
+    retry_backoff_ms: int = 1000,
+    explain: bool = False,
+) -> None:
+    ...
+
+
+def doctor_command_sync(
+    project_dir: str,
+    verbosity: Literal["quiet", "normal", "verbose"] = "normal",
+) -> list[tuple[str, str]]:
+    ...
+
+
+def init_command_sync(
+    project_dir: str,
+    verbosity: Literal["quiet", "normal", "verbose"] = "normal",
+) -> None:
+    ...
+
+
+def configure_runtime(
+    worker_threads: int,
 ) -> None:
     ...
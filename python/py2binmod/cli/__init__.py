@@ -2,7 +2,7 @@ from typing import Annotated, Optional
 
 import typer
 
-from py2binmod.cli.commands import build_cli, transpile_cli
+from py2binmod.cli.commands import build_cli, doctor_cli, init_cli, transpile_cli, watch_cli
 from py2binmod.cli.utils import AsyncCLI, apply_decorators, catch_exceptions, show_version
 
 
@@ -13,6 +13,9 @@ cli = AsyncCLI(
 
 apply_decorators(build_cli, catch_exceptions(), cli.command(name="build"))
 apply_decorators(transpile_cli, catch_exceptions(), cli.command(name="transpile"))
+apply_decorators(watch_cli, catch_exceptions(), cli.command(name="watch"))
+apply_decorators(doctor_cli, catch_exceptions(), cli.command(name="doctor"))
+apply_decorators(init_cli, catch_exceptions(), cli.command(name="init"))
 
 @cli.callback()
 @catch_exceptions()
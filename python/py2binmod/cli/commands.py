@@ -3,7 +3,20 @@ from typing import Annotated
 
 import typer
 
-from py2binmod.core import build_command, transpile_command
+from py2binmod.core import (
+    build_command,
+    doctor_command,
+    init_command,
+    transpile_command,
+    watch_command,
+)
+
+
+def verbosity(quiet: bool, verbose: bool) -> str:
+    if quiet and verbose:
+        raise typer.BadParameter("--quiet and --verbose are mutually exclusive")
+
+    return "quiet" if quiet else "verbose" if verbose else "normal"
 
 
 async def transpile_cli(
@@ -21,6 +34,44 @@ async def transpile_cli(
         help="If set, print the generated Binmod module source code to stdout.",
         is_flag=True,
     )] = False,
+    ignore: Annotated[list[str] | None, typer.Option(
+        "--ignore",
+        help="Glob of files or directories to skip, may be repeated.",
+    )] = None,
+    include: Annotated[list[str] | None, typer.Option(
+        "--include",
+        help="Glob of module files to analyze, may be repeated. Others are skipped.",
+    )] = None,
+    metadata: Annotated[Path | None, typer.Option(
+        "--metadata",
+        help="Metadata file to read instead of the project's pyproject.toml.",
+    )] = None,
+    bundle: Annotated[bool, typer.Option(
+        "--bundle",
+        help="Emit every generated file in one stream, also written to bundle.txt with --out-dir.",
+        is_flag=True,
+    )] = False,
+    emit_cargo_lock: Annotated[bool, typer.Option(
+        "--emit-cargo-lock",
+        help="Pin the dependencies in a Cargo.lock, tracked by the generated .gitignore.",
+        is_flag=True,
+    )] = False,
+    explain: Annotated[bool, typer.Option(
+        "--explain",
+        help="Print how the module, import root and virtual environment were chosen.",
+        is_flag=True,
+    )] = False,
+    quiet: Annotated[bool, typer.Option(
+        "--quiet",
+        "-q",
+        help="Only print warnings, errors and results.",
+        is_flag=True,
+    )] = False,
+    verbose: Annotated[bool, typer.Option(
+        "--verbose",
+        help="Also warn about large site-packages the module never imports.",
+        is_flag=True,
+    )] = False,
 ) -> None:
     """
     Transpile a Binmod module from a Python project directory.
@@ -33,6 +84,13 @@ async def transpile_cli(
             else project_dir.joinpath("artifacts").resolve().absolute()
         ),
         stdout=stdout,
+        ignore=ignore,
+        include=include,
+        verbosity=verbosity(quiet, verbose),
+        metadata=str(metadata.resolve().absolute()) if metadata else None,
+        bundle=bundle,
+        emit_cargo_lock=emit_cargo_lock,
+        explain=explain,
     )
 
 
@@ -44,23 +102,224 @@ async def build_cli(
     out_dir: Annotated[Path | None, typer.Option(
         "--out-dir",
         "-o",
-        help="Directory to write the compiled Binmod module to.",
+        help="Cargo target directory to build the Binmod module in. Defaults to $PY2BINMOD_TARGET_DIR, the configured target-dir, then ./artifacts.",
     )] = None,
     release: Annotated[bool, typer.Option(
         "--release",
         help="Build the Binmod module in release mode.",
         is_flag=True,
     )] = False,
+    ignore: Annotated[list[str] | None, typer.Option(
+        "--ignore",
+        help="Glob of files or directories to skip, may be repeated.",
+    )] = None,
+    include: Annotated[list[str] | None, typer.Option(
+        "--include",
+        help="Glob of module files to analyze, may be repeated. Others are skipped.",
+    )] = None,
+    metadata: Annotated[Path | None, typer.Option(
+        "--metadata",
+        help="Metadata file to read instead of the project's pyproject.toml.",
+    )] = None,
+    offline: Annotated[bool, typer.Option(
+        "--offline",
+        help="Run cargo without accessing the network.",
+        is_flag=True,
+    )] = False,
+    locked: Annotated[bool, typer.Option(
+        "--locked",
+        help="Build against the project's Cargo.lock, failing if it would need to change.",
+        is_flag=True,
+    )] = False,
+    profile: Annotated[str | None, typer.Option(
+        "--profile",
+        help="Build with the given cargo profile instead of --release.",
+    )] = None,
+    jobs: Annotated[int | None, typer.Option(
+        "--jobs",
+        "-j",
+        min=1,
+        help="Number of parallel cargo jobs, defaults to the number of CPUs.",
+    )] = None,
+    quiet: Annotated[bool, typer.Option(
+        "--quiet",
+        "-q",
+        help="Only print warnings, errors and results.",
+        is_flag=True,
+    )] = False,
+    verbose: Annotated[bool, typer.Option(
+        "--verbose",
+        help="Print the full compiler output and warn about large site-packages the module never imports.",
+        is_flag=True,
+    )] = False,
+    force: Annotated[bool, typer.Option(
+        "--force",
+        help="Rebuild even if nothing changed since the last build.",
+        is_flag=True,
+    )] = False,
+    rustflag: Annotated[list[str] | None, typer.Option(
+        "--rustflag",
+        help="Flag passed to rustc, may be repeated, e.g. --rustflag=-Copt-level=z.",
+    )] = None,
+    dry_run: Annotated[bool, typer.Option(
+        "--dry-run",
+        help="Generate the crate and show what would be built without running cargo.",
+        is_flag=True,
+    )] = False,
+    assume_target: Annotated[bool, typer.Option(
+        "--assume-target",
+        help="Skip checking that the wasm32-wasip1 target is installed.",
+        is_flag=True,
+    )] = False,
+    retries: Annotated[int, typer.Option(
+        "--retries",
+        min=0,
+        help="Times to rerun cargo when it fails on a network error.",
+    )] = 0,
+    retry_backoff: Annotated[int, typer.Option(
+        "--retry-backoff",
+        min=0,
+        help="Milliseconds to wait before the first retry, doubled for each one after.",
+    )] = 1000,
+    explain: Annotated[bool, typer.Option(
+        "--explain",
+        help="Print how the module, import root and virtual environment were chosen.",
+        is_flag=True,
+    )] = False,
 ) -> None:
     """
     Build a Binmod module from a Python project directory.
     """
     await build_command(
         project_dir=str(project_dir.resolve().absolute()),
-        out_dir=str(
-            out_dir.resolve().absolute()
-            if out_dir
-            else project_dir.joinpath("artifacts").resolve().absolute()
-        ),
+        out_dir=str(out_dir.resolve().absolute()) if out_dir else None,
+        release=release,
+        ignore=ignore,
+        include=include,
+        offline=offline,
+        locked=locked,
+        profile=profile,
+        jobs=jobs,
+        verbosity=verbosity(quiet, verbose),
+        force=force,
+        rustflags=rustflag,
+        dry_run=dry_run,
+        assume_target=assume_target,
+        retries=retries,
+        retry_backoff_ms=retry_backoff,
+        explain=explain,
+        metadata=str(metadata.resolve().absolute()) if metadata else None,
+    )
+
+
+async def watch_cli(
+    typer_context: typer.Context,
+    project_dir: Annotated[Path, typer.Argument(
+        help="Path to the Python project directory.",
+    )] = Path.cwd(),
+    out_dir: Annotated[Path | None, typer.Option(
+        "--out-dir",
+        "-o",
+        help="Cargo target directory to build the Binmod module in. Defaults to $PY2BINMOD_TARGET_DIR, the configured target-dir, then ./artifacts.",
+    )] = None,
+    release: Annotated[bool, typer.Option(
+        "--release",
+        help="Build the Binmod module in release mode.",
+        is_flag=True,
+    )] = False,
+    debounce_ms: Annotated[int, typer.Option(
+        "--debounce",
+        help="Milliseconds to wait for further changes before rebuilding.",
+    )] = 300,
+    ignore: Annotated[list[str] | None, typer.Option(
+        "--ignore",
+        help="Glob of files or directories to skip, may be repeated.",
+    )] = None,
+    include: Annotated[list[str] | None, typer.Option(
+        "--include",
+        help="Glob of module files to analyze, may be repeated. Others are skipped.",
+    )] = None,
+    metadata: Annotated[Path | None, typer.Option(
+        "--metadata",
+        help="Metadata file to read instead of the project's pyproject.toml.",
+    )] = None,
+    quiet: Annotated[bool, typer.Option(
+        "--quiet",
+        "-q",
+        help="Only print warnings, errors and results.",
+        is_flag=True,
+    )] = False,
+    verbose: Annotated[bool, typer.Option(
+        "--verbose",
+        help="Print the full compiler output and warn about large site-packages the module never imports.",
+        is_flag=True,
+    )] = False,
+) -> None:
+    """
+    Rebuild a Binmod module whenever the Python sources change.
+    """
+    await watch_command(
+        project_dir=str(project_dir.resolve().absolute()),
+        out_dir=str(out_dir.resolve().absolute()) if out_dir else None,
         release=release,
+        debounce_ms=debounce_ms,
+        ignore=ignore,
+        include=include,
+        metadata=str(metadata.resolve().absolute()) if metadata else None,
+        verbosity=verbosity(quiet, verbose),
+    )
+
+
+async def doctor_cli(
+    typer_context: typer.Context,
+    project_dir: Annotated[Path, typer.Argument(
+        help="Path to the Python project directory.",
+    )] = Path.cwd(),
+    quiet: Annotated[bool, typer.Option(
+        "--quiet",
+        "-q",
+        help="Only print warnings, errors and results.",
+        is_flag=True,
+    )] = False,
+    verbose: Annotated[bool, typer.Option(
+        "--verbose",
+        help="Print more detail.",
+        is_flag=True,
+    )] = False,
+) -> None:
+    """
+    Check that the environment is ready to build a Binmod module.
+    """
+    failures = await doctor_command(
+        project_dir=str(project_dir.resolve().absolute()),
+        verbosity=verbosity(quiet, verbose),
+    )
+
+    if failures:
+        raise typer.Exit(1)
+
+
+async def init_cli(
+    typer_context: typer.Context,
+    project_dir: Annotated[Path, typer.Argument(
+        help="Path to the Python project directory.",
+    )] = Path.cwd(),
+    quiet: Annotated[bool, typer.Option(
+        "--quiet",
+        "-q",
+        help="Only print warnings, errors and results.",
+        is_flag=True,
+    )] = False,
+    verbose: Annotated[bool, typer.Option(
+        "--verbose",
+        help="Print more detail.",
+        is_flag=True,
+    )] = False,
+) -> None:
+    """
+    Detect the project layout and write it to the [tool.py2binmod] table.
+    """
+    await init_command(
+        project_dir=str(project_dir.resolve().absolute()),
+        verbosity=verbosity(quiet, verbose),
     )
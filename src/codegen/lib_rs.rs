@@ -1,26 +1,103 @@
-use proc_macro2::{TokenStream, Span};
+use proc_macro2::{TokenStream, Span, Literal};
 use quote::quote;
 use syn::Ident;
 
 use crate::{
-    types::{ProjectContext, ParameterType, ModuleFunction}, 
-    codegen::traits::{CodeGenerator, AsTokenStream},
+    types::{ProjectContext, Parameter, ParameterKind, ParameterType, ModuleFunction, HostFunction, StructDef, StructField, StructKind},
+    codegen::{traits::{CodeGenerator, AsTokenStream}, stdlib::stdlib_closure},
+    template::units::frozen::FROZEN_PACKAGES_DIR,
 };
 
 
+/// Python's own default recursion limit.
+pub const DEFAULT_RECURSION_LIMIT: usize = 1000;
+
+/// `#[cfg]` compiling an exported function only with one of `features`.
+pub(crate) fn feature_gate(features: &[&str]) -> Option<TokenStream> {
+    match features {
+        [] => None,
+        [feature] => Some(quote! { #[cfg(feature = #feature)] }),
+        _ => Some(quote! { #[cfg(any(#(feature = #features),*))] }),
+    }
+}
+
+/// Words Rust reserves that are valid Python names.
+const RUST_KEYWORDS: &[&str] = &[
+    "_", "abstract", "as", "async", "await", "become", "box", "break", "const", "continue",
+    "crate", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if",
+    "impl", "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv",
+    "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "try",
+    "type", "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// Rust identifiers for the fields of a struct, paired with whether they
+/// differ from the Python name. Characters other than ASCII letters, digits
+/// and `_` become `_`, keywords get a trailing `_`, and names colliding
+/// with another field get more.
+pub(crate) fn field_idents<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<(Ident, bool)> {
+    let names = names.into_iter().collect::<Vec<_>>();
+    let mut used = names
+        .iter()
+        .copied()
+        .filter(|name| !RUST_KEYWORDS.contains(name))
+        .map(str::to_string)
+        .collect::<std::collections::HashSet<_>>();
+
+    names
+        .into_iter()
+        .map(|name| {
+            let valid = !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                && !RUST_KEYWORDS.contains(&name);
+
+            if valid {
+                return (Ident::new(name, Span::call_site()), false);
+            }
+
+            let mut ident = name
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect::<String>();
+
+            if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+                ident.insert(0, '_');
+            }
+            while RUST_KEYWORDS.contains(&ident.as_str()) || used.contains(&ident) {
+                ident.push('_');
+            }
+
+            used.insert(ident.clone());
+            (Ident::new(&ident, Span::call_site()), true)
+        })
+        .collect()
+}
+
 pub struct LibRsGenerator {
     context: ProjectContext,
 }
 
 impl LibRsGenerator {
-    pub fn new(context: ProjectContext) -> Self {
+    pub fn new(mut context: ProjectContext) -> Self {
+        context.sort_modules();
         Self { context }
     }
 
+    /// Lints the generated code trips regardless of the project:
+    /// - `non_snake_case`: shims and parameters keep their Python names.
+    /// - `unused_imports`: the imports are fixed, but e.g. `FuncArgs` is only
+    ///   used by shims with keyword-only or variadic parameters.
+    /// - `dead_code`: helpers like `py_new` are emitted whether used or not.
+    fn generate_attributes(&self) -> TokenStream {
+        quote! {
+            #![allow(non_snake_case, unused_imports, dead_code)]
+        }
+    }
+
     fn generate_imports(&self) -> TokenStream {
         quote! {
             use serde_json::value::Serializer;
-            use serde::{Serialize, de::DeserializeOwned};
+            use serde::{Serialize, Deserialize, de::DeserializeOwned};
             use rustpython_vm::{
                 Interpreter,
                 VirtualMachine,
@@ -29,8 +106,9 @@ impl LibRsGenerator {
                 AsObject,
                 py_freeze,
                 pymodule,
-                builtins::PyBaseExceptionRef,
+                builtins::{PyBaseExceptionRef, PyDict, PyDictRef},
                 convert::ToPyObject,
+                function::{FuncArgs, KwArgs},
                 py_serde::{serialize, deserialize},
             };
             use rustpython_stdlib::get_module_inits;
@@ -56,12 +134,20 @@ impl LibRsGenerator {
                 Ok(py_obj)
             }
 
-            fn py_to_rs<T: DeserializeOwned>(vm: &VirtualMachine, obj: PyObjectRef) -> FnResult<T> {
-                let serialized = serialize(vm, obj.as_object(), Serializer)
+            /// Serialize a Python object as JSON: `None` becomes `null`, `bool`,
+            /// `int` and `float` their JSON counterparts, `str` a string, lists
+            /// and tuples arrays, and dicts objects. Dict keys must be strings,
+            /// anything else fails to serialize.
+            fn py_to_json(vm: &VirtualMachine, obj: PyObjectRef) -> FnResult<serde_json::Value> {
+                serialize(vm, obj.as_object(), Serializer)
                     .map_err(|exc| ModuleFnErr {
                         error_type: "SerializationError".to_string(),
                         message: format!("Failed to serialize: {}", exc),
-                    })?;
+                    })
+            }
+
+            fn py_to_rs<T: DeserializeOwned>(vm: &VirtualMachine, obj: PyObjectRef) -> FnResult<T> {
+                let serialized = py_to_json(vm, obj)?;
                 let deserialized = serde_json::from_value::<T>(serialized)
                     .map_err(|exc| ModuleFnErr {
                         error_type: "DeserializationError".to_string(),
@@ -72,6 +158,77 @@ impl LibRsGenerator {
             }
 
 
+            fn py_new(vm: &VirtualMachine, module: &str, class: &str, value: PyObjectRef) -> FnResult<PyObjectRef> {
+                vm.import(module, 0)
+                    .and_then(|py_module| py_module.get_attr(class, vm))
+                    .and_then(|py_class| py_class.call((value,), vm))
+                    .map_err(|exc| from_py_exc(vm, exc))
+            }
+
+
+            /// The items of a Python iterable, which must hold `len` of them
+            /// when given.
+            fn py_items(vm: &VirtualMachine, value: PyObjectRef, len: Option<usize>) -> FnResult<Vec<PyObjectRef>> {
+                let items = value
+                    .try_into_value::<Vec<PyObjectRef>>(vm)
+                    .map_err(|exc| from_py_exc(vm, exc))?;
+
+                match len {
+                    Some(len) if items.len() != len => Err(ModuleFnErr {
+                        error_type: "TypeError".to_string(),
+                        message: format!("Expected {} items, got {}", len, items.len()),
+                    }),
+                    _ => Ok(items),
+                }
+            }
+
+            fn py_dict(value: PyObjectRef) -> FnResult<PyDictRef> {
+                value
+                    .downcast::<PyDict>()
+                    .map_err(|value| ModuleFnErr {
+                        error_type: "TypeError".to_string(),
+                        message: format!("Expected a dict, got {}", value.class()),
+                    })
+            }
+
+
+            /// Instantiate a struct class from its fields, by name.
+            fn py_struct(vm: &VirtualMachine, module: &str, class: &str, fields: Vec<(String, PyObjectRef)>) -> FnResult<PyObjectRef> {
+                vm.import(module, 0)
+                    .and_then(|py_module| py_module.get_attr(class, vm))
+                    .and_then(|py_class| py_class.call(FuncArgs::new(Vec::new(), KwArgs::from_iter(fields)), vm))
+                    .map_err(|exc| from_py_exc(vm, exc))
+            }
+
+
+            /// A Python `complex`, serialized as `{ "re": ..., "im": ... }`.
+            #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+            pub struct Complex {
+                pub re: f64,
+                pub im: f64,
+            }
+
+            fn complex_to_py(vm: &VirtualMachine, value: Complex) -> FnResult<PyObjectRef> {
+                vm.builtins
+                    .get_attr("complex", vm)
+                    .and_then(|py_class| py_class.call((value.re, value.im), vm))
+                    .map_err(|exc| from_py_exc(vm, exc))
+            }
+
+            fn complex_from_py(vm: &VirtualMachine, value: PyObjectRef) -> FnResult<PyObjectRef> {
+                let dict = vm.ctx.new_dict();
+
+                for (key, attr) in [("re", "real"), ("im", "imag")] {
+                    value
+                        .get_attr(attr, vm)
+                        .and_then(|part| dict.set_item(key, part, vm))
+                        .map_err(|exc| from_py_exc(vm, exc))?;
+                }
+
+                Ok(dict.into())
+            }
+
+
             pub fn from_py_exc(vm: &VirtualMachine, exc: PyBaseExceptionRef) -> ModuleFnErr {
                 let mut buffer = String::new();
                 vm
@@ -94,17 +251,65 @@ impl LibRsGenerator {
     }
 
     fn generate_globals(&self) -> TokenStream {
-        let module_dir_str = self.context.module_root.parent().unwrap().to_string_lossy();
-        let site_packages_dir_str = self.context.site_packages_dir.to_string_lossy();
+        let module_dir_str = self.context.import_root().to_string_lossy();
+        let site_packages_dirs = self.context
+            .site_packages_dirs()
+            .into_iter()
+            .map(|dir| dir.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        // Selected packages are copied into the crate, relative paths resolve
+        // against its manifest directory.
+        let freeze_site_packages = match &self.context.frozen_packages {
+            None => quote! { #(vm.add_frozen(py_freeze!(dir = #site_packages_dirs));)* },
+            Some(packages) if packages.is_empty() => quote! {},
+            Some(_) => quote! { vm.add_frozen(py_freeze!(dir = #FROZEN_PACKAGES_DIR)); },
+        };
+        let recursion_limit = self.context.metadata.py2binmod
+            .as_ref()
+            .and_then(|c| c.recursion_limit)
+            .unwrap_or(DEFAULT_RECURSION_LIMIT);
+        // Modules match by name, by their private `_name` twin and by their
+        // submodules, so `json` also registers `_json` and `json.decoder`.
+        // The stdlib modules they import are registered along with them.
+        let (stdlib_filter, native_modules, frozen_stdlib) = match self.context.metadata.py2binmod
+            .as_ref()
+            .and_then(|c| c.stdlib.as_ref())
+        {
+            None => (quote! {}, quote! { get_module_inits() }, quote! { FROZEN_STDLIB }),
+            Some(modules) => {
+                let modules = stdlib_closure(modules, &self.context.site_packages_dirs());
+
+                (
+                    quote! {
+                        const STDLIB_MODULES: &[&str] = &[#(#modules),*];
+
+                        fn is_stdlib_module_enabled(name: &str) -> bool {
+                            let name = name.strip_prefix('_').unwrap_or(name);
+
+                            STDLIB_MODULES.iter().any(|module| {
+                                name == *module || name
+                                    .strip_prefix(module)
+                                    .is_some_and(|rest| rest.starts_with('.'))
+                            })
+                        }
+                    },
+                    quote! { get_module_inits().filter(|(name, _)| is_stdlib_module_enabled(name)) },
+                    quote! { FROZEN_STDLIB.into_iter().filter(|(name, _)| is_stdlib_module_enabled(name)) },
+                )
+            },
+        };
 
         quote! {
+            #stdlib_filter
+
             thread_local! {
                 static INTERPRETER: Interpreter = Interpreter::with_init(Default::default(), |vm| {
-                    vm.add_native_modules(get_module_inits());
+                    vm.recursion_limit.set(#recursion_limit);
+                    vm.add_native_modules(#native_modules);
                     vm.add_native_module("hostfns", Box::new(hostfns::make_module));
-                    vm.add_frozen(FROZEN_STDLIB);
+                    vm.add_frozen(#frozen_stdlib);
                     vm.add_frozen(py_freeze!(dir = #module_dir_str));
-                    vm.add_frozen(py_freeze!(dir = #site_packages_dir_str));
+                    #freeze_site_packages
                 });
             }
         }
@@ -120,57 +325,118 @@ impl LibRsGenerator {
             None => return quote! {},
         };
 
-        let namespace = &host_functions.namespace;
+        let extern_fn = |f: &HostFunction| {
+            let name: Ident = Ident::new(&f.name, Span::call_site());
+            let params = f.parameters
+                .iter()
+                .map(|p| p.as_token_stream());
+            let return_type = f.return_type.as_token_stream();
 
-        let extern_fns = host_functions
-            .iter()
-            .map(|f| {
-                let name: Ident = Ident::new(&f.name, Span::call_site());
-                let params = f.parameters
-                    .iter()
-                    .map(|p| p.as_token_stream());
-                let return_type = f.return_type.as_token_stream();
+            quote! {
+                fn #name(#(#params),*) -> #return_type;
+            }
+        };
 
-                quote! {
-                    fn #name(#(#params),*) -> #return_type;
-                }
-            });
+        let wrapper = |f: &HostFunction| {
+            let fn_name = Ident::new(&format!("{}_wrapper", &f.name), Span::call_site());
+            let fn_name_str = &f.name;
+            let host_fn_name = Ident::new(&f.name, Span::call_site());
+            // Primitives are converted by the pyfunction itself, other
+            // values are taken as objects and deserialized like the
+            // results of exported functions, then passed to the host as
+            // the Rust type it declares. Variadic parameters arrive as a
+            // list or dict of their items.
+            let (params, conversions): (Vec<_>, Vec<_>) = f.parameters
+                .iter()
+                .map(|p| {
+                    let name = Ident::new(&p.name, Span::call_site());
+                    let item_type = p.type_hint.as_token_stream();
+                    let rust_type = match p.kind {
+                        ParameterKind::VarPositional => quote! { Vec<#item_type> },
+                        ParameterKind::VarKeyword => quote! { std::collections::HashMap<String, #item_type> },
+                        _ => item_type,
+                    };
+                    let type_hint = match p.kind {
+                        ParameterKind::VarPositional => ParameterType::List(Box::new(p.type_hint.clone())),
+                        ParameterKind::VarKeyword => ParameterType::Map {
+                            key_type: Box::new(ParameterType::String),
+                            value_type: Box::new(p.type_hint.clone()),
+                        },
+                        _ => p.type_hint.clone(),
+                    };
+                    let value = match &type_hint {
+                        ParameterType::String
+                        | ParameterType::Integer
+                        | ParameterType::Float
+                        | ParameterType::Boolean => return (p.as_token_stream(), quote! {}),
+                        ParameterType::Any | ParameterType::Unrecognized(_) => quote! { py_to_json(vm, #name) },
+                        type_hint if self.needs_conversion(type_hint) => {
+                            let value = self.convert_from_py(type_hint, quote! { #name });
 
-        let wrappers = host_functions
-            .iter()
-            .map(|f| {
-                let fn_name = Ident::new(&format!("{}_wrapper", &f.name), Span::call_site());
-                let fn_name_str = &f.name;
-                let host_fn_name = Ident::new(&f.name, Span::call_site());
-                let params = f.parameters
-                    .iter()
-                    .map(|p| p.as_token_stream());
-                let param_names = f.parameters
-                    .iter()
-                    .filter_map(|p| p.as_token_stream().to_string()
-                        .split(':')
-                        .next()
-                        .map(|s| Ident::new(s.trim(), Span::call_site()))
-                    );
-                let return_type = f.return_type.as_token_stream();
-
-                match params.len() {
-                    0 => quote! {
-                        #[pyfunction(name = #fn_name_str)]
-                        fn #fn_name(vm: &VirtualMachine) -> PyResult<#return_type> {
-                            unsafe { #host_fn_name() }
-                                .map_err(|err| to_py_exc(vm, err))
-                        }
-                    },
-                    _ => quote! {
-                        #[pyfunction(name = #fn_name_str)]
-                        fn #fn_name(#(#params),*, vm: &VirtualMachine) -> PyResult<#return_type> {
-                            unsafe { #host_fn_name(#(#param_names),*) }
-                                .map_err(|err| to_py_exc(vm, err))
-                        }
-                    },
+                            quote! { (|| py_to_rs::<#rust_type>(vm, #value))() }
+                        },
+                        _ => quote! { py_to_rs::<#rust_type>(vm, #name) },
+                    };
+
+                    (
+                        quote! { #name: PyObjectRef },
+                        quote! { let #name = #value.map_err(|err| to_py_exc(vm, err))?; },
+                    )
+                })
+                .unzip();
+            let param_names = f.parameters
+                .iter()
+                .map(|p| Ident::new(&p.name, Span::call_site()));
+            let call = quote! {
+                unsafe { #host_fn_name(#(#param_names),*) }
+                    .map_err(|err| to_py_exc(vm, err))
+            };
+            // The host returns the Rust value, which is converted explicitly
+            // rather than relying on the pyfunction's return conversion.
+            let body = match &f.return_type {
+                ParameterType::None => quote! {
+                    #call?;
+
+                    Ok(vm.ctx.none())
+                },
+                ParameterType::String
+                | ParameterType::Integer
+                | ParameterType::Float
+                | ParameterType::Boolean => quote! {
+                    let value = #call?;
+
+                    Ok(value.to_pyobject(vm))
+                },
+                return_type if self.needs_conversion(return_type) => {
+                    let value = self.convert_to_py(return_type, quote! { value });
+
+                    quote! {
+                        let value = #call?;
+
+                        (|| -> FnResult<PyObjectRef> { Ok(#value) })().map_err(|err| to_py_exc(vm, err))
+                    }
+                },
+                _ => quote! {
+                    let value = #call?;
+
+                    rs_to_py(vm, value).map_err(|err| to_py_exc(vm, err))
+                },
+            };
+
+            // Each parameter carries its own trailing comma so that `vm` is
+            // appended cleanly regardless of the parameter count.
+            quote! {
+                #[pyfunction(name = #fn_name_str)]
+                fn #fn_name(#(#params,)* vm: &VirtualMachine) -> PyResult<PyObjectRef> {
+                    #(#conversions)*
+                    #body
                 }
-            });
+            }
+        };
+
+        let namespace = &host_functions.namespace;
+        let extern_fns = host_functions.iter().map(extern_fn);
+        let wrappers = host_functions.iter().map(wrapper);
 
         quote! {
             #[host_fns(namespace = #namespace)]
@@ -184,18 +450,16 @@ impl LibRsGenerator {
 
                 #(#wrappers)*
             }
-
         }
-
     }
 
     fn generate_initialize(&self) -> TokenStream {
         let namespace = self.context
             .modules
             .iter()
-            .find(|module| module.host_functions.is_some())
-            .map(|module| module.host_functions.as_ref().unwrap().namespace.clone())
-            .unwrap_or("env".to_string());
+            .find_map(|module| module.host_functions.as_ref())
+            .map(|host_functions| host_functions.namespace.as_str())
+            .unwrap_or("env");
 
         quote! {
             #[mod_fn(name = "initialize")]
@@ -233,10 +497,7 @@ impl LibRsGenerator {
             .map(|(module, func)| self
                 .generate_exported_function_shim(
                     func, 
-                    &module.import_path(&self.context.module_root)
-                        .map(|s| format!("{}.{}", self.context.module_name, s))
-                        .unwrap_or_else(|| self.context.module_name.clone())
-                        .as_str(),
+                    &self.context.import_path(module),
                 )
             )
             .collect::<Vec<TokenStream>>();
@@ -252,10 +513,6 @@ impl LibRsGenerator {
         let docstring = func.docstring
             .as_deref()
             .unwrap_or("");
-        let parameters = func.parameters
-            .iter()
-            .map(|p| p.as_token_stream());
-        let return_type = func.return_type.as_token_stream();
 
         let body = match func.return_type {
             ParameterType::None => {
@@ -264,7 +521,7 @@ impl LibRsGenerator {
                     mod_fn_name,
                     import_path,
                     docstring,
-                    parameters
+                    func,
                 )
             }
             _ => {
@@ -273,135 +530,1626 @@ impl LibRsGenerator {
                     mod_fn_name,
                     import_path,
                     docstring,
-                    parameters,
-                    return_type
+                    func,
                 )
             }
         };
 
+        let gate = feature_gate(&self.context.features_of(&func.name));
+
         quote! {
+            #gate
             #body
         }
     }
 
-    fn generate_exported_function_shim_body(
-        &self,
-        fn_impl_name: Ident,
-        mod_fn_name: &str,
-        import_path: &str,
-        docstring: &str,
-        parameters: impl Iterator<Item = TokenStream>,
-        return_type: TokenStream,
-    ) -> TokenStream {
-        let params = parameters.collect::<Vec<TokenStream>>();
-        let param_names = params
+    /// One serde struct per struct class in the project. Fields with a
+    /// default are optional and left out when unset, so Python fills them in,
+    /// as are the keys a `TypedDict` doesn't require. Fields whose name isn't
+    /// a valid Rust identifier keep it as their serialized name. `Default`
+    /// isn't derived, not every field type has one.
+    fn generate_structs(&self) -> TokenStream {
+        let mut seen = std::collections::HashSet::new();
+        let structs = self.context.modules
             .iter()
-            .filter_map(|p| p.to_string()
-                .split(':')
-                .next()
-                .map(|s| Ident::new(s.trim(), Span::call_site()))
-            )
-            .collect::<Vec<Ident>>();
-        let call_args = match param_names.len() {
-            0 => quote! { () },
-            1 => {
-                let first = &param_names[0];
-                quote! { (rs_to_py(vm, #first)?,) }
+            .flat_map(|m| m.structs.iter())
+            .filter(|s| seen.insert(s.name.as_str()))
+            .map(|s| {
+                let name = Ident::new(&s.name, Span::call_site());
+                let idents = field_idents(s.fields.iter().map(|f| f.name.as_str()));
+                let conversions = self.generate_struct_conversions(s, &idents);
+                let fields = s.fields.iter().zip(idents).map(|(f, (field_name, renamed))| {
+                    let type_hint = f.type_hint.as_token_stream();
+                    let python_name = &f.name;
+                    let rename = renamed.then(|| quote! { #[serde(rename = #python_name)] });
+
+                    match (&f.type_hint, f.has_default) {
+                        (_, false) => quote! { #rename pub #field_name: #type_hint },
+                        (ParameterType::Optional(_), true) => quote! {
+                            #rename
+                            #[serde(default, skip_serializing_if = "Option::is_none")]
+                            pub #field_name: #type_hint
+                        },
+                        (_, true) => quote! {
+                            #rename
+                            #[serde(default, skip_serializing_if = "Option::is_none")]
+                            pub #field_name: Option<#type_hint>
+                        },
+                    }
+                });
+
+                quote! {
+                    #[derive(Serialize, Deserialize, Clone, Debug)]
+                    pub struct #name {
+                        #(#fields),*
+                    }
+
+                    #conversions
+                }
+            });
+
+        quote! {
+            #(#structs)*
+        }
+    }
+
+    /// `to_py` and `from_py` for a struct whose values need converting, doing
+    /// for each field what [`Self::convert_to_py`] and
+    /// [`Self::convert_from_py`] do for a parameter. A named tuple is
+    /// instantiated from its fields, a `TypedDict` built as a dict.
+    fn generate_struct_conversions(&self, s: &StructDef, idents: &[(Ident, bool)]) -> TokenStream {
+        if !self.needs_conversion(&ParameterType::Struct(s.name.clone())) {
+            return quote! {};
+        }
+
+        let name = Ident::new(&s.name, Span::call_site());
+        let push_fields = s.fields.iter().zip(idents).map(|(f, (field_name, _))| {
+            let python_name = &f.name;
+
+            match (&f.type_hint, f.has_default) {
+                (type_hint, false) => {
+                    let value = self.convert_to_py(type_hint, quote! { value.#field_name });
+
+                    quote! { fields.push((#python_name.to_string(), #value)); }
+                },
+                // Unset fields are left out, like when serializing.
+                (type_hint, true) => {
+                    let type_hint = match type_hint {
+                        ParameterType::Optional(inner) => inner,
+                        type_hint => type_hint,
+                    };
+                    let value = self.convert_to_py(type_hint, quote! { value });
+
+                    quote! {
+                        if let Some(value) = value.#field_name {
+                            fields.push((#python_name.to_string(), #value));
+                        }
+                    }
+                },
+            }
+        });
+        // Fields that may be left out are `Option`s on the Rust side.
+        let field_type = |f: &StructField| match &f.type_hint {
+            ParameterType::Optional(_) => f.type_hint.clone(),
+            type_hint if f.has_default => ParameterType::Optional(Box::new(type_hint.clone())),
+            type_hint => type_hint.clone(),
+        };
+
+        let (to_py, from_py) = match self.find_struct(&s.name) {
+            Some((StructDef { kind: StructKind::NamedTuple, .. }, import_path)) => {
+                let class = &s.name;
+                let len = Literal::usize_unsuffixed(s.fields.len());
+                let items = s.fields.iter().enumerate().map(|(index, f)| {
+                    let index = Literal::usize_unsuffixed(index);
+
+                    self.convert_from_py(&field_type(f), quote! { value[#index].clone() })
+                });
+
+                (
+                    quote! { py_struct(vm, #import_path, #class, fields) },
+                    quote! {
+                        let value = py_items(vm, value, Some(#len))?;
+
+                        Ok(PyObjectRef::from(vm.ctx.new_list(vec![#(#items),*])))
+                    },
+                )
+            },
+            _ => {
+                let converted = s.fields
+                    .iter()
+                    .filter(|f| self.needs_conversion(&f.type_hint))
+                    .map(|f| {
+                        let python_name = &f.name;
+                        let value = self.convert_from_py(&field_type(f), quote! { value });
+
+                        quote! { #python_name => #value, }
+                    });
+
+                (
+                    quote! {
+                        let dict = vm.ctx.new_dict();
+
+                        for (key, value) in fields {
+                            dict.set_item(key.as_str(), value, vm)
+                                .map_err(|exc| from_py_exc(vm, exc))?;
+                        }
+
+                        Ok(dict.into())
+                    },
+                    quote! {
+                        let dict = vm.ctx.new_dict();
+
+                        for (key, value) in py_dict(value)? {
+                            let value = match key.str(vm).map_err(|exc| from_py_exc(vm, exc))?.as_str() {
+                                #(#converted)*
+                                _ => value,
+                            };
+
+                            dict.set_item(&*key, value, vm)
+                                .map_err(|exc| from_py_exc(vm, exc))?;
+                        }
+
+                        Ok(dict.into())
+                    },
+                )
             },
-            _ => quote! { (#(rs_to_py(vm, #param_names)?),*) },
         };
 
         quote! {
-            #[doc = #docstring]
-            #[mod_fn(name = #mod_fn_name)]
-            pub fn #fn_impl_name(#(#params),*) -> FnResult<#return_type> {
-                INTERPRETER.with(|interpreter| {
-                    interpreter.enter(|vm| {
-                        Ok(
-                            py_to_rs::<#return_type>(
-                                vm,
-                                vm.import(#import_path, 0)
-                                    .map_err(|exc| from_py_exc(vm, exc))?
-                                    .get_attr(#mod_fn_name, vm)
-                                    .map_err(|exc| from_py_exc(vm, exc))?
-                                    .call(#call_args, vm)
-                                    .map_err(|exc| from_py_exc(vm, exc))?
-                            )?
-                        )
-                    })
-                })
+            impl #name {
+                fn to_py(vm: &VirtualMachine, value: Self) -> FnResult<PyObjectRef> {
+                    let mut fields: Vec<(String, PyObjectRef)> = Vec::new();
+
+                    #(#push_fields)*
+
+                    #to_py
+                }
+
+                fn from_py(vm: &VirtualMachine, value: PyObjectRef) -> FnResult<PyObjectRef> {
+                    #from_py
+                }
             }
         }
     }
 
-    fn generate_exported_function_shim_unit_body(
-        &self,
-        fn_impl_name: Ident,
-        mod_fn_name: &str,
-        import_path: &str,
-        docstring: &str,
-        parameters: impl Iterator<Item = TokenStream>,
-    ) -> TokenStream {
-        let params = parameters.collect::<Vec<TokenStream>>();
-        let param_names = params
+    /// The struct `name` and the import path of the module defining it.
+    fn find_struct(&self, name: &str) -> Option<(&StructDef, String)> {
+        self.context.modules
             .iter()
-            .filter_map(|p| p.to_string()
-                .split(':')
-                .next()
-                .map(|s| Ident::new(s.trim(), Span::call_site()))
+            .find_map(|m| m.structs
+                .iter()
+                .find(|s| s.name == name)
+                .map(|s| (s, self.context.import_path(m)))
             )
-            .collect::<Vec<Ident>>();
-        let call_args = match param_names.len() {
-            0 => quote! { () },
-            1 => {
-                let first = &param_names[0];
-                quote! { (rs_to_py(vm, #first)?,) }
-            },
-            _ => quote! { (#(rs_to_py(vm, #param_names)?),*) },
-        };
+    }
 
-        quote! {
-            #[doc = #docstring]
-            #[mod_fn(name = #mod_fn_name)]
-            pub fn #fn_impl_name(#(#params),*) -> FnResult<()> {
-                INTERPRETER.with(|interpreter| {
-                    interpreter.enter(|vm| {
-                        vm.import(#import_path, 0)
-                            .map_err(|exc| from_py_exc(vm, exc))?
-                            .get_attr(#mod_fn_name, vm)
-                            .map_err(|exc| from_py_exc(vm, exc))?
-                            .call(#call_args, vm)
-                            .map_err(|exc| from_py_exc(vm, exc))?;
+    /// Whether values of `type_hint` can't simply cross the boundary as the
+    /// JSON they serialize to: UUIDs, paths, complex numbers, sets and named
+    /// tuples, which Python expects as instances of their class, and
+    /// anything holding them, typed dicts included.
+    fn needs_conversion(&self, type_hint: &ParameterType) -> bool {
+        self.needs_conversion_within(type_hint, &mut std::collections::HashSet::new())
+    }
 
-                        Ok(())
-                    })
-                })
-            }
+    /// [`Self::needs_conversion`], skipping the structs in `visited` so that
+    /// self-referencing structs terminate.
+    fn needs_conversion_within<'a>(&'a self, type_hint: &'a ParameterType, visited: &mut std::collections::HashSet<&'a str>) -> bool {
+        match type_hint {
+            ParameterType::Uuid | ParameterType::Path | ParameterType::Complex => true,
+            ParameterType::Struct(class) => match self.find_struct(class) {
+                Some((StructDef { kind: StructKind::NamedTuple, .. }, _)) => true,
+                Some((s, _)) => visited.insert(class) && s.fields
+                    .iter()
+                    .any(|f| self.needs_conversion_within(&f.type_hint, visited)),
+                None => false,
+            },
+            // JSON has no sets, they'd reach Python as lists and couldn't be
+            // serialized back.
+            ParameterType::Set(_) => true,
+            ParameterType::List(inner)
+            | ParameterType::Optional(inner) => self.needs_conversion_within(inner, visited),
+            ParameterType::Tuple(items) => items.iter().any(|t| self.needs_conversion_within(t, visited)),
+            ParameterType::Map { key_type, value_type } => {
+                self.needs_conversion_within(key_type, visited) || self.needs_conversion_within(value_type, visited)
+            },
+            _ => false,
         }
     }
-}
 
-impl CodeGenerator for LibRsGenerator {
-    fn generate(&self) -> TokenStream {
-        let globals = self.generate_globals();
-        let imports = self.generate_imports();
-        let utils = self.generate_utils();
-        let host_functions = self.generate_host_functions();
-        let initialize = self.generate_initialize();
-        let exported_functions = self.generate_exported_functions();
+    /// An expression turning the Rust `value` of `type_hint` into a Python
+    /// object, converting the items of containers one by one when they need
+    /// it. Errors propagate with `?`.
+    fn convert_to_py(&self, type_hint: &ParameterType, value: TokenStream) -> TokenStream {
+        if !self.needs_conversion(type_hint) {
+            return quote! { rs_to_py(vm, #value)? };
+        }
 
-        quote! {
-            #imports
+        let items = |inner: &ParameterType| {
+            let item = self.convert_to_py(inner, quote! { value });
 
-            #utils
+            quote! {
+                #value
+                    .into_iter()
+                    .map(|value| -> FnResult<PyObjectRef> { Ok(#item) })
+                    .collect::<FnResult<Vec<_>>>()?
+            }
+        };
 
-            #globals
+        match type_hint {
+            ParameterType::Uuid => quote! { py_new(vm, "uuid", "UUID", rs_to_py(vm, #value)?)? },
+            ParameterType::Path => quote! { py_new(vm, "pathlib", "Path", rs_to_py(vm, #value)?)? },
+            ParameterType::Complex => quote! { complex_to_py(vm, #value)? },
+            ParameterType::Struct(class) => {
+                let class = Ident::new(class, Span::call_site());
 
-            #host_functions
+                quote! { #class::to_py(vm, #value)? }
+            },
+            ParameterType::List(inner) => {
+                let items = items(inner);
 
-            #initialize
+                quote! { PyObjectRef::from(vm.ctx.new_list(#items)) }
+            },
+            ParameterType::Set(inner) => {
+                let items = items(inner);
 
-            #exported_functions
+                quote! { py_new(vm, "builtins", "set", PyObjectRef::from(vm.ctx.new_list(#items)))? }
+            },
+            ParameterType::Tuple(inner) => {
+                let items = inner.iter().enumerate().map(|(index, t)| {
+                    let index = syn::Index::from(index);
+
+                    self.convert_to_py(t, quote! { value.#index })
+                });
+
+                quote! {
+                    {
+                        let value = #value;
+
+                        PyObjectRef::from(vm.ctx.new_tuple(vec![#(#items),*]))
+                    }
+                }
+            },
+            ParameterType::Map { key_type, value_type } => {
+                let key = self.convert_to_py(key_type, quote! { key });
+                let item = self.convert_to_py(value_type, quote! { value });
+
+                quote! {
+                    {
+                        let dict = vm.ctx.new_dict();
+
+                        for (key, value) in #value {
+                            dict.set_item(&*#key, #item, vm)
+                                .map_err(|exc| from_py_exc(vm, exc))?;
+                        }
+
+                        PyObjectRef::from(dict)
+                    }
+                }
+            },
+            ParameterType::Optional(inner) => {
+                let inner = self.convert_to_py(inner, quote! { value });
+
+                quote! {
+                    match #value {
+                        Some(value) => #inner,
+                        None => vm.ctx.none(),
+                    }
+                }
+            },
+            _ => quote! { rs_to_py(vm, #value)? },
         }
     }
-}
\ No newline at end of file
+
+    /// An expression turning the Python object `value` of `type_hint` into
+    /// one that serializes as the JSON of its Rust type: UUIDs and paths as
+    /// their string form, complex numbers as their real and imaginary parts,
+    /// inside containers and structs too. Errors propagate with `?`.
+    fn convert_from_py(&self, type_hint: &ParameterType, value: TokenStream) -> TokenStream {
+        if !self.needs_conversion(type_hint) {
+            return value;
+        }
+
+        match type_hint {
+            ParameterType::Uuid | ParameterType::Path => quote! {
+                #value
+                .str(vm)
+                .map_err(|exc| from_py_exc(vm, exc))?
+                .into()
+            },
+            ParameterType::Complex => quote! { complex_from_py(vm, #value)? },
+            ParameterType::Struct(class) => {
+                let class = Ident::new(class, Span::call_site());
+
+                quote! { #class::from_py(vm, #value)? }
+            },
+            ParameterType::List(inner) | ParameterType::Set(inner) => {
+                let item = self.convert_from_py(inner, quote! { value });
+
+                quote! {
+                    PyObjectRef::from(vm.ctx.new_list(py_items(vm, #value, None)?
+                        .into_iter()
+                        .map(|value| -> FnResult<PyObjectRef> { Ok(#item) })
+                        .collect::<FnResult<Vec<_>>>()?))
+                }
+            },
+            ParameterType::Tuple(inner) => {
+                let len = Literal::usize_unsuffixed(inner.len());
+                let items = inner.iter().enumerate().map(|(index, t)| {
+                    let index = Literal::usize_unsuffixed(index);
+
+                    self.convert_from_py(t, quote! { value[#index].clone() })
+                });
+
+                quote! {
+                    {
+                        let value = py_items(vm, #value, Some(#len))?;
+
+                        PyObjectRef::from(vm.ctx.new_list(vec![#(#items),*]))
+                    }
+                }
+            },
+            ParameterType::Map { key_type, value_type } => {
+                let key = self.convert_from_py(key_type, quote! { key });
+                let item = self.convert_from_py(value_type, quote! { value });
+
+                quote! {
+                    {
+                        let dict = vm.ctx.new_dict();
+
+                        for (key, value) in py_dict(#value)? {
+                            dict.set_item(&*#key, #item, vm)
+                                .map_err(|exc| from_py_exc(vm, exc))?;
+                        }
+
+                        PyObjectRef::from(dict)
+                    }
+                }
+            },
+            ParameterType::Optional(inner) => {
+                let inner = self.convert_from_py(inner, quote! { value });
+
+                quote! {
+                    {
+                        let value = #value;
+
+                        if vm.is_none(&value) { value } else { #inner }
+                    }
+                }
+            },
+            _ => value,
+        }
+    }
+
+    fn generate_call_args(&self, func: &ModuleFunction) -> TokenStream {
+        let convert = |p: &Parameter| {
+            let name = Ident::new(&p.name, Span::call_site());
+
+            self.convert_to_py(&p.type_hint, quote! { #name })
+        };
+
+        // Variadic parameters are converted item by item, like lists and dicts.
+        let find_kind = |kind: ParameterKind| func.parameters
+            .iter()
+            .find(|p| p.kind == kind)
+            .map(|p| (Ident::new(&p.name, Span::call_site()), self.convert_to_py(&p.type_hint, quote! { value })));
+        let var_positional = find_kind(ParameterKind::VarPositional);
+        let var_keyword = find_kind(ParameterKind::VarKeyword);
+
+        let (keyword, positional): (Vec<_>, Vec<_>) = func.parameters
+            .iter()
+            .filter(|p| !matches!(p.kind, ParameterKind::VarPositional | ParameterKind::VarKeyword))
+            .partition(|p| p.kind == ParameterKind::KeywordOnly);
+
+        // Keyword-only and variadic parameters can't be passed as a plain tuple.
+        if !keyword.is_empty() || var_positional.is_some() || var_keyword.is_some() {
+            let args = positional.into_iter().map(convert);
+            let kwarg_names = keyword.iter().map(|p| &p.name);
+            let kwargs = keyword.iter().map(|p| convert(p));
+
+            let args = match var_positional {
+                Some((var, item)) => quote! {
+                    [
+                        vec![#(#args),*],
+                        #var.into_iter()
+                            .map(|value| -> FnResult<PyObjectRef> { Ok(#item) })
+                            .collect::<FnResult<Vec<_>>>()?,
+                    ].concat()
+                },
+                None => quote! { vec![#(#args),*] },
+            };
+            let kwargs = match var_keyword {
+                Some((var, item)) => quote! {
+                    [#((#kwarg_names.to_string(), #kwargs)),*]
+                        .into_iter()
+                        .chain(
+                            #var.into_iter()
+                                .map(|(key, value)| -> FnResult<(String, PyObjectRef)> { Ok((key, #item)) })
+                                .collect::<FnResult<Vec<_>>>()?
+                        )
+                },
+                None => quote! { [#((#kwarg_names.to_string(), #kwargs)),*] },
+            };
+
+            return quote! {
+                FuncArgs::new(
+                    #args,
+                    KwArgs::from_iter(#kwargs),
+                )
+            };
+        }
+
+        let args = positional
+            .into_iter()
+            .map(convert)
+            .collect::<Vec<TokenStream>>();
+
+        match args.len() {
+            0 => quote! { () },
+            1 => {
+                let first = &args[0];
+                quote! { (#first,) }
+            },
+            _ => quote! { (#(#args),*) },
+        }
+    }
+
+    fn generate_exported_function_shim_body(
+        &self,
+        fn_impl_name: Ident,
+        mod_fn_name: &str,
+        import_path: &str,
+        docstring: &str,
+        func: &ModuleFunction,
+    ) -> TokenStream {
+        let params = func.parameters
+            .iter()
+            .map(|p| p.as_token_stream())
+            .collect::<Vec<TokenStream>>();
+        let call_args = self.generate_call_args(func);
+
+        let result = quote! {
+            vm.import(#import_path, 0)
+                .map_err(|exc| from_py_exc(vm, exc))?
+                .get_attr(#mod_fn_name, vm)
+                .map_err(|exc| from_py_exc(vm, exc))?
+                .call(#call_args, vm)
+                .map_err(|exc| from_py_exc(vm, exc))?
+        };
+        let result = self.convert_from_py(&func.return_type, result);
+        let return_type = func.return_type.as_token_stream();
+        // Untyped results are returned as the JSON they serialize to, without
+        // deserializing them again.
+        let result = match func.return_type {
+            ParameterType::Any | ParameterType::Unrecognized(_) => quote! { py_to_json(vm, #result)? },
+            _ => quote! { py_to_rs::<#return_type>(vm, #result)? },
+        };
+
+        quote! {
+            #[doc = #docstring]
+            #[mod_fn(name = #mod_fn_name)]
+            pub fn #fn_impl_name(#(#params),*) -> FnResult<#return_type> {
+                INTERPRETER.with(|interpreter| {
+                    interpreter.enter(|vm| {
+                        Ok(
+                            #result
+                        )
+                    })
+                })
+            }
+        }
+    }
+
+    fn generate_exported_function_shim_unit_body(
+        &self,
+        fn_impl_name: Ident,
+        mod_fn_name: &str,
+        import_path: &str,
+        docstring: &str,
+        func: &ModuleFunction,
+    ) -> TokenStream {
+        let params = func.parameters
+            .iter()
+            .map(|p| p.as_token_stream())
+            .collect::<Vec<TokenStream>>();
+        let call_args = self.generate_call_args(func);
+
+        quote! {
+            #[doc = #docstring]
+            #[mod_fn(name = #mod_fn_name)]
+            pub fn #fn_impl_name(#(#params),*) -> FnResult<()> {
+                INTERPRETER.with(|interpreter| {
+                    interpreter.enter(|vm| {
+                        vm.import(#import_path, 0)
+                            .map_err(|exc| from_py_exc(vm, exc))?
+                            .get_attr(#mod_fn_name, vm)
+                            .map_err(|exc| from_py_exc(vm, exc))?
+                            .call(#call_args, vm)
+                            .map_err(|exc| from_py_exc(vm, exc))?;
+
+                        Ok(())
+                    })
+                })
+            }
+        }
+    }
+}
+
+impl CodeGenerator for LibRsGenerator {
+    fn generate(&self) -> TokenStream {
+        let attributes = self.generate_attributes();
+        let smoke_tests = self.context.metadata.py2binmod
+            .as_ref()
+            .is_some_and(|c| c.generate_tests)
+            .then(|| quote! {
+                #[cfg(test)]
+                mod smoke_tests;
+            });
+        let globals = self.generate_globals();
+        let imports = self.generate_imports();
+        let utils = self.generate_utils();
+        let structs = self.generate_structs();
+        let host_functions = self.generate_host_functions();
+        let initialize = self.generate_initialize();
+        let exported_functions = self.generate_exported_functions();
+
+        quote! {
+            #attributes
+
+            #imports
+
+            #utils
+
+            #structs
+
+            #globals
+
+            #host_functions
+
+            #initialize
+
+            #exported_functions
+
+            #smoke_tests
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::types::{
+        ProjectMetadata, Module, ModuleFunctions, HostFunction,
+        HostFunctions, Parameter, Py2BinmodConfig,
+    };
+
+    fn parameter(name: &str, type_hint: ParameterType) -> Parameter {
+        Parameter { name: name.into(), type_hint, kind: ParameterKind::PositionalOrKeyword }
+    }
+
+    fn mod_fn(name: &str, parameters: Vec<Parameter>, return_type: ParameterType) -> ModuleFunction {
+        ModuleFunction {
+            name: name.into(),
+            docstring: None,
+            parameters,
+            return_type,
+        }
+    }
+
+    fn shim_tokens(function: ModuleFunction) -> String {
+        LibRsGenerator::new(context(vec![]))
+            .generate_exported_function_shim(&function, "my_package")
+            .to_string()
+    }
+
+    fn metadata() -> ProjectMetadata {
+        ProjectMetadata {
+            name: "my_package".into(),
+            version: "0.1.0".into(),
+            requires_python: None,
+            description: None,
+            authors: vec![],
+            license: None,
+            license_file: None,
+            urls: Default::default(),
+            optional_dependencies: Default::default(),
+            py2binmod: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn context(modules: Vec<Module>) -> ProjectContext {
+        ProjectContext {
+            venv_dir: PathBuf::from("/project/venv"),
+            site_packages_dir: PathBuf::from("/project/venv/lib/python3.11/site-packages"),
+            platlib_dir: None,
+            project_dir: PathBuf::from("/project"),
+            module_root: PathBuf::from("/project/my_package"),
+            module_name: "my_package".into(),
+            metadata: metadata(),
+            modules,
+            frozen_packages: None,
+            workspace: None,
+            layout_trace: Vec::new(),
+            layout_warnings: Vec::new(),
+            type_warnings: Vec::new(),
+        }
+    }
+
+    fn host_module(functions: Vec<HostFunction>) -> Module {
+        Module {
+            name: "host".into(),
+            file_path: PathBuf::from("/project/my_package/host.py"),
+            module_functions: ModuleFunctions::default(),
+            host_functions: Some(HostFunctions::new("env".into(), functions)),
+            structs: vec![],
+        }
+    }
+
+    fn host_fn(name: &str, parameters: Vec<(&str, ParameterType)>) -> HostFunction {
+        HostFunction {
+            name: name.into(),
+            parameters: parameters
+                .into_iter()
+                .map(|(name, type_hint)| parameter(name, type_hint))
+                .collect(),
+            return_type: ParameterType::Integer,
+        }
+    }
+
+    fn host_functions_tokens(function: HostFunction) -> String {
+        LibRsGenerator::new(context(vec![host_module(vec![function])]))
+            .generate_host_functions()
+            .to_string()
+    }
+
+    #[test]
+    fn gated_functions_are_cfg_attributed() {
+        let mut context = context(vec![]);
+        context.metadata.py2binmod = Some(Py2BinmodConfig {
+            features: [
+                ("images".to_string(), vec!["resize".to_string()]),
+                ("full".to_string(), vec!["resize".to_string(), "greet".to_string()]),
+            ].into(),
+            ..Default::default()
+        });
+        let generator = LibRsGenerator::new(context);
+        let shim = |name: &str| generator
+            .generate_exported_function_shim(&mod_fn(name, vec![], ParameterType::None), "my_package")
+            .to_string();
+
+        assert!(shim("greet").starts_with(&quote! { #[cfg(feature = "full")] }.to_string()));
+        assert!(shim("resize").starts_with(&quote! { #[cfg(any(feature = "full", feature = "images"))] }.to_string()));
+        assert!(!shim("ping").contains("cfg"));
+    }
+
+    #[test]
+    fn host_function_without_parameters() {
+        let tokens = host_functions_tokens(host_fn("now", vec![]));
+
+        let expected = quote! {
+            #[host_fns(namespace = "env")]
+            unsafe extern "host" {
+                fn now() -> i64;
+            }
+
+            #[pymodule]
+            mod hostfns {
+                use super::*;
+
+                #[pyfunction(name = "now")]
+                fn now_wrapper(vm: &VirtualMachine) -> PyResult<PyObjectRef> {
+                    let value = unsafe { now() }
+                        .map_err(|err| to_py_exc(vm, err))?;
+
+                    Ok(value.to_pyobject(vm))
+                }
+            }
+        };
+
+        assert_eq!(tokens, expected.to_string());
+    }
+
+    #[test]
+    fn host_function_with_one_parameter() {
+        let tokens = host_functions_tokens(host_fn("double", vec![("x", ParameterType::Integer)]));
+
+        let expected = quote! {
+            #[host_fns(namespace = "env")]
+            unsafe extern "host" {
+                fn double(x: i64) -> i64;
+            }
+
+            #[pymodule]
+            mod hostfns {
+                use super::*;
+
+                #[pyfunction(name = "double")]
+                fn double_wrapper(x: i64, vm: &VirtualMachine) -> PyResult<PyObjectRef> {
+                    let value = unsafe { double(x) }
+                        .map_err(|err| to_py_exc(vm, err))?;
+
+                    Ok(value.to_pyobject(vm))
+                }
+            }
+        };
+
+        assert_eq!(tokens, expected.to_string());
+    }
+
+    #[test]
+    fn host_function_with_two_parameters() {
+        let tokens = host_functions_tokens(host_fn(
+            "add",
+            vec![("a", ParameterType::Integer), ("b", ParameterType::Integer)],
+        ));
+
+        let expected = quote! {
+            #[host_fns(namespace = "env")]
+            unsafe extern "host" {
+                fn add(a: i64, b: i64) -> i64;
+            }
+
+            #[pymodule]
+            mod hostfns {
+                use super::*;
+
+                #[pyfunction(name = "add")]
+                fn add_wrapper(a: i64, b: i64, vm: &VirtualMachine) -> PyResult<PyObjectRef> {
+                    let value = unsafe { add(a, b) }
+                        .map_err(|err| to_py_exc(vm, err))?;
+
+                    Ok(value.to_pyobject(vm))
+                }
+            }
+        };
+
+        assert_eq!(tokens, expected.to_string());
+    }
+
+    #[test]
+    fn host_functions_convert_primitive_returns() {
+        for (return_type, rust_type) in [
+            (ParameterType::Boolean, quote! { bool }),
+            (ParameterType::Integer, quote! { i64 }),
+            (ParameterType::Float, quote! { f64 }),
+            (ParameterType::String, quote! { String }),
+        ] {
+            let tokens = host_functions_tokens(HostFunction {
+                return_type,
+                ..host_fn("get", vec![])
+            });
+
+            let expected = quote! {
+                #[host_fns(namespace = "env")]
+                unsafe extern "host" {
+                    fn get() -> #rust_type;
+                }
+
+                #[pymodule]
+                mod hostfns {
+                    use super::*;
+
+                    #[pyfunction(name = "get")]
+                    fn get_wrapper(vm: &VirtualMachine) -> PyResult<PyObjectRef> {
+                        let value = unsafe { get() }
+                            .map_err(|err| to_py_exc(vm, err))?;
+
+                        Ok(value.to_pyobject(vm))
+                    }
+                }
+            };
+
+            assert_eq!(tokens, expected.to_string());
+        }
+    }
+
+    #[test]
+    fn host_function_deserializes_compound_parameters() {
+        let tokens = host_functions_tokens(HostFunction {
+            return_type: ParameterType::List(Box::new(ParameterType::Integer)),
+            ..host_fn("sort", vec![
+                ("values", ParameterType::List(Box::new(ParameterType::Integer))),
+                ("reverse", ParameterType::Boolean),
+            ])
+        });
+
+        let expected = quote! {
+            #[host_fns(namespace = "env")]
+            unsafe extern "host" {
+                fn sort(values: Vec<i64>, reverse: bool) -> Vec<i64>;
+            }
+
+            #[pymodule]
+            mod hostfns {
+                use super::*;
+
+                #[pyfunction(name = "sort")]
+                fn sort_wrapper(values: PyObjectRef, reverse: bool, vm: &VirtualMachine) -> PyResult<PyObjectRef> {
+                    let values = py_to_rs::<Vec<i64> >(vm, values).map_err(|err| to_py_exc(vm, err))?;
+                    let value = unsafe { sort(values, reverse) }
+                        .map_err(|err| to_py_exc(vm, err))?;
+
+                    rs_to_py(vm, value).map_err(|err| to_py_exc(vm, err))
+                }
+            }
+        };
+
+        assert_eq!(tokens, expected.to_string());
+    }
+
+    #[test]
+    fn host_function_returning_none() {
+        let tokens = host_functions_tokens(HostFunction {
+            return_type: ParameterType::None,
+            ..host_fn("log", vec![("message", ParameterType::String)])
+        });
+
+        let expected = quote! {
+            #[pyfunction(name = "log")]
+            fn log_wrapper(message: String, vm: &VirtualMachine) -> PyResult<PyObjectRef> {
+                unsafe { log(message) }
+                    .map_err(|err| to_py_exc(vm, err))?;
+
+                Ok(vm.ctx.none())
+            }
+        };
+
+        assert!(tokens.contains(&expected.to_string()));
+    }
+
+    #[test]
+    fn shim_passes_parameters_in_order() {
+        let tokens = shim_tokens(mod_fn(
+            "add",
+            vec![parameter("a", ParameterType::Integer), parameter("b", ParameterType::Integer)],
+            ParameterType::Integer,
+        ));
+
+        assert!(tokens.contains(&quote! { pub fn add_shim(a: i64, b: i64) -> FnResult<i64> }.to_string()));
+        assert!(tokens.contains(&quote! { .call((rs_to_py(vm, a)?, rs_to_py(vm, b)?), vm) }.to_string()));
+    }
+
+    #[test]
+    fn shim_constructs_uuid_and_path_arguments() {
+        let tokens = shim_tokens(mod_fn(
+            "locate",
+            vec![parameter("id", ParameterType::Uuid), parameter("root", ParameterType::Path)],
+            ParameterType::Path,
+        ));
+
+        let expected_args = quote! {
+            .call((
+                py_new(vm, "uuid", "UUID", rs_to_py(vm, id)?)?,
+                py_new(vm, "pathlib", "Path", rs_to_py(vm, root)?)?
+            ), vm)
+        };
+        let expected_result = quote! {
+            .str(vm)
+            .map_err(|exc| from_py_exc(vm, exc))?
+            .into()
+        };
+
+        assert!(tokens.contains(&quote! { pub fn locate_shim(id: String, root: String) -> FnResult<String> }.to_string()));
+        assert!(tokens.contains(&expected_args.to_string()));
+        assert!(tokens.contains(&expected_result.to_string()));
+    }
+
+    #[test]
+    fn shim_converts_complex_arguments_and_result() {
+        let tokens = shim_tokens(mod_fn(
+            "conjugate",
+            vec![parameter("z", ParameterType::Complex)],
+            ParameterType::Complex,
+        ));
+
+        assert!(tokens.contains(&quote! { pub fn conjugate_shim(z: Complex) -> FnResult<Complex> }.to_string()));
+        assert!(tokens.contains(&quote! { .call((complex_to_py(vm, z)?,), vm) }.to_string()));
+        assert!(tokens.contains("py_to_rs :: < Complex > (vm , complex_from_py (vm , vm . import"));
+    }
+
+    #[test]
+    fn shim_converts_nested_complex_and_uuid_results() {
+        let tokens = shim_tokens(mod_fn(
+            "roots",
+            vec![],
+            ParameterType::List(Box::new(ParameterType::Complex)),
+        ));
+
+        assert!(tokens.contains("py_to_rs :: < Vec < Complex > > (vm , PyObjectRef :: from (vm . ctx . new_list (py_items (vm , vm . import"), "{tokens}");
+        assert!(tokens.contains(&quote! { .map(|value| -> FnResult<PyObjectRef> { Ok(complex_from_py(vm, value)?) }) }.to_string()), "{tokens}");
+        syn::parse_str::<syn::ItemFn>(&tokens).unwrap();
+
+        let tokens = shim_tokens(mod_fn(
+            "owners",
+            vec![],
+            ParameterType::Map {
+                key_type: Box::new(ParameterType::String),
+                value_type: Box::new(ParameterType::Uuid),
+            },
+        ));
+        let expected = quote! {
+            dict.set_item(&*key, value
+                .str(vm)
+                .map_err(|exc| from_py_exc(vm, exc))?
+                .into(), vm)
+        };
+
+        assert!(tokens.contains(&expected.to_string()), "{tokens}");
+        syn::parse_str::<syn::ItemFn>(&tokens).unwrap();
+    }
+
+    #[test]
+    fn host_functions_share_the_nested_conversions() {
+        let tokens = host_functions_tokens(HostFunction {
+            return_type: ParameterType::List(Box::new(ParameterType::Uuid)),
+            ..host_fn("resolve", vec![("paths", ParameterType::Optional(Box::new(ParameterType::Path)))])
+        });
+        let expected_param = quote! {
+            let paths = (|| py_to_rs::<Option<String> >(vm, {
+                let value = paths;
+
+                if vm.is_none(&value) { value } else { value
+                    .str(vm)
+                    .map_err(|exc| from_py_exc(vm, exc))?
+                    .into() }
+            }))().map_err(|err| to_py_exc(vm, err))?;
+        };
+        let expected_return = quote! {
+            .map(|value| -> FnResult<PyObjectRef> { Ok(py_new(vm, "uuid", "UUID", rs_to_py(vm, value)?)?) })
+        };
+
+        assert!(tokens.contains(&expected_param.to_string()), "{tokens}");
+        assert!(tokens.contains(&expected_return.to_string()), "{tokens}");
+        syn::parse_str::<syn::File>(&tokens).unwrap();
+    }
+
+    #[test]
+    fn shim_for_nested_optional_dict_values() {
+        let nested = || ParameterType::Map {
+            key_type: Box::new(ParameterType::String),
+            value_type: Box::new(ParameterType::Optional(Box::new(ParameterType::List(Box::new(ParameterType::Integer))))),
+        };
+        let tokens = LibRsGenerator::new(context(vec![]))
+            .generate_exported_function_shim(
+                &mod_fn("merge", vec![parameter("groups", nested())], ParameterType::Optional(Box::new(nested()))),
+                "my_package",
+            );
+
+        assert_eq!(tokens.to_string(), quote! {
+            #[doc = ""]
+            #[mod_fn(name = "merge")]
+            pub fn merge_shim(groups: std::collections::HashMap<String, Option<Vec<i64> > >) -> FnResult<Option<std::collections::HashMap<String, Option<Vec<i64> > > > > {
+                INTERPRETER.with(|interpreter| {
+                    interpreter.enter(|vm| {
+                        Ok(
+                            py_to_rs::<Option<std::collections::HashMap<String, Option<Vec<i64> > > > >(vm, vm.import("my_package", 0)
+                                .map_err(|exc| from_py_exc(vm, exc))?
+                                .get_attr("merge", vm)
+                                .map_err(|exc| from_py_exc(vm, exc))?
+                                .call((rs_to_py(vm, groups)?,), vm)
+                                .map_err(|exc| from_py_exc(vm, exc))?)?
+                        )
+                    })
+                })
+            }
+        }.to_string());
+        syn::parse2::<syn::ItemFn>(tokens).unwrap();
+
+        let tokens = shim_tokens(mod_fn(
+            "pick",
+            vec![
+                parameter("groups", nested()),
+                parameter("keys", ParameterType::Optional(Box::new(ParameterType::List(Box::new(ParameterType::Optional(Box::new(ParameterType::String))))))),
+            ],
+            ParameterType::List(Box::new(nested())),
+        ));
+
+        assert!(tokens.contains(&quote! { keys: Option<Vec<Option<String> > > }.to_string()), "{tokens}");
+        assert!(tokens.contains(&quote! { .call((rs_to_py(vm, groups)?, rs_to_py(vm, keys)?), vm) }.to_string()), "{tokens}");
+        syn::parse_str::<syn::ItemFn>(&tokens).unwrap();
+    }
+
+    #[test]
+    fn shim_returns_lists_of_tuples() {
+        let pair = ParameterType::Tuple(vec![Box::new(ParameterType::String), Box::new(ParameterType::Integer)]);
+        let tokens = shim_tokens(mod_fn(
+            "counts",
+            vec![parameter("words", ParameterType::List(Box::new(ParameterType::String)))],
+            ParameterType::List(Box::new(pair)),
+        ));
+
+        assert!(tokens.contains(&quote! { -> FnResult<Vec<(String, i64,)> > }.to_string()), "{tokens}");
+        assert!(tokens.contains(&quote! { py_to_rs::<Vec<(String, i64,)> > }.to_string()), "{tokens}");
+
+        // Python tuples serialize as JSON arrays, as do Rust tuples.
+        let json = serde_json::json!([["apple", 2], ["pear", 1]]);
+        let counts = serde_json::from_value::<Vec<(String, i64)>>(json.clone()).unwrap();
+
+        assert_eq!(counts, vec![("apple".to_string(), 2), ("pear".to_string(), 1)]);
+        assert_eq!(serde_json::to_value(&counts).unwrap(), json);
+        assert!(serde_json::from_value::<Vec<(String, i64)>>(serde_json::json!([["apple", 2, 3]])).is_err());
+    }
+
+    #[test]
+    fn single_element_tuples_keep_their_arity() {
+        let single = ParameterType::Tuple(vec![Box::new(ParameterType::String)]);
+
+        assert_eq!(single.as_token_stream().to_string(), quote! { (String,) }.to_string());
+        assert_eq!(ParameterType::Tuple(vec![]).as_token_stream().to_string(), quote! { () }.to_string());
+        assert_eq!(serde_json::from_value::<(String,)>(serde_json::json!(["a"])).unwrap(), ("a".to_string(),));
+    }
+
+    #[test]
+    fn shim_returns_any_as_json() {
+        let tokens = shim_tokens(mod_fn(
+            "lookup",
+            vec![parameter("key", ParameterType::String)],
+            ParameterType::Any,
+        ));
+
+        assert!(tokens.contains(&quote! { pub fn lookup_shim(key: String) -> FnResult<serde_json::Value> }.to_string()));
+        assert!(tokens.contains("Ok (py_to_json (vm , vm . import"));
+        assert!(!tokens.contains("py_to_rs"));
+    }
+
+    #[test]
+    fn exported_functions_are_ordered_deterministically() {
+        let module = |name: &str, functions: &[&str]| Module {
+            name: name.into(),
+            file_path: PathBuf::from(format!("/project/my_package/{name}.py")),
+            module_functions: ModuleFunctions::new(
+                functions
+                    .iter()
+                    .map(|f| mod_fn(f, vec![], ParameterType::Integer))
+                    .collect(),
+            ),
+            host_functions: None,
+            structs: vec![],
+        };
+        let generated = |modules: Vec<Module>| LibRsGenerator::new(context(modules))
+            .generate_exported_functions()
+            .to_string();
+
+        let first = generated(vec![
+            module("users", &["list_users", "get_user"]),
+            module("api", &["ping"]),
+            module("admin", &["reset", "audit"]),
+        ]);
+        let second = generated(vec![
+            module("api", &["ping"]),
+            module("admin", &["audit", "reset"]),
+            module("users", &["get_user", "list_users"]),
+        ]);
+
+        assert_eq!(first, second);
+
+        let order = ["audit_shim", "reset_shim", "ping_shim", "get_user_shim", "list_users_shim"]
+            .map(|name| first.find(name).unwrap());
+        assert!(order.is_sorted(), "{order:?}");
+    }
+
+    #[test]
+    fn generated_file_allows_expected_lints() {
+        let tokens = LibRsGenerator::new(context(vec![]))
+            .generate()
+            .to_string();
+
+        assert!(tokens.starts_with(&quote! { #![allow(non_snake_case, unused_imports, dead_code)] }.to_string()));
+        assert!(syn::parse_file(&tokens).is_ok());
+    }
+
+    #[test]
+    fn utils_define_complex_once() {
+        let tokens = LibRsGenerator::new(context(vec![]))
+            .generate()
+            .to_string();
+
+        assert_eq!(tokens.matches("pub struct Complex").count(), 1);
+    }
+
+    #[test]
+    fn shim_passes_keyword_only_parameters_by_name() {
+        let tokens = shim_tokens(mod_fn(
+            "greet",
+            vec![
+                parameter("name", ParameterType::String),
+                Parameter {
+                    kind: ParameterKind::KeywordOnly,
+                    ..parameter("punctuation", ParameterType::String)
+                },
+            ],
+            ParameterType::String,
+        ));
+
+        let expected_args = quote! {
+            .call(
+                FuncArgs::new(
+                    vec![rs_to_py(vm, name)?],
+                    KwArgs::from_iter([("punctuation".to_string(), rs_to_py(vm, punctuation)?)]),
+                ),
+                vm
+            )
+        };
+
+        assert!(tokens.contains(&expected_args.to_string()));
+    }
+
+    #[test]
+    fn shim_splats_variadic_parameters() {
+        let tokens = shim_tokens(mod_fn(
+            "log",
+            vec![
+                parameter("message", ParameterType::String),
+                Parameter {
+                    kind: ParameterKind::VarPositional,
+                    ..parameter("args", ParameterType::Integer)
+                },
+                Parameter {
+                    kind: ParameterKind::VarKeyword,
+                    ..parameter("kwargs", ParameterType::String)
+                },
+            ],
+            ParameterType::None,
+        ));
+
+        let expected_signature = quote! {
+            pub fn log_shim(
+                message: String,
+                args: Vec<i64>,
+                kwargs: std::collections::HashMap<String, String>
+            ) -> FnResult<()>
+        };
+        let expected_args = quote! {
+            FuncArgs::new(
+                [
+                    vec![rs_to_py(vm, message)?],
+                    args.into_iter()
+                        .map(|value| -> FnResult<PyObjectRef> { Ok(rs_to_py(vm, value)?) })
+                        .collect::<FnResult<Vec<_>>>()?,
+                ].concat(),
+                KwArgs::from_iter(
+                    []
+                        .into_iter()
+                        .chain(
+                            kwargs.into_iter()
+                                .map(|(key, value)| -> FnResult<(String, PyObjectRef)> { Ok((key, rs_to_py(vm, value)?)) })
+                                .collect::<FnResult<Vec<_>>>()?
+                        )
+                ),
+            )
+        };
+
+        assert!(tokens.contains(&expected_signature.to_string()));
+        assert!(tokens.contains(&expected_args.to_string()));
+    }
+
+    #[test]
+    fn shim_converts_sets_of_primitives() {
+        let tokens = shim_tokens(mod_fn(
+            "dedupe",
+            vec![parameter("ids", ParameterType::Set(Box::new(ParameterType::Integer)))],
+            ParameterType::Set(Box::new(ParameterType::Integer)),
+        ));
+        let expected_ids = quote! {
+            py_new(vm, "builtins", "set", PyObjectRef::from(vm.ctx.new_list(ids
+                .into_iter()
+                .map(|value| -> FnResult<PyObjectRef> { Ok(rs_to_py(vm, value)?) })
+                .collect::<FnResult<Vec<_>>>()?)))?
+        };
+
+        assert!(tokens.contains(&expected_ids.to_string()), "{tokens}");
+        assert!(tokens.contains("py_to_rs :: < std :: collections :: HashSet < i64 > > (vm , PyObjectRef :: from (vm . ctx . new_list (py_items (vm , vm . import"), "{tokens}");
+        syn::parse_str::<syn::ItemFn>(&tokens).unwrap();
+    }
+
+    #[test]
+    fn shim_converts_nested_uuid_path_and_complex_arguments() {
+        let tokens = shim_tokens(mod_fn(
+            "tag",
+            vec![
+                parameter("ids", ParameterType::List(Box::new(ParameterType::Uuid))),
+                parameter("roots", ParameterType::Map {
+                    key_type: Box::new(ParameterType::String),
+                    value_type: Box::new(ParameterType::Optional(Box::new(ParameterType::Path))),
+                }),
+                parameter("pair", ParameterType::Tuple(vec![Box::new(ParameterType::Integer), Box::new(ParameterType::Complex)])),
+                Parameter {
+                    kind: ParameterKind::VarPositional,
+                    ..parameter("more", ParameterType::Uuid)
+                },
+            ],
+            ParameterType::None,
+        ));
+
+        let expected_ids = quote! {
+            PyObjectRef::from(vm.ctx.new_list(ids
+                .into_iter()
+                .map(|value| -> FnResult<PyObjectRef> { Ok(py_new(vm, "uuid", "UUID", rs_to_py(vm, value)?)?) })
+                .collect::<FnResult<Vec<_>>>()?))
+        };
+        let expected_roots = quote! {
+            for (key, value) in roots {
+                dict.set_item(&*rs_to_py(vm, key)?, match value {
+                    Some(value) => py_new(vm, "pathlib", "Path", rs_to_py(vm, value)?)?,
+                    None => vm.ctx.none(),
+                }, vm)
+                    .map_err(|exc| from_py_exc(vm, exc))?;
+            }
+        };
+        let expected_pair = quote! {
+            let value = pair;
+
+            PyObjectRef::from(vm.ctx.new_tuple(vec![rs_to_py(vm, value.0)?, complex_to_py(vm, value.1)?]))
+        };
+        let expected_more = quote! {
+            more.into_iter()
+                .map(|value| -> FnResult<PyObjectRef> { Ok(py_new(vm, "uuid", "UUID", rs_to_py(vm, value)?)?) })
+        };
+
+        assert!(tokens.contains(&expected_ids.to_string()), "{tokens}");
+        assert!(tokens.contains(&expected_roots.to_string()), "{tokens}");
+        assert!(tokens.contains(&expected_pair.to_string()), "{tokens}");
+        assert!(tokens.contains(&expected_more.to_string()), "{tokens}");
+        syn::parse_str::<syn::ItemFn>(&tokens).unwrap();
+    }
+
+    #[test]
+    fn globals_use_configured_recursion_limit() {
+        let mut context = context(vec![]);
+        context.metadata.py2binmod = Some(Py2BinmodConfig {
+            recursion_limit: Some(5000usize),
+            ..Default::default()
+        });
+
+        let tokens = LibRsGenerator::new(context)
+            .generate_globals()
+            .to_string();
+
+        let expected = quote! {
+            thread_local! {
+                static INTERPRETER: Interpreter = Interpreter::with_init(Default::default(), |vm| {
+                    vm.recursion_limit.set(5000usize);
+                    vm.add_native_modules(get_module_inits());
+                    vm.add_native_module("hostfns", Box::new(hostfns::make_module));
+                    vm.add_frozen(FROZEN_STDLIB);
+                    vm.add_frozen(py_freeze!(dir = "/project"));
+                    vm.add_frozen(py_freeze!(dir = "/project/venv/lib/python3.11/site-packages"));
+                });
+            }
+        };
+
+        assert_eq!(tokens, expected.to_string());
+    }
+
+    #[test]
+    fn globals_default_recursion_limit() {
+        let tokens = LibRsGenerator::new(context(vec![]))
+            .generate_globals()
+            .to_string();
+
+        assert!(tokens.contains(&quote! { vm.recursion_limit.set(#DEFAULT_RECURSION_LIMIT); }.to_string()));
+    }
+
+    #[test]
+    fn globals_freeze_only_selected_packages() {
+        let mut context = context(vec![]);
+        context.frozen_packages = Some(vec!["requests".into()]);
+
+        let tokens = LibRsGenerator::new(context)
+            .generate_globals()
+            .to_string();
+
+        assert!(tokens.contains(&quote! { vm.add_frozen(py_freeze!(dir = "site-packages")); }.to_string()));
+        assert!(!tokens.contains("/project/venv/lib/python3.11/site-packages"));
+    }
+
+    #[test]
+    fn globals_register_only_the_configured_stdlib() {
+        let mut context = context(vec![]);
+        context.metadata.py2binmod = Some(Py2BinmodConfig {
+            stdlib: Some(vec!["math".into(), "json".into()]),
+            ..Default::default()
+        });
+
+        let tokens = LibRsGenerator::new(context)
+            .generate_globals()
+            .to_string();
+        let modules = stdlib_closure(&["json", "math"], &[]);
+
+        assert!(tokens.contains(&quote! { const STDLIB_MODULES: &[&str] = &[#(#modules),*]; }.to_string()));
+        assert!(tokens.contains(&quote! {
+            vm.add_native_modules(get_module_inits().filter(|(name, _)| is_stdlib_module_enabled(name)));
+        }.to_string()));
+        assert!(tokens.contains(&quote! {
+            vm.add_frozen(FROZEN_STDLIB.into_iter().filter(|(name, _)| is_stdlib_module_enabled(name)));
+        }.to_string()));
+        assert!(!tokens.contains(&quote! { vm.add_frozen(FROZEN_STDLIB); }.to_string()));
+        assert!(tokens.contains("\"re\""));
+        assert!(!tokens.contains("\"argparse\""));
+    }
+
+    #[test]
+    fn globals_freeze_purelib_and_platlib() {
+        let mut context = context(vec![]);
+        context.platlib_dir = Some(PathBuf::from("/project/venv/lib64/python3.11/site-packages"));
+
+        let tokens = LibRsGenerator::new(context)
+            .generate_globals()
+            .to_string();
+
+        assert!(tokens.contains(&quote! {
+            vm.add_frozen(py_freeze!(dir = "/project/venv/lib/python3.11/site-packages"));
+            vm.add_frozen(py_freeze!(dir = "/project/venv/lib64/python3.11/site-packages"));
+        }.to_string()));
+    }
+
+    #[tokio::test]
+    async fn single_file_module_end_to_end() {
+        use std::fs;
+        use tempfile::TempDir;
+        use crate::parser::ProjectParser;
+
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        fs::write(
+            root.join("pyproject.toml"),
+            "[project]\nname = \"my-module\"\nversion = \"0.1.0\"\n\n[tool.py2binmod]\nmodule = \"my_module\"\n",
+        ).unwrap();
+        fs::write(
+            root.join("my_module.py"),
+            "@mod_fn\ndef greet(name: str) -> str:\n    return name\n",
+        ).unwrap();
+        fs::create_dir_all(root.join("venv/lib/python3.11/site-packages")).unwrap();
+
+        let context = ProjectParser::builder()
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap();
+        let generator = LibRsGenerator::new(context);
+
+        let globals = generator.generate_globals().to_string();
+        let module_dir_str = root.to_string_lossy();
+
+        assert!(globals.contains(&format!("py_freeze ! (dir = \"{}", module_dir_str)));
+        assert!(!globals.contains(&format!("py_freeze ! (dir = \"{}\")", root.parent().unwrap().to_string_lossy())));
+
+        let functions = generator.generate_exported_functions().to_string();
+
+        assert!(functions.contains("\"my_module\""));
+        assert!(!functions.contains("my_module.my_module"));
+
+        generator.generate();
+    }
+
+    #[tokio::test]
+    async fn named_tuples_become_structs() {
+        use std::fs;
+        use tempfile::TempDir;
+        use crate::parser::ProjectParser;
+
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        fs::write(
+            root.join("pyproject.toml"),
+            "[project]\nname = \"my-package\"\nversion = \"0.1.0\"\n",
+        ).unwrap();
+        fs::create_dir_all(root.join("my_package")).unwrap();
+        fs::write(root.join("my_package/__init__.py"), "").unwrap();
+        fs::write(
+            root.join("my_package/models.py"),
+            "from typing import NamedTuple\n\n\
+             class Point(NamedTuple):\n    x: float\n    y: float = 0.0\n\n\
+             Pair = NamedTuple(\"Pair\", [(\"key\", str), (\"value\", int)])\n",
+        ).unwrap();
+        fs::write(
+            root.join("my_package/api.py"),
+            "@mod_fn\ndef origin(pair: Pair) -> Point:\n    ...\n",
+        ).unwrap();
+        fs::create_dir_all(root.join("venv/lib/python3.11/site-packages")).unwrap();
+
+        let context = ProjectParser::builder()
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap();
+        let generator = LibRsGenerator::new(context);
+
+        let structs = generator.generate_structs().to_string();
+
+        assert!(structs.contains(&quote! {
+            pub struct Point {
+                pub x: f64,
+                #[serde(default, skip_serializing_if = "Option::is_none")]
+                pub y: Option<f64>
+            }
+        }.to_string()));
+        assert!(structs.contains(&quote! {
+            pub struct Pair {
+                pub key: String,
+                pub value: i64
+            }
+        }.to_string()));
+
+        let functions = generator.generate_exported_functions().to_string();
+
+        assert!(functions.contains(&quote! { pub fn origin_shim(pair: Pair) -> FnResult<Point> }.to_string()));
+        assert!(functions.contains(&quote! { .call((Pair::to_py(vm, pair)?,), vm) }.to_string()));
+        assert!(structs.contains(&quote! { py_struct(vm, "my_package.models", "Pair", fields) }.to_string()));
+        assert!(!structs.contains("Default"));
+    }
+
+    #[tokio::test]
+    async fn struct_conversions_recurse_through_fields_and_containers() {
+        use std::fs;
+        use tempfile::TempDir;
+        use crate::parser::ProjectParser;
+
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        fs::write(
+            root.join("pyproject.toml"),
+            "[project]\nname = \"my-package\"\nversion = \"0.1.0\"\n",
+        ).unwrap();
+        fs::create_dir_all(root.join("my_package")).unwrap();
+        fs::write(root.join("my_package/__init__.py"), "").unwrap();
+        fs::write(
+            root.join("my_package/api.py"),
+            "from typing import NamedTuple, Optional, TypedDict\nfrom uuid import UUID\n\n\
+             class Point(NamedTuple):\n    x: float\n    tag: Optional[UUID] = None\n\n\
+             class Segment(NamedTuple):\n    start: Point\n    end: Point\n\n\
+             class Owner(TypedDict):\n    id: UUID\n    name: str\n\n\
+             class Label(TypedDict):\n    text: str\n\n\
+             @mod_fn\ndef draw(path: list[Segment], anchor: Optional[Point], owner: Owner, label: Label) -> list[Segment]:\n    ...\n",
+        ).unwrap();
+        fs::create_dir_all(root.join("venv/lib/python3.11/site-packages")).unwrap();
+
+        let context = ProjectParser::builder()
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap();
+        let generator = LibRsGenerator::new(context);
+
+        let functions = generator.generate_exported_functions().to_string();
+
+        assert!(functions.contains(&quote! { .map(|value| -> FnResult<PyObjectRef> { Ok(Segment::to_py(vm, value)?) }) }.to_string()), "{functions}");
+        assert!(functions.contains(&quote! { Some(value) => Point::to_py(vm, value)? }.to_string()), "{functions}");
+        assert!(functions.contains(&quote! { Owner::to_py(vm, owner)? }.to_string()), "{functions}");
+        assert!(functions.contains(&quote! { rs_to_py(vm, label)? }.to_string()), "{functions}");
+        assert!(functions.contains(&quote! { .map(|value| -> FnResult<PyObjectRef> { Ok(Segment::from_py(vm, value)?) }) }.to_string()), "{functions}");
+
+        let structs = generator.generate_structs().to_string();
+
+        assert!(structs.contains(&quote! { fields.push(("start".to_string(), Point::to_py(vm, value.start)?)); }.to_string()), "{structs}");
+        assert!(structs.contains(&quote! {
+            if let Some(value) = value.tag {
+                fields.push(("tag".to_string(), py_new(vm, "uuid", "UUID", rs_to_py(vm, value)?)?));
+            }
+        }.to_string()), "{structs}");
+        assert!(structs.contains(&quote! { fields.push(("id".to_string(), py_new(vm, "uuid", "UUID", rs_to_py(vm, value.id)?)?)); }.to_string()), "{structs}");
+        assert!(structs.contains(&quote! { "id" => value.str(vm).map_err(|exc| from_py_exc(vm, exc))?.into(), }.to_string()), "{structs}");
+        assert!(!structs.contains("impl Label"), "{structs}");
+        syn::parse_str::<syn::File>(&structs).unwrap();
+    }
+
+    #[tokio::test]
+    async fn typed_dicts_become_structs() {
+        use std::fs;
+        use tempfile::TempDir;
+        use crate::parser::ProjectParser;
+
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        fs::write(
+            root.join("pyproject.toml"),
+            "[project]\nname = \"my-package\"\nversion = \"0.1.0\"\n",
+        ).unwrap();
+        fs::create_dir_all(root.join("my_package")).unwrap();
+        fs::write(root.join("my_package/__init__.py"), "").unwrap();
+        fs::write(
+            root.join("my_package/api.py"),
+            "from typing import TypedDict\n\n\
+             class Config(TypedDict, total=False):\n    debug: bool\n\n\
+             Outcome = TypedDict(\"Outcome\", {\"ok\": bool})\n\n\
+             @mod_fn\ndef run(config: Config) -> Outcome:\n    ...\n",
+        ).unwrap();
+        fs::create_dir_all(root.join("venv/lib/python3.11/site-packages")).unwrap();
+
+        let context = ProjectParser::builder()
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap();
+        let generator = LibRsGenerator::new(context);
+
+        let structs = generator.generate_structs().to_string();
+
+        assert!(structs.contains(&quote! {
+            pub struct Config {
+                #[serde(default, skip_serializing_if = "Option::is_none")]
+                pub debug: Option<bool>
+            }
+        }.to_string()));
+        assert!(structs.contains(&quote! { pub struct Outcome { pub ok: bool } }.to_string()));
+
+        let functions = generator.generate_exported_functions().to_string();
+
+        assert!(functions.contains(&quote! { pub fn run_shim(config: Config) -> FnResult<Outcome> }.to_string()));
+        assert!(functions.contains(&quote! { .call((rs_to_py(vm, config)?,), vm) }.to_string()));
+        assert!(!functions.contains("py_struct"));
+    }
+
+    #[test]
+    fn sanitized_field_names_keep_their_python_name() {
+        // `from` is a Python keyword, but not a Rust one.
+        let idents = field_idents(["from", "type", "résumé", "type_", "2fa"])
+            .into_iter()
+            .map(|(ident, renamed)| (ident.to_string(), renamed))
+            .collect::<Vec<_>>();
+
+        assert_eq!(idents, vec![
+            ("from".to_string(), false),
+            ("type__".to_string(), true),
+            ("r_sum_".to_string(), true),
+            ("type_".to_string(), false),
+            ("_2fa".to_string(), true),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn struct_fields_are_renamed_when_sanitized() {
+        use std::fs;
+        use tempfile::TempDir;
+        use crate::parser::ProjectParser;
+
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        fs::write(
+            root.join("pyproject.toml"),
+            "[project]\nname = \"my-package\"\nversion = \"0.1.0\"\n",
+        ).unwrap();
+        fs::create_dir_all(root.join("my_package")).unwrap();
+        fs::write(
+            root.join("my_package/__init__.py"),
+            "from typing import NamedTuple, TypedDict\n\n\
+             Route = TypedDict(\"Route\", {\"from\": str, \"type\": str})\n\n\
+             class Candidate(NamedTuple):\n    name: str\n    résumé: str | None = None\n\n\
+             @mod_fn\ndef plan(route: Route) -> Candidate:\n    ...\n",
+        ).unwrap();
+        fs::create_dir_all(root.join("venv/lib/python3.11/site-packages")).unwrap();
+
+        let context = ProjectParser::builder()
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap();
+        let generator = LibRsGenerator::new(context);
+
+        let structs = generator.generate_structs().to_string();
+
+        assert!(structs.contains(&quote! {
+            pub struct Route {
+                pub from: String,
+                #[serde(rename = "type")]
+                pub type_: String
+            }
+        }.to_string()), "{structs}");
+        assert!(structs.contains(&quote! {
+            pub struct Candidate {
+                pub name: String,
+                #[serde(rename = "résumé")]
+                #[serde(default, skip_serializing_if = "Option::is_none")]
+                pub r_sum_: Option<String>
+            }
+        }.to_string()), "{structs}");
+
+        generator.generate();
+    }
+}
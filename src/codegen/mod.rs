@@ -1,2 +1,4 @@
 pub mod traits;
-pub mod lib_rs;
\ No newline at end of file
+pub mod lib_rs;
+pub mod smoke_tests;
+pub mod stdlib;
\ No newline at end of file
@@ -0,0 +1,214 @@
+use proc_macro2::{TokenStream, Span};
+use quote::quote;
+use syn::Ident;
+
+use crate::{
+    types::{ProjectContext, Parameter, ParameterKind, ParameterType},
+    codegen::{traits::CodeGenerator, lib_rs::{feature_gate, field_idents}},
+};
+
+
+/// Generates `src/smoke_tests.rs`, a test that initializes the module and calls
+/// every exported function with zeroed arguments. Errors raised by the Python
+/// code are ignored, only panics and traps fail the test.
+pub struct SmokeTestGenerator {
+    context: ProjectContext,
+}
+
+impl SmokeTestGenerator {
+    pub fn new(context: ProjectContext) -> Self {
+        Self { context }
+    }
+
+    fn default_value(&self, type_hint: &ParameterType) -> TokenStream {
+        match type_hint {
+            ParameterType::String | ParameterType::Path => quote! { String::new() },
+            ParameterType::Integer => quote! { 0i64 },
+            ParameterType::Float => quote! { 0.0f64 },
+            ParameterType::Boolean => quote! { false },
+            ParameterType::Uuid => quote! { "00000000-0000-0000-0000-000000000000".to_string() },
+            ParameterType::Complex => quote! { Complex { re: 0.0, im: 0.0 } },
+            // Structs don't derive `Default`, fields that may be left out are
+            // left unset.
+            ParameterType::Struct(name) => self.context.modules
+                .iter()
+                .flat_map(|m| m.structs.iter())
+                .find(|s| &s.name == name)
+                .map(|s| {
+                    let struct_name = Ident::new(name, Span::call_site());
+                    let fields = s.fields
+                        .iter()
+                        .zip(field_idents(s.fields.iter().map(|f| f.name.as_str())))
+                        .map(|(f, (field_name, _))| {
+                            let value = if f.has_default {
+                                quote! { None }
+                            } else {
+                                self.default_value(&f.type_hint)
+                            };
+
+                            quote! { #field_name: #value }
+                        });
+
+                    quote! { #struct_name { #(#fields),* } }
+                })
+                .unwrap_or_else(|| quote! { Default::default() }),
+            ParameterType::List(_) => quote! { Vec::new() },
+            ParameterType::Set(_) => quote! { std::collections::HashSet::new() },
+            ParameterType::Map { .. } => quote! { std::collections::HashMap::new() },
+            ParameterType::Tuple(items) => {
+                let items = items.iter().map(|t| self.default_value(t));
+
+                quote! { (#(#items,)*) }
+            },
+            ParameterType::Optional(_) => quote! { None },
+            ParameterType::None => quote! { () },
+            ParameterType::Any | ParameterType::Unrecognized(_) => quote! { serde_json::Value::Null },
+        }
+    }
+
+    fn default_argument(&self, parameter: &Parameter) -> TokenStream {
+        match parameter.kind {
+            ParameterKind::VarPositional => quote! { Vec::new() },
+            ParameterKind::VarKeyword => quote! { std::collections::HashMap::new() },
+            _ => self.default_value(&parameter.type_hint),
+        }
+    }
+}
+
+impl CodeGenerator for SmokeTestGenerator {
+    fn generate(&self) -> TokenStream {
+        let calls = self.context
+            .modules
+            .iter()
+            .flat_map(|module| module.module_functions.iter())
+            .map(|func| {
+                let shim = Ident::new(&format!("{}_shim", func.name), Span::call_site());
+                let args = func.parameters.iter().map(|p| self.default_argument(p));
+                let gate = feature_gate(&self.context.features_of(&func.name));
+
+                quote! {
+                    #gate
+                    let _ = #shim(#(#args),*);
+                }
+            });
+
+        quote! {
+            //! Run with a wasm runner, e.g.
+            //! `CARGO_TARGET_WASM32_WASIP1_RUNNER=wasmtime cargo test`.
+
+            use super::*;
+
+            #[test]
+            fn exported_functions_are_callable() {
+                initialize_impl().expect("initialize failed");
+
+                #(#calls)*
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::types::{Module, ModuleFunction, ModuleFunctions, ProjectMetadata, StructDef, StructField, StructKind};
+
+    fn context(functions: Vec<ModuleFunction>) -> ProjectContext {
+        ProjectContext {
+            venv_dir: PathBuf::from("/project/venv"),
+            site_packages_dir: PathBuf::from("/project/venv/lib/python3.11/site-packages"),
+            platlib_dir: None,
+            project_dir: PathBuf::from("/project"),
+            module_root: PathBuf::from("/project/my_package"),
+            module_name: "my_package".into(),
+            metadata: ProjectMetadata {
+                name: "my_package".into(),
+                version: "0.1.0".into(),
+                requires_python: None,
+                description: None,
+                authors: vec![],
+                license: None,
+                license_file: None,
+                urls: Default::default(),
+                optional_dependencies: Default::default(),
+                py2binmod: None,
+                warnings: Vec::new(),
+            },
+            modules: vec![Module {
+                name: "api".into(),
+                file_path: PathBuf::from("/project/my_package/api.py"),
+                module_functions: ModuleFunctions::new(functions),
+                host_functions: None,
+                structs: vec![],
+            }],
+            frozen_packages: None,
+            workspace: None,
+            layout_trace: Vec::new(),
+            layout_warnings: Vec::new(),
+            type_warnings: Vec::new(),
+        }
+    }
+
+    fn function(name: &str, parameters: Vec<(&str, ParameterType, ParameterKind)>) -> ModuleFunction {
+        ModuleFunction {
+            name: name.into(),
+            docstring: None,
+            parameters: parameters
+                .into_iter()
+                .map(|(name, type_hint, kind)| Parameter { name: name.into(), type_hint, kind })
+                .collect(),
+            return_type: ParameterType::None,
+        }
+    }
+
+    #[test]
+    fn calls_every_exported_function() {
+        let tokens = SmokeTestGenerator::new(context(vec![
+            function("greet", vec![("name", ParameterType::String, ParameterKind::PositionalOrKeyword)]),
+            function("ping", vec![]),
+            function("total", vec![("values", ParameterType::Integer, ParameterKind::VarPositional)]),
+        ]))
+        .generate()
+        .to_string();
+
+        assert!(tokens.contains(&quote! { initialize_impl() }.to_string()));
+        assert!(tokens.contains(&quote! { let _ = greet_shim(String::new()); }.to_string()));
+        assert!(tokens.contains(&quote! { let _ = ping_shim(); }.to_string()));
+        assert!(tokens.contains(&quote! { let _ = total_shim(Vec::new()); }.to_string()));
+        assert!(syn::parse_file(&tokens).is_ok());
+    }
+
+    #[test]
+    fn default_values_match_generated_types() {
+        let generator = SmokeTestGenerator::new(context(vec![]));
+        let value = |t: ParameterType| generator.default_value(&t).to_string();
+
+        assert_eq!(value(ParameterType::Integer), quote! { 0i64 }.to_string());
+        assert_eq!(value(ParameterType::Optional(Box::new(ParameterType::String))), quote! { None }.to_string());
+        assert_eq!(
+            value(ParameterType::Tuple(vec![Box::new(ParameterType::Boolean), Box::new(ParameterType::Float)])),
+            quote! { (false, 0.0f64,) }.to_string(),
+        );
+    }
+
+    #[test]
+    fn structs_are_built_field_by_field() {
+        let mut context = context(vec![]);
+        context.modules[0].structs = vec![StructDef {
+            name: "Point".into(),
+            kind: StructKind::NamedTuple,
+            fields: vec![
+                StructField { name: "x".into(), type_hint: ParameterType::Complex, has_default: false },
+                StructField { name: "type".into(), type_hint: ParameterType::String, has_default: true },
+            ],
+        }];
+        let generator = SmokeTestGenerator::new(context);
+
+        assert_eq!(
+            generator.default_value(&ParameterType::Struct("Point".into())).to_string(),
+            quote! { Point { x: Complex { re: 0.0, im: 0.0 }, type_: None } }.to_string(),
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::{collections::BTreeSet, path::PathBuf};
+
+use crate::parser::import_closure::package_imports;
+
+
+/// Standard library modules the interpreter imports while starting up, kept
+/// when the registered stdlib is restricted.
+pub const REQUIRED_STDLIB: &[&str] = &[
+    "abc", "codecs", "collections_abc", "encodings", "errno", "genericpath", "io", "os",
+    "posix", "posixpath", "signal", "site", "sitebuiltins", "stat", "thread", "warnings",
+    "weakref",
+];
+
+/// Standard library modules the generated glue imports itself, to build
+/// `uuid.UUID` and `pathlib.Path` arguments.
+pub const GLUE_STDLIB: &[&str] = &["uuid", "pathlib"];
+
+/// Package the generated `initialize` imports to register host functions.
+const MDK_PACKAGE: &str = "binmod_mdk";
+
+/// Standard library modules imported by each module of the standard library,
+/// by top-level name with the leading `_` of private twins dropped. Only
+/// modules listed here pull in others, so an unlisted module is registered
+/// on its own.
+const STDLIB_DEPENDENCIES: &[(&str, &[&str])] = &[
+    ("abc", &[]),
+    ("argparse", &["copy", "gettext", "os", "re", "shutil", "textwrap", "warnings"]),
+    ("ast", &["contextlib", "enum", "re", "warnings"]),
+    ("base64", &["binascii", "re", "struct"]),
+    ("bisect", &[]),
+    ("calendar", &["datetime", "enum", "itertools", "locale", "warnings"]),
+    ("collections", &["copy", "heapq", "itertools", "keyword", "operator", "reprlib", "weakref"]),
+    ("contextlib", &["abc", "collections", "functools", "os", "types"]),
+    ("contextvars", &[]),
+    ("copy", &["copyreg", "types", "weakref"]),
+    ("copyreg", &[]),
+    ("csv", &["io", "re", "types"]),
+    ("dataclasses", &["abc", "copy", "functools", "inspect", "itertools", "keyword", "re", "reprlib", "thread", "types"]),
+    ("datetime", &["math", "operator", "time"]),
+    ("decimal", &["collections", "contextvars", "locale", "math", "numbers", "re"]),
+    ("dis", &["collections", "io", "opcode", "types"]),
+    ("enum", &["functools", "operator", "types"]),
+    ("fnmatch", &["functools", "os", "posixpath", "re"]),
+    ("fractions", &["decimal", "functools", "math", "numbers", "operator", "re"]),
+    ("functools", &["abc", "collections", "reprlib", "thread", "types"]),
+    ("gettext", &["locale", "operator", "os", "re"]),
+    ("hashlib", &["logging"]),
+    ("heapq", &[]),
+    ("importlib", &["warnings"]),
+    ("inspect", &["abc", "ast", "collections", "dis", "enum", "functools", "importlib", "itertools", "keyword", "linecache", "operator", "os", "re", "token", "tokenize", "types", "weakref"]),
+    ("io", &["abc"]),
+    ("ipaddress", &["functools"]),
+    ("itertools", &[]),
+    ("json", &["codecs", "re"]),
+    ("keyword", &[]),
+    ("linecache", &["functools", "os", "tokenize"]),
+    ("locale", &["encodings", "functools", "re"]),
+    ("logging", &["collections", "io", "os", "re", "string", "threading", "time", "traceback", "warnings", "weakref"]),
+    ("math", &[]),
+    ("ntpath", &["genericpath", "os", "stat"]),
+    ("numbers", &["abc"]),
+    ("opcode", &[]),
+    ("operator", &["functools"]),
+    ("pathlib", &["errno", "fnmatch", "functools", "io", "ntpath", "os", "posixpath", "re", "stat", "urllib", "warnings"]),
+    ("pprint", &["collections", "dataclasses", "io", "re", "types"]),
+    ("random", &["bisect", "hashlib", "itertools", "math", "operator", "os", "warnings"]),
+    ("re", &["copyreg", "enum", "functools"]),
+    ("reprlib", &["itertools", "thread"]),
+    ("shutil", &["collections", "errno", "fnmatch", "os", "stat"]),
+    ("statistics", &["bisect", "collections", "decimal", "fractions", "functools", "itertools", "math", "numbers", "operator", "random"]),
+    ("string", &["collections", "re"]),
+    ("struct", &[]),
+    ("textwrap", &["re"]),
+    ("threading", &["collections", "functools", "itertools", "os", "time", "traceback", "warnings"]),
+    ("time", &[]),
+    ("token", &[]),
+    ("tokenize", &["codecs", "collections", "functools", "io", "itertools", "re", "token"]),
+    ("traceback", &["collections", "contextlib", "itertools", "linecache", "textwrap", "warnings"]),
+    ("types", &["functools"]),
+    ("typing", &["abc", "collections", "contextlib", "copyreg", "functools", "operator", "re", "types", "warnings"]),
+    ("urllib", &["collections", "functools", "ipaddress", "math", "re", "types", "warnings"]),
+    ("uuid", &["enum", "hashlib", "os", "random", "time"]),
+    ("warnings", &["linecache"]),
+    ("weakref", &["copy", "itertools"]),
+];
+
+/// The configured stdlib `modules`, the modules the interpreter and the glue
+/// need, and those `binmod_mdk` imports, along with every stdlib module they
+/// import in turn. Sorted.
+pub fn stdlib_closure<S: AsRef<str>>(modules: &[S], site_packages_dirs: &[PathBuf]) -> Vec<String> {
+    let mut pending = modules
+        .iter()
+        .map(|module| module.as_ref().to_string())
+        .chain(REQUIRED_STDLIB.iter().chain(GLUE_STDLIB).map(|module| module.to_string()))
+        .chain(package_imports(MDK_PACKAGE, site_packages_dirs))
+        .collect::<Vec<_>>();
+    let mut closure = BTreeSet::new();
+
+    while let Some(module) = pending.pop() {
+        let name = module.strip_prefix('_').unwrap_or(&module).to_string();
+
+        if !closure.insert(name.clone()) {
+            continue;
+        }
+
+        if let Some((_, dependencies)) = STDLIB_DEPENDENCIES.iter().find(|(m, _)| *m == name) {
+            pending.extend(dependencies.iter().map(|dependency| dependency.to_string()));
+        }
+    }
+
+    closure.into_iter().collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closure_covers_transitive_dependencies() {
+        let closure = stdlib_closure(&["json", "math"], &[]);
+
+        for module in ["json", "math", "re", "enum", "functools", "copyreg", "uuid", "pathlib", "os"] {
+            assert!(closure.contains(&module.to_string()), "{module} missing from {closure:?}");
+        }
+        assert!(!closure.contains(&"argparse".to_string()));
+    }
+
+    #[test]
+    fn closure_follows_the_mdk_imports() {
+        let td = tempfile::TempDir::new().unwrap();
+        let mdk = td.path().join("binmod_mdk");
+        std::fs::create_dir_all(&mdk).unwrap();
+        std::fs::write(mdk.join("__init__.py"), "import dataclasses\nfrom . import host\n").unwrap();
+
+        let closure = stdlib_closure::<&str>(&[], &[td.path().to_path_buf()]);
+
+        assert!(closure.contains(&"dataclasses".to_string()));
+        assert!(closure.contains(&"inspect".to_string()));
+    }
+}
@@ -1,7 +1,7 @@
 use quote::quote;
 use proc_macro2::TokenStream;
 
-use crate::types::{Parameter, ParameterType};
+use crate::types::{Parameter, ParameterKind, ParameterType};
 
 pub trait CodeGenerator {
     fn generate(&self) -> TokenStream;
@@ -16,7 +16,11 @@ impl AsTokenStream for Parameter {
         let name = syn::Ident::new(&self.name, proc_macro2::Span::call_site());
         let type_hint = self.type_hint.as_token_stream();
 
-        quote! { #name: #type_hint }
+        match self.kind {
+            ParameterKind::VarPositional => quote! { #name: Vec<#type_hint> },
+            ParameterKind::VarKeyword => quote! { #name: std::collections::HashMap<String, #type_hint> },
+            _ => quote! { #name: #type_hint },
+        }
     }
 }
 
@@ -27,17 +31,32 @@ impl AsTokenStream for ParameterType {
             ParameterType::Integer => quote! { i64 },
             ParameterType::Float => quote! { f64 },
             ParameterType::Boolean => quote! { bool },
+            ParameterType::Uuid => quote! { String },
+            ParameterType::Path => quote! { String },
+            ParameterType::Complex => quote! { Complex },
+            ParameterType::Struct(name) => {
+                let name = syn::Ident::new(name, proc_macro2::Span::call_site());
+
+                quote! { #name }
+            },
             ParameterType::List(item_type) => {
                 let item_type = item_type.as_token_stream();
                 
                 quote! { Vec<#item_type> }
             },
+            ParameterType::Set(item_type) => {
+                let item_type = item_type.as_token_stream();
+
+                quote! { std::collections::HashSet<#item_type> }
+            },
             ParameterType::Tuple(inner_types) => {
                 let inner_types = inner_types
                     .iter()
                     .map(|t| t.as_token_stream());
-                
-                quote! { (#(#inner_types),*) }
+
+                // The trailing comma keeps a single element a tuple, which
+                // serializes as an array like its Python counterpart.
+                quote! { (#(#inner_types,)*) }
             },
             ParameterType::Map { key_type, value_type } => {
                 let key_type = key_type.as_token_stream();
@@ -51,7 +70,7 @@ impl AsTokenStream for ParameterType {
                 quote! { Option<#inner_type> }
             },
             ParameterType::None => quote! { () },
-            ParameterType::Any => quote! { serde_json::Value },
+            ParameterType::Any | ParameterType::Unrecognized(_) => quote! { serde_json::Value },
         }
     }
 }
\ No newline at end of file
@@ -1,43 +1,421 @@
-use std::path::PathBuf;
+use std::{io, path::{Path, PathBuf}, sync::{Arc, OnceLock}, time::Duration};
+use indicatif::HumanBytes;
 use tempfile::tempdir;
+use tokio_util::sync::CancellationToken;
 
 use crate::{
     error::{AppError, AppResult},
-    parser::ProjectParser,
-    generator::ProjectGenerator,
-    compiler::{Compiler, cargo::CargoCompiler},
-    ui::{Printer, Spinner, Style, Syntax, LogPanel},
+    parser::{
+        ProjectParser,
+        ProjectParserOptions,
+        error::{ParserError, ParserResult},
+        site_packages::{missing_extras, python_files, unused_packages},
+        import_closure::import_closure,
+        file_walker::{
+            FileWalker,
+            default::DefaultFileIgnoreStrategy,
+            configurable::ConfigurableFileIgnoreStrategy,
+        },
+        layout_resolver::{LayoutResolver, LayoutHints},
+        metadata_parser::{traits::MetadataParser, strategy::StrategyMetadataParser},
+    },
+    generator::{ProjectGenerator, DEFAULT_STACK_SIZE, workspace_cargo_config},
+    template::types::RenderedFile,
+    compiler::{Compiler, CompilerError, Artifact, cargo::CargoCompiler, diagnostics::error_summary, retry::RetryPolicy},
+    types::{ProjectContext, ProjectMetadata, Py2BinmodConfig},
+    ui::{Printer, Spinner, Progress, Style, Syntax, LogPanel, LogLine, StreamOutputSink, Verbosity},
+    watcher::ProjectWatcher,
+    fingerprint,
 };
 
+/// Replaces the project name from the metadata file, e.g. in CI.
+pub const NAME_ENV: &str = "PY2BINMOD_NAME";
+
+/// Replaces the project version from the metadata file, e.g. in CI.
+pub const VERSION_ENV: &str = "PY2BINMOD_VERSION";
+
+/// The value of `var`, when set and not blank.
+fn env_override(var: &str) -> Option<String> {
+    std::env::var(var)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// The command options [`parse_project`] reads.
+struct ParseOptions<'a> {
+    ignore_patterns: &'a [String],
+    include_patterns: &'a [String],
+    metadata_path: Option<&'a str>,
+    explain: bool,
+    cancellation: Option<&'a CancellationToken>,
+}
+
+/// What parsing shows while it runs: a spinner counting the files found while
+/// walking the project, then a bar over the module files analyzed.
+#[derive(Default)]
+struct ParseProgress {
+    walk: OnceLock<Spinner>,
+    analysis: OnceLock<Progress>,
+}
+
+impl ParseProgress {
+    fn clear(&self) {
+        if let Some(walk) = self.walk.get() {
+            walk.finish();
+        }
+
+        if let Some(analysis) = self.analysis.get() {
+            analysis.clear();
+        }
+    }
+}
+
+/// A parser showing its progress in `progress`. Both are hidden
+/// automatically when not attached to a terminal.
+fn project_parser(options: &ParseOptions<'_>, progress: &Arc<ParseProgress>) -> ProjectParser {
+    ProjectParser::builder()
+        .walk_progress({
+            let progress = progress.clone();
+            move |found| {
+                progress.walk
+                    .get_or_init(|| Spinner::new(Style::header("collecting files")))
+                    .update(format!("{} ({} found)", Style::header("collecting files"), found));
+            }
+        })
+        .progress({
+            let progress = progress.clone();
+            move |_, total| {
+                progress.analysis
+                    .get_or_init(|| {
+                        if let Some(walk) = progress.walk.get() {
+                            walk.finish();
+                        }
+
+                        Progress::new(total as u64, Style::header("analyzing modules"))
+                    })
+                    .increment(1);
+            }
+        })
+        .options(ProjectParserOptions {
+            ignore_patterns: options.ignore_patterns.to_vec(),
+            include_patterns: options.include_patterns.to_vec(),
+            metadata_path: options.metadata_path.map(PathBuf::from),
+            explain: options.explain,
+            name_override: env_override(NAME_ENV),
+            version_override: env_override(VERSION_ENV),
+            ..Default::default()
+        })
+        .cancellation(options.cancellation.cloned())
+        .build()
+}
+
+/// Parse the project showing its progress, then report what it found.
+async fn parse_project(project_dir: &Path, options: ParseOptions<'_>) -> AppResult<ProjectContext> {
+    let progress = Arc::new(ParseProgress::default());
+    let context = project_parser(&options, &progress)
+        .parse_project(project_dir)
+        .await;
+
+    progress.clear();
+
+    let context = context?;
+    report_context(&context);
+
+    Ok(context)
+}
+
+/// Print the layout trace and warn about what won't end up in the module.
+fn report_context(context: &ProjectContext) {
+    if !context.layout_trace.is_empty() {
+        Printer::section("layout");
+
+        for step in &context.layout_trace {
+            Printer::info(step);
+        }
+    }
+
+    for warning in context.layout_warnings
+        .iter()
+        .chain(&context.type_warnings)
+        .chain(&context.metadata.warnings)
+    {
+        Printer::warning(warning);
+    }
+
+    for (extra, requirement) in missing_extras(&context.site_packages_dirs(), &context.metadata.optional_dependencies) {
+        Printer::warning(&format!(
+            "'{}' from extra '{}' is not installed and won't be bundled",
+            requirement, extra,
+        ));
+    }
+
+    // Sizing site-packages reads every file in it, too slow for every build.
+    if Verbosity::current() == Verbosity::Verbose
+        && let Some(warning) = unused_packages_warning(context)
+    {
+        Printer::warning(&warning);
+    }
+}
+
+/// Packages below this size aren't worth trimming from the venv.
+const LARGE_PACKAGE_SIZE: u64 = 1024 * 1024;
+
+/// Lists the large site-packages the module never imports, when all of
+/// site-packages is frozen into it.
+fn unused_packages_warning(context: &ProjectContext) -> Option<String> {
+    if context.frozen_packages.is_some() {
+        return None;
+    }
+
+    let site_packages_dirs = context.site_packages_dirs();
+    let imported = import_closure(&module_sources(context), &site_packages_dirs);
+    let unused = unused_packages(&site_packages_dirs, &imported, LARGE_PACKAGE_SIZE);
+
+    if unused.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "site-packages holds packages the module never imports, all frozen into it: {}. Uninstall them or set freeze-imported in [tool.py2binmod] to leave them out",
+        unused
+            .iter()
+            .map(|(name, size)| format!("{} ({})", name, HumanBytes(*size)))
+            .collect::<Vec<_>>()
+            .join(", "),
+    ))
+}
+
+/// The exported functions of each module, e.g.
+///
+/// ```text
+/// my_package.api
+///   greet(name: str) -> str
+/// ```
+pub fn export_summary(context: &ProjectContext) -> String {
+    context.modules
+        .iter()
+        .filter(|module| !module.module_functions.is_empty())
+        .map(|module| {
+            let functions = module.module_functions
+                .iter()
+                .map(|func| format!("  {}", func))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            format!("{}\n{}", context.import_path(module), functions)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Print the distinct errors of a failed build, then write its full output to
+/// `py2binmod-build.log` in the target dir, as the panel only shows the tail.
+fn report_build_failure(log: &[LogLine], target_dir: &Path) {
+    let errors = error_summary(log.iter().map(|line| line.text.as_str()));
+
+    if !errors.is_empty() {
+        Printer::section("errors");
+
+        for error in &errors {
+            Printer::error(error);
+        }
+    }
+
+    let log_path = target_dir.join("py2binmod-build.log");
+    let content = log
+        .iter()
+        .map(|line| line.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    match std::fs::create_dir_all(target_dir).and_then(|_| std::fs::write(&log_path, content)) {
+        Ok(()) => Printer::info(&format!("full build log written to {}", log_path.display())),
+        Err(e) => Printer::warning(&format!("Unable to write the build log: {}", e)),
+    }
+}
+
+/// Where a build would put the module and the files of the generated crate,
+/// as printed by a dry run.
+pub fn dry_run_report(target_dir: &Path, wasm_path: &Path, files: &[RenderedFile]) -> String {
+    let files = files
+        .iter()
+        .map(|file| format!("  {}", file.path.display()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        "target dir: {}\nmodule: {}\nfiles:\n{}",
+        target_dir.display(),
+        wasm_path.display(),
+        files,
+    )
+}
+
+/// File the bundle of a transpiled crate is written to, next to the crate.
+pub const BUNDLE_FILE: &str = "bundle.txt";
+
+/// Every rendered file in a single stream, each under a `header` naming it
+/// and fenced by separators, for reviewing the generated crate in one piece.
+pub fn bundle(files: &[RenderedFile], header: impl Fn(&str) -> String) -> String {
+    let separator = "─".repeat(80);
+
+    files
+        .iter()
+        .map(|file| format!(
+            "{}\n{}\n{}\n{}\n",
+            header(&file.path.display().to_string()),
+            separator,
+            file.content.trim_end(),
+            separator,
+        ))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `path` made absolute with symlinks resolved, as far as it exists.
+fn resolve_path(path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+
+    path.ancestors()
+        .find_map(|ancestor| {
+            let rest = path.strip_prefix(ancestor).ok()?;
+            ancestor.canonicalize().ok().map(|resolved| resolved.join(rest))
+        })
+        .unwrap_or(path)
+}
+
+/// Set the stack size of wasm modules in the `.cargo/config.toml` of the
+/// workspace at `root`, creating it if needed.
+async fn write_workspace_cargo_config(root: &Path, stack_size: u64) -> AppResult<()> {
+    let config_path = root.join(".cargo/config.toml");
+    let content = match tokio::fs::read_to_string(&config_path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    tokio::fs::create_dir_all(root.join(".cargo")).await?;
+    tokio::fs::write(&config_path, workspace_cargo_config(&content, stack_size)?).await?;
+
+    Ok(())
+}
+
+/// Render the crate generated for `context`.
+fn render_context(context: ProjectContext) -> AppResult<Vec<RenderedFile>> {
+    ProjectGenerator::builder()
+        .context(context)
+        .build()?
+        .render()
+}
+
+/// Parse the project in `project_dir` and render the generated crate in
+/// memory, without writing to disk or printing anything.
+pub async fn transpile_to_memory(
+    project_dir: impl AsRef<Path>,
+    options: ProjectParserOptions,
+) -> AppResult<Vec<RenderedFile>> {
+    let parser = ProjectParser::builder()
+        .options(options)
+        .build();
+
+    transpile_with(&parser, project_dir.as_ref(), |_| ()).await
+}
+
+/// Parse the project with `parser` and render the generated crate in memory,
+/// once `prepare` has seen, and possibly adjusted, the parsed context.
+async fn transpile_with(
+    parser: &ProjectParser,
+    project_dir: &Path,
+    prepare: impl FnOnce(&mut ProjectContext),
+) -> AppResult<Vec<RenderedFile>> {
+    let mut context = parser.parse_project(project_dir).await?;
+    prepare(&mut context);
+
+    render_context(context)
+}
+
 #[derive(Debug, Clone)]
 pub struct TranspileOptions {
     pub project_dir: String,
     pub out_dir: Option<String>,
     pub stdout: bool,
+    pub ignore_patterns: Vec<String>,
+    /// Globs limiting which module files are analyzed.
+    pub include_patterns: Vec<String>,
+    /// Metadata file to read instead of the project's pyproject.toml.
+    pub metadata_path: Option<String>,
+    pub verbosity: Verbosity,
+    /// Emit every file in one stream, and write it to [`BUNDLE_FILE`] when
+    /// writing to a directory.
+    pub bundle: bool,
+    /// Resolve the dependencies into a tracked `Cargo.lock`, on top of
+    /// `[tool.py2binmod] emit-cargo-lock`.
+    pub emit_cargo_lock: bool,
+    /// Print how the module, import root and venv were chosen.
+    pub explain: bool,
+    /// Stops the pipeline at the next phase once cancelled.
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl TranspileOptions {
+    fn parse_options(&self) -> ParseOptions<'_> {
+        ParseOptions {
+            ignore_patterns: &self.ignore_patterns,
+            include_patterns: &self.include_patterns,
+            metadata_path: self.metadata_path.as_deref(),
+            explain: self.explain,
+            cancellation: self.cancellation.as_ref(),
+        }
+    }
+}
+
+/// Fail with [`AppError::Cancelled`] once `token` is cancelled.
+fn check_cancelled(token: Option<&CancellationToken>) -> AppResult<()> {
+    match token {
+        Some(token) if token.is_cancelled() => Err(AppError::Cancelled),
+        _ => Ok(()),
+    }
 }
 
 pub async fn transpile_project(options: TranspileOptions) -> AppResult<()> {
+    Verbosity::set(options.verbosity);
+
     if !options.out_dir.is_some() {
         Printer::warning("No output directory specified; defaulting to stdout.");
     }
 
+    let project_dir = PathBuf::from(&options.project_dir);
+    let apply_options = |context: &mut ProjectContext| {
+        if options.emit_cargo_lock {
+            context.metadata.py2binmod.get_or_insert_with(Default::default).emit_cargo_lock = true;
+        }
+    };
+
     if options.out_dir.is_none() || options.stdout {
-        let files = Spinner::step(
-            Style::header("transpiling project"),
-            None::<&str>,
-            || async {
-                ProjectGenerator::builder()
-                    .context(
-                        ProjectParser::builder()
-                            .build()
-                            .parse_project(&PathBuf::from(options.project_dir))
-                            .await?,
-                    )
-                    .build()
-                    .render()
-            }
-        )
-        .await?;
+        let progress = Arc::new(ParseProgress::default());
+        let parser = project_parser(&options.parse_options(), &progress);
+
+        let files = transpile_with(&parser, &project_dir, |context| {
+            progress.clear();
+            report_context(context);
+            apply_options(context);
+
+            Printer::section("exported functions");
+            println!("{}\n", export_summary(context));
+        })
+        .await;
+
+        progress.clear();
+
+        let files = files?;
+
+        check_cancelled(options.cancellation.as_ref())?;
+
+        if options.bundle {
+            println!("{}", bundle(&files, |path| Style::key(path).to_string()));
+            return Ok(());
+        }
 
         for file in &files {
             println!(
@@ -45,24 +423,55 @@ pub async fn transpile_project(options: TranspileOptions) -> AppResult<()> {
                 Style::key(&file.path.display().to_string()),
             );
             println!("{}", "─".repeat(80));
-            println!("{}", Syntax::code(&file.content, &file.path));
+            Syntax::write_code(&file.content, &file.path, &mut io::stdout().lock())?;
+            println!();
             println!("{}{}", "─".repeat(80), "\n");
         }
-    } else if options.out_dir.is_some() {
+    } else if let Some(out_dir) = options.out_dir.as_ref().map(PathBuf::from) {
+        let mut context = parse_project(&project_dir, options.parse_options()).await?;
+        apply_options(&mut context);
+
+        let emit_cargo_lock = context.metadata.py2binmod
+            .as_ref()
+            .is_some_and(|c| c.emit_cargo_lock);
+        let stack_size = context.metadata.py2binmod
+            .as_ref()
+            .and_then(|c| c.stack_size)
+            .unwrap_or(DEFAULT_STACK_SIZE);
+        let workspace_root = context.workspace.as_ref().map(|workspace| workspace.root.clone());
+
+        if let Some(workspace) = &context.workspace
+            && !resolve_path(&out_dir).starts_with(&workspace.root)
+        {
+            return Err(AppError::GeneratorError(format!(
+                "The output directory '{}' is outside the Cargo workspace at '{}', the generated crate can't be a member of it",
+                out_dir.display(),
+                workspace.root.display(),
+            )));
+        }
+
         Spinner::step(
             Style::header("transpiling project"),
             None::<&str>,
             || async {
-                ProjectGenerator::builder()
-                    .context(
-                        ProjectParser::builder()
-                            .build()
-                            .parse_project(&PathBuf::from(options.project_dir))
-                            .await?,
-                    )
-                    .build()
-                    .generate(&PathBuf::from(options.out_dir.unwrap()))
-                    .await
+                let files = render_context(context)?;
+                check_cancelled(options.cancellation.as_ref())?;
+                ProjectGenerator::write(&files, &out_dir).await?;
+
+                if options.bundle {
+                    tokio::fs::write(out_dir.join(BUNDLE_FILE), bundle(&files, str::to_string)).await?;
+                }
+
+                if let Some(workspace_root) = &workspace_root {
+                    write_workspace_cargo_config(workspace_root, stack_size).await?;
+                }
+
+                // A member's dependencies are pinned in the workspace's lockfile.
+                if emit_cargo_lock {
+                    CargoCompiler::generate_lockfile(workspace_root.as_ref().unwrap_or(&out_dir)).await?;
+                }
+
+                AppResult::Ok(())
             }
         )
         .await?;
@@ -76,13 +485,149 @@ pub struct BuildOptions {
     pub project_dir: String,
     pub out_dir: Option<String>,
     pub release: bool,
+    pub profile: Option<String>,
+    pub offline: bool,
+    /// Build against the `Cargo.lock` pinned in the project dir, which must exist.
+    pub locked: bool,
+    /// Parallel cargo jobs, cargo's default when unset.
+    pub jobs: Option<usize>,
+    pub ignore_patterns: Vec<String>,
+    /// Globs limiting which module files are analyzed.
+    pub include_patterns: Vec<String>,
+    /// Metadata file to read instead of the project's pyproject.toml.
+    pub metadata_path: Option<String>,
+    pub verbosity: Verbosity,
+    /// Rebuild even when nothing changed since the last build.
+    pub force: bool,
+    /// Extra rustc flags, added to those from `[tool.py2binmod] rustflags`.
+    pub rustflags: Vec<String>,
+    /// Generate the crate and report what would be built, without running cargo.
+    pub dry_run: bool,
+    /// Don't check that the wasm target is installed, for toolchains the
+    /// check can't inspect.
+    pub assume_target: bool,
+    /// Times to rerun cargo after it fails on a network error.
+    pub retries: usize,
+    /// Wait before the first retry, doubled for each one after.
+    pub retry_backoff_ms: u64,
+    /// Print how the module, import root and venv were chosen.
+    pub explain: bool,
+    /// Stops the pipeline at the next phase, killing cargo, once cancelled.
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl BuildOptions {
+    fn parse_options(&self) -> ParseOptions<'_> {
+        ParseOptions {
+            ignore_patterns: &self.ignore_patterns,
+            include_patterns: &self.include_patterns,
+            metadata_path: self.metadata_path.as_deref(),
+            explain: self.explain,
+            cancellation: self.cancellation.as_ref(),
+        }
+    }
+}
+
+/// The configured rustflags followed by `extra` and the stack size. Cargo
+/// ignores the rustflags of the generated `.cargo/config.toml` once any are
+/// passed, through `RUSTFLAGS` too, so the stack size it sets is always
+/// passed again, after the user's flags.
+fn build_rustflags(context: &ProjectContext, extra: &[String]) -> Vec<String> {
+    let config = context.metadata.py2binmod.as_ref();
+    let stack_size = config
+        .and_then(|c| c.stack_size)
+        .unwrap_or(DEFAULT_STACK_SIZE);
+
+    config
+        .iter()
+        .flat_map(|c| c.rustflags.iter())
+        .chain(extra)
+        .cloned()
+        .chain(["-C".to_string(), format!("link-arg=-zstack-size={}", stack_size)])
+        .collect()
+}
+
+/// The module's Python sources.
+fn module_sources(context: &ProjectContext) -> Vec<PathBuf> {
+    if context.import_root() == context.module_root {
+        vec![context.module_root.join(format!("{}.py", context.module_name))]
+    } else {
+        python_files(&context.module_root)
+    }
+}
+
+/// Files a build depends on besides the generated crate: every Python source
+/// under the import root, since the module may import its siblings, and the
+/// site-packages directories whose modification times change when packages
+/// are installed or removed.
+fn build_inputs(context: &ProjectContext) -> Vec<PathBuf> {
+    let site_packages_dirs = context.site_packages_dirs();
+
+    python_files(context.import_root())
+        .into_iter()
+        .filter(|path| {
+            !path.starts_with(&context.venv_dir)
+                && !site_packages_dirs.iter().any(|dir| path.starts_with(dir))
+        })
+        .chain(site_packages_dirs.iter().cloned())
+        .collect()
+}
+
+/// Points every build at a shared cargo target dir, so dependencies such as
+/// RustPython are compiled once for all projects.
+pub const TARGET_DIR_ENV: &str = "PY2BINMOD_TARGET_DIR";
+
+/// Where cargo builds the module: the output dir if given, then the shared
+/// target dir from `PY2BINMOD_TARGET_DIR`, then `[tool.py2binmod] target-dir`,
+/// then `<project>/artifacts`.
+///
+/// A shared target dir holds the output of every crate built into it, each
+/// module is found by its crate name (see [`Artifact::wasm_path`]). Cleaning
+/// it, e.g. with `cargo clean --target-dir`, discards the cached builds of
+/// every project using it.
+pub fn resolve_target_dir(out_dir: Option<&str>, shared: Option<PathBuf>, context: &ProjectContext) -> PathBuf {
+    out_dir
+        .map(PathBuf::from)
+        .or(shared)
+        .or_else(|| context.metadata.py2binmod
+            .as_ref()
+            .and_then(|c| c.target_dir.as_ref())
+            .map(|dir| context.project_dir.join(dir))
+        )
+        .unwrap_or_else(|| context.project_dir.join("artifacts"))
 }
 
 pub async fn build_project(options: BuildOptions) -> AppResult<()> {
+    Verbosity::set(options.verbosity);
+
+    if let Some(report) = run_build(options).await? {
+        Printer::section("dry run");
+        println!("{}\n", report);
+    }
+
+    Ok(())
+}
+
+/// Build the module, or only report what would be built on a dry run, which
+/// neither writes the generated crate nor invokes cargo.
+async fn run_build(options: BuildOptions) -> AppResult<Option<String>> {
+    if options.jobs == Some(0) {
+        return Err(CompilerError::InvalidJobs(0).into());
+    }
+
     let project_dir = PathBuf::from(&options.project_dir);
-    let out_path = PathBuf::from(options.out_dir.unwrap_or(project_dir.join("artifacts").to_string_lossy().to_string()));
 
-    if !CargoCompiler::is_installed().await {
+    // The crate is rendered into a fresh directory without a lockfile, so
+    // --locked builds against the one pinned in the project.
+    let lockfile = options.locked.then(|| project_dir.join("Cargo.lock"));
+
+    if let Some(lockfile) = &lockfile
+        && !lockfile.is_file()
+    {
+        return Err(CompilerError::MissingLockfile(lockfile.clone()).into());
+    }
+
+    if !options.dry_run && !CargoCompiler::is_installed().await {
         Printer::error("Cargo is not installed or not found in PATH.");
         Printer::info("Please install Rust and Cargo from https://www.rust-lang.org/tools/install");
         return Err(AppError::UnknownError(anyhow::anyhow!(
@@ -90,7 +635,7 @@ pub async fn build_project(options: BuildOptions) -> AppResult<()> {
         )));
     }
 
-    if !CargoCompiler::is_target_available().await? {
+    if !options.dry_run && !options.assume_target && !CargoCompiler::is_target_available().await? {
         Printer::error("The target 'wasm32-wasip1' is not installed.");
         Printer::info("Please install the target by running: rustup target add wasm32-wasip1");
         return Err(AppError::UnknownError(anyhow::anyhow!(
@@ -99,47 +644,1115 @@ pub async fn build_project(options: BuildOptions) -> AppResult<()> {
     }
 
     {
-        let temp_dir = tempdir()?;
+        let mut context = parse_project(&project_dir, options.parse_options()).await?;
+        // The crate is compiled in a temporary directory outside of any
+        // workspace, so it can't inherit from one.
+        context.workspace = None;
+        let out_path = resolve_target_dir(
+            options.out_dir.as_deref(),
+            std::env::var_os(TARGET_DIR_ENV)
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+            &context,
+        );
+        let inputs = build_inputs(&context)
+            .into_iter()
+            .chain(lockfile.clone())
+            .collect::<Vec<_>>();
+        let crate_name = context.metadata.name.clone();
+        let build_env = context.metadata.py2binmod
+            .as_ref()
+            .map(|c| c.build_env.clone())
+            .unwrap_or_default();
+        let rustflags = build_rustflags(&context, &options.rustflags);
 
-        Spinner::step(
+        let files = Spinner::step(
             Style::header("transpiling module"),
             Some(Printer::render_success("transpiled module")),
             || async {
                 ProjectGenerator::builder()
-                    .context(
-                        ProjectParser::builder()
-                            .build()
-                            .parse_project(&project_dir.clone())
-                            .await?,
-                    )
-                    .build()
-                    .generate(temp_dir.path())
-                    .await
+                    .context(context)
+                    .build()?
+                    .render()
             }
         )
         .await?;
 
-        LogPanel::step(
-            Style::header("compiling module"),
-            10,
-            Some(Printer::render_success("compiled module")),
-            Some(Printer::render_error("compilation failed")),
-            |panel| async {
-                CargoCompiler::builder()
-                    .release(options.release)
-                    .target_dir(out_path.clone())
-                    .output_sink_arc(panel)
-                    .build()
-                    .compile(temp_dir.path())
-                    .await
-                    .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)
+        check_cancelled(options.cancellation.as_ref())?;
+
+        let compiler = CargoCompiler::builder()
+            .release(options.release)
+            .profile(options.profile.clone())
+            .offline(options.offline)
+            .locked(options.locked)
+            .jobs(options.jobs)
+            .envs(build_env.clone())
+            .rustflags(rustflags.clone())
+            .assume_target(options.assume_target)
+            .retry(RetryPolicy::new(options.retries, Duration::from_millis(options.retry_backoff_ms)))
+            .cancellation(options.cancellation.clone())
+            .target_dir(out_path.clone());
+
+        let wasm_path = Artifact {
+            target_dir: out_path.clone(),
+            profile_dir: compiler.clone().build().profile_dir().to_string(),
+        }
+        .wasm_path(&crate_name);
+        let fingerprint = fingerprint::compute(
+            &files,
+            &inputs,
+            &format!("{:?}", (options.release, &options.profile, &build_env, &rustflags)),
+        );
+
+        if options.dry_run {
+            return Ok(Some(dry_run_report(&out_path, &wasm_path, &files)));
+        }
+
+        if !options.force && fingerprint::is_up_to_date(&wasm_path, &fingerprint) {
+            Printer::success("module is up to date");
+            return Ok(None);
+        }
+
+        let temp_dir = tempdir()?;
+        ProjectGenerator::write(&files, temp_dir.path()).await?;
+
+        if let Some(lockfile) = &lockfile {
+            tokio::fs::copy(lockfile, temp_dir.path().join("Cargo.lock")).await?;
+        }
+
+        if Verbosity::current() == Verbosity::Verbose {
+            Printer::section("compiling module");
+
+            compiler
+                .output_sink(StreamOutputSink)
+                .build()
+                .compile(temp_dir.path())
+                .await?;
+
+            Printer::success("compiled module");
+        } else {
+            let crate_dir = temp_dir.path();
+            let log_panel = OnceLock::<Arc<LogPanel>>::new();
+
+            let compiled = LogPanel::step(
+                Style::header("compiling module"),
+                10,
+                Some(Printer::render_success("compiled module")),
+                Some(Printer::render_error("compilation failed")),
+                |panel| {
+                    let _ = log_panel.set(panel.clone());
+
+                    async move {
+                        compiler
+                            .output_sink_arc(panel)
+                            .build()
+                            .compile(crate_dir)
+                            .await
+                            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)
+                    }
+                }
+            )
+            .await;
+
+            if let Err(e) = compiled {
+                check_cancelled(options.cancellation.as_ref())?;
+
+                if let Some(panel) = log_panel.get() {
+                    report_build_failure(&panel.log(), &out_path);
+                }
+
+                return Err(AppError::UnknownError(anyhow::anyhow!(e)));
             }
-        )
-        .await
-        .map_err(|e| AppError::UnknownError(anyhow::anyhow!(e)))?;
+        }
 
+        fingerprint::store(&wasm_path, &fingerprint)?;
         temp_dir.close()?;
     }
 
+    Ok(None)
+}
+
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    pub project_dir: String,
+    pub out_dir: Option<String>,
+    pub release: bool,
+    pub debounce_ms: u64,
+    pub ignore_patterns: Vec<String>,
+    /// Globs limiting which module files are analyzed.
+    pub include_patterns: Vec<String>,
+    /// Metadata file to read instead of the project's pyproject.toml.
+    pub metadata_path: Option<String>,
+    pub verbosity: Verbosity,
+}
+
+/// Build the project, then rebuild whenever a Python file under the module root
+/// changes. Runs until the task is cancelled.
+pub async fn watch_project(options: WatchOptions) -> AppResult<()> {
+    Verbosity::set(options.verbosity);
+
+    let project_dir = PathBuf::from(&options.project_dir);
+    let context = parse_project(&project_dir, ParseOptions {
+        ignore_patterns: &options.ignore_patterns,
+        include_patterns: &options.include_patterns,
+        metadata_path: options.metadata_path.as_deref(),
+        explain: false,
+        cancellation: None,
+    }).await?;
+    let ignore_strategy = ConfigurableFileIgnoreStrategy::new(
+        &context.project_dir,
+        &options.ignore_patterns
+            .iter()
+            .chain(context.metadata.py2binmod.iter().flat_map(|c| c.ignore.iter()))
+            .collect::<Vec<_>>(),
+    )?;
+
+    let watcher = ProjectWatcher::new(&context.module_root, Box::new(ignore_strategy))
+        .map_err(|e| AppError::UnknownError(anyhow::anyhow!(e)))?;
+
+    Printer::info(&format!("watching {}", context.module_root.display()));
+
+    watcher
+        .watch(Duration::from_millis(options.debounce_ms), || {
+            build_project(BuildOptions {
+                project_dir: options.project_dir.clone(),
+                out_dir: options.out_dir.clone(),
+                release: options.release,
+                profile: None,
+                offline: false,
+                locked: false,
+                jobs: None,
+                ignore_patterns: options.ignore_patterns.clone(),
+                include_patterns: options.include_patterns.clone(),
+                metadata_path: options.metadata_path.clone(),
+                verbosity: options.verbosity,
+                force: false,
+                rustflags: Vec::new(),
+                dry_run: false,
+                assume_target: false,
+                retries: 0,
+                retry_backoff_ms: 0,
+                explain: false,
+                cancellation: None,
+            })
+        })
+        .await;
+
     Ok(())
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone)]
+pub struct DoctorOptions {
+    pub project_dir: String,
+    pub verbosity: Verbosity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoctorCheck {
+    Cargo,
+    WasmTarget,
+    Metadata,
+    VirtualEnv,
+}
+
+impl DoctorCheck {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DoctorCheck::Cargo => "cargo",
+            DoctorCheck::WasmTarget => "wasm-target",
+            DoctorCheck::Metadata => "metadata",
+            DoctorCheck::VirtualEnv => "virtual-env",
+        }
+    }
+
+    pub fn hint(&self) -> &'static str {
+        match self {
+            DoctorCheck::Cargo => "Please install Rust and Cargo from https://www.rust-lang.org/tools/install",
+            DoctorCheck::WasmTarget => "Please install the target by running: rustup target add wasm32-wasip1",
+            DoctorCheck::Metadata => "Ensure pyproject.toml exists and declares [project] name and version",
+            DoctorCheck::VirtualEnv => "Create a virtual environment in the project or set [tool.py2binmod] venv",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DoctorFailure {
+    pub check: DoctorCheck,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DoctorReport {
+    pub failures: Vec<DoctorFailure>,
+}
+
+impl DoctorReport {
+    pub fn is_healthy(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    fn record(&mut self, check: DoctorCheck, result: Result<String, String>) {
+        match result {
+            Ok(message) => Printer::success(&message),
+            Err(message) => {
+                Printer::error(&message);
+                Printer::info(check.hint());
+                self.failures.push(DoctorFailure { check, message });
+            }
+        }
+    }
+}
+
+pub async fn check_cargo() -> Result<(), String> {
+    CargoCompiler::is_installed()
+        .await
+        .then_some(())
+        .ok_or_else(|| "Cargo is not installed or not found in PATH.".to_string())
+}
+
+pub async fn check_wasm_target() -> Result<(), String> {
+    match CargoCompiler::is_target_available().await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err("The target 'wasm32-wasip1' is not installed.".to_string()),
+        Err(e) => Err(format!("Unable to list installed targets: {}", e)),
+    }
+}
+
+pub async fn check_metadata(project_dir: &Path) -> Result<ProjectMetadata, String> {
+    StrategyMetadataParser::new()
+        .parse(project_dir, None)
+        .await
+        .map_err(|e| format!("Unable to read the project metadata: {}", e))
+}
+
+pub fn check_virtual_env(
+    resolver: &LayoutResolver,
+    project_dir: &Path,
+    metadata: Option<&ProjectMetadata>,
+) -> Result<PathBuf, String> {
+    resolver
+        .resolve_venv(
+            project_dir,
+            &LayoutHints {
+                venv: metadata
+                    .and_then(|m| m.py2binmod.as_ref())
+                    .and_then(|c| c.venv.clone()),
+                interpreter: metadata
+                    .and_then(|m| m.py2binmod.as_ref())
+                    .and_then(|c| c.interpreter.clone()),
+                discover_tox: metadata
+                    .and_then(|m| m.py2binmod.as_ref())
+                    .is_some_and(|c| c.discover_tox),
+                python_version: metadata
+                    .and_then(|m| m.py2binmod.as_ref())
+                    .is_some_and(|c| c.use_python_version),
+                ..Default::default()
+            },
+        )
+        .map(|(_, site_packages_dir)| site_packages_dir)
+        .map_err(|e| format!("Unable to resolve the virtual environment: {}", e))
+}
+
+pub async fn doctor_project(options: DoctorOptions) -> AppResult<DoctorReport> {
+    doctor_project_with(options, &LayoutResolver::new()).await
+}
+
+/// Run the doctor's checks, looking for the venv with `resolver`.
+async fn doctor_project_with(options: DoctorOptions, resolver: &LayoutResolver) -> AppResult<DoctorReport> {
+    Verbosity::set(options.verbosity);
+
+    let project_dir = PathBuf::from(&options.project_dir);
+
+    if !project_dir.is_dir() {
+        return Err(ParserError::InvalidProjectDir(project_dir).into());
+    }
+
+    let mut report = DoctorReport::default();
+
+    Printer::section("checking environment");
+
+    report.record(
+        DoctorCheck::Cargo,
+        check_cargo().await.map(|_| "cargo is installed".to_string()),
+    );
+    report.record(
+        DoctorCheck::WasmTarget,
+        check_wasm_target().await.map(|_| "target 'wasm32-wasip1' is installed".to_string()),
+    );
+
+    let metadata = check_metadata(&project_dir).await;
+
+    report.record(
+        DoctorCheck::Metadata,
+        metadata
+            .as_ref()
+            .map(|m| format!("project metadata is valid ({} {})", m.name, m.version))
+            .map_err(Clone::clone),
+    );
+    // Asking the interpreter hint for its site-packages runs it.
+    let venv = {
+        let (resolver, project_dir, metadata) = (resolver.clone(), project_dir.clone(), metadata.clone().ok());
+
+        tokio::task::spawn_blocking(move || check_virtual_env(&resolver, &project_dir, metadata.as_ref()))
+            .await
+            .map_err(|e| AppError::UnknownError(e.into()))?
+    };
+
+    report.record(
+        DoctorCheck::VirtualEnv,
+        venv.map(|p| format!("site-packages found at {}", p.display())),
+    );
+
+    if report.is_healthy() {
+        Printer::success("environment is ready");
+    } else {
+        Printer::warning(&format!("{} check(s) failed", report.failures.len()));
+    }
+
+    Ok(report)
+}
+
+
+#[derive(Debug, Clone)]
+pub struct InitOptions {
+    pub project_dir: String,
+    pub verbosity: Verbosity,
+}
+
+/// Set `entries` in the `[tool.py2binmod]` table of a pyproject.toml, however
+/// the table is written, adding it when it does not exist yet. Formatting and
+/// comments elsewhere are kept as-is.
+pub fn augment_pyproject(content: &str, entries: &[(&str, String)]) -> ParserResult<String> {
+    let mut document = content
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|e| ParserError::PyprojectEdit(e.to_string()))?;
+    let tool = document
+        .entry("tool")
+        .or_insert_with(|| {
+            let mut tool = toml_edit::Table::new();
+            tool.set_implicit(true);
+            toml_edit::Item::Table(tool)
+        })
+        .as_table_like_mut()
+        .ok_or_else(|| ParserError::PyprojectEdit("'tool' is not a table".to_string()))?;
+    let config = tool
+        .entry("py2binmod")
+        .or_insert(toml_edit::table())
+        .as_table_like_mut()
+        .ok_or_else(|| ParserError::PyprojectEdit("'tool.py2binmod' is not a table".to_string()))?;
+
+    for (key, value) in entries {
+        config.insert(key, toml_edit::value(value.as_str()));
+    }
+
+    Ok(document.to_string())
+}
+
+/// Detect the project layout and record it in the `[tool.py2binmod]` table of
+/// pyproject.toml. Values that are already configured are left untouched, and
+/// the original file is backed up to `pyproject.toml.bak` before editing.
+pub async fn init_project(options: InitOptions) -> AppResult<Py2BinmodConfig> {
+    Verbosity::set(options.verbosity);
+
+    let project_dir = PathBuf::from(&options.project_dir);
+    let pyproject_path = project_dir.join("pyproject.toml");
+
+    if !project_dir.is_dir() {
+        return Err(ParserError::InvalidProjectDir(project_dir).into());
+    }
+
+    // Walked files are canonical, detected paths are made relative to this.
+    let project_dir = project_dir.canonicalize()?;
+
+    let content = if pyproject_path.is_file() {
+        tokio::fs::read_to_string(&pyproject_path).await?
+    } else {
+        String::new()
+    };
+
+    let existing = toml::from_str::<toml::Table>(&content)
+        .map_err(ParserError::from)?
+        .get("tool")
+        .and_then(|tool| tool.get("py2binmod"))
+        .and_then(|config| config.as_table())
+        .cloned()
+        .unwrap_or_default();
+    let existing_str = |key: &str| existing
+        .get(key)
+        .and_then(|value| value.as_str())
+        .map(|value| value.to_string());
+    let existing_bool = |key: &str| existing
+        .get(key)
+        .and_then(|value| value.as_bool())
+        .unwrap_or(false);
+
+    Printer::section("initializing project");
+
+    let files = FileWalker::new(&DefaultFileIgnoreStrategy::new())
+        .walk(&project_dir)
+        .await?;
+    // An activated virtualenv is specific to this machine, so only the project
+    // tree is inspected.
+    let layout = LayoutResolver::new()
+        .with_virtual_env(None)
+        .resolve_async(
+            &project_dir,
+            &files,
+            &LayoutHints {
+                venv: existing_str("venv").map(PathBuf::from),
+                module_root: existing_str("module-root").map(PathBuf::from),
+                module: existing_str("module"),
+                interpreter: existing_str("interpreter").map(PathBuf::from),
+                discover_tox: existing_bool("discover-tox"),
+                python_version: existing_bool("use-python-version"),
+                explain: false,
+            },
+        )
+        .await?;
+
+    for warning in &layout.warnings {
+        Printer::warning(warning);
+    }
+
+    // A package's import root is its parent, a single-file module lives in it.
+    let import_root = if layout.module_root.ends_with(&layout.module_name) {
+        layout.module_root.parent().unwrap_or(&layout.module_root).to_path_buf()
+    } else {
+        layout.module_root.clone()
+    };
+    let relative = |path: &Path| path
+        .strip_prefix(&project_dir)
+        .map(|p| if p.as_os_str().is_empty() { Path::new(".") } else { p })
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string();
+
+    let detected = [
+        ("venv", relative(&layout.venv_dir)),
+        ("module-root", relative(&import_root)),
+        ("module", layout.module_name.clone()),
+    ];
+    let mut entries = Vec::new();
+
+    for (key, value) in detected.iter() {
+        if existing.contains_key(*key) {
+            Printer::info(&format!("keeping existing {}", key));
+        } else {
+            Printer::success(&format!("{} = {}", key, value));
+            entries.push((*key, value.clone()));
+        }
+    }
+
+    if entries.is_empty() {
+        Printer::info("pyproject.toml is already configured");
+    } else {
+        if pyproject_path.is_file() {
+            tokio::fs::copy(&pyproject_path, project_dir.join("pyproject.toml.bak")).await?;
+        }
+
+        tokio::fs::write(&pyproject_path, augment_pyproject(&content, &entries)?).await?;
+        Printer::success(&format!("updated {}", pyproject_path.display()));
+    }
+
+    let [venv, module_root, module] = detected.map(|(key, value)| existing_str(key).unwrap_or(value));
+
+    Ok(Py2BinmodConfig {
+        venv: Some(PathBuf::from(venv)),
+        module_root: Some(PathBuf::from(module_root)),
+        module: Some(module),
+        ..Default::default()
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use std::fs;
+
+    const PYPROJECT: &str = "[project]\nname = \"demo\"\nversion = \"1.2.3\"\n";
+    const PING: &str = "@mod_fn\ndef ping() -> None:\n    pass\n";
+
+    fn write_pyproject(root: &Path, content: &str) {
+        fs::write(root.join("pyproject.toml"), content).unwrap();
+    }
+
+    /// A project at `root` with a `demo` package whose `__init__.py` is `init`,
+    /// and an empty venv.
+    fn make_project(root: &Path, pyproject: &str, init: &str) {
+        write_pyproject(root, pyproject);
+        fs::create_dir_all(root.join("demo")).unwrap();
+        fs::create_dir_all(root.join("venv/lib/python3.11/site-packages")).unwrap();
+        fs::write(root.join("demo/__init__.py"), init).unwrap();
+    }
+
+    #[tokio::test]
+    async fn export_summary_lists_functions_per_module() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+        make_project(root, PYPROJECT, "");
+        fs::write(
+            root.join("demo/api.py"),
+            "@mod_fn\ndef greet(name: str) -> str:\n    return name\n\n@mod_fn\ndef total(*values: int) -> int:\n    return sum(values)\n",
+        ).unwrap();
+
+        let context = ProjectParser::builder()
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            export_summary(&context),
+            "demo.api\n  greet(name: str) -> str\n  total(*values: int) -> int",
+        );
+    }
+
+    #[tokio::test]
+    async fn build_inputs_cover_sibling_modules() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+        write_pyproject(root, PYPROJECT);
+        fs::create_dir_all(root.join("src/demo")).unwrap();
+        fs::create_dir_all(root.join("venv/lib/python3.11/site-packages")).unwrap();
+        fs::write(root.join("src/demo/__init__.py"), "from helpers import shout\n\n@mod_fn\ndef ping() -> None:\n    pass\n").unwrap();
+        fs::write(root.join("src/helpers.py"), "def shout(s):\n    return s.upper()\n").unwrap();
+        fs::write(root.join("venv/lib/python3.11/site-packages/six.py"), "").unwrap();
+
+        let context = ProjectParser::builder()
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap();
+        let inputs = build_inputs(&context);
+
+        assert!(inputs.contains(&context.module_root.join("__init__.py")), "{inputs:?}");
+        assert!(inputs.iter().any(|path| path.ends_with("src/helpers.py")), "{inputs:?}");
+        assert!(!inputs.iter().any(|path| path.ends_with("six.py")), "{inputs:?}");
+    }
+
+    #[tokio::test]
+    async fn dry_run_does_not_invoke_cargo() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+        make_project(root, PYPROJECT, PING);
+        let out_dir = root.join("out");
+
+        let report = run_build(BuildOptions {
+            project_dir: root.to_string_lossy().to_string(),
+            out_dir: Some(out_dir.to_string_lossy().to_string()),
+            release: true,
+            profile: None,
+            offline: false,
+            locked: false,
+            jobs: None,
+            ignore_patterns: vec![],
+            include_patterns: vec![],
+            metadata_path: None,
+            verbosity: Verbosity::Quiet,
+            force: false,
+            rustflags: vec![],
+            dry_run: true,
+            assume_target: false,
+            retries: 0,
+            retry_backoff_ms: 0,
+            explain: false,
+            cancellation: None,
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert!(!out_dir.exists(), "cargo was invoked");
+        assert!(report.starts_with(&format!("target dir: {}\n", out_dir.display())), "{report}");
+        assert!(report.contains("demo.wasm"), "{report}");
+        assert!(report.contains("  Cargo.toml\n"), "{report}");
+        assert!(report.contains("  src/lib.rs"), "{report}");
+    }
+
+    #[tokio::test]
+    async fn locked_builds_need_a_pinned_lockfile() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+        make_project(root, PYPROJECT, PING);
+        let options = || BuildOptions {
+            project_dir: root.to_string_lossy().to_string(),
+            out_dir: Some(root.join("out").to_string_lossy().to_string()),
+            release: false,
+            profile: None,
+            offline: false,
+            locked: true,
+            jobs: None,
+            ignore_patterns: vec![],
+            include_patterns: vec![],
+            metadata_path: None,
+            verbosity: Verbosity::Quiet,
+            force: false,
+            rustflags: vec![],
+            dry_run: true,
+            assume_target: false,
+            retries: 0,
+            retry_backoff_ms: 0,
+            explain: false,
+            cancellation: None,
+        };
+
+        let err = build_project(options()).await.unwrap_err();
+        assert!(err.to_string().contains("--locked builds against the project's Cargo.lock"), "{err}");
+
+        fs::write(root.join("Cargo.lock"), "version = 4\n").unwrap();
+        build_project(options()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn cancelled_builds_stop_before_transpiling() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+        make_project(root, PYPROJECT, PING);
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let err = build_project(BuildOptions {
+            project_dir: root.to_string_lossy().to_string(),
+            out_dir: Some(root.join("out").to_string_lossy().to_string()),
+            release: false,
+            profile: None,
+            offline: false,
+            locked: false,
+            jobs: None,
+            ignore_patterns: vec![],
+            include_patterns: vec![],
+            metadata_path: None,
+            verbosity: Verbosity::Quiet,
+            force: false,
+            rustflags: vec![],
+            dry_run: false,
+            assume_target: true,
+            retries: 0,
+            retry_backoff_ms: 0,
+            explain: false,
+            cancellation: Some(token),
+        })
+        .await
+        .unwrap_err();
+
+        assert!(matches!(err, AppError::Cancelled), "{err}");
+        assert!(!root.join("out").exists());
+    }
+
+    #[tokio::test]
+    async fn stdout_transpiles_in_memory() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+        make_project(root, PYPROJECT, PING);
+        let options = |cancellation: Option<CancellationToken>| TranspileOptions {
+            project_dir: root.to_string_lossy().to_string(),
+            out_dir: None,
+            stdout: true,
+            ignore_patterns: vec![],
+            include_patterns: vec![],
+            metadata_path: None,
+            verbosity: Verbosity::Quiet,
+            bundle: true,
+            emit_cargo_lock: false,
+            explain: false,
+            cancellation,
+        };
+
+        transpile_project(options(None)).await.unwrap();
+
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let err = transpile_project(options(Some(token))).await.unwrap_err();
+        assert!(matches!(err, AppError::Cancelled), "{err}");
+        assert_eq!(fs::read_dir(root).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn dry_run_report_lists_generated_files() {
+        let files = ["Cargo.toml", "src/lib.rs"]
+            .into_iter()
+            .map(|path| RenderedFile { path: path.into(), content: String::new() })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            dry_run_report(Path::new("/out"), Path::new("/out/wasm32-wasip1/release/demo.wasm"), &files),
+            "target dir: /out\nmodule: /out/wasm32-wasip1/release/demo.wasm\nfiles:\n  Cargo.toml\n  src/lib.rs",
+        );
+    }
+
+    #[test]
+    fn bundle_includes_every_file_under_a_header() {
+        let files = [("Cargo.toml", "[package]\n"), ("src/lib.rs", "fn main() {}\n")]
+            .into_iter()
+            .map(|(path, content)| RenderedFile { path: path.into(), content: content.into() })
+            .collect::<Vec<_>>();
+        let separator = "─".repeat(80);
+
+        assert_eq!(
+            bundle(&files, |path| format!("== {path}")),
+            format!("== Cargo.toml\n{separator}\n[package]\n{separator}\n\n== src/lib.rs\n{separator}\nfn main() {{}}\n{separator}\n"),
+        );
+    }
+
+    #[tokio::test]
+    async fn transpile_to_memory_renders_without_writing() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+        make_project(root, PYPROJECT, "");
+        fs::write(root.join("demo/api.py"), "@mod_fn\ndef greet(name: str) -> str:\n    return name\n").unwrap();
+
+        let mut paths = transpile_to_memory(root, ProjectParserOptions::default())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|file| file.path)
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        assert_eq!(paths, [
+            ".cargo/config.toml",
+            ".gitignore",
+            "Cargo.toml",
+            "README.md",
+            "rust-toolchain.toml",
+            "signatures.json",
+            "src/lib.rs",
+        ].map(PathBuf::from));
+        assert!(!root.join("artifacts").exists());
+    }
+
+    #[tokio::test]
+    async fn workspace_members_are_written_inside_the_workspace() {
+        let td = TempDir::new().unwrap();
+        let root = td.path().join("workspace/python/demo");
+        fs::create_dir_all(&root).unwrap();
+        make_project(&root, &format!("{PYPROJECT}\n[tool.py2binmod]\nworkspace-root = \"../..\"\n"), PING);
+        fs::write(td.path().join("workspace/Cargo.toml"), "[workspace]\nmembers = [\"modules/*\"]\n\n[workspace.package]\nversion = \"2.0.0\"\n").unwrap();
+
+        let options = |out_dir: PathBuf| TranspileOptions {
+            project_dir: root.to_string_lossy().to_string(),
+            out_dir: Some(out_dir.to_string_lossy().to_string()),
+            stdout: false,
+            ignore_patterns: vec![],
+            include_patterns: vec![],
+            metadata_path: None,
+            verbosity: Verbosity::Quiet,
+            bundle: false,
+            emit_cargo_lock: false,
+            explain: false,
+            cancellation: None,
+        };
+
+        let outside = td.path().join("elsewhere");
+        let err = transpile_project(options(outside.clone())).await.unwrap_err();
+
+        assert!(err.to_string().contains("outside the Cargo workspace"), "{err}");
+        assert!(!outside.exists());
+
+        let member = td.path().join("workspace/modules/demo");
+        fs::create_dir_all(td.path().join("workspace/.cargo")).unwrap();
+        fs::write(td.path().join("workspace/.cargo/config.toml"), "[build]\njobs = 2\n").unwrap();
+        transpile_project(options(member.clone())).await.unwrap();
+
+        let manifest = fs::read_to_string(member.join("Cargo.toml")).unwrap();
+        assert!(manifest.contains("version.workspace = true"), "{manifest}");
+
+        let member_config = fs::read_to_string(member.join(".cargo/config.toml")).unwrap();
+        assert!(!member_config.contains("zstack-size"), "{member_config}");
+
+        let workspace_config = fs::read_to_string(td.path().join("workspace/.cargo/config.toml")).unwrap();
+        assert!(workspace_config.starts_with("[build]\njobs = 2\n"), "{workspace_config}");
+        assert!(workspace_config.contains(&format!("link-arg=-zstack-size={DEFAULT_STACK_SIZE}")), "{workspace_config}");
+    }
+
+    #[tokio::test]
+    async fn warns_about_large_unused_site_packages() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+        let site_packages = root.join("venv/lib/python3.11/site-packages");
+        make_project(root, PYPROJECT, &format!("import requests\n\n{PING}"));
+
+        for package in ["requests", "numpy"] {
+            fs::create_dir_all(site_packages.join(package)).unwrap();
+            fs::write(site_packages.join(package).join("__init__.py"), "#".repeat(2 * LARGE_PACKAGE_SIZE as usize)).unwrap();
+        }
+
+        let mut context = ProjectParser::builder()
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap();
+
+        let warning = unused_packages_warning(&context).unwrap();
+        assert!(warning.contains("numpy (2.00 MiB)"), "{warning}");
+        assert!(!warning.contains("requests"), "{warning}");
+
+        context.frozen_packages = Some(vec!["requests".into()]);
+        assert!(unused_packages_warning(&context).is_none());
+    }
+
+    #[tokio::test]
+    async fn target_dir_resolution_order() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+        make_project(root, PYPROJECT, PING);
+
+        let mut context = ProjectParser::builder()
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap();
+        let shared = PathBuf::from("/cache/py2binmod");
+
+        assert_eq!(resolve_target_dir(None, None, &context), context.project_dir.join("artifacts"));
+
+        context.metadata.py2binmod = Some(Py2BinmodConfig {
+            target_dir: Some("../target".into()),
+            ..Default::default()
+        });
+
+        assert_eq!(resolve_target_dir(None, None, &context), context.project_dir.join("../target"));
+        assert_eq!(resolve_target_dir(None, Some(shared.clone()), &context), shared);
+        assert_eq!(resolve_target_dir(Some("/out"), Some(shared), &context), PathBuf::from("/out"));
+    }
+
+    #[tokio::test]
+    async fn rustflags_keep_the_stack_size() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+        make_project(root, &format!("{PYPROJECT}\n[tool.py2binmod]\nstack-size = 1024\nrustflags = [\"-C\", \"lto\"]\n"), PING);
+
+        let mut context = ProjectParser::builder()
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            build_rustflags(&context, &["-Copt-level=z".to_string()]),
+            vec!["-C", "lto", "-Copt-level=z", "-C", "link-arg=-zstack-size=1024"],
+        );
+
+        context.metadata.py2binmod = Some(Py2BinmodConfig {
+            build_env: [("RUSTFLAGS".to_string(), "-C lto".to_string())].into(),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            build_rustflags(&context, &[]),
+            vec!["-C".to_string(), format!("link-arg=-zstack-size={DEFAULT_STACK_SIZE}")],
+        );
+    }
+
+    #[test]
+    fn crates_sharing_a_target_dir_keep_their_own_module() {
+        let artifact = Artifact {
+            target_dir: PathBuf::from("/cache/py2binmod"),
+            profile_dir: "release".into(),
+        };
+
+        assert_eq!(
+            artifact.wasm_path("my-package"),
+            PathBuf::from("/cache/py2binmod/wasm32-wasip1/release/my_package.wasm"),
+        );
+        assert_eq!(
+            artifact.wasm_path("other-package"),
+            PathBuf::from("/cache/py2binmod/wasm32-wasip1/release/other_package.wasm"),
+        );
+        assert_ne!(
+            fingerprint::fingerprint_path(&artifact.wasm_path("my-package")),
+            fingerprint::fingerprint_path(&artifact.wasm_path("other-package")),
+        );
+    }
+
+    #[tokio::test]
+    async fn check_metadata_reads_pyproject() {
+        let td = TempDir::new().unwrap();
+        write_pyproject(td.path(), PYPROJECT);
+
+        let metadata = check_metadata(td.path()).await.unwrap();
+
+        assert_eq!(metadata.name, "demo");
+        assert_eq!(metadata.version, "1.2.3");
+    }
+
+    #[tokio::test]
+    async fn check_metadata_reports_missing_pyproject() {
+        let td = TempDir::new().unwrap();
+
+        assert!(check_metadata(td.path()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn check_metadata_follows_the_metadata_strategy() {
+        let td = TempDir::new().unwrap();
+        write_pyproject(td.path(), "[tool.py2binmod]\nmetadata-strategy = \"setupcfg\"\n");
+        std::fs::write(td.path().join("setup.cfg"), "[metadata]\nname = demo\nversion = 0.3.0\n").unwrap();
+
+        let metadata = check_metadata(td.path()).await.unwrap();
+
+        assert_eq!(metadata.name, "demo");
+        assert_eq!(metadata.version, "0.3.0");
+    }
+
+    #[tokio::test]
+    async fn check_metadata_reports_invalid_pyproject() {
+        let td = TempDir::new().unwrap();
+        write_pyproject(td.path(), "[project\nname = ");
+
+        assert!(check_metadata(td.path()).await.is_err());
+    }
+
+    #[test]
+    fn check_virtual_env_finds_site_packages() {
+        let td = TempDir::new().unwrap();
+        let site_packages = td.path().join(".venv/lib/python3.12/site-packages");
+        fs::create_dir_all(&site_packages).unwrap();
+
+        assert_eq!(
+            check_virtual_env(&LayoutResolver::new().with_virtual_env(None), td.path(), None).unwrap(),
+            site_packages,
+        );
+    }
+
+    #[test]
+    fn check_virtual_env_reports_missing_venv() {
+        let td = TempDir::new().unwrap();
+
+        assert!(check_virtual_env(&LayoutResolver::new().with_virtual_env(None), td.path(), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn doctor_reports_project_failures() {
+        let td = TempDir::new().unwrap();
+
+        let report = doctor_project_with(
+            DoctorOptions { project_dir: td.path().to_string_lossy().to_string(), verbosity: Verbosity::Quiet },
+            &LayoutResolver::new().with_virtual_env(None),
+        )
+        .await
+        .unwrap();
+
+        let failed = report.failures
+            .iter()
+            .map(|f| f.check)
+            .collect::<Vec<_>>();
+
+        assert!(!report.is_healthy());
+        assert!(failed.contains(&DoctorCheck::Metadata));
+        assert!(failed.contains(&DoctorCheck::VirtualEnv));
+    }
+
+
+    fn make_sample_project(root: &Path) {
+        write_pyproject(root, "# sample\n[project]\nname = \"demo\"\nversion = \"0.1.0\"\n");
+        fs::create_dir_all(root.join("src/demo")).unwrap();
+        fs::write(root.join("src/demo/__init__.py"), "").unwrap();
+        fs::create_dir_all(root.join(".venv/lib/python3.12/site-packages")).unwrap();
+    }
+
+    #[test]
+    fn augment_pyproject_appends_table() {
+        let content = augment_pyproject(
+            "[project]\nname = \"demo\"\n",
+            &[("venv", ".venv".into()), ("module", "demo".into())],
+        )
+        .unwrap();
+
+        assert_eq!(
+            content,
+            "[project]\nname = \"demo\"\n\n[tool.py2binmod]\nvenv = \".venv\"\nmodule = \"demo\"\n",
+        );
+    }
+
+    #[test]
+    fn augment_pyproject_extends_existing_table() {
+        let content = augment_pyproject(
+            "[tool.py2binmod] # build settings\nmodule = \"demo\"\n\n[tool.ruff]\n",
+            &[("venv", ".venv".into())],
+        )
+        .unwrap();
+
+        assert_eq!(
+            content,
+            "[tool.py2binmod] # build settings\nmodule = \"demo\"\nvenv = \".venv\"\n\n[tool.ruff]\n",
+        );
+    }
+
+    #[test]
+    fn augment_pyproject_extends_inline_and_dotted_tables() {
+        let inline = augment_pyproject(
+            "[tool]\npy2binmod = { module = \"demo\" }\n",
+            &[("venv", ".venv".into())],
+        )
+        .unwrap();
+        let dotted = augment_pyproject(
+            "[tool]\npy2binmod.module = \"demo\"\n",
+            &[("venv", ".venv".into())],
+        )
+        .unwrap();
+
+        for content in [inline, dotted] {
+            let config = toml::from_str::<toml::Table>(&content).unwrap()["tool"]["py2binmod"].clone();
+
+            assert_eq!(config["module"].as_str(), Some("demo"), "{content}");
+            assert_eq!(config["venv"].as_str(), Some(".venv"), "{content}");
+            assert!(!content.contains("[tool.py2binmod]"), "{content}");
+        }
+
+        assert!(matches!(
+            augment_pyproject("tool = 1\n", &[("venv", ".venv".into())]),
+            Err(ParserError::PyprojectEdit(_)),
+        ));
+    }
+
+    #[tokio::test]
+    async fn init_writes_detected_layout() {
+        let td = TempDir::new().unwrap();
+        make_sample_project(td.path());
+
+        init_project(InitOptions {
+            project_dir: td.path().to_string_lossy().to_string(),
+            verbosity: Verbosity::Quiet,
+        })
+        .await
+        .unwrap();
+
+        let metadata = check_metadata(td.path()).await.unwrap();
+        let config = metadata.py2binmod.unwrap();
+
+        assert_eq!(config.venv, Some(PathBuf::from(".venv")));
+        assert_eq!(config.module_root, Some(PathBuf::from("src")));
+        assert_eq!(config.module.as_deref(), Some("demo"));
+        assert!(fs::read_to_string(td.path().join("pyproject.toml")).unwrap().starts_with("# sample\n"));
+        assert!(td.path().join("pyproject.toml.bak").is_file());
+    }
+
+    #[tokio::test]
+    async fn init_keeps_existing_values() {
+        let td = TempDir::new().unwrap();
+        make_sample_project(td.path());
+        fs::create_dir_all(td.path().join("env/lib/python3.12/site-packages")).unwrap();
+        fs::write(
+            td.path().join("pyproject.toml"),
+            "[project]\nname = \"demo\"\nversion = \"0.1.0\"\n\n[tool.py2binmod]\nvenv = \"env\"\n",
+        )
+        .unwrap();
+
+        let config = init_project(InitOptions {
+            project_dir: td.path().to_string_lossy().to_string(),
+            verbosity: Verbosity::Quiet,
+        })
+        .await
+        .unwrap();
+
+        let written = check_metadata(td.path()).await.unwrap().py2binmod.unwrap();
+
+        assert_eq!(config.venv, Some(PathBuf::from("env")));
+        assert_eq!(written.venv, Some(PathBuf::from("env")));
+        assert_eq!(written.module.as_deref(), Some("demo"));
+    }
+}
@@ -1,24 +1,48 @@
 use std::{path::{Path, PathBuf}, process::Stdio, sync::Arc, env::current_dir};
 use async_trait::async_trait;
 use tokio::{process::Command, io::{AsyncBufReadExt, BufReader}};
+use tokio_util::sync::CancellationToken;
 
 use crate::compiler::{
     error::{CompilerError, CompilerResult},
     traits::{Compiler, OutputSink, NullOutputSink},
     types::Artifact,
-    utils::command_exists,
+    retry::RetryPolicy,
+    utils::{command_exists, cancelled},
 };
 
 
 pub struct CargoCompiler {
     pub release: bool,
+    /// A custom cargo profile, used instead of `release` when set.
+    pub profile: Option<String>,
+    pub offline: bool,
+    pub locked: bool,
+    /// Passed to cargo as `--jobs`. Only compilation is parallelized this way,
+    /// the project's modules are always analyzed one after another.
+    pub jobs: Option<usize>,
+    /// Set on top of the inherited environment, overriding variables of the same name.
+    pub env: Vec<(String, String)>,
+    /// Passed to rustc through `CARGO_ENCODED_RUSTFLAGS`, which takes precedence
+    /// over `RUSTFLAGS` and the rustflags of `.cargo/config.toml`, after the
+    /// flags those variables already hold.
+    pub rustflags: Vec<String>,
     pub target_dir: Option<PathBuf>,
+    /// Skip checking that the wasm target is installed before compiling.
+    pub assume_target: bool,
+    /// Retries of builds failing on a transient network error, none by default.
+    pub retry: RetryPolicy,
+    /// Kills cargo and fails with [`CompilerError::Cancelled`] once cancelled.
+    pub cancellation: Option<CancellationToken>,
     pub sink: Arc<dyn OutputSink + Send + Sync>,
 }
 
+/// The target the generated crate is compiled for.
+pub const WASM_TARGET: &str = "wasm32-wasip1";
+
 impl CargoCompiler {
     pub fn new(release: bool, target_dir: Option<PathBuf>, sink: Arc<dyn OutputSink + Send + Sync>) -> Self {
-        Self { release, target_dir, sink }
+        Self { release, profile: None, offline: false, locked: false, jobs: None, env: Vec::new(), rustflags: Vec::new(), target_dir, assume_target: false, retry: RetryPolicy::default(), cancellation: None, sink }
     }
 
     pub fn builder() -> CargoCompilerBuilder {
@@ -30,66 +54,218 @@ impl CargoCompiler {
     }
 
     pub async fn is_target_available() -> CompilerResult<bool> {
-        Ok(
-            String::from_utf8(
-                Command::new("rustup")
-                .arg("target")
-                .arg("list")
-                .arg("--installed")
-                .output()
-                .await?
-                .stdout
-            )
-            .map_err(|_| CompilerError::CompilationFailed("Failed to read rustup output".into()))?
-            .lines()
-            .any(|line| line == "wasm32-wasip1")
+        Self::is_target_available_with("rustup", "rustc").await
+    }
+
+    /// Ask `rustup` for the installed targets. Without rustup, as with distro
+    /// packaged toolchains, look for the target's libraries in the sysroot
+    /// `rustc` reports instead.
+    async fn is_target_available_with(rustup: &str, rustc: &str) -> CompilerResult<bool> {
+        if command_exists(rustup).await {
+            return Ok(
+                String::from_utf8(
+                    Command::new(rustup)
+                    .arg("target")
+                    .arg("list")
+                    .arg("--installed")
+                    .output()
+                    .await?
+                    .stdout
+                )
+                .map_err(|_| CompilerError::CompilationFailed("Failed to read rustup output".into()))?
+                .lines()
+                .any(|line| line == WASM_TARGET)
+            );
+        }
+
+        let output = Command::new(rustc)
+            .arg("--print")
+            .arg("sysroot")
+            .output()
+            .await
+            .map_err(|_| CompilerError::TargetCheckUnavailable)?;
+
+        if !output.status.success() {
+            return Err(CompilerError::TargetCheckUnavailable);
+        }
+
+        Ok(sysroot_has_target(Path::new(String::from_utf8_lossy(&output.stdout).trim())))
+    }
+
+    /// Resolve the dependencies of the crate in `project_dir` into its
+    /// `Cargo.lock`, without building it.
+    pub async fn generate_lockfile(project_dir: &Path) -> CompilerResult<()> {
+        Self::generate_lockfile_with("cargo", project_dir).await
+    }
+
+    async fn generate_lockfile_with(cargo: &str, project_dir: &Path) -> CompilerResult<()> {
+        if !command_exists(cargo).await {
+            return Err(CompilerError::LockfileFailed("cargo is not installed".into()));
+        }
+
+        let output = Command::new(cargo)
+            .arg("generate-lockfile")
+            .current_dir(project_dir)
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            return Err(CompilerError::LockfileFailed(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// The directory under the target dir cargo writes build output to, which
+    /// is named after the profile except for the built-in `dev` and `release`.
+    pub fn profile_dir(&self) -> &str {
+        match self.profile.as_deref() {
+            Some("dev") | Some("test") => "debug",
+            Some("bench") => "release",
+            Some(profile) => profile,
+            None if self.release => "release",
+            None => "debug",
+        }
+    }
+
+    /// The arguments passed to `cargo`.
+    pub fn args(&self) -> CompilerResult<Vec<String>> {
+        let target_dir = match &self.target_dir {
+            Some(dir) => dir.clone(),
+            None => current_dir()
+                .map_err(|e| CompilerError::CompilationFailed(e.to_string()))?
+                .join("artifacts"),
+        };
+
+        let mut args = vec!["build".to_string()];
+
+        match (&self.profile, self.release) {
+            (Some(profile), true) => return Err(CompilerError::ConflictingProfile(profile.clone())),
+            (Some(profile), false) => {
+                args.push("--profile".into());
+                args.push(profile.clone());
+            },
+            (None, true) => args.push("--release".into()),
+            (None, false) => {},
+        }
+        if self.offline {
+            args.push("--offline".into());
+        }
+        if self.locked {
+            args.push("--locked".into());
+        }
+        match self.jobs {
+            Some(0) => return Err(CompilerError::InvalidJobs(0)),
+            Some(jobs) => {
+                args.push("--jobs".into());
+                args.push(jobs.to_string());
+            },
+            None => {},
+        }
+
+        args.push("--target-dir".into());
+        args.push(
+            target_dir
+                .to_str()
+                .ok_or(CompilerError::CompilationFailed(
+                    "Failed to convert target dir to string".into(),
+                ))?
+                .to_string()
+        );
+        args.push("--message-format=short".into());
+
+        Ok(args)
+    }
+
+    /// The rustflags as cargo expects them in `CARGO_ENCODED_RUSTFLAGS`,
+    /// separated by `0x1f` so flags may contain whitespace. Cargo would ignore
+    /// the flags already set through `RUSTFLAGS`, so they come first: those of
+    /// `env`, or else those cargo would have used from the inherited
+    /// environment.
+    pub fn encoded_rustflags(&self) -> Option<String> {
+        self.encoded_rustflags_with(|var| std::env::var(var).ok())
+    }
+
+    /// [`Self::encoded_rustflags`], looking up inherited variables with `ambient`.
+    fn encoded_rustflags_with(&self, ambient: impl Fn(&str) -> Option<String>) -> Option<String> {
+        if self.rustflags.is_empty() {
+            return None;
+        }
+
+        let configured = self.env
+            .iter()
+            .rev()
+            .find(|(key, _)| key == "RUSTFLAGS")
+            .map(|(_, value)| value.split_whitespace().map(str::to_string).collect::<Vec<_>>());
+        let inherited = || match ambient("CARGO_ENCODED_RUSTFLAGS").filter(|flags| !flags.is_empty()) {
+            Some(encoded) => encoded.split('\x1f').map(str::to_string).collect(),
+            None => ambient("RUSTFLAGS")
+                .map(|flags| flags.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_default(),
+        };
+
+        Some(
+            configured
+                .unwrap_or_else(inherited)
+                .into_iter()
+                .chain(self.rustflags.iter().cloned())
+                .collect::<Vec<_>>()
+                .join("\x1f")
         )
     }
-}
 
-#[async_trait]
-impl Compiler for CargoCompiler {
-    async fn compile(&self, project_dir: &Path) -> CompilerResult<Artifact> {
-        Self::is_target_available().await?;
+    /// The `cargo` invocation building the crate in `project_dir`.
+    pub fn command(&self, project_dir: &Path) -> CompilerResult<Command> {
+        let mut command = Command::new("cargo");
 
-        let mut child = Command::new("cargo")
+        command
             .current_dir(project_dir)
-            .arg("build")
-            .args(self.release.then_some(vec!["--release"]).unwrap_or_default())
-            .args(
-                self.target_dir
-                    .as_ref()
-                    .map(|dir| vec!["--target-dir", dir.to_str().unwrap()])
-                    .unwrap_or(
-                        vec![
-                            "--target-dir",
-                            current_dir()
-                                .map_err(|e| CompilerError::CompilationFailed(e.to_string()))?
-                                .join("artifacts")
-                                .to_str()
-                                .ok_or(CompilerError::CompilationFailed(
-                                    "Failed to convert target dir to string".into(),
-                                ))?,
-                        ]
-                    )
-            )
-            .arg("--message-format=short")
+            .args(self.args()?)
+            .envs(self.env.iter().cloned())
+            .kill_on_drop(true)
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
+            .stderr(Stdio::piped());
+
+        if let Some(rustflags) = self.encoded_rustflags() {
+            command.env("CARGO_ENCODED_RUSTFLAGS", rustflags);
+        }
+
+        Ok(command)
+    }
+
+    /// Run cargo once, streaming its output to the sink. Returns whether the
+    /// build succeeded along with the lines cargo wrote to stderr.
+    async fn run(&self, project_dir: &Path) -> CompilerResult<(CompilerResult<()>, Vec<String>)> {
+        let mut child = self.command(project_dir)?.spawn()?;
 
         let mut stdout = BufReader::new(child.stdout.take().unwrap()).lines();
         let mut stderr = BufReader::new(child.stderr.take().unwrap()).lines();
+        let mut stderr_lines = Vec::new();
+        let (mut stdout_done, mut stderr_done) = (false, false);
 
-        loop {
+        while !(stdout_done && stderr_done) {
             tokio::select! {
-                Ok(Some(line)) = stdout.next_line() => self.sink.stdout(&line).await,
-                Ok(Some(line)) = stderr.next_line() => self.sink.stderr(&line).await,
-                else => break,
+                line = stdout.next_line(), if !stdout_done => match line {
+                    Ok(Some(line)) => self.sink.stdout(&line).await,
+                    _ => stdout_done = true,
+                },
+                line = stderr.next_line(), if !stderr_done => match line {
+                    Ok(Some(line)) => {
+                        self.sink.stderr(&line).await;
+                        stderr_lines.push(line);
+                    },
+                    _ => stderr_done = true,
+                },
+                _ = cancelled(self.cancellation.as_ref()) => {
+                    let _ = child.kill().await;
+                    return Err(CompilerError::Cancelled);
+                },
             }
         }
 
-        child.wait()
+        let result = child.wait()
             .await
             .map_err(|e| CompilerError::CompilationFailed(e.to_string()))
             .and_then(|status| status
@@ -99,19 +275,76 @@ impl Compiler for CargoCompiler {
                     "cargo exited with status code {}",
                     status.code().unwrap_or(-1)
                 )))
-            )?;
+            );
+
+        Ok((result, stderr_lines))
+    }
+}
+
+#[async_trait]
+impl Compiler for CargoCompiler {
+    async fn compile(&self, project_dir: &Path) -> CompilerResult<Artifact> {
+        if !self.assume_target {
+            Self::is_target_available().await?;
+        }
+
+        let mut attempt = 0;
+
+        loop {
+            if self.cancellation.as_ref().is_some_and(CancellationToken::is_cancelled) {
+                return Err(CompilerError::Cancelled);
+            }
+
+            let (result, stderr) = self.run(project_dir).await?;
+
+            match result {
+                Err(_) if self.retry.should_retry(attempt, &stderr) => {
+                    let delay = self.retry.delay(attempt);
+
+                    self.sink.stderr(&format!(
+                        "warning: cargo failed on a network error, retrying in {:.1}s ({}/{})",
+                        delay.as_secs_f64(),
+                        attempt + 1,
+                        self.retry.retries,
+                    )).await;
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {},
+                        _ = cancelled(self.cancellation.as_ref()) => return Err(CompilerError::Cancelled),
+                    }
+
+                    attempt += 1;
+                },
+                result => break result?,
+            }
+        }
 
         Ok(Artifact {
             target_dir: self.target_dir
                 .clone()
                 .unwrap_or_else(|| project_dir.join("artifacts")),
+            profile_dir: self.profile_dir().to_string(),
         })
     }
 }
 
+/// Whether the standard library of the wasm target is installed in `sysroot`.
+pub fn sysroot_has_target(sysroot: &Path) -> bool {
+    sysroot.join("lib").join("rustlib").join(WASM_TARGET).join("lib").is_dir()
+}
+
+#[derive(Clone)]
 pub struct CargoCompilerBuilder {
     release: bool,
+    profile: Option<String>,
+    offline: bool,
+    locked: bool,
+    jobs: Option<usize>,
+    env: Vec<(String, String)>,
+    rustflags: Vec<String>,
     target_dir: Option<PathBuf>,
+    assume_target: bool,
+    retry: RetryPolicy,
+    cancellation: Option<CancellationToken>,
     sink: Option<Arc<dyn OutputSink + Send + Sync>>,
 }
 
@@ -119,7 +352,16 @@ impl CargoCompilerBuilder {
     pub fn builder() -> Self {
         Self {
             release: false,
+            profile: None,
+            offline: false,
+            locked: false,
+            jobs: None,
+            env: Vec::new(),
+            rustflags: Vec::new(),
             target_dir: None,
+            assume_target: false,
+            retry: RetryPolicy::default(),
+            cancellation: None,
             sink: None,
         }
     }
@@ -129,6 +371,65 @@ impl CargoCompilerBuilder {
         self
     }
 
+    pub fn profile<S: Into<String>>(mut self, profile: Option<S>) -> Self {
+        self.profile = profile.map(Into::into);
+        self
+    }
+
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    pub fn locked(mut self, locked: bool) -> Self {
+        self.locked = locked;
+        self
+    }
+
+    pub fn jobs(mut self, jobs: Option<usize>) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    pub fn env<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn envs<I, K, V>(mut self, vars: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.env.extend(vars.into_iter().map(|(key, value)| (key.into(), value.into())));
+        self
+    }
+
+    pub fn rustflags<I, S>(mut self, flags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.rustflags.extend(flags.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn assume_target(mut self, assume_target: bool) -> Self {
+        self.assume_target = assume_target;
+        self
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn cancellation(mut self, token: Option<CancellationToken>) -> Self {
+        self.cancellation = token;
+        self
+    }
+
     pub fn target_dir<P: AsRef<Path>>(mut self, target_dir: P) -> Self {
         self.target_dir = Some(target_dir.as_ref().to_path_buf());
         self
@@ -145,6 +446,263 @@ impl CargoCompilerBuilder {
     }
 
     pub fn build(self) -> CargoCompiler {
-        CargoCompiler::new(self.release, self.target_dir, self.sink.unwrap_or(Arc::new(NullOutputSink)))
+        CargoCompiler {
+            profile: self.profile,
+            offline: self.offline,
+            locked: self.locked,
+            jobs: self.jobs,
+            env: self.env,
+            rustflags: self.rustflags,
+            assume_target: self.assume_target,
+            retry: self.retry,
+            cancellation: self.cancellation,
+            ..CargoCompiler::new(self.release, self.target_dir, self.sink.unwrap_or(Arc::new(NullOutputSink)))
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_args() {
+        let args = CargoCompiler::builder()
+            .target_dir("/out")
+            .build()
+            .args()
+            .unwrap();
+
+        assert_eq!(args, vec!["build", "--target-dir", "/out", "--message-format=short"]);
+    }
+
+    #[test]
+    fn offline_and_locked_args() {
+        let args = CargoCompiler::builder()
+            .release(true)
+            .offline(true)
+            .locked(true)
+            .target_dir("/out")
+            .build()
+            .args()
+            .unwrap();
+
+        assert_eq!(
+            args,
+            vec!["build", "--release", "--offline", "--locked", "--target-dir", "/out", "--message-format=short"],
+        );
+    }
+
+    #[test]
+    fn jobs_args() {
+        let args = CargoCompiler::builder()
+            .jobs(Some(4))
+            .target_dir("/out")
+            .build()
+            .args()
+            .unwrap();
+
+        assert_eq!(args, vec!["build", "--jobs", "4", "--target-dir", "/out", "--message-format=short"]);
+    }
+
+    #[test]
+    fn env_is_added_to_the_inherited_environment() {
+        let command = CargoCompiler::builder()
+            .env("RUSTFLAGS", "-C opt-level=z")
+            .envs([("CC", "clang")])
+            .target_dir("/out")
+            .build()
+            .command(Path::new("/project"))
+            .unwrap();
+        let envs = command
+            .as_std()
+            .get_envs()
+            .map(|(key, value)| (key.to_str().unwrap(), value.and_then(|v| v.to_str())))
+            .collect::<Vec<_>>();
+
+        assert_eq!(envs, vec![("CC", Some("clang")), ("RUSTFLAGS", Some("-C opt-level=z"))]);
+        assert!(!command.as_std().get_envs().any(|(_, value)| value.is_none()));
+    }
+
+    #[test]
+    fn rustflags_are_encoded() {
+        let compiler = CargoCompiler::builder()
+            .rustflags(["-C", "opt-level=z"])
+            .rustflags(["-C", "link-arg=--export-dynamic-symbol=my fn"])
+            .target_dir("/out")
+            .build();
+        let command = compiler
+            .command(Path::new("/project"))
+            .unwrap();
+        let encoded = command
+            .as_std()
+            .get_envs()
+            .find(|(key, _)| *key == "CARGO_ENCODED_RUSTFLAGS")
+            .and_then(|(_, value)| value)
+            .and_then(|value| value.to_str())
+            .unwrap();
+
+        assert_eq!(Some(encoded.to_string()), compiler.encoded_rustflags());
+
+        let encoded = compiler.encoded_rustflags_with(|_| None).unwrap();
+
+        assert_eq!(encoded, "-C\x1fopt-level=z\x1f-C\x1flink-arg=--export-dynamic-symbol=my fn");
+        assert_eq!(
+            encoded.split('\x1f').collect::<Vec<_>>(),
+            vec!["-C", "opt-level=z", "-C", "link-arg=--export-dynamic-symbol=my fn"],
+        );
+    }
+
+    #[test]
+    fn rustflags_keep_configured_rustflags_env() {
+        let compiler = CargoCompiler::builder()
+            .env("RUSTFLAGS", "-C lto  -C codegen-units=1")
+            .rustflags(["-C", "opt-level=z"])
+            .build();
+
+        assert_eq!(
+            compiler.encoded_rustflags().unwrap(),
+            "-C\x1flto\x1f-C\x1fcodegen-units=1\x1f-C\x1fopt-level=z",
+        );
+        assert_eq!(CargoCompiler::builder().build().encoded_rustflags(), None);
+    }
+
+    #[test]
+    fn rustflags_keep_inherited_rustflags() {
+        let compiler = CargoCompiler::builder()
+            .rustflags(["-C", "opt-level=z"])
+            .build();
+        let ambient = |vars: &'static [(&'static str, &'static str)]| move |var: &str| vars
+            .iter()
+            .find(|(key, _)| *key == var)
+            .map(|(_, value)| value.to_string());
+
+        assert_eq!(
+            compiler.encoded_rustflags_with(ambient(&[("RUSTFLAGS", "-C target-cpu=mvp")])).unwrap(),
+            "-C\x1ftarget-cpu=mvp\x1f-C\x1fopt-level=z",
+        );
+        assert_eq!(
+            compiler.encoded_rustflags_with(ambient(&[("RUSTFLAGS", "-C lto"), ("CARGO_ENCODED_RUSTFLAGS", "--cfg\x1fmy flag")])).unwrap(),
+            "--cfg\x1fmy flag\x1f-C\x1fopt-level=z",
+        );
+        assert_eq!(compiler.encoded_rustflags_with(ambient(&[])).unwrap(), "-C\x1fopt-level=z");
+
+        let compiler = CargoCompiler::builder()
+            .env("RUSTFLAGS", "-C lto")
+            .rustflags(["-C", "opt-level=z"])
+            .build();
+
+        assert_eq!(
+            compiler.encoded_rustflags_with(ambient(&[("RUSTFLAGS", "-C target-cpu=mvp")])).unwrap(),
+            "-C\x1flto\x1f-C\x1fopt-level=z",
+        );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn zero_jobs_are_rejected() {
+        let compiler = CargoCompiler::builder()
+            .jobs(Some(0))
+            .build();
+
+        assert!(matches!(compiler.args(), Err(CompilerError::InvalidJobs(0))));
+    }
+
+    #[test]
+    fn custom_profile_args() {
+        let compiler = CargoCompiler::builder()
+            .profile(Some("wasm-size"))
+            .target_dir("/out")
+            .build();
+
+        assert_eq!(
+            compiler.args().unwrap(),
+            vec!["build", "--profile", "wasm-size", "--target-dir", "/out", "--message-format=short"],
+        );
+    }
+
+    #[test]
+    fn custom_profile_conflicts_with_release() {
+        let compiler = CargoCompiler::builder()
+            .release(true)
+            .profile(Some("wasm-size"))
+            .build();
+
+        assert!(matches!(compiler.args(), Err(CompilerError::ConflictingProfile(p)) if p == "wasm-size"));
+    }
+
+    #[test]
+    fn profile_dir_follows_profile() {
+        let profile_dir = |release: bool, profile: Option<&str>| CargoCompiler::builder()
+            .release(release)
+            .profile(profile)
+            .build()
+            .profile_dir()
+            .to_string();
+
+        assert_eq!(profile_dir(false, None), "debug");
+        assert_eq!(profile_dir(true, None), "release");
+        assert_eq!(profile_dir(false, Some("dev")), "debug");
+        assert_eq!(profile_dir(false, Some("wasm-size")), "wasm-size");
+    }
+
+    #[test]
+    fn wasm_path_uses_profile_dir() {
+        let artifact = Artifact {
+            target_dir: PathBuf::from("/out"),
+            profile_dir: "wasm-size".into(),
+        };
+
+        assert_eq!(
+            artifact.wasm_path("my-package"),
+            PathBuf::from("/out/wasm32-wasip1/wasm-size/my_package.wasm"),
+        );
+    }
+
+    #[test]
+    fn finds_the_target_in_the_sysroot() {
+        let td = tempfile::TempDir::new().unwrap();
+
+        assert!(!sysroot_has_target(td.path()));
+
+        std::fs::create_dir_all(td.path().join("lib/rustlib/wasm32-wasip1/lib")).unwrap();
+
+        assert!(sysroot_has_target(td.path()));
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_sysroot_without_rustup() {
+        assert!(CargoCompiler::is_target_available_with("py2binmod-missing-rustup", "rustc").await.is_ok());
+        assert!(matches!(
+            CargoCompiler::is_target_available_with("py2binmod-missing-rustup", "py2binmod-missing-rustc").await,
+            Err(CompilerError::TargetCheckUnavailable),
+        ));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn generates_the_lockfile_in_the_crate() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let td = tempfile::TempDir::new().unwrap();
+        let cargo = td.path().join("cargo");
+        let crate_dir = td.path().join("crate");
+        std::fs::create_dir_all(&crate_dir).unwrap();
+        std::fs::write(
+            &cargo,
+            "#!/bin/sh\n[ \"$1\" = generate-lockfile ] || exit 2\n[ -f Cargo.toml ] || { echo 'error: could not find `Cargo.toml`' >&2; exit 101; }\necho 'version = 4' > Cargo.lock\n",
+        ).unwrap();
+        std::fs::set_permissions(&cargo, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let cargo = cargo.to_str().unwrap();
+
+        assert!(matches!(
+            CargoCompiler::generate_lockfile_with(cargo, &crate_dir).await,
+            Err(CompilerError::LockfileFailed(message)) if message.contains("could not find `Cargo.toml`"),
+        ));
+
+        std::fs::write(crate_dir.join("Cargo.toml"), "[package]\nname = \"demo\"\n").unwrap();
+        CargoCompiler::generate_lockfile_with(cargo, &crate_dir).await.unwrap();
+
+        assert!(crate_dir.join("Cargo.lock").is_file());
+    }
+}
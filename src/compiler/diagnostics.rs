@@ -0,0 +1,64 @@
+/// The distinct errors in cargo's `--message-format=short` output, such as
+/// `src/lib.rs:3:5: error[E0425]: cannot find value `x` in this scope`, in
+/// the order they were reported. The closing `could not compile` and
+/// `aborting due to` lines only repeat that the build failed and are left out.
+pub fn error_summary<'a>(lines: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut errors: Vec<String> = Vec::new();
+
+    for line in lines {
+        let line = line.trim();
+        let message = match line.find("error") {
+            Some(0) => line,
+            Some(i) if line[..i].ends_with(": ") => &line[i..],
+            _ => continue,
+        };
+
+        let is_error = message.starts_with("error:") || message.starts_with("error[");
+        let is_epilogue = message.starts_with("error: could not compile")
+            || message.starts_with("error: aborting due to");
+
+        if is_error && !is_epilogue && !errors.iter().any(|e| e == line) {
+            errors.push(line.to_string());
+        }
+    }
+
+    errors
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_distinct_errors() {
+        let output = "\
+   Compiling once_cell v1.21.3
+   Compiling demo v0.1.0 (/tmp/.tmpAbc)
+src/lib.rs:12:5: warning: unused variable: `x`
+src/lib.rs:40:17: error[E0425]: cannot find value `vm` in this scope
+src/lib.rs:52:9: error[E0308]: mismatched types
+src/lib.rs:40:17: error[E0425]: cannot find value `vm` in this scope
+error: linking with `rust-lld` failed: exit status: 1
+warning: `demo` (lib) generated 1 warning
+error: could not compile `demo` (lib) due to 3 previous errors; 1 warning emitted
+";
+
+        assert_eq!(error_summary(output.lines()), vec![
+            "src/lib.rs:40:17: error[E0425]: cannot find value `vm` in this scope",
+            "src/lib.rs:52:9: error[E0308]: mismatched types",
+            "error: linking with `rust-lld` failed: exit status: 1",
+        ]);
+    }
+
+    #[test]
+    fn ignores_lines_merely_mentioning_errors() {
+        let output = [
+            "   Compiling thiserror v2.0.12",
+            "   Compiling error-chain v0.12.4",
+            "note: the error is reported below",
+        ];
+
+        assert!(error_summary(output).is_empty());
+    }
+}
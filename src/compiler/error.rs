@@ -7,12 +7,42 @@ pub enum CompilerError {
     CompilationFailed(String),
     #[error("Unsupported target platform: {0}")]
     UnsupportedTargetPlatform(String),
+    #[error("Cannot combine --release with the '{0}' profile")]
+    ConflictingProfile(String),
+    #[error("Invalid number of jobs: {0}, expected at least 1")]
+    InvalidJobs(usize),
+    #[error("Can't check whether the wasm32-wasip1 target is installed without rustup or rustc, pass --assume-target to skip the check")]
+    TargetCheckUnavailable,
+    #[error("Failed to generate Cargo.lock: {0}")]
+    LockfileFailed(String),
+    #[error("--locked builds against the project's Cargo.lock, but there is none at '{}'", .0.display())]
+    MissingLockfile(std::path::PathBuf),
     #[error("Missing build configuration")]
     MissingBuildConfiguration,
+    #[error("The build was cancelled")]
+    Cancelled,
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
     #[error("Unknown error: {0}")]
     UnknownError(#[from] anyhow::Error),
 }
 
+impl CompilerError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            CompilerError::CompilationFailed(_) => "compiler.compilation_failed",
+            CompilerError::UnsupportedTargetPlatform(_) => "compiler.unsupported_target_platform",
+            CompilerError::ConflictingProfile(_) => "compiler.conflicting_profile",
+            CompilerError::InvalidJobs(_) => "compiler.invalid_jobs",
+            CompilerError::TargetCheckUnavailable => "compiler.target_check_unavailable",
+            CompilerError::LockfileFailed(_) => "compiler.lockfile_failed",
+            CompilerError::MissingLockfile(_) => "compiler.missing_lockfile",
+            CompilerError::MissingBuildConfiguration => "compiler.missing_build_configuration",
+            CompilerError::Cancelled => "compiler.cancelled",
+            CompilerError::IoError(_) => "compiler.io",
+            CompilerError::UnknownError(_) => "compiler.unknown",
+        }
+    }
+}
+
 pub type CompilerResult<T> = Result<T, CompilerError>;
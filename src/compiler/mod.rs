@@ -3,6 +3,8 @@ pub mod traits;
 pub mod cargo;
 pub mod utils;
 pub mod types;
+pub mod diagnostics;
+pub mod retry;
 
 pub use crate::compiler::{
     error::{CompilerError, CompilerResult},
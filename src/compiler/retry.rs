@@ -0,0 +1,155 @@
+use std::time::Duration;
+
+
+/// Output of cargo failing to reach the registry, worth another try.
+const TRANSIENT_PATTERNS: &[&str] = &[
+    "spurious network error",
+    "failed to download from",
+    "failed to fetch `",
+    "unable to update registry",
+    "from registry",
+    "couldn't resolve host name",
+    "couldn't connect to server",
+    "timeout was reached",
+    "connection reset by peer",
+    "ssl connect error",
+    "failure when receiving data from the peer",
+    "503 service unavailable",
+];
+
+/// Output of failures retrying can't fix, even when they come with a
+/// network error.
+const FATAL_PATTERNS: &[&str] = &[
+    "no matching package named",
+    "failed to select a version",
+    "--locked was passed",
+    "error[e",
+];
+
+/// How often a failed cargo build is retried. Each retry waits twice as long
+/// as the previous one, starting from `backoff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub retries: usize,
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { retries: 0, backoff: Duration::from_secs(1) }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(retries: usize, backoff: Duration) -> Self {
+        Self { retries, backoff }
+    }
+
+    /// How long to wait before retry number `attempt`, counting from zero.
+    pub fn delay(&self, attempt: usize) -> Duration {
+        self.backoff.saturating_mul(1 << attempt.min(16) as u32)
+    }
+
+    /// Whether a build failing with `output` after `attempt` retries should be retried.
+    pub fn should_retry<S: AsRef<str>>(&self, attempt: usize, output: &[S]) -> bool {
+        attempt < self.retries && is_transient(output)
+    }
+}
+
+/// Whether cargo's `output` shows it failed on a network error, rather than
+/// on the crate or its dependencies.
+pub fn is_transient<S: AsRef<str>>(output: &[S]) -> bool {
+    let lines = output
+        .iter()
+        .map(|line| line.as_ref().to_lowercase())
+        .collect::<Vec<_>>();
+    let matches = |patterns: &[&str]| lines
+        .iter()
+        .any(|line| patterns.iter().any(|pattern| line.contains(pattern)));
+
+    matches(TRANSIENT_PATTERNS) && !matches(FATAL_PATTERNS)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn network_failures_are_transient() {
+        let outputs: &[&[&str]] = &[
+            &[
+                "    Updating crates.io index",
+                "warning: spurious network error (2 tries remaining): [6] Couldn't resolve host name",
+                "error: failed to get `serde` as a dependency of package `my_package v0.1.0`",
+                "Caused by:",
+                "  failed to query replaced source registry `crates-io`",
+                "Caused by:",
+                "  download of config.json failed",
+                "Caused by:",
+                "  failed to download from `https://index.crates.io/config.json`",
+                "Caused by:",
+                "  [28] Timeout was reached",
+            ],
+            &[
+                "error: failed to get `rustpython-vm` from registry `crates-io`",
+                "  [56] Failure when receiving data from the peer",
+            ],
+            &[
+                "error: failed to fetch `https://github.com/rust-lang/crates.io-index`",
+                "  network failure seems to have happened",
+                "  Connection reset by peer (os error 104)",
+            ],
+        ];
+
+        for output in outputs {
+            assert!(is_transient(output), "{output:?}");
+        }
+    }
+
+    #[test]
+    fn build_failures_are_fatal() {
+        let outputs: &[&[&str]] = &[
+            &[
+                "error[E0308]: mismatched types",
+                "error: could not compile `my_package` (lib) due to 1 previous error",
+            ],
+            &[
+                "error: no matching package named `rustpython-vmm` found",
+                "location searched: registry `crates-io`",
+            ],
+            &[
+                "    Updating crates.io index",
+                "error: failed to select a version for the requirement `serde = \"^99\"`",
+                "candidate versions found which didn't match: 1.0.219",
+                "location searched: crates.io index",
+            ],
+            &[
+                "error: the lock file needs to be updated but --locked was passed to prevent this",
+            ],
+            &[],
+        ];
+
+        for output in outputs {
+            assert!(!is_transient(output), "{output:?}");
+        }
+    }
+
+    #[test]
+    fn retries_are_bounded_and_back_off() {
+        let transient = ["warning: spurious network error (1 tries remaining)"];
+
+        assert!(!RetryPolicy::default().should_retry(0, &transient));
+
+        let policy = RetryPolicy::new(2, Duration::from_millis(500));
+
+        assert!(policy.should_retry(0, &transient));
+        assert!(policy.should_retry(1, &transient));
+        assert!(!policy.should_retry(2, &transient));
+        assert!(!policy.should_retry(0, &["error[E0425]: cannot find value `x` in this scope"]));
+
+        assert_eq!(policy.delay(0), Duration::from_millis(500));
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(2), Duration::from_secs(2));
+    }
+}
@@ -3,4 +3,16 @@ use std::path::PathBuf;
 #[derive(Debug, Clone)]
 pub struct Artifact {
     pub target_dir: PathBuf,
+    /// Name of the directory cargo writes the profile's output to.
+    pub profile_dir: String,
+}
+
+impl Artifact {
+    /// Path of the compiled module for the crate named `crate_name`.
+    pub fn wasm_path(&self, crate_name: &str) -> PathBuf {
+        self.target_dir
+            .join("wasm32-wasip1")
+            .join(&self.profile_dir)
+            .join(format!("{}.wasm", crate_name.replace('-', "_")))
+    }
 }
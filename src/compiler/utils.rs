@@ -1,5 +1,6 @@
 use std::process::Stdio;
 use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
 
 
 pub async fn command_exists(cmd: &str) -> bool {
@@ -23,4 +24,12 @@ pub async fn command_exists(cmd: &str) -> bool {
         Ok(output) => output.status.success(),
         Err(_) => false,
     }
+}
+
+/// Resolves once `token` is cancelled, never without one.
+pub async fn cancelled(token: Option<&CancellationToken>) {
+    match token {
+        Some(token) => token.cancelled().await,
+        None => std::future::pending().await,
+    }
 }
\ No newline at end of file
@@ -5,9 +5,9 @@ use crate::{compiler::error::CompilerError, parser::error::ParserError, template
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Compilation error: {0}")]
-    CompilationError(#[from] CompilerError),
+    CompilationError(CompilerError),
     #[error("Parser error: {0}")]
-    ParserError(#[from] ParserError),
+    ParserError(ParserError),
     #[error("Template error: {0}")]
     TemplateError(#[from] TemplateError),
     #[error("Generator error: {0}")]
@@ -16,6 +16,59 @@ pub enum AppError {
     IoError(#[from] std::io::Error),
     #[error("Unknown error: {0}")]
     UnknownError(#[from] anyhow::Error),
+    #[error("Cancelled")]
+    Cancelled,
 }
 
-pub type AppResult<T> = Result<T, AppError>;
\ No newline at end of file
+/// Cancelled parses and builds are reported as [`AppError::Cancelled`],
+/// whichever step noticed.
+impl From<ParserError> for AppError {
+    fn from(error: ParserError) -> Self {
+        match error {
+            ParserError::Cancelled => AppError::Cancelled,
+            error => AppError::ParserError(error),
+        }
+    }
+}
+
+impl From<CompilerError> for AppError {
+    fn from(error: CompilerError) -> Self {
+        match error {
+            CompilerError::Cancelled => AppError::Cancelled,
+            error => AppError::CompilationError(error),
+        }
+    }
+}
+
+impl AppError {
+    /// Stable identifier of the error kind, for callers that need to branch on
+    /// it rather than on the message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::CompilationError(e) => e.code(),
+            AppError::ParserError(e) => e.code(),
+            AppError::TemplateError(e) => e.code(),
+            AppError::GeneratorError(_) => "generator.failed",
+            AppError::IoError(_) => "io",
+            AppError::UnknownError(_) => "unknown",
+            AppError::Cancelled => "cancelled",
+        }
+    }
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_come_from_the_wrapped_error() {
+        assert_eq!(AppError::from(ParserError::MissingModule(Default::default())).code(), "parser.missing_module");
+        assert_eq!(AppError::from(CompilerError::InvalidJobs(0)).code(), "compiler.invalid_jobs");
+        assert_eq!(AppError::from(TemplateError::RenderFailed("x".into())).code(), "template.render_failed");
+        assert_eq!(AppError::GeneratorError("x".into()).code(), "generator.failed");
+        assert_eq!(AppError::from(ParserError::Cancelled).code(), "cancelled");
+        assert_eq!(AppError::from(CompilerError::Cancelled).code(), "cancelled");
+    }
+}
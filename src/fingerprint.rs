@@ -0,0 +1,92 @@
+use std::{
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+use crate::template::types::RenderedFile;
+
+
+/// Hash of everything a build depends on: the generated crate, the build
+/// settings and the modification times of `inputs`. Only compared against
+/// fingerprints of the same installation, so the std hasher is stable enough.
+pub fn compute(files: &[RenderedFile], inputs: &[PathBuf], settings: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+
+    settings.hash(&mut hasher);
+
+    for file in files {
+        file.path.hash(&mut hasher);
+        file.content.hash(&mut hasher);
+    }
+
+    for input in inputs {
+        input.hash(&mut hasher);
+        fs::metadata(input)
+            .ok()
+            .map(|m| (m.len(), m.modified().ok()))
+            .hash(&mut hasher);
+    }
+
+    format!("{:016x}", hasher.finish())
+}
+
+/// Where the fingerprint of the build producing `wasm_path` is kept.
+pub fn fingerprint_path(wasm_path: &Path) -> PathBuf {
+    wasm_path.with_extension("fingerprint")
+}
+
+/// Whether `wasm_path` exists and was built from `fingerprint`.
+pub fn is_up_to_date(wasm_path: &Path, fingerprint: &str) -> bool {
+    wasm_path.is_file()
+        && fs::read_to_string(fingerprint_path(wasm_path)).is_ok_and(|stored| stored.trim() == fingerprint)
+}
+
+pub fn store(wasm_path: &Path, fingerprint: &str) -> std::io::Result<()> {
+    fs::write(fingerprint_path(wasm_path), fingerprint)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn rendered(content: &str) -> Vec<RenderedFile> {
+        vec![RenderedFile { path: "src/lib.rs".into(), content: content.into() }]
+    }
+
+    #[test]
+    fn fingerprint_tracks_files_inputs_and_settings() {
+        let td = TempDir::new().unwrap();
+        let source = td.path().join("api.py");
+        fs::write(&source, "a = 1\n").unwrap();
+
+        let inputs = vec![source.clone()];
+        let base = compute(&rendered("fn a() {}"), &inputs, "release");
+
+        assert_eq!(base, compute(&rendered("fn a() {}"), &inputs, "release"));
+        assert_ne!(base, compute(&rendered("fn b() {}"), &inputs, "release"));
+        assert_ne!(base, compute(&rendered("fn a() {}"), &inputs, "debug"));
+        assert_ne!(base, compute(&rendered("fn a() {}"), &[], "release"));
+
+        fs::write(&source, "a = 12\n").unwrap();
+
+        assert_ne!(base, compute(&rendered("fn a() {}"), &inputs, "release"));
+    }
+
+    #[test]
+    fn skips_only_when_artifact_and_fingerprint_match() {
+        let td = TempDir::new().unwrap();
+        let wasm = td.path().join("my_package.wasm");
+
+        assert!(!is_up_to_date(&wasm, "abc"));
+
+        store(&wasm, "abc").unwrap();
+        assert!(!is_up_to_date(&wasm, "abc"), "missing artifact");
+
+        fs::write(&wasm, b"\0asm").unwrap();
+        assert!(is_up_to_date(&wasm, "abc"));
+        assert!(!is_up_to_date(&wasm, "def"));
+    }
+}
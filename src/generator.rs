@@ -1,40 +1,163 @@
-use std::path::{Path, PathBuf};
+use std::{collections::BTreeMap, path::{Component, Path, PathBuf}, sync::Arc};
+use proc_macro2::TokenStream;
 use tokio::fs;
 
 use crate::{
-    types::{ProjectContext, ProjectMetadata, Module},
+    types::{ProjectContext, ProjectMetadata, Module, Workspace},
     template::{
         traits::TemplateUnit,
         types::RenderedFile,
-        units::{jinja::{JinjaTemplateUnit, context}, codegen::CodegenUnit},
+        units::{
+            jinja::{JinjaTemplateUnit, Value, context},
+            codegen::{CodegenUnit, TokenTransform},
+            manifest::SignatureManifestUnit,
+            frozen::FrozenPackagesUnit,
+        },
         error::TemplateResult,
     },
-    codegen::lib_rs::LibRsGenerator,
-    error::AppResult,
+    codegen::{lib_rs::LibRsGenerator, smoke_tests::SmokeTestGenerator},
+    error::{AppError, AppResult},
 };
 
 
+/// RustPython git revision used by the generated crate unless overridden.
+pub const DEFAULT_RUSTPYTHON_VERSION: &str = "2025-09-01-main-45";
+/// `binmod-mdk` version used by the generated crate unless overridden.
+pub const DEFAULT_MDK_VERSION: &str = "0.1.4";
+/// `[lib] crate-type` of the generated crate unless overridden.
+pub const DEFAULT_CRATE_TYPES: &[&str] = &["cdylib"];
+/// Stack size in bytes of the generated module unless overridden. The wasm
+/// default of 1 MiB is easily exhausted by the interpreter.
+pub const DEFAULT_STACK_SIZE: u64 = 8 * 1024 * 1024;
+
+/// `content`, a workspace's `.cargo/config.toml`, with the stack size of
+/// wasm32-wasip1 modules set. A member's own config is only read when Cargo
+/// runs in its directory, so building it from the workspace root needs the
+/// flag here. Other flags and settings are kept.
+pub fn workspace_cargo_config(content: &str, stack_size: u64) -> AppResult<String> {
+    let invalid = |reason: &str| AppError::GeneratorError(format!(
+        "Unable to set the stack size in the workspace's .cargo/config.toml: {reason}",
+    ));
+    let mut document = content
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|e| invalid(&e.to_string()))?;
+    let target = document
+        .entry("target")
+        .or_insert_with(|| {
+            let mut target = toml_edit::Table::new();
+            target.set_implicit(true);
+            toml_edit::Item::Table(target)
+        })
+        .as_table_like_mut()
+        .ok_or_else(|| invalid("'target' is not a table"))?
+        .entry("wasm32-wasip1")
+        .or_insert(toml_edit::table())
+        .as_table_like_mut()
+        .ok_or_else(|| invalid("'target.wasm32-wasip1' is not a table"))?;
+
+    // rustflags may be an array or a space separated string.
+    let mut rustflags = match target.get("rustflags") {
+        Some(item) if item.is_str() => item.as_str().unwrap_or_default().split_whitespace().map(str::to_string).collect(),
+        Some(item) => item
+            .as_array()
+            .ok_or_else(|| invalid("'target.wasm32-wasip1.rustflags' is not a list"))?
+            .iter()
+            .filter_map(|flag| flag.as_str().map(str::to_string))
+            .collect(),
+        None => Vec::<String>::new(),
+    };
+
+    // Replaces the stack size set by an earlier transpile.
+    if let Some(index) = rustflags.iter().position(|flag| flag.starts_with("link-arg=-zstack-size=")) {
+        rustflags.remove(index);
+
+        if index > 0 && rustflags[index - 1] == "-C" {
+            rustflags.remove(index - 1);
+        }
+    }
+
+    rustflags.extend(["-C".to_string(), format!("link-arg=-zstack-size={}", stack_size)]);
+    target.insert("rustflags", toml_edit::value(rustflags.into_iter().collect::<toml_edit::Array>()));
+
+    Ok(document.to_string())
+}
+
+/// Strip a docstring's indentation like `inspect.cleandoc`: the first line is
+/// trimmed, the common indentation of the others removed.
+fn clean_docstring(docstring: &str) -> String {
+    let mut lines = docstring.lines();
+    let first = lines.next().unwrap_or_default().trim();
+    let rest = lines.collect::<Vec<_>>();
+    let indent = rest
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    std::iter::once(first)
+        .chain(rest.iter().map(|line| line.get(indent..).unwrap_or_default().trim_end()))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
 pub struct ProjectGenerator {
     context: ProjectContext,
+    /// Directory the rendered files are placed under, relative to the output dir.
+    output_prefix: Option<PathBuf>,
+    /// Destinations replacing the default path of individual rendered files.
+    remapped: BTreeMap<PathBuf, PathBuf>,
+    /// Post-processing applied to the generated `src/lib.rs`.
+    token_transform: Option<TokenTransform>,
 }
 
 
 impl ProjectGenerator {
-    pub fn new(context: ProjectContext) -> Self {
-        Self { context }
+    pub fn new(mut context: ProjectContext) -> Self {
+        context.sort_modules();
+        Self { context, output_prefix: None, remapped: BTreeMap::new(), token_transform: None }
     }
 
     pub fn builder() -> ProjectGeneratorBuilder {
         ProjectGeneratorBuilder::default()
     }
 
+    /// The exported functions as documented in the README.
+    fn readme_functions(&self) -> Vec<Value> {
+        self.context.modules
+            .iter()
+            .flat_map(|module| {
+                let import_path = self.context.import_path(module);
+
+                module.module_functions
+                    .iter()
+                    .map(move |function| context! {
+                        name => &function.name,
+                        module => &import_path,
+                        signature => function.to_string(),
+                        parameters => function.parameters
+                            .iter()
+                            .map(|p| context! { name => &p.name, type => p.type_hint.to_string() })
+                            .collect::<Vec<_>>(),
+                        return_type => function.return_type.to_string(),
+                        docstring => function.docstring.as_deref().map(clean_docstring),
+                    })
+            })
+            .collect()
+    }
+
     fn units(&self) -> Vec<Box<dyn TemplateUnit>> {
-        vec![
+        let config = self.context.metadata.py2binmod.as_ref();
+
+        let mut units: Vec<Box<dyn TemplateUnit>> = vec![
             Box::new(JinjaTemplateUnit {
                 template_name: "README.md".into(),
                 context: context! {
                     name => &self.context.metadata.name,
                     description => &self.context.metadata.description,
+                    functions => self.readme_functions(),
                 }
             }),
             Box::new(JinjaTemplateUnit {
@@ -45,21 +168,86 @@ impl ProjectGenerator {
                     description => &self.context.metadata.description,
                     authors => &self.context.metadata.authors,
                     license => &self.context.metadata.license,
+                    license_file => &self.context.metadata.license_file,
+                    urls => &self.context.metadata.urls,
+                    rustpython_version => config
+                        .and_then(|c| c.rustpython_version.as_deref())
+                        .unwrap_or(DEFAULT_RUSTPYTHON_VERSION),
+                    mdk_version => config
+                        .and_then(|c| c.mdk_version.as_deref())
+                        .unwrap_or(DEFAULT_MDK_VERSION),
+                    features => config.map(|c| &c.features),
+                    crate_types => config
+                        .and_then(|c| c.crate_types.clone())
+                        .unwrap_or_else(|| DEFAULT_CRATE_TYPES.iter().map(|t| t.to_string()).collect()),
+                    inherited => self.context.workspace
+                        .as_ref()
+                        .map(|w| w.inherited.clone())
+                        .unwrap_or_default(),
                 }
             }),
             Box::new(JinjaTemplateUnit {
                 template_name: ".cargo/config.toml".into(),
-                context: context! {}
+                context: context! {
+                    stack_size => config
+                        .and_then(|c| c.stack_size)
+                        .unwrap_or(DEFAULT_STACK_SIZE),
+                    workspace => self.context.workspace.is_some(),
+                }
             }),
             Box::new(JinjaTemplateUnit {
                 template_name: "rust-toolchain.toml".into(),
                 context: context! {}
             }),
+            Box::new(JinjaTemplateUnit {
+                template_name: ".gitignore".into(),
+                context: context! {
+                    // The generated crate is a library, so its lockfile is not
+                    // tracked unless the dependencies are pinned. A workspace
+                    // member has none, the workspace's is at its root.
+                    library => self.context.workspace.is_none() && !config.is_some_and(|c| c.emit_cargo_lock),
+                }
+            }),
             Box::new(CodegenUnit {
                 destination: "src/lib.rs".into(),
                 generator: LibRsGenerator::new(self.context.clone()),
-            })
-        ]
+                transform: self.token_transform.clone(),
+            }),
+            Box::new(SignatureManifestUnit {
+                destination: "signatures.json".into(),
+                context: self.context.clone(),
+            }),
+        ];
+
+        if config.is_some_and(|c| c.generate_tests) {
+            units.push(Box::new(CodegenUnit {
+                destination: "src/smoke_tests.rs".into(),
+                generator: SmokeTestGenerator::new(self.context.clone()),
+                transform: None,
+            }));
+        }
+
+        if let Some(packages) = &self.context.frozen_packages {
+            units.push(Box::new(FrozenPackagesUnit {
+                site_packages_dirs: self.context.site_packages_dirs(),
+                packages: packages.clone(),
+            }));
+        }
+
+        units
+    }
+
+    /// Where a file rendered to `path` is written, relative to the output dir.
+    fn destination(&self, path: PathBuf) -> PathBuf {
+        let path = self.remapped
+            .get(&path)
+            .cloned()
+            .unwrap_or(path);
+
+        match &self.output_prefix {
+            Some(prefix) => prefix.join(path),
+            None => path,
+        }
     }
 
     pub fn render(&self) -> AppResult<Vec<RenderedFile>> {
@@ -70,19 +258,25 @@ impl ProjectGenerator {
                 .collect::<TemplateResult<Vec<Vec<RenderedFile>>>>()?
                 .into_iter()
                 .flatten()
+                .map(|file| RenderedFile { path: self.destination(file.path), content: file.content })
                 .collect()
         )
     }
 
     pub async fn generate(&self, output_dir: &Path) -> AppResult<()> {
-        for file in self.render()? {
+        Self::write(&self.render()?, output_dir).await
+    }
+
+    /// Write already rendered files to `output_dir`.
+    pub async fn write(files: &[RenderedFile], output_dir: &Path) -> AppResult<()> {
+        for file in files {
             let output_path = output_dir.join(&file.path);
 
             if let Some(parent) = output_path.parent() {
                 fs::create_dir_all(parent).await?;
             }
 
-            fs::write(&output_path, file.content).await?;
+            fs::write(&output_path, &file.content).await?;
         }
 
         Ok(())
@@ -94,24 +288,45 @@ impl ProjectGenerator {
 pub struct ProjectGeneratorBuilder {
     venv_dir: Option<PathBuf>,
     site_packages_dir: Option<PathBuf>,
+    platlib_dir: Option<PathBuf>,
     project_dir: Option<PathBuf>,
     module_root: Option<PathBuf>,
     import_root: Option<PathBuf>,
     module_name: Option<String>,
     metadata: Option<ProjectMetadata>,
     modules: Vec<Module>,
+    frozen_packages: Option<Vec<String>>,
+    workspace: Option<Workspace>,
+    output_prefix: Option<PathBuf>,
+    remapped: BTreeMap<PathBuf, PathBuf>,
+    token_transform: Option<TokenTransform>,
+}
+
+/// Check that `path` stays inside the output dir once joined to it.
+fn relative_destination(path: PathBuf) -> AppResult<PathBuf> {
+    if path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+        Ok(path)
+    } else {
+        Err(AppError::GeneratorError(format!(
+            "The destination '{}' must be relative to the output directory",
+            path.display(),
+        )))
+    }
 }
 
 impl ProjectGeneratorBuilder {
     pub fn context(mut self, context: ProjectContext) -> Self {
         self.venv_dir = Some(context.venv_dir);
         self.site_packages_dir = Some(context.site_packages_dir);
+        self.platlib_dir = context.platlib_dir;
         self.project_dir = Some(context.project_dir);
         self.module_root = Some(context.module_root);
         // self.import_root = Some(context.import_root);
         self.module_name = Some(context.module_name);
         self.metadata = Some(context.metadata);
         self.modules = context.modules;
+        self.frozen_packages = context.frozen_packages;
+        self.workspace = context.workspace;
         self
     }
 
@@ -125,6 +340,11 @@ impl ProjectGeneratorBuilder {
         self
     }
 
+    pub fn platlib_dir(mut self, platlib_dir: impl Into<PathBuf>) -> Self {
+        self.platlib_dir = Some(platlib_dir.into());
+        self
+    }
+
     pub fn project_dir(mut self, project_dir: impl Into<PathBuf>) -> Self {
         self.project_dir = Some(project_dir.into());
         self
@@ -163,18 +383,709 @@ impl ProjectGeneratorBuilder {
         self
     }
 
-    pub fn build(self) -> ProjectGenerator {
-        ProjectGenerator::new(
-            ProjectContext {
-                venv_dir: self.venv_dir.expect("Virtual environment directory is required"),
-                site_packages_dir: self.site_packages_dir.expect("Site-packages directory is required"),
-                project_dir: self.project_dir.expect("Project directory is required"),
-                // import_root: self.import_root.expect("Import root directory is required"),
-                module_root: self.module_root.expect("Module root directory is required"),
-                module_name: self.module_name.expect("Module name is required"),
-                metadata: self.metadata.expect("Metadata is required"),
-                modules: self.modules
-            }
+    pub fn frozen_packages<I>(mut self, packages: I) -> Self
+    where
+        I: IntoIterator<Item = String>,
+    {
+        self.frozen_packages = Some(packages.into_iter().collect());
+        self
+    }
+
+    pub fn workspace(mut self, workspace: Workspace) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// Place every rendered file under `prefix`, e.g. `crates/foo`.
+    pub fn output_prefix(mut self, prefix: impl Into<PathBuf>) -> Self {
+        self.output_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Write the file rendered to `from`, e.g. `src/lib.rs`, to `to` instead.
+    /// Applied before the output prefix.
+    pub fn remap(mut self, from: impl Into<PathBuf>, to: impl Into<PathBuf>) -> Self {
+        self.remapped.insert(from.into(), to.into());
+        self
+    }
+
+    /// Rewrite the tokens of the generated `src/lib.rs` with `transform`.
+    /// It runs before the code is formatted with `prettyplease`.
+    pub fn token_transform<F>(mut self, transform: F) -> Self
+    where
+        F: Fn(TokenStream) -> TokenStream + Send + Sync + 'static,
+    {
+        self.token_transform = Some(Arc::new(transform));
+        self
+    }
+
+    pub fn build(self) -> AppResult<ProjectGenerator> {
+        let missing = |field: &str| AppError::GeneratorError(format!("The {field} is required"));
+        let context = ProjectContext {
+            venv_dir: self.venv_dir.ok_or_else(|| missing("virtual environment"))?,
+            site_packages_dir: self.site_packages_dir.ok_or_else(|| missing("site-packages directory"))?,
+            platlib_dir: self.platlib_dir,
+            project_dir: self.project_dir.ok_or_else(|| missing("project directory"))?,
+            // import_root: self.import_root.expect("Import root directory is required"),
+            module_root: self.module_root.ok_or_else(|| missing("module root"))?,
+            module_name: self.module_name.ok_or_else(|| missing("module name"))?,
+            metadata: self.metadata.ok_or_else(|| missing("project metadata"))?,
+            modules: self.modules,
+            frozen_packages: self.frozen_packages,
+            workspace: self.workspace,
+            layout_trace: Vec::new(),
+            layout_warnings: Vec::new(),
+            type_warnings: Vec::new(),
+        };
+        context.validate()?;
+
+        let mut generator = ProjectGenerator::new(context);
+        generator.output_prefix = self.output_prefix
+            .map(relative_destination)
+            .transpose()?;
+        generator.remapped = self.remapped
+            .into_iter()
+            .map(|(from, to)| Ok((from, relative_destination(to)?)))
+            .collect::<AppResult<_>>()?;
+        generator.token_transform = self.token_transform;
+
+        Ok(generator)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use crate::types::{
+        Py2BinmodConfig, ProjectUrls, ModuleFunction, ModuleFunctions, Parameter, ParameterType,
+    };
+
+    /// A project directory with the layout the generated crate is built from.
+    fn make_project() -> TempDir {
+        let td = TempDir::new().unwrap();
+        std::fs::create_dir_all(td.path().join("venv/lib/python3.11/site-packages")).unwrap();
+        std::fs::create_dir_all(td.path().join("my_package")).unwrap();
+        td
+    }
+
+    /// A builder for the project at `project`, still missing its metadata.
+    fn builder(project: &Path) -> ProjectGeneratorBuilder {
+        ProjectGenerator::builder()
+            .venv_dir(project.join("venv"))
+            .site_packages_dir(project.join("venv/lib/python3.11/site-packages"))
+            .project_dir(project)
+            .module_root(project.join("my_package"))
+            .module_name("my_package")
+    }
+
+    fn metadata() -> ProjectMetadata {
+        ProjectMetadata {
+            name: "my_package".into(),
+            version: "0.1.0".into(),
+            requires_python: None,
+            description: None,
+            authors: vec![],
+            license: None,
+            license_file: None,
+            urls: Default::default(),
+            optional_dependencies: Default::default(),
+            py2binmod: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn generator_with(project: &Path, metadata: ProjectMetadata) -> ProjectGenerator {
+        builder(project)
+            .metadata(metadata)
+            .build()
+            .unwrap()
+    }
+
+    fn generator(project: &Path) -> ProjectGenerator {
+        generator_with(project, metadata())
+    }
+
+    fn rendered(generator: &ProjectGenerator, path: &str) -> Option<String> {
+        generator
+            .render()
+            .unwrap()
+            .into_iter()
+            .find(|file| file.path == Path::new(path))
+            .map(|file| file.content)
+    }
+
+    #[test]
+    fn builder_reports_missing_fields() {
+        let project = make_project();
+        let err = ProjectGenerator::builder()
+            .venv_dir(project.path().join("venv"))
+            .site_packages_dir(project.path().join("venv/lib/python3.11/site-packages"))
+            .project_dir(project.path())
+            .module_root(project.path().join("my_package"))
+            .metadata(metadata())
+            .build()
+            .err()
+            .unwrap();
+
+        assert_eq!(err.to_string(), "Generator error: The module name is required");
+        assert!(builder(project.path()).build().err().unwrap().to_string().contains("project metadata"));
+    }
+
+    #[test]
+    fn builder_validates_the_context() {
+        let project = make_project();
+        let err = builder(project.path())
+            .module_root(project.path().join("missing_package"))
+            .metadata(metadata())
+            .build()
+            .err()
+            .unwrap();
+
+        assert_eq!(err.code(), "generator.failed");
+        assert!(err.to_string().contains("module root"), "{err}");
+        assert!(err.to_string().contains("missing_package"), "{err}");
+
+        let err = builder(project.path())
+            .metadata(ProjectMetadata { version: "".into(), ..metadata() })
+            .build()
+            .err()
+            .unwrap();
+
+        assert!(err.to_string().contains("project version is empty"), "{err}");
+
+        let err = builder(project.path())
+            .platlib_dir(project.path().join("venv/lib64/python3.11/site-packages"))
+            .metadata(metadata())
+            .build()
+            .err()
+            .unwrap();
+
+        assert!(err.to_string().contains("platlib directory"), "{err}");
+
+        // Generation never reads the venv itself, only its site-packages.
+        assert!(
+            builder(project.path())
+                .venv_dir(project.path().join("missing_venv"))
+                .metadata(metadata())
+                .build()
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn output_prefix_relocates_rendered_files() {
+        let project = make_project();
+        let default_paths = generator(project.path())
+            .render()
+            .unwrap()
+            .into_iter()
+            .map(|file| file.path)
+            .collect::<Vec<_>>();
+        let generator = builder(project.path())
+            .metadata(metadata())
+            .output_prefix("crates/foo")
+            .remap("src/lib.rs", "src/module.rs")
+            .build()
+            .unwrap();
+        let paths = generator
+            .render()
+            .unwrap()
+            .into_iter()
+            .map(|file| file.path)
+            .collect::<Vec<_>>();
+
+        assert_eq!(paths.len(), default_paths.len());
+        assert!(paths.iter().all(|path| path.starts_with("crates/foo")), "{paths:?}");
+        assert!(paths.contains(&PathBuf::from("crates/foo/Cargo.toml")));
+        assert!(paths.contains(&PathBuf::from("crates/foo/src/module.rs")));
+        assert!(!paths.contains(&PathBuf::from("crates/foo/src/lib.rs")));
+
+        let td = TempDir::new().unwrap();
+        generator.generate(td.path()).await.unwrap();
+
+        assert!(td.path().join("crates/foo/Cargo.toml").is_file());
+        assert!(!td.path().join("Cargo.toml").exists());
+    }
+
+    #[test]
+    fn destinations_stay_in_the_output_dir() {
+        let project = make_project();
+        for prefix in ["/crates/foo", "../foo", "crates/../../foo"] {
+            let err = builder(project.path())
+                .metadata(metadata())
+                .output_prefix(prefix)
+                .build()
+                .err()
+                .unwrap();
+
+            assert!(err.to_string().contains("must be relative"), "{prefix}: {err}");
+        }
+
+        assert!(builder(project.path()).metadata(metadata()).remap("src/lib.rs", "../lib.rs").build().is_err());
+    }
+
+    #[test]
+    fn token_transform_rewrites_lib_rs() {
+        let project = make_project();
+        let transformed = builder(project.path())
+            .metadata(metadata())
+            .token_transform(|tokens| quote::quote! {
+                #![allow(clippy::all)]
+                #tokens
+            })
+            .build()
+            .unwrap();
+        let content = rendered(&transformed, "src/lib.rs").expect("src/lib.rs was not rendered");
+
+        assert!(content.starts_with("#![allow(clippy::all)]\n"), "{content}");
+        assert!(!rendered(&generator(project.path()), "src/lib.rs").unwrap().contains("clippy::all"));
+    }
+
+    #[test]
+    fn renders_gitignore() {
+        let project = make_project();
+        let content = rendered(&generator(project.path()), ".gitignore").expect(".gitignore was not rendered");
+        let lines = content.lines().collect::<Vec<_>>();
+
+        assert!(lines.contains(&"/target"));
+        assert!(lines.contains(&"/artifacts"));
+        assert!(lines.contains(&"Cargo.lock"));
+    }
+
+    #[test]
+    fn gitignore_tracks_pinned_lockfile() {
+        let project = make_project();
+        let content = rendered(
+            &generator_with(project.path(), ProjectMetadata {
+                py2binmod: Some(Py2BinmodConfig {
+                    emit_cargo_lock: true,
+                    ..Default::default()
+                }),
+                ..metadata()
+            }),
+            ".gitignore",
+        )
+        .unwrap();
+        let lines = content.lines().collect::<Vec<_>>();
+
+        assert!(lines.contains(&"/target"));
+        assert!(!lines.contains(&"Cargo.lock"));
+    }
+
+    #[test]
+    fn cargo_toml_uses_default_dependency_versions() {
+        let project = make_project();
+        let content = rendered(&generator(project.path()), "Cargo.toml").unwrap();
+
+        assert!(content.contains(&format!("rev = \"{}\"", DEFAULT_RUSTPYTHON_VERSION)));
+        assert!(content.contains(&format!("binmod-mdk = \"{}\"", DEFAULT_MDK_VERSION)));
+    }
+
+    #[test]
+    fn cargo_toml_uses_configured_dependency_versions() {
+        let project = make_project();
+        let content = rendered(
+            &generator_with(project.path(), ProjectMetadata {
+                py2binmod: Some(Py2BinmodConfig {
+                    rustpython_version: Some("2025-10-01-main-50".into()),
+                    mdk_version: Some("0.2.0".into()),
+                    ..Default::default()
+                }),
+                ..metadata()
+            }),
+            "Cargo.toml",
+        )
+        .unwrap();
+
+        assert_eq!(content.matches("rev = \"2025-10-01-main-50\"").count(), 3);
+        assert!(content.contains("binmod-mdk = \"0.2.0\""));
+        assert!(!content.contains(DEFAULT_RUSTPYTHON_VERSION));
+    }
+
+    #[test]
+    fn cargo_toml_links_project_urls() {
+        let project = make_project();
+        let content = rendered(
+            &generator_with(project.path(), ProjectMetadata {
+                urls: ProjectUrls {
+                    homepage: Some("https://example.com".into()),
+                    repository: Some("https://github.com/example/demo".into()),
+                    documentation: None,
+                },
+                ..metadata()
+            }),
+            "Cargo.toml",
+        )
+        .unwrap();
+        let manifest = content.parse::<toml::Table>().unwrap();
+
+        assert_eq!(manifest["package"]["homepage"].as_str(), Some("https://example.com"));
+        assert_eq!(manifest["package"]["repository"].as_str(), Some("https://github.com/example/demo"));
+        assert!(manifest["package"].get("documentation").is_none());
+    }
+
+    #[test]
+    fn cargo_toml_lists_features() {
+        let project = make_project();
+        assert!(!rendered(&generator(project.path()), "Cargo.toml").unwrap().contains("[features]"));
+
+        let content = rendered(
+            &generator_with(project.path(), ProjectMetadata {
+                py2binmod: Some(Py2BinmodConfig {
+                    features: [
+                        ("images".to_string(), vec!["resize".to_string()]),
+                        ("text".to_string(), vec!["greet".to_string()]),
+                    ].into(),
+                    ..Default::default()
+                }),
+                ..metadata()
+            }),
+            "Cargo.toml",
+        )
+        .unwrap();
+        let features = content
+            .parse::<toml::Table>()
+            .unwrap()["features"]
+            .as_table()
+            .unwrap()
+            .clone();
+
+        assert_eq!(features["default"].as_array().unwrap().len(), 2);
+        assert_eq!(features["images"].as_array().unwrap().len(), 0);
+        assert_eq!(features["text"].as_array().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn readme_documents_exported_functions() {
+        let project = make_project();
+        assert_eq!(rendered(&generator(project.path()), "README.md").unwrap(), "# my_package");
+
+        let generator = builder(project.path())
+            .metadata(metadata())
+            .module(Module {
+                name: "text".into(),
+                file_path: project.path().join("my_package/text.py"),
+                module_functions: ModuleFunctions::new(vec![
+                    ModuleFunction {
+                        name: "greet".into(),
+                        docstring: Some("Greet someone.\n\n        Returns the greeting.\n    ".into()),
+                        parameters: vec![Parameter { name: "name".into(), type_hint: ParameterType::String, kind: Default::default() }],
+                        return_type: ParameterType::String,
+                    },
+                    ModuleFunction {
+                        name: "ping".into(),
+                        docstring: None,
+                        parameters: vec![],
+                        return_type: ParameterType::None,
+                    },
+                ]),
+                host_functions: None,
+                structs: vec![],
+            })
+            .build()
+            .unwrap();
+
+        let readme = rendered(&generator, "README.md").unwrap();
+
+        assert!(readme.contains("## API"), "{readme}");
+        assert!(readme.contains("### `greet(name: str) -> str`\n\nExported from `my_package.text`."), "{readme}");
+        assert!(readme.contains("| `name` | `str` |"), "{readme}");
+        assert!(readme.contains("Greet someone.\n\nReturns the greeting."), "{readme}");
+        assert!(readme.contains("### `ping() -> None`"), "{readme}");
+        assert!(readme.ends_with("Returns `None`.\n\n_Undocumented._"), "{readme}");
+    }
+
+    #[test]
+    fn signature_manifest_lists_exported_functions() {
+        let project = make_project();
+        let module = |file_path: &str, functions: Vec<ModuleFunction>| Module {
+            name: "my_package".into(),
+            file_path: project.path().join(file_path),
+            module_functions: ModuleFunctions::new(functions),
+            host_functions: None,
+            structs: vec![],
+        };
+        let function = |name: &str, parameters: Vec<Parameter>, return_type: ParameterType| ModuleFunction {
+            name: name.into(),
+            docstring: None,
+            parameters,
+            return_type,
+        };
+
+        let generator = builder(project.path())
+            .metadata(metadata())
+            .module(module("my_package/__init__.py", vec![
+                function(
+                    "add",
+                    vec![
+                        Parameter { name: "a".into(), type_hint: ParameterType::Integer, kind: Default::default() },
+                        Parameter { name: "b".into(), type_hint: ParameterType::Integer, kind: Default::default() },
+                    ],
+                    ParameterType::Integer,
+                ),
+            ]))
+            .module(module("my_package/text.py", vec![
+                function(
+                    "words",
+                    vec![Parameter { name: "text".into(), type_hint: ParameterType::String, kind: Default::default() }],
+                    ParameterType::List(Box::new(ParameterType::String)),
+                ),
+            ]))
+            .build()
+            .unwrap();
+
+        let manifest: serde_json::Value = serde_json::from_str(
+            &rendered(&generator, "signatures.json").expect("signatures.json was not rendered"),
+        )
+        .unwrap();
+
+        assert_eq!(manifest["name"], "my_package");
+        assert_eq!(
+            manifest["functions"],
+            serde_json::json!([
+                {
+                    "name": "add",
+                    "module": "my_package",
+                    "docstring": null,
+                    "parameters": [
+                        { "name": "a", "type_hint": "Integer", "kind": "PositionalOrKeyword" },
+                        { "name": "b", "type_hint": "Integer", "kind": "PositionalOrKeyword" },
+                    ],
+                    "return_type": "Integer",
+                },
+                {
+                    "name": "words",
+                    "module": "my_package.text",
+                    "docstring": null,
+                    "parameters": [
+                        { "name": "text", "type_hint": "String", "kind": "PositionalOrKeyword" },
+                    ],
+                    "return_type": { "List": "String" },
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn signature_manifest_follows_the_import_prefix() {
+        let project = make_project();
+        let module = |file_path: &str, name: &str| Module {
+            name: "my_package".into(),
+            file_path: project.path().join(file_path),
+            module_functions: ModuleFunctions::new(vec![ModuleFunction {
+                name: name.into(),
+                docstring: None,
+                parameters: vec![],
+                return_type: ParameterType::None,
+            }]),
+            host_functions: None,
+            structs: vec![],
+        };
+        let generator = builder(project.path())
+            .metadata(ProjectMetadata {
+                py2binmod: Some(Py2BinmodConfig {
+                    import_prefix: Some("vendored.my_package".into()),
+                    ..Default::default()
+                }),
+                ..metadata()
+            })
+            .module(module("my_package/__init__.py", "ping"))
+            .module(module("my_package/text.py", "words"))
+            .build()
+            .unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(
+            &rendered(&generator, "signatures.json").unwrap(),
+        )
+        .unwrap();
+        let modules = manifest["functions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f["module"].as_str().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(modules, ["vendored.my_package", "vendored.my_package.text"]);
+    }
+
+    #[test]
+    fn smoke_tests_are_generated_on_request() {
+        let project = make_project();
+        assert!(rendered(&generator(project.path()), "src/smoke_tests.rs").is_none());
+
+        let generator = generator_with(project.path(), ProjectMetadata {
+            py2binmod: Some(Py2BinmodConfig {
+                generate_tests: true,
+                ..Default::default()
+            }),
+            ..metadata()
+        });
+
+        assert!(rendered(&generator, "src/smoke_tests.rs").unwrap().contains("fn exported_functions_are_callable()"));
+        assert!(rendered(&generator, "src/lib.rs").unwrap().contains("mod smoke_tests;"));
+    }
+
+    #[test]
+    fn cargo_toml_lists_authors() {
+        let project = make_project();
+        let content = rendered(
+            &generator_with(project.path(), ProjectMetadata {
+                authors: vec!["Ada Lovelace <ada@example.com>".into(), "team@example.com".into()],
+                ..metadata()
+            }),
+            "Cargo.toml",
+        )
+        .unwrap();
+        let manifest = content.parse::<toml::Table>().unwrap();
+
+        assert_eq!(
+            manifest["package"]["authors"],
+            toml::Value::Array(vec![
+                "Ada Lovelace <ada@example.com>".into(),
+                "team@example.com".into(),
+            ]),
+        );
+    }
+
+    #[test]
+    fn cargo_toml_uses_configured_crate_types() {
+        let project = make_project();
+        let crate_types = |config: Option<Py2BinmodConfig>| {
+            let content = rendered(
+                &generator_with(project.path(), ProjectMetadata { py2binmod: config, ..metadata() }),
+                "Cargo.toml",
+            )
+            .unwrap();
+
+            content.parse::<toml::Table>().unwrap()["lib"]["crate-type"].clone()
+        };
+
+        assert_eq!(crate_types(None), toml::Value::Array(vec!["cdylib".into()]));
+        assert_eq!(
+            crate_types(Some(Py2BinmodConfig {
+                crate_types: Some(vec!["cdylib".into(), "rlib".into()]),
+                ..Default::default()
+            })),
+            toml::Value::Array(vec!["cdylib".into(), "rlib".into()]),
+        );
+    }
+
+    #[test]
+    fn cargo_toml_uses_spdx_license() {
+        let project = make_project();
+        let content = rendered(
+            &generator_with(project.path(), ProjectMetadata {
+                license: Some("MIT OR Apache-2.0".into()),
+                ..metadata()
+            }),
+            "Cargo.toml",
+        )
+        .unwrap();
+        let manifest = content.parse::<toml::Table>().unwrap();
+
+        assert_eq!(manifest["package"]["license"].as_str(), Some("MIT OR Apache-2.0"));
+        assert!(manifest["package"].get("license-file").is_none());
+        assert!(content.contains("\n\n[lib]"));
+    }
+
+    #[test]
+    fn cargo_toml_uses_license_file() {
+        let project = make_project();
+        let content = rendered(
+            &generator_with(project.path(), ProjectMetadata {
+                license_file: Some(PathBuf::from("/project/LICENSE")),
+                ..metadata()
+            }),
+            "Cargo.toml",
+        )
+        .unwrap();
+        let manifest = content.parse::<toml::Table>().unwrap();
+
+        assert_eq!(manifest["package"]["license-file"].as_str(), Some("/project/LICENSE"));
+        assert!(manifest["package"].get("license").is_none());
+    }
+
+    #[test]
+    fn cargo_toml_inherits_from_the_workspace() {
+        let project = make_project();
+        let standalone = rendered(&generator(project.path()), "Cargo.toml").unwrap();
+        let generator = ProjectGenerator::builder()
+            .context(generator(project.path()).context)
+            .workspace(Workspace {
+                root: "/workspace".into(),
+                inherited: vec!["version".into(), "edition".into(), "license".into()],
+            })
+            .build()
+            .unwrap();
+        let member = rendered(&generator, "Cargo.toml").unwrap();
+
+        assert_ne!(standalone, member);
+
+        let standalone = standalone.parse::<toml::Table>().unwrap();
+        let member = member.parse::<toml::Table>().unwrap();
+
+        assert_eq!(standalone["package"]["version"].as_str(), Some("0.1.0"));
+        assert_eq!(standalone["package"]["edition"].as_str(), Some("2024"));
+
+        for key in ["version", "edition", "license"] {
+            assert_eq!(member["package"][key]["workspace"].as_bool(), Some(true), "{key}");
+        }
+
+        assert_eq!(member["package"]["name"].as_str(), Some("my_package"));
+        assert!(member["package"]["authors"].is_array());
+        assert_eq!(member["dependencies"], standalone["dependencies"]);
+
+        let config = rendered(&generator, ".cargo/config.toml").unwrap();
+        assert!(config.starts_with("# Kept with the crate"), "{config}");
+        assert!(!config.contains("[target.wasm32-wasip1]"), "{config}");
+        assert!(config.parse::<toml::Table>().is_ok(), "{config}");
+
+        let gitignore = rendered(&generator, ".gitignore").unwrap();
+        assert!(!gitignore.lines().any(|line| line == "Cargo.lock"), "{gitignore}");
+    }
+
+    #[test]
+    fn workspace_cargo_config_sets_the_stack_size() {
+        let stack_size = |content: &str| {
+            let config = content.parse::<toml::Table>().unwrap();
+            config["target"]["wasm32-wasip1"]["rustflags"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|flag| flag.as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let created = workspace_cargo_config("", 1024).unwrap();
+        assert_eq!(stack_size(&created), ["-C", "link-arg=-zstack-size=1024"]);
+        assert!(created.starts_with("[target.wasm32-wasip1]"), "{created}");
+
+        let existing = "# shared\n[build]\njobs = 2\n\n[target.wasm32-wasip1]\nrustflags = \"-C opt-level=s -C link-arg=-zstack-size=1024\"\n";
+        let updated = workspace_cargo_config(existing, 2048).unwrap();
+        assert!(updated.starts_with("# shared\n[build]\njobs = 2\n"), "{updated}");
+        assert_eq!(stack_size(&updated), ["-C", "opt-level=s", "-C", "link-arg=-zstack-size=2048"]);
+
+        assert!(workspace_cargo_config("target = 1\n", 1024).is_err());
+    }
+
+    #[test]
+    fn cargo_config_sets_stack_size() {
+        let project = make_project();
+        let content = rendered(&generator(project.path()), ".cargo/config.toml").unwrap();
+        assert!(content.contains(&format!("link-arg=-zstack-size={}", DEFAULT_STACK_SIZE)));
+
+        let content = rendered(
+            &generator_with(project.path(), ProjectMetadata {
+                py2binmod: Some(Py2BinmodConfig {
+                    stack_size: Some(16777216),
+                    ..Default::default()
+                }),
+                ..metadata()
+            }),
+            ".cargo/config.toml",
         )
+        .unwrap();
+        assert!(content.contains("link-arg=-zstack-size=16777216"));
     }
 }
@@ -11,4 +11,6 @@ pub mod template;
 pub mod commands;
 pub mod error;
 pub mod ui;
+pub mod watcher;
+pub mod fingerprint;
 mod py;
@@ -1,18 +1,90 @@
 use anyhow::{Context, anyhow};
-use ruff_python_parser::parse_module;
+use ruff_python_parser::{parse_expression, parse_module};
 use ruff_python_ast::{self as ast};
-use std::path::Path;
+use std::{collections::HashMap, path::Path};
 use tokio::fs;
 
 use crate::{
     types::{
         ModuleFunction, ModuleFunctions, HostFunction,
-        HostFunctions, Module,
+        HostFunctions, Module, ParameterType, StructDef, StructField, StructKind,
     },
-    parser::{error::ParserResult, traits::TryFromAst},
+    parser::{error::{ParserError, ParserResult}, source::decode_source, traits::TryFromAst},
 };
 
 
+/// Local names bound by `from ... import name as alias` or `import name as
+/// alias`, mapped to the imported name, so aliased decorators and annotations
+/// resolve to the ones we look for.
+type ImportAliases = HashMap<String, String>;
+
+/// Rewrite an annotation into the form [`ParameterType`] parses: names bound
+/// by an aliased import become the imported name, and quoted annotations, as
+/// some tools write every annotation of a module using `from __future__
+/// import annotations`, the expression they quote. Quotes that don't hold an
+/// expression are left for the type parser to report.
+fn resolve_annotation(expr: &mut ast::Expr, aliases: &ImportAliases) {
+    match expr {
+        ast::Expr::Name(name) => {
+            if let Some(imported) = aliases.get(name.id.as_str()) {
+                name.id = ast::name::Name::new(imported.as_str());
+            }
+        },
+        ast::Expr::Attribute(attr) => resolve_annotation(&mut attr.value, aliases),
+        ast::Expr::Subscript(sub) => {
+            resolve_annotation(&mut sub.value, aliases);
+            resolve_annotation(&mut sub.slice, aliases);
+        },
+        ast::Expr::Tuple(tuple) => tuple.elts.iter_mut().for_each(|e| resolve_annotation(e, aliases)),
+        ast::Expr::List(list) => list.elts.iter_mut().for_each(|e| resolve_annotation(e, aliases)),
+        ast::Expr::BinOp(binop) => {
+            resolve_annotation(&mut binop.left, aliases);
+            resolve_annotation(&mut binop.right, aliases);
+        },
+        ast::Expr::StringLiteral(s) => {
+            if let Ok(parsed) = parse_expression(s.value.to_str().trim()) {
+                *expr = parsed.into_expr();
+                resolve_annotation(expr, aliases);
+            }
+        },
+        _ => {},
+    }
+}
+
+/// The type of `annotation` once resolved by [`resolve_annotation`].
+fn annotation_type(annotation: &ast::Expr, aliases: &ImportAliases) -> anyhow::Result<ParameterType> {
+    let mut annotation = annotation.clone();
+    resolve_annotation(&mut annotation, aliases);
+
+    ParameterType::try_from_ast(&annotation)
+}
+
+/// A copy of `func` with the annotations of its parameters and return value
+/// resolved by [`resolve_annotation`].
+fn resolve_signature(func: &ast::StmtFunctionDef, aliases: &ImportAliases) -> ast::StmtFunctionDef {
+    let mut func = func.clone();
+    let parameters = &mut *func.parameters;
+
+    for parameter in parameters.posonlyargs
+        .iter_mut()
+        .chain(parameters.args.iter_mut())
+        .chain(parameters.kwonlyargs.iter_mut())
+        .map(|p| &mut p.parameter)
+        .chain(parameters.vararg.as_deref_mut())
+        .chain(parameters.kwarg.as_deref_mut())
+    {
+        if let Some(annotation) = parameter.annotation.as_deref_mut() {
+            resolve_annotation(annotation, aliases);
+        }
+    }
+
+    if let Some(returns) = func.returns.as_deref_mut() {
+        resolve_annotation(returns, aliases);
+    }
+
+    func
+}
+
 pub struct AstAnalyzer;
 
 impl AstAnalyzer {
@@ -21,26 +93,52 @@ impl AstAnalyzer {
     }
 
     pub async fn analyze_file(&self, file_path: &Path) -> ParserResult<Option<Module>> {
-        let content = fs::read_to_string(file_path).await?;
+        let content = decode_source(file_path, &fs::read(file_path).await?)?;
         let module_ast = parse_module(&content)
             .map(|m| m.into_suite())
             .context(format!("Failed to parse Python module: {:?}", file_path))?;
 
+        self.analyze_module(file_path, &module_ast)
+            .map_err(|e| e.in_file(file_path))
+    }
+
+    fn analyze_module(&self, file_path: &Path, module_ast: &[ast::Stmt]) -> ParserResult<Option<Module>> {
+        let aliases = self.collect_import_aliases(module_ast);
         let mut module_functions = Vec::new();
         let mut host_functions = None;
+        let mut structs = Vec::new();
+
+        for stmt in module_ast {
+            if let ast::Stmt::ClassDef(class) = stmt {
+                self.reject_mod_fn_methods(class, &aliases)?;
+            }
 
-        for stmt in &module_ast {
             match stmt {
                 ast::Stmt::FunctionDef(func) => {
-                    if self.has_func_decorator(func, "mod_fn") {
+                    if self.has_func_decorator(func, "mod_fn", &aliases) && self.is_exported(func, &aliases)? {
                         module_functions.push(
-                            ModuleFunction::try_from_ast(func)?
+                            ModuleFunction::try_from_ast(&resolve_signature(func, &aliases))?
                         );
                     }
                 }
+                ast::Stmt::ClassDef(class) if class.bases().iter().any(|b| self.is_name(b, "NamedTuple", &aliases)) => {
+                    structs.push(self.parse_named_tuple_class(class, &aliases)?);
+                }
+                ast::Stmt::ClassDef(class) if class.bases().iter().any(|b| self.is_name(b, "TypedDict", &aliases)) => {
+                    structs.push(self.parse_typed_dict_class(class, &aliases)?);
+                }
+                ast::Stmt::Assign(assign) => {
+                    if let (Some(ast::Expr::Name(target)), ast::Expr::Call(call)) = (assign.targets.first(), &*assign.value) {
+                        if self.is_name(&call.func, "NamedTuple", &aliases) {
+                            structs.push(self.parse_named_tuple_call(target.id.as_str(), call, &aliases)?);
+                        } else if self.is_name(&call.func, "TypedDict", &aliases) {
+                            structs.push(self.parse_typed_dict_call(target.id.as_str(), call, &aliases)?);
+                        }
+                    }
+                }
                 ast::Stmt::ClassDef(class) => {
-                    if self.has_class_decorator(class, "host_fns") {
-                        if let Some((namespace, host_fns)) = self.parse_host_fns_class(class)? {
+                    if self.has_class_decorator(class, "host_fns", &aliases) {
+                        if let Some((namespace, host_fns)) = self.parse_host_fns_class(class, &aliases)? {
                             host_functions = Some((namespace, host_fns))
                         }
                     }
@@ -49,7 +147,7 @@ impl AstAnalyzer {
             }
         }
 
-        if module_functions.is_empty() && host_functions.is_none() {
+        if module_functions.is_empty() && host_functions.is_none() && structs.is_empty() {
             return Ok(None);
         }
 
@@ -63,42 +161,227 @@ impl AstAnalyzer {
             module_functions: ModuleFunctions::new(module_functions),
             host_functions: host_functions
                 .map(|(namespace, fns)| HostFunctions::new(namespace, fns)),
+            structs,
         }))
     }
 
-    fn has_func_decorator(&self, func: &ast::StmtFunctionDef, name: &str) -> bool {
+    fn collect_import_aliases(&self, suite: &[ast::Stmt]) -> ImportAliases {
+        suite
+            .iter()
+            .filter_map(|stmt| match stmt {
+                ast::Stmt::ImportFrom(import) => Some(&import.names),
+                ast::Stmt::Import(import) => Some(&import.names),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|alias| {
+                alias.asname
+                    .as_ref()
+                    .map(|asname| (asname.to_string(), alias.name.to_string()))
+            })
+            .collect()
+    }
+
+    fn has_func_decorator(&self, func: &ast::StmtFunctionDef, name: &str, aliases: &ImportAliases) -> bool {
         func
             .decorator_list
             .iter()
             .any(|decorator| {
-                self.is_decorator_name(decorator, name)
+                self.is_decorator_name(decorator, name, aliases)
             })
     }
 
-    fn has_class_decorator(&self, class: &ast::StmtClassDef, name: &str) -> bool {
+    fn has_class_decorator(&self, class: &ast::StmtClassDef, name: &str, aliases: &ImportAliases) -> bool {
         class
             .decorator_list
             .iter()
             .any(|decorator| {
-                self.is_decorator_name(decorator, name)
+                self.is_decorator_name(decorator, name, aliases)
             })
     }
 
-    fn is_decorator_name(&self, decorator: &ast::Decorator, name: &str) -> bool {
-        match &decorator.expression {
-            ast::Expr::Name(n) => n.id.as_str() == name,
+    fn is_decorator_name(&self, decorator: &ast::Decorator, name: &str, aliases: &ImportAliases) -> bool {
+        let expr = match &decorator.expression {
+            ast::Expr::Call(call) => &*call.func,
+            expr => expr,
+        };
+
+        self.is_name(expr, name, aliases)
+    }
+
+    /// Whether `expr` refers to `name`, bare, aliased or as an attribute (`typing.NamedTuple`).
+    fn is_name(&self, expr: &ast::Expr, name: &str, aliases: &ImportAliases) -> bool {
+        match expr {
+            ast::Expr::Name(n) => {
+                let id = n.id.as_str();
+                aliases.get(id).map_or(id, String::as_str) == name
+            },
             ast::Expr::Attribute(attr) => attr.attr.as_str() == name,
-            ast::Expr::Call(call) => {
-                match &*call.func {
-                    ast::Expr::Name(n) => n.id.as_str() == name,
-                    ast::Expr::Attribute(attr) => attr.attr.as_str() == name,
-                    _ => false,
-                }
-            }
             _ => false,
         }
     }
 
+    /// `class Point(NamedTuple): x: int; y: int = 0`
+    fn parse_named_tuple_class(&self, class: &ast::StmtClassDef, aliases: &ImportAliases) -> ParserResult<StructDef> {
+        let mut fields = Vec::new();
+
+        for stmt in &class.body {
+            if let ast::Stmt::AnnAssign(assign) = stmt
+                && let ast::Expr::Name(target) = &*assign.target
+            {
+                fields.push(StructField {
+                    name: target.id.to_string(),
+                    type_hint: annotation_type(&assign.annotation, aliases)?,
+                    has_default: assign.value.is_some(),
+                });
+            }
+        }
+
+        Ok(StructDef {
+            name: class.name.to_string(),
+            kind: StructKind::NamedTuple,
+            fields,
+        })
+    }
+
+    /// `Point = NamedTuple("Point", [("x", int), ("y", int)])`
+    fn parse_named_tuple_call(&self, name: &str, call: &ast::ExprCall, aliases: &ImportAliases) -> ParserResult<StructDef> {
+        let field_list = match call.arguments.find_argument_value("fields", 1) {
+            Some(ast::Expr::List(list)) => &list.elts,
+            Some(ast::Expr::Tuple(tuple)) => &tuple.elts,
+            _ => return Err(anyhow!("NamedTuple '{}' must list its fields as (name, type) pairs", name).into()),
+        };
+
+        let fields = field_list
+            .iter()
+            .map(|field| match field {
+                ast::Expr::Tuple(pair) => match pair.elts.as_slice() {
+                    [ast::Expr::StringLiteral(field_name), annotation] => Ok(StructField {
+                        name: field_name.value.to_string(),
+                        type_hint: annotation_type(annotation, aliases)?,
+                        has_default: false,
+                    }),
+                    _ => Err(anyhow!("Invalid field in NamedTuple '{}'", name).into()),
+                },
+                _ => Err(anyhow!("Invalid field in NamedTuple '{}'", name).into()),
+            })
+            .collect::<ParserResult<Vec<_>>>()?;
+
+        Ok(StructDef {
+            name: name.to_string(),
+            kind: StructKind::NamedTuple,
+            fields,
+        })
+    }
+
+    /// `class Config(TypedDict, total=False): debug: bool; name: Required[str]`
+    fn parse_typed_dict_class(&self, class: &ast::StmtClassDef, aliases: &ImportAliases) -> ParserResult<StructDef> {
+        let total = self.is_total(class.arguments.as_deref());
+        let mut fields = Vec::new();
+
+        for stmt in &class.body {
+            if let ast::Stmt::AnnAssign(assign) = stmt
+                && let ast::Expr::Name(target) = &*assign.target
+            {
+                fields.push(self.parse_typed_dict_field(target.id.as_str(), &assign.annotation, total, aliases)?);
+            }
+        }
+
+        Ok(StructDef {
+            name: class.name.to_string(),
+            kind: StructKind::TypedDict,
+            fields,
+        })
+    }
+
+    /// `Config = TypedDict("Config", {"debug": bool, "name": str}, total=False)`
+    fn parse_typed_dict_call(&self, name: &str, call: &ast::ExprCall, aliases: &ImportAliases) -> ParserResult<StructDef> {
+        let Some(ast::Expr::Dict(dict)) = call.arguments.find_argument_value("fields", 1) else {
+            return Err(anyhow!("TypedDict '{}' must list its fields as a dict", name).into());
+        };
+        let total = self.is_total(Some(&call.arguments));
+
+        let fields = dict
+            .iter()
+            .map(|item| match &item.key {
+                Some(ast::Expr::StringLiteral(field_name)) => {
+                    self.parse_typed_dict_field(field_name.value.to_str(), &item.value, total, aliases)
+                },
+                _ => Err(anyhow!("Invalid field in TypedDict '{}'", name).into()),
+            })
+            .collect::<ParserResult<Vec<_>>>()?;
+
+        Ok(StructDef {
+            name: name.to_string(),
+            kind: StructKind::TypedDict,
+            fields,
+        })
+    }
+
+    /// A field is optional when the `TypedDict` isn't total, unless marked
+    /// `Required[T]`, or when marked `NotRequired[T]`.
+    fn parse_typed_dict_field(
+        &self,
+        name: &str,
+        annotation: &ast::Expr,
+        total: bool,
+        aliases: &ImportAliases,
+    ) -> ParserResult<StructField> {
+        let mut annotation = annotation.clone();
+        resolve_annotation(&mut annotation, aliases);
+
+        let (annotation, required) = match &annotation {
+            ast::Expr::Subscript(sub) if self.is_name(&sub.value, "Required", aliases) => (&*sub.slice, true),
+            ast::Expr::Subscript(sub) if self.is_name(&sub.value, "NotRequired", aliases) => (&*sub.slice, false),
+            annotation => (annotation, total),
+        };
+
+        Ok(StructField {
+            name: name.to_string(),
+            type_hint: ParameterType::try_from_ast(annotation)?,
+            has_default: !required,
+        })
+    }
+
+    fn is_total(&self, arguments: Option<&ast::Arguments>) -> bool {
+        !matches!(
+            arguments.and_then(|args| args.find_keyword("total")).map(|k| &k.value),
+            Some(ast::Expr::BooleanLiteral(b)) if !b.value
+        )
+    }
+
+    /// Whether a `mod_fn` function is exported, which `@mod_fn(export=False)`
+    /// opts out of. The flag takes precedence over `__all__`, which isn't
+    /// consulted: a function listed there is still left out.
+    fn is_exported(&self, func: &ast::StmtFunctionDef, aliases: &ImportAliases) -> ParserResult<bool> {
+        let export = func.decorator_list
+            .iter()
+            .filter(|decorator| self.is_decorator_name(decorator, "mod_fn", aliases))
+            .filter_map(|decorator| self.get_decorator_args(decorator))
+            .find_map(|args| args.find_keyword("export"))
+            .map(|keyword| &keyword.value);
+
+        match export {
+            None => Ok(true),
+            Some(ast::Expr::BooleanLiteral(b)) => Ok(b.value),
+            Some(_) => Err(anyhow!("mod_fn export flag of '{}' must be True or False", func.name).into()),
+        }
+    }
+
+    /// Methods decorated with `mod_fn` would otherwise be skipped silently.
+    fn reject_mod_fn_methods(&self, class: &ast::StmtClassDef, aliases: &ImportAliases) -> ParserResult<()> {
+        match class.body.iter().find_map(|stmt| match stmt {
+            ast::Stmt::FunctionDef(func) if self.has_func_decorator(func, "mod_fn", aliases) => Some(func),
+            _ => None,
+        }) {
+            Some(method) => Err(ParserError::MethodModFn {
+                class: class.name.to_string(),
+                method: method.name.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
     fn get_decorator_args<'a>(&self, decorator: &'a ast::Decorator) -> Option<&'a ast::Arguments> {
         match &decorator.expression {
             ast::Expr::Call(call) => Some(&call.arguments),
@@ -106,35 +389,36 @@ impl AstAnalyzer {
         }
     }
 
-    fn parse_host_fns_class(&self, class: &ast::StmtClassDef) -> ParserResult<Option<(String, Vec<HostFunction>)>> {
-        let namespace = self
-            .get_decorator_args(
-                class
-                    .decorator_list
-                    .iter()
-                    .find(|d| self.is_decorator_name(d, "host_fns"))
-                    .ok_or_else(|| anyhow!("Decorator not found"))?,
-            )
-            .and_then(|args| {
-                args
-                .find_argument_value("namespace", 0)
-                .and_then(|expr| {
-                    match expr {
-                        ast::Expr::StringLiteral(s) => Some(s.value.to_string()),
-                        _ => None,
-                    }
-                })
-            })
-            .ok_or(anyhow!("Missing 'namespace' argument in host_fns decorator"))?
-            .to_string();
+    /// The namespace is given positionally, `@host_fns("env")`, or by keyword,
+    /// `@host_fns(namespace="env")`, and must be a string literal either way.
+    fn parse_host_fns_class(&self, class: &ast::StmtClassDef, aliases: &ImportAliases) -> ParserResult<Option<(String, Vec<HostFunction>)>> {
+        let class_name = class.name.to_string();
+        let decorator = class
+            .decorator_list
+            .iter()
+            .find(|d| self.is_decorator_name(d, "host_fns", aliases))
+            .ok_or_else(|| anyhow!("Decorator not found"))?;
+        let arguments = self.get_decorator_args(decorator);
+
+        if arguments.is_some_and(|args| args.find_keyword("namespace").is_some() && args.find_positional(0).is_some()) {
+            return Err(ParserError::InvalidHostNamespace(class_name));
+        }
+
+        let namespace = match arguments.map(|args| args.find_argument_value("namespace", 0)) {
+            None => return Err(ParserError::HostFnsWithoutArguments(class_name)),
+            Some(None) => return Err(ParserError::MissingHostNamespace(class_name)),
+            Some(Some(ast::Expr::StringLiteral(s))) => s.value.to_string(),
+            Some(Some(_)) => return Err(ParserError::InvalidHostNamespace(class_name)),
+        };
 
         let mut host_functions = Vec::new();
 
         for stmt in &class.body {
             if let ast::Stmt::FunctionDef(func) = stmt {
-                if self.has_func_decorator(func, "host_fn") {
+                if self.has_func_decorator(func, "host_fn", aliases) {
+                    self.check_host_fn_decorator(func, aliases)?;
                     host_functions.push(
-                        HostFunction::try_from_ast(func)?
+                        HostFunction::try_from_ast(&resolve_signature(func, aliases))?
                     );
                 }
             }
@@ -146,4 +430,329 @@ impl AstAnalyzer {
             Ok(Some((namespace, host_functions)))
         }
     }
-}
\ No newline at end of file
+
+    /// binmod_mdk's `@host_fn` is a bare decorator, so a call such as
+    /// `@host_fn(namespace="fs")` would fail once the module is imported.
+    fn check_host_fn_decorator(&self, func: &ast::StmtFunctionDef, aliases: &ImportAliases) -> ParserResult<()> {
+        let called = func.decorator_list
+            .iter()
+            .filter(|decorator| self.is_decorator_name(decorator, "host_fn", aliases))
+            .any(|decorator| self.get_decorator_args(decorator).is_some());
+
+        if called {
+            return Err(ParserError::HostFnArguments(func.name.to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn analyze(content: &str) -> Option<Module> {
+        let td = TempDir::new().unwrap();
+        let path = td.path().join("api.py");
+        std::fs::write(&path, content).unwrap();
+
+        AstAnalyzer::new().analyze_file(&path).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn reads_bom_and_latin1_sources() {
+        let td = TempDir::new().unwrap();
+        let path = td.path().join("api.py");
+        let function = b"@mod_fn\ndef greet(name: str) -> str:\n    return name\n";
+
+        std::fs::write(&path, [b"\xEF\xBB\xBF".as_slice(), function].concat()).unwrap();
+        assert!(AstAnalyzer::new().analyze_file(&path).await.unwrap().is_some());
+
+        std::fs::write(&path, [b"# -*- coding: latin-1 -*-\n# caf\xE9\n".as_slice(), function].concat()).unwrap();
+        assert!(AstAnalyzer::new().analyze_file(&path).await.unwrap().is_some());
+
+        std::fs::write(&path, [b"# caf\xE9\n".as_slice(), function].concat()).unwrap();
+        assert!(matches!(
+            AstAnalyzer::new().analyze_file(&path).await,
+            Err(ParserError::UndecodableSource { file, encoding }) if file == path && encoding == "utf-8"
+        ));
+    }
+
+    #[tokio::test]
+    async fn missing_annotations_point_at_the_file() {
+        let td = TempDir::new().unwrap();
+        let path = td.path().join("api.py");
+
+        std::fs::write(&path, "from binmod import mod_fn\n\n@mod_fn\ndef greet(name) -> str:\n    return name\n").unwrap();
+        let err = AstAnalyzer::new().analyze_file(&path).await.unwrap_err();
+
+        assert_eq!(err.code(), "parser.parameter_missing_type_annotation");
+        assert_eq!(
+            err.to_string(),
+            format!("{}: Parameter 'name' of 'greet' is missing a type annotation", path.display()),
+        );
+
+        std::fs::write(&path, "from binmod import host_fns, host_fn\n\n@host_fns(\"env\")\nclass Host:\n    @host_fn\n    def now(): ...\n").unwrap();
+
+        assert!(matches!(
+            AstAnalyzer::new().analyze_file(&path).await,
+            Err(ParserError::ReturnMissingTypeAnnotation { file: Some(file), function })
+                if file == path && function == "now"
+        ));
+    }
+
+    #[tokio::test]
+    async fn resolves_aliased_mod_fn() {
+        let module = analyze(
+            "from binmod import mod_fn as export\n\n@export\ndef greet(name: str) -> str:\n    return name\n",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(module.module_functions.as_slice().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn resolves_aliased_host_fns_and_host_fn() {
+        let module = analyze(
+            "from binmod import host_fns as hfs, host_fn as hf\n\n@hfs(\"env\")\nclass Host:\n    @hf\n    def now() -> int: ...\n",
+        )
+        .await
+        .unwrap();
+
+        let host_functions = module.host_functions.unwrap();
+        assert_eq!(host_functions.namespace, "env");
+        assert_eq!(host_functions.as_slice().len(), 1);
+    }
+
+    async fn analyze_err(content: &str) -> ParserError {
+        let td = TempDir::new().unwrap();
+        let path = td.path().join("api.py");
+        std::fs::write(&path, content).unwrap();
+
+        AstAnalyzer::new().analyze_file(&path).await.unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn host_fns_decorator_arguments() {
+        let class = "class Host:\n    @host_fn\n    def now() -> int: ...\n";
+
+        let bare = analyze_err(&format!("@host_fns\n{class}")).await;
+        assert!(matches!(&bare, ParserError::HostFnsWithoutArguments(name) if name == "Host"), "{bare:?}");
+        assert!(bare.to_string().contains("without arguments"));
+
+        let empty = analyze_err(&format!("@host_fns()\n{class}")).await;
+        assert!(matches!(&empty, ParserError::MissingHostNamespace(name) if name == "Host"), "{empty:?}");
+        assert!(empty.to_string().contains("missing its 'namespace' argument"));
+
+        let keyword_only = analyze_err(&format!("@host_fns(version=2)\n{class}")).await;
+        assert!(matches!(keyword_only, ParserError::MissingHostNamespace(_)), "{keyword_only:?}");
+
+        let not_a_string = analyze_err(&format!("NAMESPACE = \"env\"\n\n@host_fns(namespace=NAMESPACE)\n{class}")).await;
+        assert!(matches!(not_a_string, ParserError::InvalidHostNamespace(_)), "{not_a_string:?}");
+
+        let module = analyze(&format!("@host_fns(namespace=\"x\")\n{class}")).await.unwrap();
+        assert_eq!(module.host_functions.unwrap().namespace, "x");
+    }
+
+    #[tokio::test]
+    async fn host_fns_namespace_positional_or_keyword() {
+        let class = "class Host:\n    @host_fn\n    def now() -> int: ...\n";
+
+        for decorator in ["@host_fns(\"env\")", "@host_fns(namespace=\"env\")"] {
+            let module = analyze(&format!("{decorator}\n{class}")).await.unwrap();
+            assert_eq!(module.host_functions.unwrap().namespace, "env", "{decorator}");
+        }
+
+        for decorator in ["@host_fns(ENV)", "@host_fns(f\"{ENV}\")", "@host_fns(\"env\", namespace=\"other\")"] {
+            let err = analyze_err(&format!("{decorator}\n{class}")).await;
+            assert!(matches!(err, ParserError::InvalidHostNamespace(_)), "{decorator}: {err:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn host_fn_takes_no_arguments() {
+        for decorator in ["@host_fn(namespace=\"fs\")", "@host_fn(\"fs\")", "@host_fn()"] {
+            let err = analyze_err(&format!("@host_fns(\"env\")\nclass Host:\n    {decorator}\n    def read(path: str) -> str: ...\n")).await;
+            assert!(matches!(&err, ParserError::HostFnArguments(name) if name == "read"), "{decorator}: {err:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn resolves_dotted_decorators() {
+        let module = analyze(
+            "import binmod as b\n\n@b.mod_fn\ndef greet(name: str) -> str:\n    return name\n",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(module.module_functions.as_slice().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn ignores_unrelated_aliases() {
+        let module = analyze(
+            "from functools import cache as mod_fn\nfrom binmod import mod_fn as export\n\n@mod_fn\ndef greet(name: str) -> str:\n    return name\n",
+        )
+        .await;
+
+        assert!(module.is_none());
+    }
+
+    #[tokio::test]
+    async fn skips_functions_not_exported() {
+        let module = analyze(
+            "from binmod import mod_fn\n\n__all__ = [\"greet\", \"helper\"]\n\n@mod_fn\ndef greet(name: str) -> str:\n    return name\n\n@mod_fn(export=False)\ndef helper(name: str) -> str:\n    return name\n\n@mod_fn(export=True)\ndef shout(name: str) -> str:\n    return name\n",
+        )
+        .await
+        .unwrap();
+
+        let names = module.module_functions
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(names, vec!["greet", "shout"]);
+    }
+
+    #[tokio::test]
+    async fn rejects_mod_fn_methods() {
+        let td = TempDir::new().unwrap();
+        let path = td.path().join("api.py");
+        std::fs::write(
+            &path,
+            "from binmod import mod_fn\n\nclass Api:\n    @mod_fn\n    def greet(self, name: str) -> str:\n        return name\n",
+        )
+        .unwrap();
+
+        let err = AstAnalyzer::new().analyze_file(&path).await.unwrap_err();
+
+        assert!(matches!(
+            &err,
+            ParserError::MethodModFn { class, method } if class == "Api" && method == "greet"
+        ));
+        assert!(err.to_string().contains("only module-level functions can be exported"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn parses_class_named_tuples() {
+        let module = analyze(
+            "from typing import NamedTuple\n\nclass Point(NamedTuple):\n    \"\"\"A point.\"\"\"\n    x: float\n    y: float = 0.0\n    label: str | None = None\n",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(module.structs, vec![StructDef {
+            name: "Point".into(),
+            kind: StructKind::NamedTuple,
+            fields: vec![
+                StructField { name: "x".into(), type_hint: ParameterType::Float, has_default: false },
+                StructField { name: "y".into(), type_hint: ParameterType::Float, has_default: true },
+                StructField {
+                    name: "label".into(),
+                    type_hint: ParameterType::Optional(Box::new(ParameterType::String)),
+                    has_default: true,
+                },
+            ],
+        }]);
+    }
+
+    #[tokio::test]
+    async fn parses_functional_named_tuples() {
+        let module = analyze(
+            "import typing\n\nPair = typing.NamedTuple(\"Pair\", [(\"key\", str), (\"value\", int)])\n",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(module.structs, vec![StructDef {
+            name: "Pair".into(),
+            kind: StructKind::NamedTuple,
+            fields: vec![
+                StructField { name: "key".into(), type_hint: ParameterType::String, has_default: false },
+                StructField { name: "value".into(), type_hint: ParameterType::Integer, has_default: false },
+            ],
+        }]);
+    }
+
+    #[tokio::test]
+    async fn parses_class_typed_dicts() {
+        let module = analyze(
+            "from typing import TypedDict, Required, NotRequired\n\n\
+             class Config(TypedDict):\n    name: str\n    debug: NotRequired[bool]\n\n\
+             class Overrides(TypedDict, total=False):\n    name: Required[str]\n    retries: int\n",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(module.structs, vec![
+            StructDef {
+                name: "Config".into(),
+                kind: StructKind::TypedDict,
+                fields: vec![
+                    StructField { name: "name".into(), type_hint: ParameterType::String, has_default: false },
+                    StructField { name: "debug".into(), type_hint: ParameterType::Boolean, has_default: true },
+                ],
+            },
+            StructDef {
+                name: "Overrides".into(),
+                kind: StructKind::TypedDict,
+                fields: vec![
+                    StructField { name: "name".into(), type_hint: ParameterType::String, has_default: false },
+                    StructField { name: "retries".into(), type_hint: ParameterType::Integer, has_default: true },
+                ],
+            },
+        ]);
+    }
+
+    #[tokio::test]
+    async fn resolves_aliased_and_quoted_typed_dict_fields() {
+        let module = analyze(
+            "from __future__ import annotations\n\
+             from typing import TypedDict, NotRequired as Maybe\n\n\
+             class Config(TypedDict):\n    name: \"str\"\n    debug: \"Maybe[bool]\"\n",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(module.structs[0].fields, vec![
+            StructField { name: "name".into(), type_hint: ParameterType::String, has_default: false },
+            StructField { name: "debug".into(), type_hint: ParameterType::Boolean, has_default: true },
+        ]);
+    }
+
+    #[tokio::test]
+    async fn parses_functional_typed_dicts() {
+        let module = analyze(
+            "import typing\n\n\
+             Config = typing.TypedDict(\"Config\", {\"name\": str, \"tags\": list[str]})\n\
+             Partial = typing.TypedDict(\"Partial\", {\"name\": str}, total=False)\n",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(module.structs, vec![
+            StructDef {
+                name: "Config".into(),
+                kind: StructKind::TypedDict,
+                fields: vec![
+                    StructField { name: "name".into(), type_hint: ParameterType::String, has_default: false },
+                    StructField {
+                        name: "tags".into(),
+                        type_hint: ParameterType::List(Box::new(ParameterType::String)),
+                        has_default: false,
+                    },
+                ],
+            },
+            StructDef {
+                name: "Partial".into(),
+                kind: StructKind::TypedDict,
+                fields: vec![
+                    StructField { name: "name".into(), type_hint: ParameterType::String, has_default: true },
+                ],
+            },
+        ]);
+    }
+}
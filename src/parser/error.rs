@@ -1,6 +1,28 @@
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 
+/// `path: ` for errors that know the file they were raised in.
+fn location(file: &Option<PathBuf>) -> String {
+    file.as_ref()
+        .map(|file| format!("{}: ", file.display()))
+        .unwrap_or_default()
+}
+
+/// The layout steps taken before a module or venv couldn't be found, listed
+/// after the error when `explain` is set.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Explanation(pub Vec<String>);
+
+impl std::fmt::Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for step in &self.0 {
+            write!(f, "\n  {step}")?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ParserError {
     #[error("Missing file: {0}")]
@@ -13,24 +35,194 @@ pub enum ParserError {
     },
     #[error("Unsupported metadata strategy: {0}")]
     UnsupportedMetadataStrategy(String),
-    #[error("Parameter '{0}' is missing a type annotation")]
-    ParameterMissingTypeAnnotation(String),
+    #[error("{}Parameter '{parameter}' of '{function}' is missing a type annotation", location(.file))]
+    ParameterMissingTypeAnnotation {
+        file: Option<PathBuf>,
+        function: String,
+        parameter: String,
+    },
+    #[error("{}'{function}' is missing a return type annotation", location(.file))]
+    ReturnMissingTypeAnnotation {
+        file: Option<PathBuf>,
+        function: String,
+    },
+    #[error("Unsupported type annotation '{annotation}' for {parameter} of '{function}'")]
+    UnsupportedType {
+        function: String,
+        parameter: String,
+        annotation: String,
+    },
+    #[error("Unsupported callable annotation for {parameter} of '{function}': Python callables can't be passed across the wasm boundary")]
+    UnsupportedCallable {
+        function: String,
+        parameter: String,
+    },
+    #[error("Unsupported dict key type '{key}' for {parameter} of '{function}': dicts cross the wasm boundary as JSON objects, whose keys are strings")]
+    UnsupportedMapKey {
+        function: String,
+        parameter: String,
+        key: String,
+    },
+    #[error("Unsupported set item type '{item}' for {parameter} of '{function}': set items must be hashable, which floats, dicts and untyped values aren't")]
+    UnhashableSetItem {
+        function: String,
+        parameter: String,
+        item: String,
+    },
+    #[error("Parameter '{parameter}' of '{function}' is declared more than once")]
+    DuplicateParameter {
+        function: String,
+        parameter: String,
+    },
+    #[error("Method '{class}.{method}' is decorated with mod_fn, but only module-level functions can be exported")]
+    MethodModFn {
+        class: String,
+        method: String,
+    },
+    #[error("Class '{0}' uses @host_fns without arguments: pass the namespace its functions are imported from, e.g. @host_fns(namespace=\"env\")")]
+    HostFnsWithoutArguments(String),
+    #[error("@host_fns on class '{0}' is missing its 'namespace' argument, e.g. @host_fns(namespace=\"env\")")]
+    MissingHostNamespace(String),
+    #[error("The namespace of @host_fns on class '{0}' must be a single string literal, given positionally or as namespace=")]
+    InvalidHostNamespace(String),
+    #[error("@host_fn on '{0}' takes no arguments: host functions are imported from the namespace of their @host_fns class")]
+    HostFnArguments(String),
+    #[error("Struct '{name}' is defined in both {first} and {second}, struct names must be unique across the project")]
+    DuplicateStruct {
+        name: String,
+        first: String,
+        second: String,
+    },
+    #[error("Feature '{feature}' gates '{function}', which is not an exported function")]
+    UnknownFeatureFunction {
+        feature: String,
+        function: String,
+    },
+    #[error("Invalid crate-type {crate_types:?}: {reason}")]
+    InvalidCrateType {
+        crate_types: Vec<String>,
+        reason: String,
+    },
+    #[error("No exported functions found in '{0}': decorate a function with @mod_fn or a class with @host_fns")]
+    NoExportedFunctions(String),
+    #[error("Can't decode '{}' as {encoding}, only UTF-8 and Latin-1 sources are supported", .file.display())]
+    UndecodableSource {
+        file: std::path::PathBuf,
+        encoding: String,
+    },
     #[error("Missing project metadata")]
     MissingProjectMetadata,
-    #[error("Missing module")]
-    MissingModule,
-    #[error("Missing virtual environment")]
-    MissingVirtualEnv,
+    #[error("Project field '{0}' is dynamic and can't be read from pyproject.toml: set it in [tool.py2binmod]")]
+    UnresolvedDynamicField(String),
+    #[error("Invalid license expression '{expression}': {reason}")]
+    InvalidLicenseExpression {
+        expression: String,
+        reason: String,
+    },
+    #[error("Invalid license-files: {0}")]
+    InvalidLicenseFiles(String),
+    #[error("Missing module{0}")]
+    MissingModule(Explanation),
+    #[error("Module '{module}' conflicts with module_root '{}': {reason}", .module_root.display())]
+    ConflictingLayoutHints {
+        module: String,
+        module_root: std::path::PathBuf,
+        reason: String,
+    },
+    #[error("Missing virtual environment{0}")]
+    MissingVirtualEnv(Explanation),
     #[error("Missing site packages")]
     MissingSitePackages,
+    #[error("Invalid ignore pattern: {0}")]
+    InvalidIgnorePattern(String),
+    #[error("Invalid include pattern: {0}")]
+    InvalidIncludePattern(String),
+    #[error("No Cargo workspace at '{}': expected a Cargo.toml with a [workspace] table", .0.display())]
+    InvalidWorkspace(std::path::PathBuf),
     #[error("Invalid project directory: {0}")]
     InvalidProjectDir(std::path::PathBuf),
+    #[error("Parsing was cancelled")]
+    Cancelled,
     #[error("Invalid TOML: {0}")]
     TomlError(#[from] toml::de::Error),
+    #[error("Unable to edit pyproject.toml: {0}")]
+    PyprojectEdit(String),
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
     #[error("Unknown error: {0}")]
-    UnknownError(#[from] anyhow::Error),
+    UnknownError(anyhow::Error),
+    #[error(
+        "{} errors occurred:\n{}",
+        .0.len(),
+        .0.iter().map(|e| format!("  - {}", e)).collect::<Vec<_>>().join("\n")
+    )]
+    Multiple(Vec<ParserError>),
+}
+
+impl ParserError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParserError::MissingFile(_) => "parser.missing_file",
+            ParserError::InvalidSyntax { .. } => "parser.invalid_syntax",
+            ParserError::UnsupportedMetadataStrategy(_) => "parser.unsupported_metadata_strategy",
+            ParserError::ParameterMissingTypeAnnotation { .. } => "parser.parameter_missing_type_annotation",
+            ParserError::ReturnMissingTypeAnnotation { .. } => "parser.return_missing_type_annotation",
+            ParserError::UnsupportedType { .. } => "parser.unsupported_type",
+            ParserError::UnsupportedCallable { .. } => "parser.unsupported_callable",
+            ParserError::UnsupportedMapKey { .. } => "parser.unsupported_map_key",
+            ParserError::UnhashableSetItem { .. } => "parser.unhashable_set_item",
+            ParserError::DuplicateParameter { .. } => "parser.duplicate_parameter",
+            ParserError::MethodModFn { .. } => "parser.method_mod_fn",
+            ParserError::HostFnsWithoutArguments(_) => "parser.host_fns_without_arguments",
+            ParserError::MissingHostNamespace(_) => "parser.missing_host_namespace",
+            ParserError::InvalidHostNamespace(_) => "parser.invalid_host_namespace",
+            ParserError::HostFnArguments(_) => "parser.host_fn_arguments",
+            ParserError::DuplicateStruct { .. } => "parser.duplicate_struct",
+            ParserError::UnknownFeatureFunction { .. } => "parser.unknown_feature_function",
+            ParserError::InvalidCrateType { .. } => "parser.invalid_crate_type",
+            ParserError::NoExportedFunctions(_) => "parser.no_exported_functions",
+            ParserError::UndecodableSource { .. } => "parser.undecodable_source",
+            ParserError::MissingProjectMetadata => "parser.missing_project_metadata",
+            ParserError::UnresolvedDynamicField(_) => "parser.unresolved_dynamic_field",
+            ParserError::InvalidLicenseExpression { .. } => "parser.invalid_license_expression",
+            ParserError::InvalidLicenseFiles(_) => "parser.invalid_license_files",
+            ParserError::MissingModule(_) => "parser.missing_module",
+            ParserError::ConflictingLayoutHints { .. } => "parser.conflicting_layout_hints",
+            ParserError::MissingVirtualEnv(_) => "parser.missing_virtual_env",
+            ParserError::MissingSitePackages => "parser.missing_site_packages",
+            ParserError::InvalidIgnorePattern(_) => "parser.invalid_ignore_pattern",
+            ParserError::InvalidIncludePattern(_) => "parser.invalid_include_pattern",
+            ParserError::InvalidWorkspace(_) => "parser.invalid_workspace",
+            ParserError::InvalidProjectDir(_) => "parser.invalid_project_dir",
+            ParserError::Cancelled => "parser.cancelled",
+            ParserError::TomlError(_) => "parser.toml",
+            ParserError::PyprojectEdit(_) => "parser.pyproject_edit",
+            ParserError::IoError(_) => "parser.io",
+            ParserError::UnknownError(_) => "parser.unknown",
+            ParserError::Multiple(_) => "parser.multiple",
+        }
+    }
+
+    /// Record the file errors about a function's annotations were raised in,
+    /// which is only known once they reach the module being analyzed.
+    pub fn in_file(self, path: &Path) -> Self {
+        match self {
+            ParserError::ParameterMissingTypeAnnotation { file: None, function, parameter } =>
+                ParserError::ParameterMissingTypeAnnotation { file: Some(path.to_path_buf()), function, parameter },
+            ParserError::ReturnMissingTypeAnnotation { file: None, function } =>
+                ParserError::ReturnMissingTypeAnnotation { file: Some(path.to_path_buf()), function },
+            other => other,
+        }
+    }
+}
+
+/// Parser errors raised from within `anyhow` contexts keep their variant.
+impl From<anyhow::Error> for ParserError {
+    fn from(error: anyhow::Error) -> Self {
+        error
+            .downcast::<ParserError>()
+            .unwrap_or_else(ParserError::UnknownError)
+    }
 }
 
 pub type ParserResult<T> = Result<T, ParserError>;
@@ -0,0 +1,139 @@
+use std::path::{Path, PathBuf};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+use crate::parser::{
+    file_walker::{traits::FileIgnoreStrategy, default::DefaultFileIgnoreStrategy},
+    error::{ParserError, ParserResult},
+};
+
+
+/// Ignores everything [`DefaultFileIgnoreStrategy`] does, plus any path matching
+/// one of the user supplied globs. Globs are matched against the path relative
+/// to the project dir at any depth, so `tests` ignores every `tests` directory
+/// of the project and `migrations/*.py` every migration file.
+pub struct ConfigurableFileIgnoreStrategy {
+    default: DefaultFileIgnoreStrategy,
+    root: PathBuf,
+    globs: GlobSet,
+}
+
+/// `path` relative to `root`, the directories above the project aren't
+/// matched against. Paths outside of `root` are matched whole.
+pub(crate) fn relative_to<'a>(root: &Path, path: &'a Path) -> &'a Path {
+    path.strip_prefix(root).unwrap_or(path)
+}
+
+/// Build a set of globs matched at any depth, reporting invalid ones with
+/// `invalid`. `*` doesn't match across directories, `**` does.
+pub(crate) fn glob_set<S: AsRef<str>>(patterns: &[S], invalid: fn(String) -> ParserError) -> ParserResult<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+
+    for pattern in patterns {
+        let normalized = pattern
+            .as_ref()
+            .trim_start_matches("./")
+            .trim_matches('/');
+        let glob = if normalized.starts_with("**/") {
+            normalized.to_string()
+        } else {
+            format!("**/{}", normalized)
+        };
+
+        builder.add(
+            GlobBuilder::new(&glob)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| invalid(format!("{}: {}", pattern.as_ref(), e)))?,
+        );
+    }
+
+    builder
+        .build()
+        .map_err(|e| invalid(e.to_string()))
+}
+
+impl ConfigurableFileIgnoreStrategy {
+    pub fn new<S: AsRef<str>>(root: impl Into<PathBuf>, patterns: &[S]) -> ParserResult<Self> {
+        Ok(Self {
+            default: DefaultFileIgnoreStrategy::new(),
+            root: root.into(),
+            globs: glob_set(patterns, ParserError::InvalidIgnorePattern)?,
+        })
+    }
+}
+
+impl FileIgnoreStrategy for ConfigurableFileIgnoreStrategy {
+    fn should_ignore(&self, path: &Path) -> bool {
+        self.default.should_ignore(path) || self.globs.is_match(relative_to(&self.root, path))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+    use crate::parser::file_walker::FileWalker;
+
+    #[test]
+    fn keeps_default_ignores() {
+        let strategy = ConfigurableFileIgnoreStrategy::new("/project", &["tests"]).unwrap();
+
+        assert!(strategy.should_ignore(Path::new("/project/__pycache__")));
+        assert!(strategy.should_ignore(Path::new("/project/.venv")));
+    }
+
+    #[test]
+    fn matches_configured_patterns() {
+        let strategy = ConfigurableFileIgnoreStrategy::new("/project", &["tests/", "migrations/*.py", "*_test.py"]).unwrap();
+
+        assert!(strategy.should_ignore(Path::new("/project/tests")));
+        assert!(strategy.should_ignore(Path::new("/project/pkg/migrations/0001_initial.py")));
+        assert!(strategy.should_ignore(Path::new("/project/pkg/api_test.py")));
+        assert!(!strategy.should_ignore(Path::new("/project/pkg/api.py")));
+        assert!(!strategy.should_ignore(Path::new("/project/pkg/migrations")));
+    }
+
+    #[test]
+    fn matches_relative_to_the_project_dir() {
+        let strategy = ConfigurableFileIgnoreStrategy::new("/home/tests/project", &["tests", "migrations/*.py"]).unwrap();
+
+        assert!(!strategy.should_ignore(Path::new("/home/tests/project/pkg/api.py")));
+        assert!(strategy.should_ignore(Path::new("/home/tests/project/tests")));
+        assert!(strategy.should_ignore(Path::new("/home/tests/project/pkg/migrations/0001.py")));
+        assert!(!strategy.should_ignore(Path::new("/home/tests/project/pkg/migrations/old/0001.py")));
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        assert!(matches!(
+            ConfigurableFileIgnoreStrategy::new("/project", &["[unclosed"]),
+            Err(ParserError::InvalidIgnorePattern(_)),
+        ));
+    }
+
+    #[tokio::test]
+    async fn walker_skips_configured_patterns() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        for file in ["pkg/__init__.py", "pkg/api.py", "tests/test_api.py", "pkg/migrations/0001.py"] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+
+        let strategy = ConfigurableFileIgnoreStrategy::new(root, &["tests", "migrations"]).unwrap();
+        let mut files = FileWalker::new(&strategy)
+            .walk(root)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|p| p.strip_prefix(root).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        files.sort();
+
+        assert_eq!(files, vec![Path::new("pkg/__init__.py"), Path::new("pkg/api.py")]);
+    }
+}
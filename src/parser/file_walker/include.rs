@@ -0,0 +1,64 @@
+use std::path::{Path, PathBuf};
+use globset::GlobSet;
+
+use crate::parser::{
+    file_walker::configurable::{glob_set, relative_to},
+    error::{ParserError, ParserResult},
+};
+
+
+/// Restricts analysis to the files matching one of the user supplied globs,
+/// matched relative to the project dir at any depth like ignore patterns.
+/// Without any, every file is included.
+pub struct IncludeFilter {
+    root: PathBuf,
+    globs: Option<GlobSet>,
+}
+
+impl IncludeFilter {
+    pub fn new<S: AsRef<str>>(root: impl Into<PathBuf>, patterns: &[S]) -> ParserResult<Self> {
+        Ok(Self {
+            root: root.into(),
+            globs: match patterns {
+                [] => None,
+                patterns => Some(glob_set(patterns, ParserError::InvalidIncludePattern)?),
+            },
+        })
+    }
+
+    pub fn includes(&self, path: &Path) -> bool {
+        self.globs
+            .as_ref()
+            .is_none_or(|globs| globs.is_match(relative_to(&self.root, path)))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn includes_everything_without_patterns() {
+        assert!(IncludeFilter::new::<&str>("/project", &[]).unwrap().includes(Path::new("/project/pkg/api.py")));
+    }
+
+    #[test]
+    fn includes_only_matching_files() {
+        let filter = IncludeFilter::new("/project", &["api/*.py", "models.py"]).unwrap();
+
+        assert!(filter.includes(Path::new("/project/pkg/api/users.py")));
+        assert!(filter.includes(Path::new("/project/pkg/models.py")));
+        assert!(!filter.includes(Path::new("/project/pkg/utils.py")));
+        assert!(!filter.includes(Path::new("/project/pkg/api/v1/users.py")));
+        assert!(!IncludeFilter::new("/api/project", &["api/*"]).unwrap().includes(Path::new("/api/project/pkg.py")));
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        assert!(matches!(
+            IncludeFilter::new("/project", &["[unclosed"]),
+            Err(ParserError::InvalidIncludePattern(_)),
+        ));
+    }
+}
@@ -1,7 +1,9 @@
 pub mod traits;
 pub mod default;
+pub mod configurable;
+pub mod include;
 
-use std::path::{Path, PathBuf};
+use std::{collections::HashSet, path::{Path, PathBuf}};
 use tokio::fs;
 
 use crate::parser::{file_walker::traits::FileIgnoreStrategy, error::ParserResult};
@@ -9,16 +11,31 @@ use crate::parser::{file_walker::traits::FileIgnoreStrategy, error::ParserResult
 
 pub struct FileWalker<'a> {
     ignore_strategy: &'a dyn FileIgnoreStrategy,
+    on_file: Option<&'a (dyn Fn(usize) + Send + Sync)>,
 }
 
 impl<'a> FileWalker<'a> {
     pub fn new(ignore_strategy: &'a dyn FileIgnoreStrategy) -> Self {
-        Self { ignore_strategy }
+        Self { ignore_strategy, on_file: None }
     }
 
+    /// Call `callback` with the number of files found so far each time the
+    /// walk finds one.
+    pub fn on_file(mut self, callback: Option<&'a (dyn Fn(usize) + Send + Sync)>) -> Self {
+        self.on_file = callback;
+        self
+    }
+
+    /// Every file under `project_dir` that isn't ignored. Paths are
+    /// canonical, so a file reachable through several symlinks is returned
+    /// once; files symlinked in from outside the project keep the path they
+    /// were found at.
     pub async fn walk(&self, project_dir: &Path) -> ParserResult<Vec<PathBuf>> {
+        let root = fs::canonicalize(project_dir).await?;
         let mut files = Vec::new();
-        let mut stack = vec![project_dir.to_path_buf()];
+        let mut seen = HashSet::new();
+        let mut visited = HashSet::from([root.clone()]);
+        let mut stack = vec![root.clone()];
 
         while let Some(dir) = stack.pop() {
             let mut entries = fs::read_dir(&dir).await?;
@@ -31,13 +48,24 @@ impl<'a> FileWalker<'a> {
                 }
 
                 if path.is_dir() {
-                    stack.push(path);
+                    // Symlinked directories may point back at an ancestor.
+                    if visited.insert(fs::canonicalize(&path).await?) {
+                        stack.push(path);
+                    }
                 } else if path.is_file() {
-                    files.push(path);
+                    let canonical = fs::canonicalize(&path).await?;
+
+                    if seen.insert(canonical.clone()) {
+                        files.push(if canonical.starts_with(&root) { canonical } else { path });
+
+                        if let Some(on_file) = self.on_file {
+                            on_file(files.len());
+                        }
+                    }
                 }
             }
         }
 
         Ok(files)
     }
-}
\ No newline at end of file
+}
@@ -2,4 +2,15 @@ use std::path::Path;
 
 pub trait FileIgnoreStrategy: Send + Sync {
     fn should_ignore(&self, path: &Path) -> bool;
-}
\ No newline at end of file
+}
+
+/// A pair of strategies ignores a path when either of them does.
+impl<A, B> FileIgnoreStrategy for (&A, &B)
+where
+    A: FileIgnoreStrategy + ?Sized,
+    B: FileIgnoreStrategy + ?Sized,
+{
+    fn should_ignore(&self, path: &Path) -> bool {
+        self.0.should_ignore(path) || self.1.should_ignore(path)
+    }
+}
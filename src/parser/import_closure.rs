@@ -0,0 +1,182 @@
+use std::{collections::BTreeSet, fs, path::{Path, PathBuf}};
+use ruff_python_parser::parse_module;
+use ruff_python_ast::{self as ast};
+
+use crate::parser::{site_packages::{locate_package, python_files}, source::decode_source};
+
+
+/// Top-level names of the modules imported anywhere in `suite`, including
+/// imports nested in functions, classes and control flow. Relative imports
+/// are skipped since they never leave their own package.
+pub fn imported_modules(suite: &[ast::Stmt]) -> BTreeSet<String> {
+    fn top_level(name: &str) -> String {
+        name.split('.').next().unwrap_or(name).to_string()
+    }
+
+    fn visit(suite: &[ast::Stmt], names: &mut BTreeSet<String>) {
+        for stmt in suite {
+            match stmt {
+                ast::Stmt::Import(import) => {
+                    names.extend(import.names.iter().map(|alias| top_level(alias.name.as_str())));
+                },
+                ast::Stmt::ImportFrom(import) if import.level == 0 => {
+                    if let Some(module) = &import.module {
+                        names.insert(top_level(module.as_str()));
+                    }
+                },
+                ast::Stmt::FunctionDef(func) => visit(&func.body, names),
+                ast::Stmt::ClassDef(class) => visit(&class.body, names),
+                ast::Stmt::If(stmt_if) => {
+                    visit(&stmt_if.body, names);
+                    for clause in &stmt_if.elif_else_clauses {
+                        visit(&clause.body, names);
+                    }
+                },
+                ast::Stmt::Try(stmt_try) => {
+                    visit(&stmt_try.body, names);
+                    for ast::ExceptHandler::ExceptHandler(handler) in &stmt_try.handlers {
+                        visit(&handler.body, names);
+                    }
+                    visit(&stmt_try.orelse, names);
+                    visit(&stmt_try.finalbody, names);
+                },
+                ast::Stmt::With(stmt_with) => visit(&stmt_with.body, names),
+                ast::Stmt::For(stmt_for) => {
+                    visit(&stmt_for.body, names);
+                    visit(&stmt_for.orelse, names);
+                },
+                ast::Stmt::While(stmt_while) => {
+                    visit(&stmt_while.body, names);
+                    visit(&stmt_while.orelse, names);
+                },
+                _ => {},
+            }
+        }
+    }
+
+    let mut names = BTreeSet::new();
+    visit(suite, &mut names);
+    names
+}
+
+fn file_imports(path: &Path) -> BTreeSet<String> {
+    fs::read(path)
+        .ok()
+        .and_then(|source| decode_source(path, &source).ok())
+        .and_then(|content| parse_module(&content).ok())
+        .map(|module| imported_modules(module.suite()))
+        .unwrap_or_default()
+}
+
+/// Top-level names imported by the files of `package`, found in the first of
+/// `site_packages_dirs` holding it. Empty when it isn't installed.
+pub fn package_imports(package: &str, site_packages_dirs: &[PathBuf]) -> BTreeSet<String> {
+    site_packages_dirs
+        .iter()
+        .find_map(|dir| locate_package(dir, package))
+        .map(|location| python_files(&location)
+            .iter()
+            .flat_map(|file| file_imports(file))
+            .filter(|name| name != package)
+            .collect()
+        )
+        .unwrap_or_default()
+}
+
+/// Top-level packages in `site_packages_dirs` reachable from the imports of
+/// `entry_files`, following imports inside those packages transitively.
+/// Imports that don't resolve to site-packages (stdlib, the project itself)
+/// are ignored, as are files that fail to parse.
+pub fn import_closure(entry_files: &[PathBuf], site_packages_dirs: &[PathBuf]) -> BTreeSet<String> {
+    let mut pending = entry_files
+        .iter()
+        .flat_map(|file| file_imports(file))
+        .collect::<Vec<_>>();
+    let mut closure = BTreeSet::new();
+
+    while let Some(name) = pending.pop() {
+        if closure.contains(&name) {
+            continue;
+        }
+
+        let Some(location) = site_packages_dirs.iter().find_map(|dir| locate_package(dir, &name)) else {
+            continue;
+        };
+
+        closure.insert(name);
+        pending.extend(
+            python_files(&location)
+                .iter()
+                .flat_map(|file| file_imports(file))
+        );
+    }
+
+    closure
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn collects_nested_and_dotted_imports() {
+        let module = parse_module(
+            "import os.path\nfrom a.b import c\nfrom . import sibling\n\ndef f():\n    try:\n        import b\n    except ImportError:\n        from c import d\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            imported_modules(module.suite()),
+            BTreeSet::from(["a".to_string(), "b".to_string(), "c".to_string(), "os".to_string()]),
+        );
+    }
+
+    #[test]
+    fn follows_imports_through_site_packages() {
+        let td = TempDir::new().unwrap();
+        let project = td.path().join("my_package");
+        let site_packages = td.path().join("site-packages");
+
+        write(&project.join("__init__.py"), "import json\nfrom my_package import api\n");
+        write(&project.join("api.py"), "import requests\nimport tomli\n");
+        write(&site_packages.join("requests/__init__.py"), "from .api import get\nimport urllib3\n");
+        write(&site_packages.join("requests/api.py"), "import idna\n");
+        write(&site_packages.join("urllib3/__init__.py"), "");
+        write(&site_packages.join("idna.py"), "");
+        write(&site_packages.join("tomli/__init__.py"), "");
+        write(&site_packages.join("unused/__init__.py"), "import requests\n");
+
+        let closure = import_closure(
+            &[project.join("__init__.py"), project.join("api.py")],
+            &[site_packages],
+        );
+
+        assert_eq!(
+            closure.into_iter().collect::<Vec<_>>(),
+            vec!["idna", "requests", "tomli", "urllib3"],
+        );
+    }
+
+    #[test]
+    fn handles_import_cycles() {
+        let td = TempDir::new().unwrap();
+        let site_packages = td.path().join("site-packages");
+        let entry = td.path().join("main.py");
+
+        write(&entry, "import a\n");
+        write(&site_packages.join("a/__init__.py"), "import b\n");
+        write(&site_packages.join("b/__init__.py"), "import a\n");
+
+        assert_eq!(
+            import_closure(&[entry], &[site_packages]).into_iter().collect::<Vec<_>>(),
+            vec!["a", "b"],
+        );
+    }
+}
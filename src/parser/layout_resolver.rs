@@ -1,6 +1,6 @@
-use std::{collections::HashSet, fs::read_dir, path::{Path, PathBuf}};
+use std::{collections::HashSet, env, fs::read_dir, path::{Component, Path, PathBuf}, process::Command};
 
-use crate::parser::error::{ParserError, ParserResult};
+use crate::parser::error::{Explanation, ParserError, ParserResult};
 
 
 #[derive(Clone, Debug)]
@@ -8,11 +8,27 @@ pub struct LayoutHints {
     pub venv: Option<PathBuf>,
     pub module_root: Option<PathBuf>,
     pub module: Option<String>,
+    /// Python interpreter asked for its site-packages instead of scanning the venv.
+    pub interpreter: Option<PathBuf>,
+    /// Also look for a venv among the `.tox/<env>` environments.
+    pub discover_tox: bool,
+    /// Fall back to the pyenv interpreter named by `.python-version`.
+    pub python_version: bool,
+    /// Record why each part of the layout was chosen in [`LayoutResult::trace`].
+    pub explain: bool,
 }
 
 impl Default for LayoutHints {
     fn default() -> Self {
-        Self { venv: None, module_root: None, module: None }
+        Self {
+            venv: None,
+            module_root: None,
+            module: None,
+            interpreter: None,
+            discover_tox: false,
+            python_version: false,
+            explain: false,
+        }
     }
 }
 
@@ -20,15 +36,52 @@ impl Default for LayoutHints {
 pub struct LayoutResult {
     pub venv_dir: PathBuf,
     pub site_packages_dir: PathBuf,
+    pub platlib_dir: Option<PathBuf>,
     pub module_root: PathBuf,
     pub module_name: String,
+    /// The decisions made resolving the layout, in order. Empty unless
+    /// [`LayoutHints::explain`] is set.
+    pub trace: Vec<String>,
+    /// Hints that couldn't be followed, recorded whether or not
+    /// [`LayoutHints::explain`] is set.
+    pub warnings: Vec<String>,
 }
 
-pub struct LayoutResolver;
+/// How many directories, including the project dir, are searched for a venv.
+const MAX_VENV_SEARCH_DEPTH: usize = 4;
+
+/// Prints the interpreter's prefix, purelib and platlib, one per line.
+const INTERPRETER_QUERY: &str = "import sys, sysconfig; print(sys.prefix); print(sysconfig.get_path('purelib')); print(sysconfig.get_path('platlib'))";
+
+#[derive(Clone)]
+pub struct LayoutResolver {
+    virtual_env: Option<PathBuf>,
+    pyenv_root: Option<PathBuf>,
+}
 
 impl LayoutResolver {
+    /// Create a resolver that also considers the activated virtualenv from
+    /// `VIRTUAL_ENV`, and the pyenv installation from `PYENV_ROOT` or `~/.pyenv`.
     pub fn new() -> Self {
-        Self
+        Self {
+            virtual_env: env::var_os("VIRTUAL_ENV")
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from),
+            pyenv_root: env::var_os("PYENV_ROOT")
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| env::home_dir().map(|home| home.join(".pyenv"))),
+        }
+    }
+
+    pub fn with_virtual_env(mut self, virtual_env: Option<PathBuf>) -> Self {
+        self.virtual_env = virtual_env;
+        self
+    }
+
+    pub fn with_pyenv_root(mut self, pyenv_root: Option<PathBuf>) -> Self {
+        self.pyenv_root = pyenv_root;
+        self
     }
 
     pub fn resolve(
@@ -37,55 +90,103 @@ impl LayoutResolver {
         files: &[PathBuf],
         hints: &LayoutHints,
     ) -> ParserResult<LayoutResult> {
+        let mut trace = Vec::new();
+        let mut warnings = Vec::new();
+
         // import_root: hint -> project/src -> project
-        let import_root = hints
+        let (import_root, source) = hints
             .module_root
             .as_ref()
-            .map(|p| if p.is_absolute() { p.clone() } else { project_dir.join(p) })
+            .map(|p| (if p.is_absolute() { p.clone() } else { project_dir.join(p) }, "from the module-root hint"))
             .unwrap_or_else(|| {
                 let src = project_dir.join("src");
-                if src.is_dir() { src } else { project_dir.to_path_buf() }
+                if src.is_dir() {
+                    (src, "as the project has a src directory")
+                } else {
+                    (project_dir.to_path_buf(), "as the project has no src directory")
+                }
             });
+        let import_root = import_root.canonicalize().unwrap_or(import_root);
+        trace.push(format!("import root '{}' {}", import_root.display(), source));
 
-        // venv_dir: hint -> heuristic
-        let venv_dir = hints
-            .venv
-            .as_ref()
-            .map(|p| if p.is_absolute() { p.clone() } else { project_dir.join(p) })
-            .or_else(|| self.find_venv(project_dir))
-            .ok_or(ParserError::MissingVirtualEnv)?;
-
-        // site-packages
-        let site_packages_dir = self.find_site_packages(&venv_dir)
-            .ok_or(ParserError::MissingSitePackages)?;
+        let (venv_dir, site_packages_dir, platlib_dir) = self.resolve_environment(project_dir, hints, &mut trace, &mut warnings)?;
 
         // module override -> discovery
         if let Some(name_raw) = hints.module.as_ref() {
-            let name = name_raw.trim_end_matches(".py").to_string();
-            let file_path = import_root.join(format!("{}.py", &name));
-            let module_root = import_root.join(&name);
+            let conflict = |reason: &str| ParserError::ConflictingLayoutHints {
+                module: name_raw.clone(),
+                module_root: import_root.clone(),
+                reason: reason.to_string(),
+            };
+            let mut name_raw = name_raw.clone();
+
+            // With both hints set, the module has to live under module_root.
+            if hints.module_root.is_some() {
+                let hint = Path::new(&name_raw);
+
+                if hint.components().any(|c| c == Component::ParentDir) {
+                    return Err(conflict("it points outside module_root"));
+                }
 
-            if file_path.is_file() {
-                return Ok(LayoutResult {
-                    venv_dir,
-                    site_packages_dir,
-                    module_root: import_root,
-                    module_name: name,
-                });
+                if hint.is_absolute() {
+                    let hint = hint.canonicalize().unwrap_or_else(|_| hint.to_path_buf());
+                    name_raw = hint
+                        .strip_prefix(&import_root)
+                        .map_err(|_| conflict("it points outside module_root"))?
+                        .to_string_lossy()
+                        .into_owned();
+                }
             }
 
-            if module_root.is_dir()
-                && module_root.join("__init__.py").is_file()
-            {
+            // Nested modules may be given dotted (`pkg.sub`) or as a path
+            // (`pkg/sub`, `pkg/sub.py`). They are imported by their own name
+            // from the directory containing them.
+            let parts = name_raw
+                .trim_end_matches(".py")
+                .split(['.', '/', '\\'])
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>();
+            let Some((name, parents)) = parts.split_last() else {
+                return Err(ParserError::MissingModule(explained(hints, &trace)));
+            };
+
+            if let Some((module_root, path)) = Self::locate_module(&import_root, parents, name) {
+                trace.push(format!(
+                    "module '{}' from the module hint '{}', found at '{}'",
+                    name,
+                    name_raw,
+                    path.display(),
+                ));
+
                 return Ok(LayoutResult {
                     venv_dir,
                     site_packages_dir,
+                    platlib_dir,
                     module_root,
-                    module_name: name,
+                    module_name: name.to_string(),
+                    trace: if hints.explain { trace } else { Vec::new() },
+                    warnings,
                 });
             }
 
-            return Err(ParserError::MissingModule);
+            if hints.module_root.is_none() {
+                trace.push(format!("module hint '{}' not found under the import root", name_raw));
+                return Err(ParserError::MissingModule(explained(hints, &trace)));
+            }
+
+            // Point at the module when it exists where it would be found
+            // without the module_root hint.
+            let elsewhere = [project_dir.join("src"), project_dir.to_path_buf()]
+                .iter()
+                .find_map(|root| Self::locate_module(root, parents, name));
+
+            return Err(match elsewhere {
+                Some((_, path)) => conflict(&format!(
+                    "it is not under module_root, but was found at '{}'",
+                    path.display(),
+                )),
+                None => conflict("it is not under module_root"),
+            });
         }
 
         // Discovery: find top-level directories under import_root that contain __init__.py.
@@ -107,42 +208,271 @@ impl LayoutResolver {
             }
         }
 
+        let mut candidates = candidates.into_iter().collect::<Vec<_>>();
+        candidates.sort();
+
         if candidates.len() != 1 {
-            return Err(ParserError::MissingModule);
+            trace.push(match candidates.is_empty() {
+                true => "no package with an __init__.py under the import root, and no module hint".to_string(),
+                false => format!(
+                    "packages with an __init__.py under the import root: {}, and no module hint to pick one",
+                    candidates.join(", "),
+                ),
+            });
+            return Err(ParserError::MissingModule(explained(hints, &trace)));
         }
 
-        let module_name = candidates.into_iter().next().unwrap();
+        let module_name = candidates.remove(0);
         let module_root = import_root.join(&module_name);
 
         if !module_root.is_dir() || !module_root.join("__init__.py").is_file() {
-            return Err(ParserError::MissingModule);
+            trace.push(format!("package '{}' is not a directory under the import root", module_name));
+            return Err(ParserError::MissingModule(explained(hints, &trace)));
         }
 
+        trace.push(format!(
+            "module '{}' discovered as the only package with an __init__.py under the import root, without a module hint",
+            module_name,
+        ));
+
         Ok(LayoutResult {
             venv_dir,
             site_packages_dir,
+            platlib_dir,
             module_root,
             module_name,
+            trace: if hints.explain { trace } else { Vec::new() },
+            warnings,
         })
     }
 
-    fn find_venv(&self, project_dir: &Path) -> Option<PathBuf> {
-        for dir in ["venv", ".venv", "env", ".env"].iter() {
-            let p = project_dir.join(dir);
-            if p.is_dir() {
+    /// [`Self::resolve`] on the blocking thread pool, as asking the
+    /// interpreter hint for its site-packages runs it.
+    pub async fn resolve_async(
+        &self,
+        project_dir: &Path,
+        files: &[PathBuf],
+        hints: &LayoutHints,
+    ) -> ParserResult<LayoutResult> {
+        let (resolver, project_dir, files, hints) = (self.clone(), project_dir.to_path_buf(), files.to_vec(), hints.clone());
+
+        tokio::task::spawn_blocking(move || resolver.resolve(&project_dir, &files, &hints))
+            .await
+            .map_err(|e| ParserError::UnknownError(e.into()))?
+    }
+
+    /// Find `name` in `parents` under `root`, as a single file or a package.
+    /// Returns the module root and the file or directory found.
+    fn locate_module(root: &Path, parents: &[&str], name: &str) -> Option<(PathBuf, PathBuf)> {
+        let parent_dir = parents.iter().fold(root.to_path_buf(), |dir, part| dir.join(part));
+        let file_path = parent_dir.join(format!("{}.py", name));
+        let package_dir = parent_dir.join(name);
+
+        if file_path.is_file() {
+            return Some((parent_dir, file_path));
+        }
+
+        if package_dir.is_dir() && package_dir.join("__init__.py").is_file() {
+            return Some((package_dir.clone(), package_dir));
+        }
+
+        None
+    }
+
+    /// Resolve the virtual environment and its site-packages directory.
+    pub fn resolve_venv(
+        &self,
+        project_dir: &Path,
+        hints: &LayoutHints,
+    ) -> ParserResult<(PathBuf, PathBuf)> {
+        self.resolve_environment(project_dir, hints, &mut Vec::new(), &mut Vec::new())
+            .map(|(venv_dir, site_packages_dir, _)| (venv_dir, site_packages_dir))
+    }
+
+    /// Resolve the environment's prefix, purelib and distinct platlib, from the
+    /// interpreter hint when it answers, by scanning the venv otherwise.
+    fn resolve_environment(
+        &self,
+        project_dir: &Path,
+        hints: &LayoutHints,
+        trace: &mut Vec<String>,
+        warnings: &mut Vec<String>,
+    ) -> ParserResult<(PathBuf, PathBuf, Option<PathBuf>)> {
+        let interpreter = hints
+            .interpreter
+            .as_ref()
+            .map(|p| if p.components().count() > 1 && !p.is_absolute() { project_dir.join(p) } else { p.clone() });
+
+        if let Some(interpreter) = interpreter {
+            match query_interpreter(&interpreter) {
+                Some((prefix, purelib, platlib)) => {
+                    trace.push(format!(
+                        "site-packages '{}' reported by the interpreter '{}'",
+                        purelib.display(),
+                        interpreter.display(),
+                    ));
+
+                    let platlib = distinct_platlib(platlib, &purelib);
+                    return Ok((prefix, purelib, platlib));
+                },
+                None => {
+                    trace.push(format!(
+                        "the interpreter '{}' did not answer, looking for a venv instead",
+                        interpreter.display(),
+                    ));
+                    warnings.push(format!(
+                        "The interpreter '{}' could not be asked for its site-packages, using the venv found instead",
+                        interpreter.display(),
+                    ));
+                },
+            }
+        }
+
+        let (venv_dir, site_packages_dir) = self.scan_venv(project_dir, hints, trace)?;
+        let platlib_dir = self.find_platlib(&venv_dir, &site_packages_dir);
+
+        if let Some(platlib_dir) = &platlib_dir {
+            trace.push(format!("compiled packages from the separate platlib '{}'", platlib_dir.display()));
+        }
+
+        Ok((venv_dir, site_packages_dir, platlib_dir))
+    }
+
+    fn scan_venv(
+        &self,
+        project_dir: &Path,
+        hints: &LayoutHints,
+        trace: &mut Vec<String>,
+    ) -> ParserResult<(PathBuf, PathBuf)> {
+        if hints.venv.is_none()
+            && let Some(virtual_env) = &self.virtual_env
+            && self.find_site_packages(virtual_env).is_none()
+        {
+            trace.push(format!("VIRTUAL_ENV '{}' skipped, as it has no site-packages", virtual_env.display()));
+        }
+
+        // venv_dir: hint -> VIRTUAL_ENV (when it has site-packages) -> heuristic
+        // -> pyenv interpreter from .python-version (opt-in)
+        let found = hints
+            .venv
+            .as_ref()
+            .map(|p| (if p.is_absolute() { p.clone() } else { project_dir.join(p) }, "from the venv hint"))
+            .or_else(|| self.virtual_env
+                .clone()
+                .filter(|p| self.find_site_packages(p).is_some())
+                .map(|p| (p, "from VIRTUAL_ENV"))
+            )
+            .or_else(|| self.find_venv(project_dir, hints.discover_tox)
+                .map(|p| (p, "found searching the project directory and its parents"))
+            )
+            .or_else(|| hints.python_version
+                .then(|| self.find_pyenv_version(project_dir))
+                .flatten()
+                .map(|p| (p, "as the pyenv interpreter named by .python-version"))
+            );
+
+        let Some((venv_dir, source)) = found else {
+            trace.push(format!("no venv found in '{}' or its parents", project_dir.display()));
+            return Err(ParserError::MissingVirtualEnv(explained(hints, trace)));
+        };
+
+        trace.push(format!("venv '{}' {}", venv_dir.display(), source));
+
+        // site-packages
+        let site_packages_dir = self.find_site_packages(&venv_dir)
+            .ok_or(ParserError::MissingSitePackages)?;
+
+        trace.push(format!("site-packages '{}' in the venv", site_packages_dir.display()));
+
+        Ok((venv_dir, site_packages_dir))
+    }
+
+    /// Look for a venv in `project_dir`, then its parents, so a venv shared at
+    /// the root of a monorepo is found. Stops at the repository root (the
+    /// directory containing `.git`) or after `MAX_VENV_SEARCH_DEPTH` levels.
+    /// With `discover_tox`, the first `.tox/<env>` with site-packages counts as
+    /// a venv too, after the conventionally named ones.
+    fn find_venv(&self, project_dir: &Path, discover_tox: bool) -> Option<PathBuf> {
+        for dir in project_dir.ancestors().take(MAX_VENV_SEARCH_DEPTH) {
+            for name in ["venv", ".venv", "env", ".env"].iter() {
+                let p = dir.join(name);
+                if p.is_dir() {
+                    return Some(p);
+                }
+            }
+
+            if discover_tox && let Some(p) = self.find_tox_env(&dir.join(".tox")) {
                 return Some(p);
             }
+
+            if dir.join(".git").exists() {
+                break;
+            }
         }
         None
     }
 
+    fn find_tox_env(&self, tox_dir: &Path) -> Option<PathBuf> {
+        let mut envs = read_dir(tox_dir)
+            .ok()?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect::<Vec<_>>();
+        envs.sort();
+
+        envs.into_iter().find(|env| self.find_site_packages(env).is_some())
+    }
+
+    /// The prefix of the pyenv interpreter named by the nearest `.python-version`,
+    /// searched for like a venv. A version prefix such as `3.11` picks the
+    /// newest matching installation.
+    fn find_pyenv_version(&self, project_dir: &Path) -> Option<PathBuf> {
+        let versions_dir = self.pyenv_root.as_ref()?.join("versions");
+        let version = project_dir
+            .ancestors()
+            .take(MAX_VENV_SEARCH_DEPTH)
+            .find_map(|dir| std::fs::read_to_string(dir.join(".python-version")).ok())?
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))?
+            .to_string();
+
+        let exact = versions_dir.join(&version);
+        if exact.is_dir() {
+            return Some(exact);
+        }
+
+        read_dir(&versions_dir)
+            .ok()?
+            .flatten()
+            .filter(|entry| entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.starts_with(&format!("{version}.")))
+            )
+            .map(|entry| entry.path())
+            .max_by_key(|path| version_key(path))
+    }
+
     fn find_site_packages(&self, venv_path: &Path) -> Option<PathBuf> {
-        let lib_path = venv_path.join("lib");
+        self.find_site_packages_in(&venv_path.join("lib"))
+    }
+
+    /// Distro Pythons install compiled packages under `lib64`, which in most
+    /// venvs is only a symlink to `lib`.
+    fn find_platlib(&self, venv_path: &Path, site_packages_dir: &Path) -> Option<PathBuf> {
+        let platlib = self.find_site_packages_in(&venv_path.join("lib64"))?;
+
+        distinct_platlib(platlib, site_packages_dir)
+    }
+
+    fn find_site_packages_in(&self, lib_path: &Path) -> Option<PathBuf> {
         if !lib_path.is_dir() {
             return None;
         }
 
-        for entry in read_dir(&lib_path).ok()? {
+        for entry in read_dir(lib_path).ok()? {
             let entry = entry.ok()?;
             if entry.file_name().to_str()?.starts_with("python") {
                 let sp = entry.path().join("site-packages");
@@ -156,6 +486,46 @@ impl LayoutResolver {
     }
 }
 
+/// The trace so far, to attach to an error when `explain` is set.
+fn explained(hints: &LayoutHints, trace: &[String]) -> Explanation {
+    Explanation(if hints.explain { trace.to_vec() } else { Vec::new() })
+}
+
+/// Numeric parts of an installation directory name such as `3.11.4`, so
+/// `3.11.10` sorts after `3.11.9`.
+fn version_key(path: &Path) -> Vec<u32> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+/// `platlib`, unless it is the same directory as `site_packages_dir` or missing.
+fn distinct_platlib(platlib: PathBuf, site_packages_dir: &Path) -> Option<PathBuf> {
+    match (platlib.canonicalize(), site_packages_dir.canonicalize()) {
+        (Ok(a), Ok(b)) if a == b => None,
+        (Err(_), _) => None,
+        _ => Some(platlib),
+    }
+}
+
+/// Ask `interpreter` where it installs packages, as `(prefix, purelib, platlib)`.
+/// `None` when it can't be run or its purelib doesn't exist.
+pub fn query_interpreter(interpreter: &Path) -> Option<(PathBuf, PathBuf, PathBuf)> {
+    let output = Command::new(interpreter)
+        .args(["-c", INTERPRETER_QUERY])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    let mut lines = stdout.lines().map(str::trim).filter(|l| !l.is_empty()).map(PathBuf::from);
+    let (prefix, purelib, platlib) = (lines.next()?, lines.next()?, lines.next()?);
+
+    purelib.is_dir().then_some((prefix, purelib, platlib))
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -240,6 +610,68 @@ mod tests {
         assert_eq!(res.module_root, root.join("src/my_package"));
     }
 
+    #[test]
+    fn explain_traces_why_the_module_was_chosen() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        write(&root.join("src/my_package/__init__.py"), "");
+        write(&root.join("src/tool.py"), "");
+        make_venv(&root.join(".venv"));
+
+        let files = collect_files(root);
+        let resolver = LayoutResolver::new().with_virtual_env(None);
+
+        let discovered = resolver
+            .resolve(root, &files, &LayoutHints { explain: true, ..Default::default() })
+            .unwrap()
+            .trace;
+
+        assert!(discovered[0].contains("as the project has a src directory"), "{discovered:?}");
+        assert!(discovered.iter().any(|step| step.contains(".venv' found searching the project directory")), "{discovered:?}");
+        assert!(discovered.last().unwrap().starts_with("module 'my_package' discovered as the only package"), "{discovered:?}");
+
+        let hinted = resolver
+            .resolve(root, &files, &LayoutHints { module: Some("tool".into()), explain: true, ..Default::default() })
+            .unwrap()
+            .trace;
+
+        assert!(hinted.last().unwrap().starts_with("module 'tool' from the module hint"), "{hinted:?}");
+        assert!(resolver.resolve(root, &files, &LayoutHints::default()).unwrap().trace.is_empty());
+    }
+
+    #[test]
+    fn explain_traces_why_discovery_failed() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        write(&root.join("pkg_b/__init__.py"), "");
+        write(&root.join("pkg_a/__init__.py"), "");
+
+        let files = collect_files(root);
+        let resolver = LayoutResolver::new().with_virtual_env(Some(root.join("not_a_venv")));
+        let explain = LayoutHints { explain: true, ..Default::default() };
+
+        let Err(ParserError::MissingVirtualEnv(Explanation(steps))) = resolver.resolve(root, &files, &explain) else {
+            panic!("expected a missing venv");
+        };
+        assert!(steps.iter().any(|step| step.contains("not_a_venv' skipped")), "{steps:?}");
+        assert!(steps.last().unwrap().starts_with("no venv found"), "{steps:?}");
+
+        make_venv(&root.join("venv"));
+
+        let err = resolver.resolve(root, &files, &explain).unwrap_err();
+        let Explanation(steps) = match &err {
+            ParserError::MissingModule(explanation) => explanation,
+            other => panic!("expected a missing module, got {other:?}"),
+        };
+        assert!(steps.last().unwrap().contains("import root: pkg_a, pkg_b"), "{steps:?}");
+        assert!(err.to_string().starts_with("Missing module\n  import root"), "{err}");
+
+        let err = resolver.resolve(root, &files, &LayoutHints::default()).unwrap_err();
+        assert_eq!(err.to_string(), "Missing module");
+    }
+
     #[test]
     fn resolves_single_file_module() {
         let td = TempDir::new().unwrap();
@@ -265,6 +697,71 @@ mod tests {
         assert_eq!(res.module_root, root.join(""));
     }
 
+    #[test]
+    fn module_override_nested_single_file() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        write(&root.join("sub/thing.py"), "");
+        write(&root.join("thing.py"), "");
+
+        make_venv(&root.join("venv"));
+
+        let files = collect_files(root);
+
+        for module in ["sub.thing", "sub/thing", "sub/thing.py"] {
+            let hints = LayoutHints {
+                module: Some(module.into()),
+                ..Default::default()
+            };
+
+            let res = LayoutResolver::new()
+                .resolve(root, &files, &hints)
+                .unwrap();
+
+            assert_eq!(res.module_name, "thing", "{module}");
+            assert_eq!(res.module_root, root.join("sub"), "{module}");
+        }
+    }
+
+    #[test]
+    fn module_override_nested_package() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        write(&root.join("src/pkg/__init__.py"), "");
+        write(&root.join("src/pkg/subpkg/__init__.py"), "");
+        write(&root.join("src/pkg/subpkg/a.py"), "");
+
+        make_venv(&root.join("venv"));
+
+        let files = collect_files(root);
+
+        for module in ["pkg/subpkg", "pkg.subpkg"] {
+            let hints = LayoutHints {
+                module: Some(module.into()),
+                ..Default::default()
+            };
+
+            let res = LayoutResolver::new()
+                .resolve(root, &files, &hints)
+                .unwrap();
+
+            assert_eq!(res.module_name, "subpkg", "{module}");
+            assert_eq!(res.module_root, root.join("src/pkg/subpkg"), "{module}");
+        }
+
+        let hints = LayoutHints {
+            module: Some("pkg.missing".into()),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            LayoutResolver::new().resolve(root, &files, &hints),
+            Err(ParserError::MissingModule(_)),
+        ));
+    }
+
     #[test]
     fn module_override_directory() {
         let td = TempDir::new().unwrap();
@@ -316,6 +813,89 @@ mod tests {
         assert_eq!(res.module_name, "my_pkg");
     }
 
+    #[test]
+    fn module_not_under_module_root_conflicts() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        write(&root.join("src/my_pkg/__init__.py"), "");
+        fs::create_dir_all(root.join("code")).unwrap();
+
+        make_venv(&root.join("venv"));
+
+        let files = collect_files(root);
+
+        let hints = LayoutHints {
+            module_root: Some("code".into()),
+            module: Some("my_pkg".into()),
+            ..Default::default()
+        };
+
+        let err = LayoutResolver::new()
+            .resolve(root, &files, &hints)
+            .unwrap_err();
+
+        let ParserError::ConflictingLayoutHints { module, reason, .. } = &err else {
+            panic!("expected conflicting hints, got {err:?}");
+        };
+
+        assert_eq!(module, "my_pkg");
+        assert!(reason.contains("src/my_pkg"), "{reason}");
+
+        let hints = LayoutHints {
+            module_root: Some("code".into()),
+            module: Some("missing".into()),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            LayoutResolver::new().resolve(root, &files, &hints),
+            Err(ParserError::ConflictingLayoutHints { reason, .. }) if reason == "it is not under module_root",
+        ));
+    }
+
+    #[test]
+    fn module_outside_module_root_conflicts() {
+        let td = TempDir::new().unwrap();
+        let root = td.path().canonicalize().unwrap();
+
+        write(&root.join("code/my_pkg/__init__.py"), "");
+        write(&root.join("other/my_pkg/__init__.py"), "");
+
+        make_venv(&root.join("venv"));
+
+        let files = collect_files(&root);
+
+        for module in ["../other/my_pkg".to_string(), root.join("other/my_pkg").display().to_string()] {
+            let hints = LayoutHints {
+                module_root: Some("code".into()),
+                module: Some(module.clone()),
+                ..Default::default()
+            };
+
+            assert!(
+                matches!(
+                    LayoutResolver::new().resolve(&root, &files, &hints),
+                    Err(ParserError::ConflictingLayoutHints { .. }),
+                ),
+                "{module}",
+            );
+        }
+
+        let hints = LayoutHints {
+            module_root: Some("code".into()),
+            module: Some(root.join("code/my_pkg").display().to_string()),
+            ..Default::default()
+        };
+
+        let res = LayoutResolver::new()
+            .resolve(&root, &files, &hints)
+            .unwrap();
+
+        assert_eq!(res.module_name, "my_pkg");
+        assert_eq!(res.module_root, root.join("code/my_pkg"));
+    }
+
     #[test]
     fn venv_override() {
         let td = TempDir::new().unwrap();
@@ -353,7 +933,7 @@ mod tests {
             .resolve(root, &files, &LayoutHints::default())
             .unwrap_err();
 
-        matches!(err, ParserError::MissingVirtualEnv);
+        matches!(err, ParserError::MissingVirtualEnv(_));
     }
 
     #[test]
@@ -390,7 +970,7 @@ mod tests {
             .resolve(root, &files, &LayoutHints::default())
             .unwrap_err();
 
-        matches!(err, ParserError::MissingModule);
+        matches!(err, ParserError::MissingModule(_));
     }
 
     #[test]
@@ -408,6 +988,313 @@ mod tests {
             .resolve(root, &files, &LayoutHints::default())
             .unwrap_err();
 
-        matches!(err, ParserError::MissingModule);
+        matches!(err, ParserError::MissingModule(_));
+    }
+
+    #[test]
+    fn virtual_env_is_preferred_over_heuristic() {
+        let td = TempDir::new().unwrap();
+        let root = td.path().join("project");
+        let active = td.path().join("active_env");
+
+        write(&root.join("my_package/__init__.py"), "");
+        make_venv(&root.join("venv"));
+        make_venv(&active);
+
+        let files = collect_files(&root);
+
+        let res = LayoutResolver::new()
+            .with_virtual_env(Some(active.clone()))
+            .resolve(&root, &files, &LayoutHints::default())
+            .unwrap();
+
+        assert_eq!(res.venv_dir, active);
+        assert_eq!(res.site_packages_dir, active.join("lib/python3.11/site-packages"));
+    }
+
+    #[test]
+    fn venv_hint_is_preferred_over_virtual_env() {
+        let td = TempDir::new().unwrap();
+        let root = td.path().join("project");
+        let active = td.path().join("active_env");
+
+        write(&root.join("my_package/__init__.py"), "");
+        make_venv(&root.join("custom_env"));
+        make_venv(&active);
+
+        let files = collect_files(&root);
+
+        let res = LayoutResolver::new()
+            .with_virtual_env(Some(active))
+            .resolve(
+                &root,
+                &files,
+                &LayoutHints {
+                    venv: Some("custom_env".into()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(res.venv_dir, root.join("custom_env"));
+    }
+
+    #[test]
+    fn virtual_env_without_site_packages_is_skipped() {
+        let td = TempDir::new().unwrap();
+        let root = td.path().join("project");
+        let active = td.path().join("active_env");
+
+        write(&root.join("my_package/__init__.py"), "");
+        make_venv(&root.join("venv"));
+        fs::create_dir_all(&active).unwrap();
+
+        let files = collect_files(&root);
+
+        let res = LayoutResolver::new()
+            .with_virtual_env(Some(active))
+            .resolve(&root, &files, &LayoutHints::default())
+            .unwrap();
+
+        assert_eq!(res.venv_dir, root.join("venv"));
+    }
+
+    #[test]
+    fn finds_venv_in_parent_directories() {
+        let td = TempDir::new().unwrap();
+        let repo = td.path().join("repo");
+        let root = repo.join("packages/app");
+
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        make_venv(&repo.join(".venv"));
+        write(&root.join("my_package/__init__.py"), "");
+
+        let files = collect_files(&root);
+
+        let res = LayoutResolver::new()
+            .with_virtual_env(None)
+            .resolve(&root, &files, &LayoutHints::default())
+            .unwrap();
+
+        assert_eq!(res.venv_dir, repo.join(".venv"));
+        assert_eq!(res.site_packages_dir, repo.join(".venv/lib/python3.11/site-packages"));
+    }
+
+    #[test]
+    fn nearest_venv_wins() {
+        let td = TempDir::new().unwrap();
+        let repo = td.path().join("repo");
+        let root = repo.join("packages/app");
+
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        make_venv(&repo.join(".venv"));
+        make_venv(&root.join("venv"));
+        write(&root.join("my_package/__init__.py"), "");
+
+        let files = collect_files(&root);
+
+        let res = LayoutResolver::new()
+            .with_virtual_env(None)
+            .resolve(&root, &files, &LayoutHints::default())
+            .unwrap();
+
+        assert_eq!(res.venv_dir, root.join("venv"));
+    }
+
+    #[test]
+    fn tox_envs_are_discovered_when_enabled() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        write(&root.join("my_package/__init__.py"), "");
+        fs::create_dir_all(root.join(".tox/.tmp")).unwrap();
+        make_venv(&root.join(".tox/py312"));
+        make_venv(&root.join(".tox/py311"));
+
+        let files = collect_files(root);
+        let resolver = LayoutResolver::new().with_virtual_env(None);
+
+        assert!(matches!(
+            resolver.resolve(root, &files, &LayoutHints::default()),
+            Err(ParserError::MissingVirtualEnv(_)),
+        ));
+
+        let res = resolver
+            .resolve(root, &files, &LayoutHints { discover_tox: true, ..Default::default() })
+            .unwrap();
+
+        assert_eq!(res.venv_dir, root.join(".tox/py311"));
+        assert_eq!(res.site_packages_dir, root.join(".tox/py311/lib/python3.11/site-packages"));
+
+        make_venv(&root.join(".venv"));
+
+        let res = resolver
+            .resolve(root, &files, &LayoutHints { discover_tox: true, ..Default::default() })
+            .unwrap();
+
+        assert_eq!(res.venv_dir, root.join(".venv"));
+    }
+
+    #[test]
+    fn python_version_selects_the_pyenv_interpreter() {
+        let td = TempDir::new().unwrap();
+        let root = td.path().join("project");
+        let pyenv = td.path().join("pyenv");
+
+        write(&root.join("my_package/__init__.py"), "");
+        write(&root.join(".python-version"), "# pinned\n3.11");
+        make_venv(&pyenv.join("versions/3.11.9"));
+        make_venv(&pyenv.join("versions/3.11.10"));
+        make_venv(&pyenv.join("versions/3.12.1"));
+
+        let files = collect_files(&root);
+        let resolver = LayoutResolver::new()
+            .with_virtual_env(None)
+            .with_pyenv_root(Some(pyenv.clone()));
+        let hints = LayoutHints { python_version: true, ..Default::default() };
+
+        assert!(resolver.resolve(&root, &files, &LayoutHints::default()).is_err());
+        assert_eq!(
+            resolver.resolve(&root, &files, &hints).unwrap().venv_dir,
+            pyenv.join("versions/3.11.10"),
+        );
+
+        write(&root.join(".python-version"), "3.12.1");
+
+        assert_eq!(
+            resolver.resolve(&root, &files, &hints).unwrap().site_packages_dir,
+            pyenv.join("versions/3.12.1/lib/python3.11/site-packages"),
+        );
+    }
+
+    #[test]
+    fn venv_search_stops_at_git_boundary() {
+        let td = TempDir::new().unwrap();
+        let root = td.path().join("repo/app");
+
+        make_venv(&td.path().join(".venv"));
+        fs::create_dir_all(td.path().join("repo/.git")).unwrap();
+        write(&root.join("my_package/__init__.py"), "");
+
+        let files = collect_files(&root);
+
+        let res = LayoutResolver::new()
+            .with_virtual_env(None)
+            .resolve(&root, &files, &LayoutHints::default());
+
+        assert!(matches!(res, Err(ParserError::MissingVirtualEnv(_))));
+    }
+
+    #[test]
+    fn resolves_split_purelib_and_platlib() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        write(&root.join("my_package/__init__.py"), "");
+        make_venv(&root.join("venv"));
+        fs::create_dir_all(root.join("venv/lib64/python3.11/site-packages")).unwrap();
+
+        let res = LayoutResolver::new()
+            .with_virtual_env(None)
+            .resolve(root, &collect_files(root), &LayoutHints::default())
+            .unwrap();
+
+        assert_eq!(res.site_packages_dir, root.join("venv/lib/python3.11/site-packages"));
+        assert_eq!(res.platlib_dir, Some(root.join("venv/lib64/python3.11/site-packages")));
+    }
+
+    /// A stand-in interpreter printing the given prefix, purelib and platlib.
+    #[cfg(unix)]
+    fn fake_interpreter(path: &Path, paths: [&Path; 3]) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let script = paths
+            .iter()
+            .map(|p| format!("echo '{}'\n", p.display()))
+            .collect::<String>();
+        fs::write(path, format!("#!/bin/sh\n{script}")).unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn interpreter_hint_locates_site_packages() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+        let prefix = td.path().join("python");
+        let purelib = prefix.join("lib/python3.12/site-packages");
+        let platlib = prefix.join("lib64/python3.12/site-packages");
+
+        write(&root.join("my_package/__init__.py"), "");
+        make_venv(&root.join("venv"));
+        fs::create_dir_all(&purelib).unwrap();
+        fs::create_dir_all(&platlib).unwrap();
+        fake_interpreter(&root.join("python3"), [&prefix, &purelib, &platlib]);
+
+        let res = LayoutResolver::new()
+            .with_virtual_env(None)
+            .resolve(
+                root,
+                &collect_files(root),
+                &LayoutHints {
+                    interpreter: Some("./python3".into()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(res.venv_dir, prefix);
+        assert_eq!(res.site_packages_dir, purelib);
+        assert_eq!(res.platlib_dir, Some(platlib));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn failing_interpreter_falls_back_to_the_scan() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        write(&root.join("my_package/__init__.py"), "");
+        make_venv(&root.join("venv"));
+        // Prints paths that don't exist.
+        fake_interpreter(&root.join("python3"), [Path::new("/nowhere"); 3]);
+
+        for interpreter in [root.join("python3"), root.join("missing-python")] {
+            let res = LayoutResolver::new()
+                .with_virtual_env(None)
+                .resolve_async(
+                    root,
+                    &collect_files(root),
+                    &LayoutHints {
+                        interpreter: Some(interpreter.clone()),
+                        ..Default::default()
+                    },
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(res.venv_dir, root.join("venv"));
+            assert_eq!(res.site_packages_dir, root.join("venv/lib/python3.11/site-packages"));
+            assert_eq!(res.warnings.len(), 1);
+            assert!(res.warnings[0].contains(&interpreter.display().to_string()), "{:?}", res.warnings);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_platlib_is_not_distinct() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        write(&root.join("my_package/__init__.py"), "");
+        make_venv(&root.join("venv"));
+        std::os::unix::fs::symlink(root.join("venv/lib"), root.join("venv/lib64")).unwrap();
+
+        let res = LayoutResolver::new()
+            .with_virtual_env(None)
+            .resolve(root, &collect_files(root), &LayoutHints::default())
+            .unwrap();
+
+        assert_eq!(res.platlib_dir, None);
     }
 }
@@ -0,0 +1,232 @@
+use std::path::{Path, PathBuf};
+use globset::GlobBuilder;
+
+use crate::parser::{
+    error::{ParserError, ParserResult},
+    file_walker::{traits::FileIgnoreStrategy, default::DefaultFileIgnoreStrategy},
+};
+
+
+/// Check that `expression` is a well-formed SPDX license expression, as PEP 639
+/// and cargo both expect, and normalize its operators and spacing, e.g.
+/// `MIT or(Zlib )` becomes `MIT OR (Zlib)`.
+/// License identifiers are not checked against the SPDX list.
+pub fn normalize_expression(expression: &str) -> ParserResult<String> {
+    let invalid = |reason: &str| ParserError::InvalidLicenseExpression {
+        expression: expression.to_string(),
+        reason: reason.to_string(),
+    };
+    let tokens = expression
+        .replace('(', " ( ")
+        .replace(')', " ) ")
+        .split_whitespace()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+
+    if tokens.is_empty() {
+        return Err(invalid("it is empty"));
+    }
+
+    let mut parser = ExpressionParser { tokens: &tokens, position: 0 };
+    let normalized = parser.or_expression().map_err(invalid)?;
+
+    match parser.peek() {
+        None => Ok(normalized),
+        Some(")") => Err(invalid("unbalanced ')'")),
+        Some(token) => Err(invalid(&format!("expected AND, OR or WITH before '{token}'"))),
+    }
+}
+
+/// Recursive descent over the grammar of SPDX expressions, where AND binds
+/// tighter than OR and WITH tighter than both.
+struct ExpressionParser<'a> {
+    tokens: &'a [String],
+    position: usize,
+}
+
+impl ExpressionParser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Option<&str> {
+        self.position += 1;
+        self.tokens.get(self.position - 1).map(String::as_str)
+    }
+
+    fn operator(&self) -> Option<&'static str> {
+        match self.peek()?.to_ascii_uppercase().as_str() {
+            "AND" => Some("AND"),
+            "OR" => Some("OR"),
+            "WITH" => Some("WITH"),
+            _ => None,
+        }
+    }
+
+    fn or_expression(&mut self) -> Result<String, &'static str> {
+        let mut expression = self.and_expression()?;
+
+        while self.operator() == Some("OR") {
+            self.position += 1;
+            expression = format!("{expression} OR {}", self.and_expression()?);
+        }
+
+        Ok(expression)
+    }
+
+    fn and_expression(&mut self) -> Result<String, &'static str> {
+        let mut expression = self.simple_expression()?;
+
+        while self.operator() == Some("AND") {
+            self.position += 1;
+            expression = format!("{expression} AND {}", self.simple_expression()?);
+        }
+
+        Ok(expression)
+    }
+
+    fn simple_expression(&mut self) -> Result<String, &'static str> {
+        if self.peek() == Some("(") {
+            self.position += 1;
+            let expression = self.or_expression()?;
+
+            return match self.next() {
+                Some(")") => Ok(format!("({expression})")),
+                _ => Err("unbalanced '('"),
+            };
+        }
+
+        let license = self.identifier(true)?;
+
+        if self.operator() == Some("WITH") {
+            self.position += 1;
+            return Ok(format!("{license} WITH {}", self.identifier(false)?));
+        }
+
+        Ok(license)
+    }
+
+    fn identifier(&mut self, allow_plus: bool) -> Result<String, &'static str> {
+        if self.operator().is_some() {
+            return Err("an operator is missing an operand");
+        }
+
+        let token = self.next().ok_or("an operator is missing an operand")?;
+        let name = match token.strip_suffix('+') {
+            Some(name) if allow_plus => name,
+            _ => token,
+        };
+        let valid = !name.is_empty() && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':'));
+
+        if valid { Ok(token.to_string()) } else { Err("it contains an invalid license identifier") }
+    }
+}
+
+/// Files under `root` matching the PEP 639 `license-files` globs, sorted.
+/// Each glob has to match at least one file.
+pub fn find_license_files<S: AsRef<str>>(root: &Path, patterns: &[S]) -> ParserResult<Vec<PathBuf>> {
+    let files = walk(root, root);
+    let mut matched = Vec::new();
+
+    for pattern in patterns {
+        let matcher = GlobBuilder::new(pattern.as_ref())
+            .literal_separator(true)
+            .build()
+            .map_err(|e| ParserError::InvalidLicenseFiles(format!("{}: {}", pattern.as_ref(), e)))?
+            .compile_matcher();
+        let before = matched.len();
+
+        matched.extend(files
+            .iter()
+            .filter(|file| matcher.is_match(file))
+            .map(|file| root.join(file))
+        );
+
+        if matched.len() == before {
+            return Err(ParserError::InvalidLicenseFiles(format!("{} matches no file", pattern.as_ref())));
+        }
+    }
+
+    matched.sort();
+    matched.dedup();
+
+    Ok(matched)
+}
+
+/// The files under `dir`, relative to `root`, skipping the directories the
+/// file walker ignores.
+fn walk(root: &Path, dir: &Path) -> Vec<PathBuf> {
+    let ignore = DefaultFileIgnoreStrategy::new();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| !ignore.should_ignore(path))
+        .flat_map(|path| match path.is_dir() {
+            true => walk(root, &path),
+            false => path.strip_prefix(root).map(Path::to_path_buf).into_iter().collect(),
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn compound_expressions_pass_through() {
+        for expression in [
+            "MIT",
+            "MIT OR Apache-2.0",
+            "(MIT OR Apache-2.0) AND BSD-3-Clause",
+            "Apache-2.0 WITH LLVM-exception OR MIT",
+            "GPL-2.0+ AND LicenseRef-Proprietary",
+        ] {
+            assert_eq!(normalize_expression(expression).unwrap(), expression);
+        }
+
+        assert_eq!(
+            normalize_expression("  mit or(Apache-2.0  and Zlib )").unwrap(),
+            "mit OR (Apache-2.0 AND Zlib)",
+        );
+    }
+
+    #[test]
+    fn malformed_expressions_are_rejected() {
+        for expression in ["", "MIT License", "MIT OR", "AND MIT", "(MIT OR Apache-2.0", "MIT)", "MIT/Apache-2.0", "MIT WITH"] {
+            assert!(
+                matches!(normalize_expression(expression), Err(ParserError::InvalidLicenseExpression { .. })),
+                "{expression}",
+            );
+        }
+    }
+
+    #[test]
+    fn license_files_are_globbed() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        for file in ["LICENSE", "LICENSE-MIT", "licenses/APACHE.txt", "venv/LICENSE", "README.md"] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+
+        assert_eq!(
+            find_license_files(root, &["LICEN[CS]E*", "licenses/*.txt"]).unwrap(),
+            vec![root.join("LICENSE"), root.join("LICENSE-MIT"), root.join("licenses/APACHE.txt")],
+        );
+        assert!(matches!(
+            find_license_files(root, &["COPYING"]),
+            Err(ParserError::InvalidLicenseFiles(_)),
+        ));
+    }
+}
@@ -1,2 +1,6 @@
 pub mod traits;
-pub mod pep621;
\ No newline at end of file
+pub mod pep621;
+pub mod poetry;
+pub mod setupcfg;
+pub mod strategy;
+pub mod license;
@@ -1,12 +1,12 @@
-use std::path::{Path, PathBuf};
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
 use serde::Deserialize;
 use async_trait::async_trait;
 use tokio::fs;
 
 use crate::{
-    types::{ProjectMetadata, Py2BinmodConfig},
+    types::{ProjectMetadata, ProjectUrls, Py2BinmodConfig},
     parser::error::{ParserError, ParserResult},
-    parser::metadata_parser::traits::MetadataParser
+    parser::metadata_parser::{traits::{MetadataParser, MetadataOverrides}, license},
 };
 
 
@@ -25,12 +25,46 @@ struct ToolSection {
 #[derive(Deserialize, Debug)]
 struct ProjectSection {
     name: String,
-    version: String,
+    /// Absent when listed in `dynamic`.
+    version: Option<String>,
+    #[serde(default)]
+    dynamic: Vec<String>,
     description: Option<String>,
     authors: Option<Vec<Author>>,
     license: Option<License>,
+    /// PEP 639 globs, relative to the pyproject.toml.
+    #[serde(rename = "license-files")]
+    license_files: Option<Vec<String>>,
     #[serde(rename = "requires-python")]
     requires_python: Option<String>,
+    #[serde(rename = "optional-dependencies", default)]
+    optional_dependencies: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    urls: BTreeMap<String, String>,
+}
+
+/// Map `[project.urls]` to cargo's fields by their label, which is free-form,
+/// so labels are compared ignoring case and punctuation.
+pub(crate) fn project_urls(urls: &BTreeMap<String, String>) -> ProjectUrls {
+    let mut project_urls = ProjectUrls::default();
+
+    for (label, url) in urls {
+        let label = label
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+        let field = match label.as_str() {
+            "homepage" | "home" => &mut project_urls.homepage,
+            "repository" | "repo" | "source" | "sourcecode" | "code" => &mut project_urls.repository,
+            "documentation" | "docs" => &mut project_urls.documentation,
+            _ => continue,
+        };
+
+        field.get_or_insert_with(|| url.clone());
+    }
+
+    project_urls
 }
 
 #[derive(Deserialize, Debug)]
@@ -39,6 +73,18 @@ struct Author {
     email: Option<String>,
 }
 
+impl Author {
+    /// Cargo's `Name <email>` form, or whichever of the two is present.
+    fn to_cargo(&self) -> Option<String> {
+        match (&self.name, &self.email) {
+            (Some(name), Some(email)) => Some(format!("{} <{}>", name, email)),
+            (Some(name), None) => Some(name.clone()),
+            (None, Some(email)) => Some(email.clone()),
+            (None, None) => None,
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(untagged)]
 enum License {
@@ -47,11 +93,111 @@ enum License {
 }
 
 #[derive(Deserialize, Debug)]
-struct Py2BinmodToml {
+pub(crate) struct Py2BinmodToml {
     pub venv: Option<String>,
+    pub interpreter: Option<String>,
     #[serde(rename = "module-root")]
     pub module_root: Option<String>,
     pub module: Option<String>,
+    #[serde(rename = "rustpython-version")]
+    pub rustpython_version: Option<String>,
+    #[serde(rename = "mdk-version")]
+    pub mdk_version: Option<String>,
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(rename = "stack-size")]
+    pub stack_size: Option<u64>,
+    #[serde(rename = "recursion-limit")]
+    pub recursion_limit: Option<usize>,
+    pub freeze: Option<Vec<String>>,
+    #[serde(rename = "freeze-imported", default)]
+    pub freeze_imported: bool,
+    pub stdlib: Option<Vec<String>>,
+    #[serde(default)]
+    pub strict: bool,
+    #[serde(rename = "generate-tests", default)]
+    pub generate_tests: bool,
+    #[serde(rename = "build-env", default)]
+    pub build_env: BTreeMap<String, String>,
+    #[serde(rename = "target-dir")]
+    pub target_dir: Option<String>,
+    #[serde(default)]
+    pub features: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub rustflags: Vec<String>,
+    #[serde(rename = "import-prefix")]
+    pub import_prefix: Option<String>,
+    #[serde(rename = "workspace-root")]
+    pub workspace_root: Option<String>,
+    #[serde(rename = "emit-cargo-lock", default)]
+    pub emit_cargo_lock: bool,
+    #[serde(rename = "crate-type")]
+    pub crate_types: Option<Vec<String>>,
+    #[serde(rename = "discover-tox", default)]
+    pub discover_tox: bool,
+    #[serde(rename = "use-python-version", default)]
+    pub use_python_version: bool,
+    /// Used when the project's version is dynamic.
+    pub version: Option<String>,
+}
+
+impl Py2BinmodToml {
+    pub(crate) fn into_config(self) -> Py2BinmodConfig {
+        Py2BinmodConfig {
+            venv: self.venv.map(PathBuf::from),
+            interpreter: self.interpreter.map(PathBuf::from),
+            module_root: self.module_root.map(PathBuf::from),
+            module: self.module,
+            rustpython_version: self.rustpython_version,
+            mdk_version: self.mdk_version,
+            ignore: self.ignore,
+            include: self.include,
+            stack_size: self.stack_size,
+            recursion_limit: self.recursion_limit,
+            freeze: self.freeze,
+            freeze_imported: self.freeze_imported,
+            stdlib: self.stdlib,
+            strict: self.strict,
+            generate_tests: self.generate_tests,
+            build_env: self.build_env,
+            target_dir: self.target_dir.map(PathBuf::from),
+            features: self.features,
+            rustflags: self.rustflags,
+            import_prefix: self.import_prefix,
+            workspace_root: self.workspace_root.map(PathBuf::from),
+            emit_cargo_lock: self.emit_cargo_lock,
+            crate_types: self.crate_types,
+            discover_tox: self.discover_tox,
+            use_python_version: self.use_python_version,
+        }
+    }
+}
+
+impl ProjectSection {
+    /// The static version, or `fallback` when the version is dynamic, as
+    /// build backends compute it and it can't be read from the file.
+    fn resolve_version(&self, fallback: Option<&str>) -> ParserResult<String> {
+        match (&self.version, self.dynamic.iter().any(|field| field == "version")) {
+            (Some(version), _) => Ok(version.clone()),
+            (None, true) => fallback
+                .map(str::to_string)
+                .ok_or_else(|| ParserError::UnresolvedDynamicField("version".to_string())),
+            (None, false) => Err(ParserError::MissingProjectMetadata),
+        }
+    }
+}
+
+/// The `[tool.py2binmod]` table of the pyproject.toml at `path`, for parsers
+/// reading the metadata from elsewhere. A missing file has none.
+pub(crate) async fn read_tool_config(path: &Path) -> ParserResult<Option<Py2BinmodToml>> {
+    let Ok(content) = fs::read_to_string(path).await else {
+        return Ok(None);
+    };
+    let pyproject: PyProjectToml = toml::from_str(&content)?;
+
+    Ok(pyproject.tool.and_then(|tool| tool.py2binmod))
 }
 
 pub struct Pep621MetadataParser;
@@ -64,34 +210,67 @@ impl Pep621MetadataParser {
 
 #[async_trait]
 impl MetadataParser for Pep621MetadataParser {
-    async fn parse(&self, project_dir: &Path) -> ParserResult<ProjectMetadata> {
-        let pyproject_path = project_dir.join("pyproject.toml");
+    async fn parse_with(
+        &self,
+        project_dir: &Path,
+        metadata_path: Option<&Path>,
+        overrides: &MetadataOverrides,
+    ) -> ParserResult<ProjectMetadata> {
+        let pyproject_path = metadata_path
+            .map(|path| project_dir.join(path))
+            .unwrap_or_else(|| project_dir.join("pyproject.toml"));
+        // PEP 621 paths are relative to the pyproject.toml they appear in.
+        let metadata_dir = pyproject_path.parent().unwrap_or(project_dir);
         let content = fs::read_to_string(&pyproject_path)
             .await
             .map_err(|_| ParserError::MissingProjectMetadata)?;
         let pyproject: PyProjectToml = toml::from_str(&content)?;
+        let fallback_version = pyproject
+            .tool
+            .as_ref()
+            .and_then(|tool| tool.py2binmod.as_ref())
+            .and_then(|c| c.version.clone());
         let py2binmod_config = pyproject
             .tool
             .and_then(|tool| tool.py2binmod)
-            .map(|c| Py2BinmodConfig {
-                venv: c.venv.map(PathBuf::from),
-                module_root: c.module_root.map(PathBuf::from),
-                module: c.module,
-            });
+            .map(Py2BinmodToml::into_config);
+
+
+        let project = pyproject
+            .project
+            .as_ref()
+            .ok_or(ParserError::MissingProjectMetadata)?;
+
+        // The generated crate is built elsewhere, so the path is made absolute.
+        // Cargo takes a single file, the first one `license-files` matches.
+        let mut warnings = Vec::new();
+        let license_file = match project {
+            ProjectSection { license: Some(License::Detailed { text: None, file: Some(file) }), .. } =>
+                Some(metadata_dir.join(file)),
+            ProjectSection { license_files: Some(patterns), .. } => {
+                let files = license::find_license_files(metadata_dir, patterns)?;
+
+                if files.len() > 1 {
+                    warnings.push(format!(
+                        "license-files matched {} files but Cargo takes one, only '{}' is used",
+                        files.len(),
+                        files[0].display(),
+                    ));
+                }
+
+                files.into_iter().next()
+            },
+            _ => None,
+        };
 
         Ok(ProjectMetadata {
-            name: pyproject
-                .project
-                .as_ref()
-                .ok_or(ParserError::MissingProjectMetadata)?
-                .name
-                .clone(),
-            version: pyproject
-                .project
-                .as_ref()
-                .ok_or(ParserError::MissingProjectMetadata)?
-                .version
-                .clone(),
+            name: overrides.name
+                .clone()
+                .unwrap_or_else(|| project.name.clone()),
+            version: match &overrides.version {
+                Some(version) => version.clone(),
+                None => project.resolve_version(fallback_version.as_deref())?,
+            },
             requires_python: pyproject
                 .project
                 .as_ref()
@@ -107,7 +286,7 @@ impl MetadataParser for Pep621MetadataParser {
                 .map(|authors| {
                     authors
                         .iter()
-                        .filter_map(|a| a.name.clone().or_else(|| a.email.clone()))
+                        .filter_map(Author::to_cargo)
                         .collect::<Vec<String>>()
                 })
                 .unwrap_or_default(),
@@ -116,10 +295,240 @@ impl MetadataParser for Pep621MetadataParser {
                 .as_ref()
                 .and_then(|p| p.license.as_ref())
                 .and_then(|lic| match lic {
-                    License::Simple(s) => Some(s.clone()),
-                    License::Detailed { text, file } => text.clone().or_else(|| file.clone()),
-                }),
+                    License::Simple(s) => Some(license::normalize_expression(s)),
+                    License::Detailed { text, .. } => text.clone().map(Ok),
+                })
+                .transpose()?,
+            license_file,
+            urls: pyproject
+                .project
+                .as_ref()
+                .map(|p| project_urls(&p.urls))
+                .unwrap_or_default(),
+            optional_dependencies: pyproject
+                .project
+                .as_ref()
+                .map(|p| p.optional_dependencies.clone())
+                .unwrap_or_default(),
             py2binmod: py2binmod_config,
+            warnings,
         })
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn parse(content: &str) -> ProjectMetadata {
+        let td = TempDir::new().unwrap();
+        std::fs::write(td.path().join("pyproject.toml"), content).unwrap();
+
+        Pep621MetadataParser::new()
+            .parse(td.path(), None)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn parses_optional_dependencies() {
+        let metadata = parse(r#"
+[project]
+name = "demo"
+version = "0.1.0"
+
+[project.optional-dependencies]
+yaml = ["pyyaml>=6"]
+http = ["requests[socks]>=2.31", "idna"]
+"#).await;
+
+        assert_eq!(metadata.optional_dependencies.len(), 2);
+        assert_eq!(metadata.optional_dependencies["yaml"], vec!["pyyaml>=6"]);
+        assert_eq!(metadata.optional_dependencies["http"], vec!["requests[socks]>=2.31", "idna"]);
+    }
+
+    #[tokio::test]
+    async fn optional_dependencies_default_to_empty() {
+        let metadata = parse("[project]\nname = \"demo\"\nversion = \"0.1.0\"\n").await;
+
+        assert!(metadata.optional_dependencies.is_empty());
+    }
+
+    #[tokio::test]
+    async fn formats_authors_like_cargo() {
+        let metadata = parse(r#"
+[project]
+name = "demo"
+version = "0.1.0"
+authors = [
+    { name = "Ada Lovelace", email = "ada@example.com" },
+    { name = "Charles Babbage" },
+    { email = "team@example.com" },
+    {},
+]
+"#).await;
+
+        assert_eq!(metadata.authors, vec![
+            "Ada Lovelace <ada@example.com>",
+            "Charles Babbage",
+            "team@example.com",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn maps_project_urls_by_label() {
+        let metadata = parse(r#"
+[project]
+name = "demo"
+version = "0.1.0"
+
+[project.urls]
+Homepage = "https://example.com"
+"Source Code" = "https://github.com/example/demo"
+Docs = "https://docs.example.com"
+"Bug Tracker" = "https://github.com/example/demo/issues"
+"#).await;
+
+        assert_eq!(metadata.urls, ProjectUrls {
+            homepage: Some("https://example.com".into()),
+            repository: Some("https://github.com/example/demo".into()),
+            documentation: Some("https://docs.example.com".into()),
+        });
+    }
+
+    #[tokio::test]
+    async fn parses_build_env() {
+        let metadata = parse(r#"
+[project]
+name = "demo"
+version = "0.1.0"
+
+[tool.py2binmod.build-env]
+RUSTFLAGS = "-C opt-level=z"
+CC = "clang"
+"#).await;
+
+        let rustflags = parse(r#"
+[project]
+name = "demo"
+version = "0.1.0"
+
+[tool.py2binmod]
+rustflags = ["-C", "opt-level=z"]
+"#).await.py2binmod.unwrap().rustflags;
+
+        assert_eq!(rustflags, vec!["-C", "opt-level=z"]);
+
+        assert_eq!(
+            metadata.py2binmod.unwrap().build_env,
+            BTreeMap::from([
+                ("CC".to_string(), "clang".to_string()),
+                ("RUSTFLAGS".to_string(), "-C opt-level=z".to_string()),
+            ]),
+        );
+    }
+
+    #[tokio::test]
+    async fn separates_license_expressions_from_files() {
+        let metadata = parse("[project]\nname = \"demo\"\nversion = \"0.1.0\"\nlicense = \"MIT\"\n").await;
+
+        assert_eq!(metadata.license.as_deref(), Some("MIT"));
+        assert_eq!(metadata.license_file, None);
+
+        let metadata = parse("[project]\nname = \"demo\"\nversion = \"0.1.0\"\nlicense = { file = \"LICENSE\" }\n").await;
+
+        assert_eq!(metadata.license, None);
+        assert!(metadata.license_file.is_some_and(|file| file.is_absolute() && file.ends_with("LICENSE")));
+    }
+
+    #[tokio::test]
+    async fn reads_pep639_license_fields() {
+        let metadata = parse("[project]\nname = \"demo\"\nversion = \"0.1.0\"\nlicense = \"MIT or (Apache-2.0 WITH LLVM-exception)\"\n").await;
+
+        assert_eq!(metadata.license.as_deref(), Some("MIT OR (Apache-2.0 WITH LLVM-exception)"));
+
+        let td = TempDir::new().unwrap();
+        std::fs::write(td.path().join("LICENSE-MIT"), "").unwrap();
+        std::fs::write(td.path().join("LICENSE-APACHE"), "").unwrap();
+        std::fs::write(
+            td.path().join("pyproject.toml"),
+            "[project]\nname = \"demo\"\nversion = \"0.1.0\"\nlicense = \"MIT OR Apache-2.0\"\nlicense-files = [\"LICENSE-*\"]\n",
+        ).unwrap();
+
+        let metadata = Pep621MetadataParser::new().parse(td.path(), None).await.unwrap();
+
+        assert_eq!(metadata.license.as_deref(), Some("MIT OR Apache-2.0"));
+        assert_eq!(metadata.license_file, Some(td.path().join("LICENSE-APACHE")));
+        assert_eq!(metadata.warnings.len(), 1);
+        assert!(metadata.warnings[0].contains("matched 2 files"), "{:?}", metadata.warnings);
+
+        std::fs::write(
+            td.path().join("pyproject.toml"),
+            "[project]\nname = \"demo\"\nversion = \"0.1.0\"\nlicense = \"MIT License\"\n",
+        ).unwrap();
+
+        assert!(matches!(
+            Pep621MetadataParser::new().parse(td.path(), None).await,
+            Err(ParserError::InvalidLicenseExpression { .. }),
+        ));
+    }
+
+    #[tokio::test]
+    async fn reads_metadata_from_custom_path() {
+        let td = TempDir::new().unwrap();
+        let config_dir = td.path().join("config");
+        std::fs::create_dir_all(&config_dir).unwrap();
+        std::fs::write(
+            config_dir.join("py2binmod.toml"),
+            "[project]\nname = \"custom\"\nversion = \"2.0.0\"\nlicense = { file = \"LICENSE\" }\n",
+        ).unwrap();
+
+        let metadata = Pep621MetadataParser::new()
+            .parse(td.path(), Some(Path::new("config/py2binmod.toml")))
+            .await
+            .unwrap();
+
+        assert_eq!(metadata.name, "custom");
+        assert_eq!(metadata.version, "2.0.0");
+        assert_eq!(metadata.license_file, Some(config_dir.join("LICENSE")));
+
+        assert!(matches!(
+            Pep621MetadataParser::new().parse(td.path(), None).await,
+            Err(ParserError::MissingProjectMetadata),
+        ));
+    }
+
+    #[tokio::test]
+    async fn dynamic_version_needs_a_fallback() {
+        let td = TempDir::new().unwrap();
+        let pyproject = td.path().join("pyproject.toml");
+
+        std::fs::write(&pyproject, "[project]\nname = \"demo\"\ndynamic = [\"version\"]\n").unwrap();
+
+        let err = Pep621MetadataParser::new().parse(td.path(), None).await.unwrap_err();
+
+        assert!(matches!(&err, ParserError::UnresolvedDynamicField(field) if field == "version"));
+        assert!(err.to_string().contains("[tool.py2binmod]"), "{err}");
+
+        std::fs::write(&pyproject, "[project]\nname = \"demo\"\n").unwrap();
+
+        assert!(matches!(
+            Pep621MetadataParser::new().parse(td.path(), None).await,
+            Err(ParserError::MissingProjectMetadata),
+        ));
+
+        let metadata = parse(r#"
+[project]
+name = "demo"
+dynamic = ["version", "description"]
+
+[tool.py2binmod]
+version = "1.4.0"
+"#).await;
+
+        assert_eq!(metadata.version, "1.4.0");
+        assert_eq!(metadata.description, None);
+    }
+}
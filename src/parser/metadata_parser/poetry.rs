@@ -0,0 +1,165 @@
+use std::{collections::BTreeMap, path::Path};
+use serde::Deserialize;
+use async_trait::async_trait;
+use tokio::fs;
+
+use crate::{
+    types::ProjectMetadata,
+    parser::error::{ParserError, ParserResult},
+    parser::metadata_parser::{
+        traits::{MetadataParser, MetadataOverrides},
+        pep621::{Py2BinmodToml, project_urls},
+        license,
+    },
+};
+
+
+#[derive(Deserialize, Debug)]
+struct PyProjectToml {
+    tool: Option<ToolSection>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ToolSection {
+    poetry: Option<PoetrySection>,
+    py2binmod: Option<Py2BinmodToml>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PoetrySection {
+    name: String,
+    version: Option<String>,
+    description: Option<String>,
+    /// Already in cargo's `Name <email>` form.
+    #[serde(default)]
+    authors: Vec<String>,
+    license: Option<String>,
+    homepage: Option<String>,
+    repository: Option<String>,
+    documentation: Option<String>,
+    #[serde(default)]
+    urls: BTreeMap<String, String>,
+    #[serde(default)]
+    dependencies: toml::Table,
+    #[serde(default)]
+    extras: BTreeMap<String, Vec<String>>,
+}
+
+/// Reads the `[tool.poetry]` table of Poetry projects predating PEP 621
+/// support.
+#[derive(Default)]
+pub struct PoetryMetadataParser;
+
+impl PoetryMetadataParser {
+    pub fn new() -> Self {
+        PoetryMetadataParser
+    }
+}
+
+#[async_trait]
+impl MetadataParser for PoetryMetadataParser {
+    async fn parse_with(
+        &self,
+        project_dir: &Path,
+        metadata_path: Option<&Path>,
+        overrides: &MetadataOverrides,
+    ) -> ParserResult<ProjectMetadata> {
+        let pyproject_path = metadata_path
+            .map(|path| project_dir.join(path))
+            .unwrap_or_else(|| project_dir.join("pyproject.toml"));
+        let content = fs::read_to_string(&pyproject_path)
+            .await
+            .map_err(|_| ParserError::MissingProjectMetadata)?;
+        let pyproject: PyProjectToml = toml::from_str(&content)?;
+        let tool = pyproject.tool.ok_or(ParserError::MissingProjectMetadata)?;
+        let poetry = tool.poetry.ok_or(ParserError::MissingProjectMetadata)?;
+        let version = overrides.version
+            .clone()
+            .or(poetry.version)
+            .or_else(|| tool.py2binmod.as_ref().and_then(|c| c.version.clone()))
+            .ok_or_else(|| ParserError::UnresolvedDynamicField("version".to_string()))?;
+
+        // The dedicated fields take precedence over the free-form urls.
+        let mut urls = project_urls(&poetry.urls);
+        urls.homepage = poetry.homepage.or(urls.homepage);
+        urls.repository = poetry.repository.or(urls.repository);
+        urls.documentation = poetry.documentation.or(urls.documentation);
+
+        Ok(ProjectMetadata {
+            name: overrides.name.clone().unwrap_or(poetry.name),
+            version,
+            requires_python: poetry.dependencies
+                .get("python")
+                .and_then(|python| python.as_str())
+                .map(str::to_string),
+            description: poetry.description,
+            authors: poetry.authors,
+            license: poetry.license
+                .map(|expression| license::normalize_expression(&expression))
+                .transpose()?,
+            license_file: None,
+            urls,
+            optional_dependencies: poetry.extras,
+            py2binmod: tool.py2binmod.map(Py2BinmodToml::into_config),
+            warnings: Vec::new(),
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn parses_the_poetry_table() {
+        let td = TempDir::new().unwrap();
+        std::fs::write(td.path().join("pyproject.toml"), r#"
+[tool.poetry]
+name = "demo"
+version = "0.3.0"
+description = "A demo"
+authors = ["Ada Lovelace <ada@example.com>"]
+license = "MIT"
+repository = "https://github.com/example/demo"
+
+[tool.poetry.urls]
+Homepage = "https://example.com"
+Repository = "https://example.com/ignored"
+
+[tool.poetry.dependencies]
+python = "^3.11"
+pyyaml = { version = "^6", optional = true }
+
+[tool.poetry.extras]
+yaml = ["pyyaml"]
+
+[tool.py2binmod]
+module = "demo"
+"#).unwrap();
+
+        let metadata = PoetryMetadataParser::new().parse(td.path(), None).await.unwrap();
+
+        assert_eq!(metadata.name, "demo");
+        assert_eq!(metadata.version, "0.3.0");
+        assert_eq!(metadata.authors, vec!["Ada Lovelace <ada@example.com>"]);
+        assert_eq!(metadata.license.as_deref(), Some("MIT"));
+        assert_eq!(metadata.requires_python.as_deref(), Some("^3.11"));
+        assert_eq!(metadata.urls.homepage.as_deref(), Some("https://example.com"));
+        assert_eq!(metadata.urls.repository.as_deref(), Some("https://github.com/example/demo"));
+        assert_eq!(metadata.optional_dependencies["yaml"], vec!["pyyaml"]);
+        assert_eq!(metadata.py2binmod.unwrap().module.as_deref(), Some("demo"));
+    }
+
+    #[tokio::test]
+    async fn requires_the_poetry_table() {
+        let td = TempDir::new().unwrap();
+        std::fs::write(td.path().join("pyproject.toml"), "[project]\nname = \"demo\"\nversion = \"0.1.0\"\n").unwrap();
+
+        assert!(matches!(
+            PoetryMetadataParser::new().parse(td.path(), None).await,
+            Err(ParserError::MissingProjectMetadata),
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+use std::{collections::BTreeMap, path::Path};
+use async_trait::async_trait;
+use tokio::fs;
+
+use crate::{
+    types::ProjectMetadata,
+    parser::error::{ParserError, ParserResult},
+    parser::metadata_parser::{
+        traits::{MetadataParser, MetadataOverrides},
+        pep621::{Py2BinmodToml, project_urls, read_tool_config},
+    },
+};
+
+
+type Section = BTreeMap<String, String>;
+
+/// Parse the INI dialect of setup.cfg: `[section]` headers, `key = value` or
+/// `key: value` pairs with values continued on indented lines, and `#` or `;`
+/// comments. Keys are normalized to underscores, as setuptools accepts both.
+fn parse_ini(content: &str) -> BTreeMap<String, Section> {
+    let mut sections = BTreeMap::<String, Section>::new();
+    let mut section = None::<String>;
+    let mut key = None::<String>;
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+            continue;
+        }
+
+        if line.starts_with(char::is_whitespace) {
+            if let (Some(section), Some(key)) = (&section, &key)
+                && let Some(value) = sections.get_mut(section).and_then(|s| s.get_mut(key))
+            {
+                value.push('\n');
+                value.push_str(trimmed);
+            }
+            continue;
+        }
+
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            sections.entry(name.trim().to_string()).or_default();
+            section = Some(name.trim().to_string());
+            key = None;
+            continue;
+        }
+
+        let Some(separator) = trimmed.find(['=', ':']) else {
+            continue;
+        };
+
+        if let Some(section) = &section {
+            let name = trimmed[..separator].trim().to_lowercase().replace('-', "_");
+            let value = trimmed[separator + 1..].trim().to_string();
+
+            sections.entry(section.clone()).or_default().insert(name.clone(), value);
+            key = Some(name);
+        }
+    }
+
+    sections
+}
+
+/// The entries of a value listing one per line.
+fn lines(value: &str) -> impl Iterator<Item = &str> {
+    value.lines().map(str::trim).filter(|line| !line.is_empty())
+}
+
+/// Reads the `[metadata]` of a setuptools project's setup.cfg. Its
+/// `[tool.py2binmod]` configuration still lives in pyproject.toml.
+#[derive(Default)]
+pub struct SetupCfgMetadataParser;
+
+impl SetupCfgMetadataParser {
+    pub fn new() -> Self {
+        SetupCfgMetadataParser
+    }
+
+    /// The version, unless setuptools computes it from an `attr:` of the
+    /// package, which can't be resolved without running it.
+    async fn resolve_version(project_dir: &Path, version: &str, config: Option<&Py2BinmodToml>) -> ParserResult<String> {
+        let fallback = || config
+            .and_then(|c| c.version.clone())
+            .ok_or_else(|| ParserError::UnresolvedDynamicField("version".to_string()));
+
+        if version.starts_with("attr:") {
+            return fallback();
+        }
+
+        match version.strip_prefix("file:") {
+            Some(file) => match fs::read_to_string(project_dir.join(file.trim())).await {
+                Ok(content) => Ok(content.trim().to_string()),
+                Err(_) => fallback(),
+            },
+            None => Ok(version.to_string()),
+        }
+    }
+}
+
+#[async_trait]
+impl MetadataParser for SetupCfgMetadataParser {
+    async fn parse_with(
+        &self,
+        project_dir: &Path,
+        metadata_path: Option<&Path>,
+        overrides: &MetadataOverrides,
+    ) -> ParserResult<ProjectMetadata> {
+        let content = fs::read_to_string(project_dir.join("setup.cfg"))
+            .await
+            .map_err(|_| ParserError::MissingProjectMetadata)?;
+        let sections = parse_ini(&content);
+        let metadata = sections
+            .get("metadata")
+            .ok_or(ParserError::MissingProjectMetadata)?;
+        let field = |key: &str| metadata
+            .get(key)
+            .filter(|value| !value.is_empty())
+            .cloned();
+        let config = read_tool_config(
+            &metadata_path
+                .map(|path| project_dir.join(path))
+                .unwrap_or_else(|| project_dir.join("pyproject.toml")),
+        ).await?;
+        let version = match (&overrides.version, field("version")) {
+            (Some(version), _) => version.clone(),
+            (None, Some(version)) => Self::resolve_version(project_dir, &version, config.as_ref()).await?,
+            (None, None) => config
+                .as_ref()
+                .and_then(|c| c.version.clone())
+                .ok_or(ParserError::MissingProjectMetadata)?,
+        };
+
+        let mut urls = project_urls(
+            &field("project_urls")
+                .iter()
+                .flat_map(|value| lines(value))
+                .filter_map(|line| line.split_once('='))
+                .map(|(label, url)| (label.trim().to_string(), url.trim().to_string()))
+                .collect(),
+        );
+        urls.homepage = field("url").or(urls.homepage);
+
+        Ok(ProjectMetadata {
+            name: match &overrides.name {
+                Some(name) => name.clone(),
+                None => field("name").ok_or(ParserError::MissingProjectMetadata)?,
+            },
+            version,
+            requires_python: sections
+                .get("options")
+                .and_then(|options| options.get("python_requires"))
+                .cloned(),
+            description: field("description"),
+            authors: match (field("author"), field("author_email")) {
+                (Some(name), Some(email)) => vec![format!("{} <{}>", name, email)],
+                (Some(name), None) => vec![name],
+                (None, Some(email)) => vec![email],
+                (None, None) => vec![],
+            },
+            license: field("license"),
+            // The generated crate is built elsewhere, so the path is made absolute.
+            license_file: field("license")
+                .is_none()
+                .then(|| field("license_file"))
+                .flatten()
+                .map(|file| project_dir.join(file)),
+            urls,
+            optional_dependencies: sections
+                .get("options.extras_require")
+                .map(|extras| extras
+                    .iter()
+                    .map(|(extra, requirements)| (extra.clone(), lines(requirements).map(str::to_string).collect()))
+                    .collect()
+                )
+                .unwrap_or_default(),
+            py2binmod: config.map(Py2BinmodToml::into_config),
+            warnings: Vec::new(),
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn parses_continued_values() {
+        let sections = parse_ini("[metadata]\nname = demo\n# comment\nproject-urls =\n    Source = https://example.com/src\n    Docs = https://example.com/docs\n\n[options]\npython_requires: >=3.10\n");
+
+        assert_eq!(sections["metadata"]["name"], "demo");
+        assert_eq!(sections["metadata"]["project_urls"], "\nSource = https://example.com/src\nDocs = https://example.com/docs");
+        assert_eq!(sections["options"]["python_requires"], ">=3.10");
+    }
+
+    #[tokio::test]
+    async fn parses_setup_cfg_metadata() {
+        let td = TempDir::new().unwrap();
+        std::fs::write(td.path().join("setup.cfg"), r#"
+[metadata]
+name = demo
+version = attr: demo.__version__
+description = A demo
+author = Ada Lovelace
+author_email = ada@example.com
+license = MIT
+url = https://example.com
+project_urls =
+    Source Code = https://github.com/example/demo
+
+[options]
+python_requires = >=3.10
+
+[options.extras_require]
+yaml =
+    pyyaml>=6
+"#).unwrap();
+        std::fs::write(td.path().join("pyproject.toml"), "[tool.py2binmod]\nversion = \"1.2.0\"\n").unwrap();
+
+        let metadata = SetupCfgMetadataParser::new().parse(td.path(), None).await.unwrap();
+
+        assert_eq!(metadata.name, "demo");
+        assert_eq!(metadata.version, "1.2.0");
+        assert_eq!(metadata.authors, vec!["Ada Lovelace <ada@example.com>"]);
+        assert_eq!(metadata.requires_python.as_deref(), Some(">=3.10"));
+        assert_eq!(metadata.urls.homepage.as_deref(), Some("https://example.com"));
+        assert_eq!(metadata.urls.repository.as_deref(), Some("https://github.com/example/demo"));
+        assert_eq!(metadata.optional_dependencies["yaml"], vec!["pyyaml>=6"]);
+        assert!(metadata.py2binmod.is_some());
+
+        std::fs::remove_file(td.path().join("pyproject.toml")).unwrap();
+
+        assert!(matches!(
+            SetupCfgMetadataParser::new().parse(td.path(), None).await,
+            Err(ParserError::UnresolvedDynamicField(_)),
+        ));
+    }
+}
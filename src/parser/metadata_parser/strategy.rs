@@ -0,0 +1,192 @@
+use std::{path::Path, str::FromStr};
+use async_trait::async_trait;
+use tokio::fs;
+
+use crate::{
+    types::ProjectMetadata,
+    parser::error::{ParserError, ParserResult},
+    parser::metadata_parser::{
+        traits::{MetadataParser, MetadataOverrides},
+        pep621::Pep621MetadataParser,
+        poetry::PoetryMetadataParser,
+        setupcfg::SetupCfgMetadataParser,
+    },
+};
+
+
+/// Where the project's metadata is read from, as set by
+/// `[tool.py2binmod] metadata-strategy`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MetadataStrategy {
+    #[default]
+    Pep621,
+    Poetry,
+    SetupCfg,
+    /// Each of the others in turn, until one reads the metadata.
+    Auto,
+}
+
+impl MetadataStrategy {
+    const AUTO_ORDER: [MetadataStrategy; 3] = [
+        MetadataStrategy::Pep621,
+        MetadataStrategy::Poetry,
+        MetadataStrategy::SetupCfg,
+    ];
+
+    fn parser(&self) -> Box<dyn MetadataParser> {
+        match self {
+            MetadataStrategy::Pep621 | MetadataStrategy::Auto => Box::new(Pep621MetadataParser::new()),
+            MetadataStrategy::Poetry => Box::new(PoetryMetadataParser::new()),
+            MetadataStrategy::SetupCfg => Box::new(SetupCfgMetadataParser::new()),
+        }
+    }
+}
+
+impl FromStr for MetadataStrategy {
+    type Err = ParserError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pep621" => Ok(MetadataStrategy::Pep621),
+            "poetry" => Ok(MetadataStrategy::Poetry),
+            "setupcfg" => Ok(MetadataStrategy::SetupCfg),
+            "auto" => Ok(MetadataStrategy::Auto),
+            _ => Err(ParserError::UnsupportedMetadataStrategy(s.to_string())),
+        }
+    }
+}
+
+/// Reads the metadata with the parser `metadata-strategy` selects, PEP 621
+/// unless set. The strategy is looked up in the metadata file, or in
+/// pyproject.toml.
+#[derive(Default)]
+pub struct StrategyMetadataParser;
+
+impl StrategyMetadataParser {
+    pub fn new() -> Self {
+        StrategyMetadataParser
+    }
+
+    async fn strategy(project_dir: &Path, metadata_path: Option<&Path>) -> ParserResult<MetadataStrategy> {
+        let pyproject_path = metadata_path
+            .map(|path| project_dir.join(path))
+            .unwrap_or_else(|| project_dir.join("pyproject.toml"));
+        let Ok(content) = fs::read_to_string(&pyproject_path).await else {
+            return Ok(MetadataStrategy::default());
+        };
+        let pyproject: toml::Table = toml::from_str(&content)?;
+
+        match pyproject
+            .get("tool")
+            .and_then(|tool| tool.get("py2binmod"))
+            .and_then(|config| config.get("metadata-strategy"))
+        {
+            Some(toml::Value::String(strategy)) => strategy.parse(),
+            Some(other) => Err(ParserError::UnsupportedMetadataStrategy(other.to_string())),
+            None => Ok(MetadataStrategy::default()),
+        }
+    }
+}
+
+#[async_trait]
+impl MetadataParser for StrategyMetadataParser {
+    async fn parse_with(
+        &self,
+        project_dir: &Path,
+        metadata_path: Option<&Path>,
+        overrides: &MetadataOverrides,
+    ) -> ParserResult<ProjectMetadata> {
+        let strategy = Self::strategy(project_dir, metadata_path).await?;
+
+        if strategy != MetadataStrategy::Auto {
+            return strategy.parser().parse_with(project_dir, metadata_path, overrides).await;
+        }
+
+        // Reports why the PEP 621 metadata couldn't be read when none could.
+        let mut first_error = None;
+
+        for strategy in MetadataStrategy::AUTO_ORDER {
+            match strategy.parser().parse_with(project_dir, metadata_path, overrides).await {
+                Ok(metadata) => return Ok(metadata),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        Err(first_error.unwrap_or(ParserError::MissingProjectMetadata))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const SETUP_CFG: &str = "[metadata]\nname = from-setup-cfg\nversion = 0.3.0\n";
+
+    fn project(pyproject: &str, setup_cfg: Option<&str>) -> TempDir {
+        let td = TempDir::new().unwrap();
+        std::fs::write(td.path().join("pyproject.toml"), pyproject).unwrap();
+
+        if let Some(setup_cfg) = setup_cfg {
+            std::fs::write(td.path().join("setup.cfg"), setup_cfg).unwrap();
+        }
+
+        td
+    }
+
+    fn pyproject(strategy: Option<&str>) -> String {
+        let config = strategy
+            .map(|s| format!("\n[tool.py2binmod]\nmetadata-strategy = \"{s}\"\n"))
+            .unwrap_or_default();
+
+        format!("[project]\nname = \"from-pep621\"\nversion = \"0.1.0\"\n\n[tool.poetry]\nname = \"from-poetry\"\nversion = \"0.2.0\"\n{config}")
+    }
+
+    async fn parsed_name(td: &TempDir) -> ParserResult<String> {
+        StrategyMetadataParser::new()
+            .parse(td.path(), None)
+            .await
+            .map(|metadata| metadata.name)
+    }
+
+    #[tokio::test]
+    async fn selects_the_configured_parser() {
+        for (strategy, name) in [
+            (None, "from-pep621"),
+            (Some("pep621"), "from-pep621"),
+            (Some("poetry"), "from-poetry"),
+            (Some("setupcfg"), "from-setup-cfg"),
+            (Some("auto"), "from-pep621"),
+        ] {
+            let td = project(&pyproject(strategy), Some(SETUP_CFG));
+
+            assert_eq!(parsed_name(&td).await.unwrap(), name, "{strategy:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn auto_falls_back_in_order() {
+        let td = project("[tool.poetry]\nname = \"from-poetry\"\nversion = \"0.2.0\"\n\n[tool.py2binmod]\nmetadata-strategy = \"auto\"\n", Some(SETUP_CFG));
+        assert_eq!(parsed_name(&td).await.unwrap(), "from-poetry");
+
+        let td = project("[tool.py2binmod]\nmetadata-strategy = \"auto\"\n", Some(SETUP_CFG));
+        assert_eq!(parsed_name(&td).await.unwrap(), "from-setup-cfg");
+
+        let td = project("[tool.py2binmod]\nmetadata-strategy = \"auto\"\n", None);
+        assert!(matches!(parsed_name(&td).await, Err(ParserError::MissingProjectMetadata)));
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_strategies() {
+        for strategy in ["\"flit\"", "1"] {
+            let td = project(&format!("[tool.py2binmod]\nmetadata-strategy = {strategy}\n"), None);
+            let err = parsed_name(&td).await.unwrap_err();
+
+            assert!(matches!(err, ParserError::UnsupportedMetadataStrategy(_)), "{strategy}");
+            assert_eq!(err.code(), "parser.unsupported_metadata_strategy");
+        }
+    }
+}
@@ -4,7 +4,28 @@ use async_trait::async_trait;
 use crate::{types::ProjectMetadata, parser::error::ParserResult};
 
 
+/// Values used instead of those the metadata file holds. A version given
+/// here is used even when the file's can't be resolved, e.g. a dynamic one.
+#[derive(Debug, Clone, Default)]
+pub struct MetadataOverrides {
+    pub name: Option<String>,
+    pub version: Option<String>,
+}
+
 #[async_trait]
 pub trait MetadataParser: Send + Sync {
-    async fn parse(&self, project_dir: &Path) -> ParserResult<ProjectMetadata>;
-}
\ No newline at end of file
+    /// Reads the metadata of `project_dir`, from `metadata_path` when given
+    /// instead of the parser's default location.
+    async fn parse(&self, project_dir: &Path, metadata_path: Option<&Path>) -> ParserResult<ProjectMetadata> {
+        self.parse_with(project_dir, metadata_path, &MetadataOverrides::default()).await
+    }
+
+    /// [`MetadataParser::parse`], preferring the values of `overrides` to
+    /// those of the file.
+    async fn parse_with(
+        &self,
+        project_dir: &Path,
+        metadata_path: Option<&Path>,
+        overrides: &MetadataOverrides,
+    ) -> ParserResult<ProjectMetadata>;
+}
@@ -2,30 +2,73 @@ pub mod file_walker;
 pub mod metadata_parser;
 pub mod ast_analyzer;
 pub mod layout_resolver;
+pub mod site_packages;
+pub mod import_closure;
+pub mod source;
+pub mod workspace;
 pub mod error;
 pub mod traits;
 
-use std::path::{Path, PathBuf};
+use std::{collections::{BTreeMap, BTreeSet, HashSet}, path::{Path, PathBuf}};
 use futures::stream::{self, StreamExt, TryStreamExt};
 use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
 
 use crate::{
     parser::{
-        file_walker::{FileWalker, traits::FileIgnoreStrategy, default::DefaultFileIgnoreStrategy},
-        metadata_parser::{traits::MetadataParser, pep621::Pep621MetadataParser},
+        file_walker::{
+            FileWalker,
+            traits::FileIgnoreStrategy,
+            default::DefaultFileIgnoreStrategy,
+            configurable::ConfigurableFileIgnoreStrategy,
+            include::IncludeFilter,
+        },
+        metadata_parser::{traits::{MetadataParser, MetadataOverrides}, strategy::StrategyMetadataParser},
         ast_analyzer::AstAnalyzer,
         layout_resolver::{LayoutResolver, LayoutHints},
+        import_closure::import_closure,
+        workspace::read_workspace,
         error::{ParserError, ParserResult},
     },
-    types::ProjectContext,
+    types::{ProjectContext, Module, Parameter, ParameterType},
 };
 
 
+/// Invoked after each module file is analyzed with `(analyzed, total)`.
+pub type ProgressCallback = Box<dyn Fn(usize, usize) + Send + Sync>;
+
+/// Invoked for each file found while walking the project, with the number
+/// found so far.
+pub type WalkProgressCallback = Box<dyn Fn(usize) + Send + Sync>;
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct ProjectParserOptions {
     pub venv_override: Option<PathBuf>,
+    /// Interpreter to ask for site-packages, over `[tool.py2binmod] interpreter`.
+    pub interpreter_override: Option<PathBuf>,
     pub module_root_override: Option<PathBuf>,
     pub module_override: Option<String>,
+    /// Extra ignore globs, added to those from `[tool.py2binmod] ignore`.
+    pub ignore_patterns: Vec<String>,
+    /// Only analyze module files matching one of these globs or those from
+    /// `[tool.py2binmod] include`, every file when there are none.
+    pub include_patterns: Vec<String>,
+    /// Stop at the first module that fails to analyze instead of reporting
+    /// every failure at once.
+    pub fail_fast: bool,
+    /// Reject annotations that don't map to a supported type instead of
+    /// treating them as `Any`, as does `[tool.py2binmod] strict`.
+    pub strict: bool,
+    /// Metadata file to read instead of `pyproject.toml`, relative to the
+    /// project dir unless absolute.
+    pub metadata_path: Option<PathBuf>,
+    /// Record how the layout was resolved in [`ProjectContext::layout_trace`].
+    pub explain: bool,
+    /// Replaces the project name read from the metadata file.
+    pub name_override: Option<String>,
+    /// Replaces the project version read from the metadata file, which then
+    /// may be dynamic.
+    pub version_override: Option<String>,
 }
 
 pub struct ProjectParser {
@@ -34,6 +77,9 @@ pub struct ProjectParser {
     ast_analyzer: AstAnalyzer,
     layout_resolver: LayoutResolver,
     options: ProjectParserOptions,
+    progress: Option<ProgressCallback>,
+    walk_progress: Option<WalkProgressCallback>,
+    cancellation: Option<CancellationToken>,
 }
 
 impl ProjectParser {
@@ -41,6 +87,7 @@ impl ProjectParser {
         ignore_strategy: Box<dyn FileIgnoreStrategy + Send + Sync>,
         metadata_parser: Box<dyn MetadataParser + Send + Sync>,
         options: ProjectParserOptions,
+        progress: Option<ProgressCallback>,
     ) -> Self {
         Self {
             ignore_strategy,
@@ -48,6 +95,17 @@ impl ProjectParser {
             ast_analyzer: AstAnalyzer::new(),
             layout_resolver: LayoutResolver::new(),
             options,
+            progress,
+            walk_progress: None,
+            cancellation: None,
+        }
+    }
+
+    /// Fail with [`ParserError::Cancelled`] once the cancellation token fires.
+    fn check_cancelled(&self) -> ParserResult<()> {
+        match &self.cancellation {
+            Some(token) if token.is_cancelled() => Err(ParserError::Cancelled),
+            _ => Ok(()),
         }
     }
 
@@ -55,21 +113,72 @@ impl ProjectParser {
         ProjectParserBuilder::default()
     }
 
+    /// Analyze a single module file, without reading the project metadata or
+    /// resolving its layout. Annotations naming structs defined in the file
+    /// are resolved, those from other files aren't. The module's import path
+    /// is unknown, callers needing it have to parse the whole project.
+    pub async fn parse_file(&self, file_path: &Path) -> ParserResult<Option<Module>> {
+        if !file_path.is_file() {
+            return Err(ParserError::MissingFile(file_path.display().to_string()));
+        }
+
+        let mut modules = self.ast_analyzer
+            .analyze_file(file_path)
+            .await?
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        resolve_structs(&mut modules)?;
+
+        if self.options.strict {
+            validate_types(&modules)?;
+        }
+
+        Ok(modules.pop())
+    }
+
     pub async fn parse_project(&self, project_dir: &Path) -> ParserResult<ProjectContext> {
         if !project_dir.is_dir() {
             return Err(ParserError::InvalidProjectDir(project_dir.to_path_buf()));
         }
 
-        let files = FileWalker::new(self.ignore_strategy.as_ref())
-            .walk(project_dir)
-            .await?;
+        // The walker returns canonical paths, everything derived from the
+        // project dir has to match them.
+        let project_dir = &project_dir.canonicalize()?;
+
+        self.check_cancelled()?;
 
+        let overrides = MetadataOverrides {
+            name: self.options.name_override.clone(),
+            version: self.options.version_override.clone(),
+        };
         let metadata = self.metadata_parser
-            .parse(project_dir)
+            .parse_with(project_dir, self.options.metadata_path.as_deref(), &overrides)
             .await?;
 
+        let workspace = metadata.py2binmod
+            .as_ref()
+            .and_then(|c| c.workspace_root.as_ref())
+            .map(|root| read_workspace(&project_dir.join(root)))
+            .transpose()?;
+
+        let configured_ignore = ConfigurableFileIgnoreStrategy::new(
+            project_dir,
+            &self.options.ignore_patterns
+                .iter()
+                .chain(metadata.py2binmod.iter().flat_map(|c| c.ignore.iter()))
+                .collect::<Vec<_>>(),
+        )?;
+
+        let files = FileWalker::new(&(self.ignore_strategy.as_ref(), &configured_ignore))
+            .on_file(self.walk_progress.as_deref())
+            .walk(project_dir)
+            .await?;
+
+        self.check_cancelled()?;
+
         let layout = self.layout_resolver
-            .resolve(
+            .resolve_async(
                 project_dir,
                 &files,
                 &LayoutHints {
@@ -79,40 +188,348 @@ impl ProjectParser {
                         .or_else(|| metadata.py2binmod.as_ref().and_then(|c| c.module_root.clone())),
                     module: self.options.module_override.clone()
                         .or_else(|| metadata.py2binmod.as_ref().and_then(|c| c.module.clone())),
+                    interpreter: self.options.interpreter_override.clone()
+                        .or_else(|| metadata.py2binmod.as_ref().and_then(|c| c.interpreter.clone())),
+                    discover_tox: metadata.py2binmod.as_ref().is_some_and(|c| c.discover_tox),
+                    python_version: metadata.py2binmod.as_ref().is_some_and(|c| c.use_python_version),
+                    explain: self.options.explain,
                 }
-            )?;
-
-        let modules = stream::iter(
-                files
-                    .into_iter()
-                    .filter(|p| p.extension().is_some_and(|ext| ext == "py" && p.starts_with(&layout.module_root)))
             )
-            .then(|p| async move { self.ast_analyzer.analyze_file(&p).await })
-            .map_ok(|m| m.into_iter())
-            .try_collect::<Vec<_>>()
-            .await?
+            .await?;
+
+        let module_files = files
+            .into_iter()
+            .filter(|p| p.extension().is_some_and(|ext| ext == "py" && p.starts_with(&layout.module_root)))
+            .collect::<Vec<_>>();
+
+        let frozen_packages = metadata.py2binmod
+            .as_ref()
+            .filter(|c| c.freeze.is_some() || c.freeze_imported)
+            .map(|c| {
+                let mut packages = c.freeze
+                    .iter()
+                    .flatten()
+                    .cloned()
+                    .collect::<BTreeSet<_>>();
+
+                if c.freeze_imported {
+                    packages.extend(import_closure(
+                        &module_files,
+                        &std::iter::once(layout.site_packages_dir.clone())
+                            .chain(layout.platlib_dir.clone())
+                            .collect::<Vec<_>>(),
+                    ));
+                }
+
+                packages.into_iter().collect()
+            });
+
+        // Excluded files are still part of the module and may import
+        // packages to freeze, they just don't export anything.
+        let include = IncludeFilter::new(
+            project_dir,
+            &self.options.include_patterns
+                .iter()
+                .chain(metadata.py2binmod.iter().flat_map(|c| c.include.iter()))
+                .collect::<Vec<_>>(),
+        )?;
+        let analyzed_files = module_files
             .into_iter()
-            .flatten()
-            .collect();
+            .filter(|p| include.includes(p))
+            .collect::<Vec<_>>();
+        let total = analyzed_files.len();
+
+        // Files left once cancelled fail right away, without being read.
+        let analyzed = stream::iter(analyzed_files.into_iter().enumerate())
+            .then(|(index, p)| async move {
+                self.check_cancelled()?;
+
+                let module = self.ast_analyzer.analyze_file(&p).await;
+
+                if let Some(progress) = &self.progress {
+                    progress(index + 1, total);
+                }
+
+                module
+            });
+
+        let mut modules: Vec<Module> = if self.options.fail_fast {
+            analyzed
+                .map_ok(|m| m.into_iter())
+                .try_collect::<Vec<_>>()
+                .await?
+                .into_iter()
+                .flatten()
+                .collect()
+        } else {
+            let (modules, mut errors): (Vec<_>, Vec<_>) = analyzed
+                .collect::<Vec<_>>()
+                .await
+                .into_iter()
+                .partition(|m| m.is_ok());
+
+            self.check_cancelled()?;
+
+            match errors.len() {
+                0 => {},
+                1 => return Err(errors.remove(0).unwrap_err()),
+                _ => return Err(ParserError::Multiple(
+                    errors
+                        .into_iter()
+                        .filter_map(|e| e.err())
+                        .collect()
+                )),
+            }
+
+            modules
+                .into_iter()
+                .filter_map(|m| m.ok().flatten())
+                .collect()
+        };
+
+        let exports_nothing = modules
+            .iter()
+            .all(|m| m.module_functions.is_empty() && m.host_functions.is_none());
+
+        if exports_nothing {
+            return Err(ParserError::NoExportedFunctions(layout.module_name));
+        }
+
+        resolve_structs(&mut modules)?;
+
+        let strict = self.options.strict
+            || metadata.py2binmod.as_ref().is_some_and(|c| c.strict);
+        let type_warnings = check_map_keys(&mut modules, strict)?;
+
+        if strict {
+            validate_types(&modules)?;
+        }
+
+        if let Some(config) = &metadata.py2binmod {
+            validate_features(&modules, &config.features)?;
+
+            if let Some(crate_types) = &config.crate_types {
+                validate_crate_types(crate_types)?;
+            }
+        }
 
         Ok(ProjectContext {
             venv_dir: layout.venv_dir,
             site_packages_dir: layout.site_packages_dir,
+            platlib_dir: layout.platlib_dir,
             project_dir: project_dir.to_path_buf(),
             module_root: layout.module_root,
             module_name: layout.module_name,
             metadata: metadata,
             modules: modules,
+            frozen_packages,
+            workspace,
+            layout_trace: layout.trace,
+            layout_warnings: layout.warnings,
+            type_warnings,
         })
     }
 }
 
 
+/// Point annotations naming a struct defined anywhere in the project at it.
+/// Structs are generated by name, so no two may share one.
+fn resolve_structs(modules: &mut [Module]) -> ParserResult<()> {
+    let mut defined_in = BTreeMap::new();
+
+    for module in modules.iter() {
+        for s in &module.structs {
+            if let Some(first) = defined_in.insert(s.name.clone(), &module.file_path) {
+                return Err(ParserError::DuplicateStruct {
+                    name: s.name.clone(),
+                    first: first.display().to_string(),
+                    second: module.file_path.display().to_string(),
+                });
+            }
+        }
+    }
+
+    let structs = defined_in.into_keys().collect::<HashSet<_>>();
+
+    if structs.is_empty() {
+        return Ok(());
+    }
+
+    for module in modules.iter_mut() {
+        for function in module.module_functions.as_mut_slice() {
+            function.parameters.iter_mut().for_each(|p| p.type_hint.resolve_structs(&structs));
+            function.return_type.resolve_structs(&structs);
+        }
+
+        for function in module.host_functions.iter_mut().flat_map(|h| h.as_mut_slice()) {
+            function.parameters.iter_mut().for_each(|p| p.type_hint.resolve_structs(&structs));
+            function.return_type.resolve_structs(&structs);
+        }
+
+        for field in module.structs.iter_mut().flat_map(|s| s.fields.iter_mut()) {
+            field.type_hint.resolve_structs(&structs);
+        }
+    }
+
+    Ok(())
+}
+
+/// Reject dict keys that can't cross as JSON object keys. Untyped keys fall
+/// back to `str` with a warning, unless `strict`.
+fn check_map_keys(modules: &mut [Module], strict: bool) -> ParserResult<Vec<String>> {
+    let mut warnings = Vec::new();
+    let mut errors = Vec::new();
+    let mut check = |function: &str, parameter: String, type_hint: &mut ParameterType| {
+        for key in type_hint.map_keys_mut() {
+            match key {
+                ParameterType::String | ParameterType::Uuid | ParameterType::Path => {},
+                ParameterType::Any | ParameterType::Unrecognized(_) if !strict => {
+                    warnings.push(format!(
+                        "Untyped dict key '{}' for {} of '{}' treated as 'str'",
+                        key, parameter, function,
+                    ));
+                    *key = ParameterType::String;
+                },
+                key => errors.push(ParserError::UnsupportedMapKey {
+                    function: function.to_string(),
+                    parameter: parameter.clone(),
+                    key: key.to_string(),
+                }),
+            }
+        }
+    };
+
+    for module in modules.iter_mut() {
+        for function in module.module_functions.as_mut_slice() {
+            for p in function.parameters.iter_mut() {
+                check(&function.name, format!("parameter '{}'", p.name), &mut p.type_hint);
+            }
+            check(&function.name, "return value".to_string(), &mut function.return_type);
+        }
+
+        for function in module.host_functions.iter_mut().flat_map(|h| h.as_mut_slice()) {
+            for p in function.parameters.iter_mut() {
+                check(&function.name, format!("parameter '{}'", p.name), &mut p.type_hint);
+            }
+            check(&function.name, "return value".to_string(), &mut function.return_type);
+        }
+
+        for s in module.structs.iter_mut() {
+            for field in s.fields.iter_mut() {
+                check(&s.name, format!("field '{}'", field.name), &mut field.type_hint);
+            }
+        }
+    }
+
+    match errors.len() {
+        0 => Ok(warnings),
+        1 => Err(errors.remove(0)),
+        _ => Err(ParserError::Multiple(errors)),
+    }
+}
+
+/// Reject features gating functions that aren't exported.
+fn validate_features(modules: &[Module], features: &BTreeMap<String, Vec<String>>) -> ParserResult<()> {
+    let exported = modules
+        .iter()
+        .flat_map(|m| m.module_functions.iter().map(|f| f.name.as_str()))
+        .collect::<HashSet<_>>();
+
+    match features
+        .iter()
+        .flat_map(|(feature, functions)| functions.iter().map(move |f| (feature, f)))
+        .find(|(_, function)| !exported.contains(function.as_str()))
+    {
+        Some((feature, function)) => Err(ParserError::UnknownFeatureFunction {
+            feature: feature.clone(),
+            function: function.clone(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Crate types a library crate may be built as.
+const LIBRARY_CRATE_TYPES: &[&str] = &["cdylib", "rlib", "lib", "staticlib", "dylib"];
+
+/// Reject crate types that aren't library ones, or that leave out `cdylib`,
+/// which the wasm module is built from.
+fn validate_crate_types(crate_types: &[String]) -> ParserResult<()> {
+    let invalid = |reason: String| Err(ParserError::InvalidCrateType {
+        crate_types: crate_types.to_vec(),
+        reason,
+    });
+    let mut seen = HashSet::new();
+
+    for crate_type in crate_types {
+        if !LIBRARY_CRATE_TYPES.contains(&crate_type.as_str()) {
+            return invalid(format!(
+                "'{crate_type}' is not one of {}",
+                LIBRARY_CRATE_TYPES.join(", "),
+            ));
+        }
+
+        if !seen.insert(crate_type) {
+            return invalid(format!("'{crate_type}' is listed more than once"));
+        }
+    }
+
+    if !seen.contains(&"cdylib".to_string()) {
+        return invalid("'cdylib' is required to build the wasm module".to_string());
+    }
+
+    Ok(())
+}
+
+/// Reject every exported or host function signature that uses an
+/// unrecognized annotation.
+fn validate_types(modules: &[Module]) -> ParserResult<()> {
+    fn check(function: &str, parameters: &[Parameter], return_type: &ParameterType) -> Vec<ParserError> {
+        parameters
+            .iter()
+            .map(|p| (format!("parameter '{}'", p.name), &p.type_hint))
+            .chain([("return value".to_string(), return_type)])
+            .filter_map(|(parameter, type_hint)| {
+                type_hint.unrecognized().map(|annotation| ParserError::UnsupportedType {
+                    function: function.to_string(),
+                    parameter,
+                    annotation: annotation.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    let mut errors = modules
+        .iter()
+        .flat_map(|module| {
+            module.module_functions
+                .iter()
+                .flat_map(|f| check(&f.name, &f.parameters, &f.return_type))
+                .chain(
+                    module.host_functions
+                        .iter()
+                        .flat_map(|h| h.as_slice())
+                        .flat_map(|f| check(&f.name, &f.parameters, &f.return_type))
+                )
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(errors.remove(0)),
+        _ => Err(ParserError::Multiple(errors)),
+    }
+}
+
+
 #[derive(Default)]
 pub struct ProjectParserBuilder {
     ignore_strategy: Option<Box<dyn FileIgnoreStrategy + Send + Sync>>,
     metadata_parser: Option<Box<dyn MetadataParser + Send + Sync>>,
     options: Option<ProjectParserOptions>,
+    progress: Option<ProgressCallback>,
+    walk_progress: Option<WalkProgressCallback>,
+    cancellation: Option<CancellationToken>,
 }
 
 impl ProjectParserBuilder {
@@ -121,6 +538,9 @@ impl ProjectParserBuilder {
             ignore_strategy: None,
             metadata_parser: None,
             options: None,
+            progress: None,
+            walk_progress: None,
+            cancellation: None,
         }
     }
 
@@ -145,11 +565,790 @@ impl ProjectParserBuilder {
         self
     }
 
+    pub fn ignore_patterns<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options
+            .get_or_insert_with(Default::default)
+            .ignore_patterns
+            .extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn progress(
+        mut self,
+        callback: impl Fn(usize, usize) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    pub fn walk_progress(
+        mut self,
+        callback: impl Fn(usize) + Send + Sync + 'static,
+    ) -> Self {
+        self.walk_progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Stop parsing at the next file or phase once `token` is cancelled.
+    pub fn cancellation(mut self, token: Option<CancellationToken>) -> Self {
+        self.cancellation = token;
+        self
+    }
+
     pub fn build(self) -> ProjectParser {
-        ProjectParser::new(
-            self.ignore_strategy.unwrap_or_else(|| Box::new(DefaultFileIgnoreStrategy::new())),
-            self.metadata_parser.unwrap_or_else(|| Box::new(Pep621MetadataParser::new())),
-            self.options.unwrap_or_default(),
-        )
+        ProjectParser {
+            walk_progress: self.walk_progress,
+            cancellation: self.cancellation,
+            ..ProjectParser::new(
+                self.ignore_strategy.unwrap_or_else(|| Box::new(DefaultFileIgnoreStrategy::new())),
+                self.metadata_parser.unwrap_or_else(|| Box::new(StrategyMetadataParser::new())),
+                self.options.unwrap_or_default(),
+                self.progress,
+            )
+        }
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use std::{
+        fs,
+        sync::{Arc, Mutex},
+    };
+
+    fn write(path: &Path, content: &str) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, content).unwrap();
+    }
+
+    fn make_project(root: &Path) {
+        write(
+            &root.join("pyproject.toml"),
+            "[project]\nname = \"my-package\"\nversion = \"0.1.0\"\n",
+        );
+        fs::create_dir_all(root.join("venv/lib/python3.11/site-packages")).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reports_progress_once_per_module_file() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        make_project(root);
+        write(&root.join("my_package/__init__.py"), "");
+        write(
+            &root.join("my_package/api.py"),
+            "@mod_fn\ndef greet(name: str) -> str:\n    return name\n",
+        );
+        write(&root.join("my_package/utils.py"), "def helper() -> None:\n    pass\n");
+        write(&root.join("my_package/README.md"), "not python");
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let context = ProjectParser::builder()
+            .progress({
+                let calls = calls.clone();
+                move |analyzed, total| calls.lock().unwrap().push((analyzed, total))
+            })
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap();
+
+        assert_eq!(context.modules.len(), 1);
+        assert_eq!(*calls.lock().unwrap(), vec![(1, 3), (2, 3), (3, 3)]);
+    }
+
+    #[tokio::test]
+    async fn reports_each_file_found_by_the_walk() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        make_project(root);
+        write(&root.join("my_package/__init__.py"), "@mod_fn\ndef ping() -> None:\n    pass\n");
+        write(&root.join("my_package/README.md"), "not python");
+
+        let found = Arc::new(Mutex::new(Vec::new()));
+        ProjectParser::builder()
+            .walk_progress({
+                let found = found.clone();
+                move |count| found.lock().unwrap().push(count)
+            })
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap();
+
+        let found = found.lock().unwrap();
+        assert!(found.len() >= 3, "{found:?}");
+        assert_eq!(*found, (1..=found.len()).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn cancelling_stops_before_the_next_file() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        make_project(root);
+        write(&root.join("my_package/__init__.py"), "");
+        write(
+            &root.join("my_package/api.py"),
+            "@mod_fn\ndef greet(name: str) -> str:\n    return name\n",
+        );
+        write(&root.join("my_package/utils.py"), "def helper() -> None:\n    pass\n");
+
+        let token = CancellationToken::new();
+        let calls = Arc::new(Mutex::new(0));
+        let err = ProjectParser::builder()
+            .progress({
+                let (calls, token) = (calls.clone(), token.clone());
+                move |_, _| {
+                    *calls.lock().unwrap() += 1;
+                    token.cancel();
+                }
+            })
+            .cancellation(Some(token))
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, ParserError::Cancelled));
+        assert_eq!(*calls.lock().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn overrides_replace_the_file_name_and_version() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        make_project(root);
+        write(
+            &root.join("my_package/__init__.py"),
+            "@mod_fn\ndef greet(name: str) -> str:\n    return name\n",
+        );
+
+        let parser = |name_override: Option<&str>, version_override: Option<&str>| ProjectParser::builder()
+            .options(ProjectParserOptions {
+                name_override: name_override.map(str::to_string),
+                version_override: version_override.map(str::to_string),
+                ..Default::default()
+            })
+            .build();
+
+        let metadata = parser(None, Some("1.4.0-rc.1")).parse_project(root).await.unwrap().metadata;
+        assert_eq!((metadata.name.as_str(), metadata.version.as_str()), ("my-package", "1.4.0-rc.1"));
+
+        let metadata = parser(Some("ci-package"), None).parse_project(root).await.unwrap().metadata;
+        assert_eq!((metadata.name.as_str(), metadata.version.as_str()), ("ci-package", "0.1.0"));
+    }
+
+    #[tokio::test]
+    async fn version_override_resolves_dynamic_versions() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        make_project(root);
+        write(
+            &root.join("pyproject.toml"),
+            "[project]\nname = \"my-package\"\ndynamic = [\"version\"]\n",
+        );
+        write(
+            &root.join("my_package/__init__.py"),
+            "@mod_fn\ndef greet(name: str) -> str:\n    return name\n",
+        );
+
+        let parser = |version_override: Option<&str>| ProjectParser::builder()
+            .options(ProjectParserOptions {
+                version_override: version_override.map(str::to_string),
+                ..Default::default()
+            })
+            .build();
+
+        assert!(matches!(
+            parser(None).parse_project(root).await,
+            Err(ParserError::UnresolvedDynamicField(field)) if field == "version"
+        ));
+
+        let metadata = parser(Some("2.1.0")).parse_project(root).await.unwrap().metadata;
+        assert_eq!(metadata.version, "2.1.0");
+    }
+
+    #[tokio::test]
+    async fn include_patterns_restrict_analyzed_files() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        make_project(root);
+        write(&root.join("my_package/__init__.py"), "");
+        write(
+            &root.join("my_package/api.py"),
+            "@mod_fn\ndef greet(name: str) -> str:\n    return name\n",
+        );
+        write(
+            &root.join("my_package/scripts.py"),
+            "@mod_fn\ndef run(count: int) -> int:\n    return count\n",
+        );
+
+        let context = ProjectParser::builder()
+            .options(ProjectParserOptions {
+                include_patterns: vec!["api.py".into()],
+                ..Default::default()
+            })
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap();
+
+        let functions = context.modules
+            .iter()
+            .flat_map(|m| m.module_functions.iter().map(|f| f.name.as_str()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(functions, vec!["greet"]);
+    }
+
+    fn make_broken_project(root: &Path) {
+        make_project(root);
+        write(&root.join("my_package/__init__.py"), "");
+        write(
+            &root.join("my_package/first.py"),
+            "@mod_fn\ndef first(name) -> str:\n    return name\n",
+        );
+        write(
+            &root.join("my_package/second.py"),
+            "@mod_fn\ndef second(count: int):\n    return count\n",
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_every_failing_module() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        make_broken_project(root);
+
+        let err = ProjectParser::builder()
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap_err();
+
+        let ParserError::Multiple(errors) = &err else {
+            panic!("expected multiple errors, got {err:?}");
+        };
+        let message = err.to_string();
+
+        assert_eq!(errors.len(), 2);
+        assert!(message.contains("first.py: Parameter 'name' of 'first'"));
+        assert!(message.contains("second.py: 'second' is missing a return type annotation"));
+    }
+
+    #[tokio::test]
+    async fn fail_fast_reports_first_failure() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        make_broken_project(root);
+
+        let err = ProjectParser::builder()
+            .options(ProjectParserOptions {
+                fail_fast: true,
+                ..Default::default()
+            })
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap_err();
+
+        assert!(!matches!(err, ParserError::Multiple(_)));
+    }
+
+    #[tokio::test]
+    async fn reads_metadata_from_configured_path() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        make_project(root);
+        write(
+            &root.join("build/pyproject.toml"),
+            "[project]\nname = \"other-package\"\nversion = \"0.2.0\"\n\n[tool.py2binmod]\nmodule = \"my_package\"\n",
+        );
+        write(&root.join("my_package/__init__.py"), "@mod_fn\ndef ping() -> None:\n    pass\n");
+
+        let context = ProjectParser::builder()
+            .options(ProjectParserOptions {
+                metadata_path: Some("build/pyproject.toml".into()),
+                ..Default::default()
+            })
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap();
+
+        assert_eq!(context.metadata.name, "other-package");
+        assert_eq!(context.metadata.version, "0.2.0");
+    }
+
+    #[tokio::test]
+    async fn rejects_projects_without_exported_functions() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        make_project(root);
+        write(&root.join("my_package/__init__.py"), "");
+        write(
+            &root.join("my_package/api.py"),
+            "def greet(name: str) -> str:\n    return name\n\nclass Greeter:\n    def greet(self) -> str:\n        return ''\n",
+        );
+
+        let err = ProjectParser::builder()
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, ParserError::NoExportedFunctions(module) if module == "my_package"));
+    }
+
+    #[tokio::test]
+    async fn rejects_features_gating_unknown_functions() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        make_project(root);
+        write(
+            &root.join("pyproject.toml"),
+            "[project]\nname = \"my-package\"\nversion = \"0.1.0\"\n\n[tool.py2binmod.features]\ntext = [\"greet\", \"shout\"]\n",
+        );
+        write(
+            &root.join("my_package/__init__.py"),
+            "@mod_fn\ndef greet(name: str) -> str:\n    return name\n",
+        );
+
+        let err = ProjectParser::builder()
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            ParserError::UnknownFeatureFunction { feature, function } if feature == "text" && function == "shout"
+        ));
+    }
+
+    #[tokio::test]
+    async fn validates_configured_crate_types() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        make_project(root);
+        write(
+            &root.join("my_package/__init__.py"),
+            "@mod_fn\ndef greet(name: str) -> str:\n    return name\n",
+        );
+
+        for (crate_types, valid) in [
+            ("[\"cdylib\", \"rlib\"]", true),
+            ("[\"rlib\"]", false),
+            ("[\"cdylib\", \"bin\"]", false),
+            ("[\"cdylib\", \"cdylib\"]", false),
+            ("[]", false),
+        ] {
+            write(
+                &root.join("pyproject.toml"),
+                &format!("[project]\nname = \"my-package\"\nversion = \"0.1.0\"\n\n[tool.py2binmod]\ncrate-type = {crate_types}\n"),
+            );
+
+            let result = ProjectParser::builder().build().parse_project(root).await;
+
+            match valid {
+                true => assert_eq!(
+                    result.unwrap().metadata.py2binmod.unwrap().crate_types,
+                    Some(vec!["cdylib".to_string(), "rlib".to_string()]),
+                ),
+                false => assert!(
+                    matches!(result, Err(ParserError::InvalidCrateType { .. })),
+                    "{crate_types}",
+                ),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn configured_ignore_patterns_exclude_files() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        make_project(root);
+        write(
+            &root.join("pyproject.toml"),
+            "[project]\nname = \"my-package\"\nversion = \"0.1.0\"\n\n[tool.py2binmod]\nignore = [\"legacy\"]\n",
+        );
+        write(&root.join("my_package/__init__.py"), "");
+        write(
+            &root.join("my_package/api.py"),
+            "@mod_fn\ndef greet(name: str) -> str:\n    return name\n",
+        );
+        write(
+            &root.join("my_package/legacy/old.py"),
+            "@mod_fn\ndef old(name: str) -> str:\n    return name\n",
+        );
+        write(
+            &root.join("my_package/broken_test.py"),
+            "@mod_fn\ndef broken(name) -> str:\n    return name\n",
+        );
+
+        let context = ProjectParser::builder()
+            .ignore_patterns(["*_test.py"])
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap();
+
+        let functions = context.modules
+            .iter()
+            .flat_map(|m| m.module_functions.iter().map(|f| f.name.as_str()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(functions, vec!["greet"]);
+    }
+
+    fn make_typed_project(root: &Path, annotation: &str) {
+        make_project(root);
+        write(&root.join("my_package/__init__.py"), "");
+        write(
+            &root.join("my_package/api.py"),
+            &format!("@mod_fn\ndef handle(payload: {}) -> str:\n    return ''\n", annotation),
+        );
+    }
+
+    async fn parse_strict(root: &Path, strict: bool) -> ParserResult<ProjectContext> {
+        ProjectParser::builder()
+            .options(ProjectParserOptions {
+                strict,
+                ..Default::default()
+            })
+            .build()
+            .parse_project(root)
+            .await
+    }
+
+    #[tokio::test]
+    async fn strict_mode_allows_explicit_any() {
+        let td = TempDir::new().unwrap();
+        make_typed_project(td.path(), "Any");
+
+        assert!(parse_strict(td.path(), true).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn strict_mode_rejects_unrecognized_annotations() {
+        let td = TempDir::new().unwrap();
+        make_typed_project(td.path(), "list[MyModel]");
+
+        assert!(parse_strict(td.path(), false).await.is_ok());
+
+        let err = parse_strict(td.path(), true).await.unwrap_err();
+
+        assert!(matches!(
+            &err,
+            ParserError::UnsupportedType { function, parameter, annotation }
+                if function == "handle" && parameter == "parameter 'payload'" && annotation == "MyModel"
+        ));
+    }
+
+    #[tokio::test]
+    async fn untyped_dict_keys_fall_back_to_str_unless_strict() {
+        let td = TempDir::new().unwrap();
+        make_typed_project(td.path(), "dict[Any, int]");
+
+        let context = parse_strict(td.path(), false).await.unwrap();
+        let handle = context.modules
+            .iter()
+            .flat_map(|m| m.module_functions.iter())
+            .find(|f| f.name == "handle")
+            .unwrap();
+
+        assert_eq!(
+            handle.parameters[0].type_hint,
+            ParameterType::Map {
+                key_type: Box::new(ParameterType::String),
+                value_type: Box::new(ParameterType::Integer),
+            },
+        );
+        assert_eq!(
+            context.type_warnings,
+            ["Untyped dict key 'Any' for parameter 'payload' of 'handle' treated as 'str'"],
+        );
+
+        let err = parse_strict(td.path(), true).await.unwrap_err();
+
+        assert!(matches!(
+            &err,
+            ParserError::UnsupportedMapKey { function, parameter, key }
+                if function == "handle" && parameter == "parameter 'payload'" && key == "Any"
+        ));
+    }
+
+    #[tokio::test]
+    async fn struct_dict_keys_are_rejected() {
+        let td = TempDir::new().unwrap();
+        make_typed_project(td.path(), "dict[Point, int]");
+        write(
+            &td.path().join("my_package/models.py"),
+            "from typing import NamedTuple\n\nclass Point(NamedTuple):\n    x: float\n",
+        );
+
+        let err = parse_strict(td.path(), false).await.unwrap_err();
+
+        assert!(matches!(
+            &err,
+            ParserError::UnsupportedMapKey { key, .. } if key == "Point"
+        ));
+    }
+
+    #[tokio::test]
+    async fn named_tuples_resolve_across_modules() {
+        let td = TempDir::new().unwrap();
+        make_typed_project(td.path(), "list[Point]");
+        write(
+            &td.path().join("my_package/models.py"),
+            "from typing import NamedTuple\n\nclass Point(NamedTuple):\n    x: float\n    y: float\n",
+        );
+
+        let context = parse_strict(td.path(), true).await.unwrap();
+        let handle = context.modules
+            .iter()
+            .flat_map(|m| m.module_functions.iter())
+            .find(|f| f.name == "handle")
+            .unwrap();
+
+        assert_eq!(
+            handle.parameters[0].type_hint,
+            ParameterType::List(Box::new(ParameterType::Struct("Point".into()))),
+        );
+    }
+
+    #[tokio::test]
+    async fn struct_names_must_be_unique_across_modules() {
+        let td = TempDir::new().unwrap();
+        make_typed_project(td.path(), "Point");
+        write(
+            &td.path().join("my_package/models.py"),
+            "from typing import NamedTuple\n\nclass Point(NamedTuple):\n    x: float\n",
+        );
+        write(
+            &td.path().join("my_package/shapes.py"),
+            "from typing import TypedDict\n\nclass Point(TypedDict):\n    x: float\n    y: float\n",
+        );
+
+        let err = parse_strict(td.path(), false).await.unwrap_err();
+
+        assert!(matches!(
+            &err,
+            ParserError::DuplicateStruct { name, first, second }
+                if name == "Point" && first.ends_with("models.py") && second.ends_with("shapes.py")
+        ), "{err}");
+    }
+
+    #[tokio::test]
+    async fn resolves_stringified_annotations() {
+        let td = TempDir::new().unwrap();
+        let file = td.path().join("api.py");
+        write(
+            &file,
+            concat!(
+                "from __future__ import annotations\n",
+                "import typing as t\n",
+                "from typing import NamedTuple, Optional as Opt\n\n",
+                "@mod_fn\ndef scale(p: \"Point\", factor: \"float\") -> \"Point\": ...\n\n",
+                "@mod_fn\ndef tags(names: \"list[Opt[str]]\", counts: t.Mapping[str, \"int\"]) -> \"dict[str, list[Point]]\": ...\n\n",
+                "class Point(NamedTuple):\n    x: \"float\"\n    y: float\n",
+            ),
+        );
+
+        let module = ProjectParser::builder()
+            .build()
+            .parse_file(&file)
+            .await
+            .unwrap()
+            .unwrap();
+        let signature = |index: usize| {
+            let function = &module.module_functions[index];
+
+            (
+                function.parameters.iter().map(|p| p.type_hint.clone()).collect::<Vec<_>>(),
+                function.return_type.clone(),
+            )
+        };
+        let point = || ParameterType::Struct("Point".into());
+
+        assert_eq!(signature(0), (vec![point(), ParameterType::Float], point()));
+        assert_eq!(signature(1), (
+            vec![
+                ParameterType::List(Box::new(ParameterType::Optional(Box::new(ParameterType::String)))),
+                ParameterType::Map { key_type: Box::new(ParameterType::String), value_type: Box::new(ParameterType::Integer) },
+            ],
+            ParameterType::Map { key_type: Box::new(ParameterType::String), value_type: Box::new(ParameterType::List(Box::new(point()))) },
+        ));
+        assert_eq!(module.structs[0].fields[0].type_hint, ParameterType::Float);
+    }
+
+    #[tokio::test]
+    async fn parses_a_single_file_without_a_project() {
+        let td = TempDir::new().unwrap();
+        let file = td.path().join("api.py");
+        write(
+            &file,
+            concat!(
+                "from typing import NamedTuple\n\n",
+                "class Point(NamedTuple):\n    x: float\n    y: float\n\n",
+                "@mod_fn\ndef origin() -> Point:\n    return Point(0, 0)\n\n",
+                "def helper(p: Point) -> float:\n    return p.x\n\n",
+                "@mod_fn\ndef norm(p: Point) -> float:\n    return helper(p)\n",
+            ),
+        );
+
+        let module = ProjectParser::builder()
+            .build()
+            .parse_file(&file)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let functions = module.module_functions
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(module.name, "api");
+        assert_eq!(functions, vec!["origin", "norm"]);
+        assert_eq!(module.module_functions[0].return_type, ParameterType::Struct("Point".into()));
+
+        write(&file, "def helper() -> None:\n    pass\n");
+
+        assert!(ProjectParser::builder().build().parse_file(&file).await.unwrap().is_none());
+        assert!(matches!(
+            ProjectParser::builder().build().parse_file(&td.path().join("missing.py")).await,
+            Err(ParserError::MissingFile(_)),
+        ));
+    }
+
+    #[tokio::test]
+    async fn forward_references_resolve_to_later_structs() {
+        let td = TempDir::new().unwrap();
+        make_project(td.path());
+        write(
+            &td.path().join("my_package/__init__.py"),
+            concat!(
+                "from typing import TypedDict\n\n",
+                "@mod_fn\ndef load(config: \"Config\") -> list[\"Config\"]:\n    return [config]\n\n",
+                "class Config(TypedDict):\n    name: str\n",
+            ),
+        );
+
+        let context = parse_strict(td.path(), true).await.unwrap();
+        let load = &context.modules[0].module_functions[0];
+
+        assert_eq!(load.parameters[0].type_hint, ParameterType::Struct("Config".into()));
+        assert_eq!(load.return_type, ParameterType::List(Box::new(ParameterType::Struct("Config".into()))));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlinked_files_are_parsed_once() {
+        use std::os::unix::fs::symlink;
+
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+
+        make_project(root);
+        write(&root.join("my_package/__init__.py"), "");
+        write(&root.join("my_package/models/__init__.py"), "");
+        write(
+            &root.join("my_package/models/api.py"),
+            "@mod_fn\ndef greet(name: str) -> str:\n    return name\n",
+        );
+        symlink(root.join("my_package/models"), root.join("my_package/models_link")).unwrap();
+        symlink(root.join("my_package/models/api.py"), root.join("my_package/api_link.py")).unwrap();
+
+        let context = ProjectParser::builder()
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap();
+
+        assert_eq!(context.modules.len(), 1);
+        assert_eq!(context.import_path(&context.modules[0]), "my_package.models.api");
+    }
+
+    async fn import_paths(root: &Path) -> Vec<String> {
+        let context = ProjectParser::builder()
+            .build()
+            .parse_project(root)
+            .await
+            .unwrap();
+        let mut paths = context.modules
+            .iter()
+            .map(|m| context.import_path(m))
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+
+    #[tokio::test]
+    async fn import_paths_follow_the_layout() {
+        let api = "@mod_fn\ndef greet(name: str) -> str:\n    return name\n";
+
+        let flat = TempDir::new().unwrap();
+        make_project(flat.path());
+        write(&flat.path().join("my_package/__init__.py"), api);
+        write(&flat.path().join("my_package/models/api.py"), api);
+
+        assert_eq!(import_paths(flat.path()).await, vec!["my_package", "my_package.models.api"]);
+
+        let src = TempDir::new().unwrap();
+        make_project(src.path());
+        write(&src.path().join("src/my_package/__init__.py"), "");
+        write(&src.path().join("src/my_package/api.py"), api);
+
+        assert_eq!(import_paths(src.path()).await, vec!["my_package.api"]);
+
+        let single_file = TempDir::new().unwrap();
+        make_project(single_file.path());
+        write(
+            &single_file.path().join("pyproject.toml"),
+            "[project]\nname = \"my-package\"\nversion = \"0.1.0\"\n\n[tool.py2binmod]\nmodule = \"my_package\"\n",
+        );
+        write(&single_file.path().join("my_package.py"), api);
+
+        assert_eq!(import_paths(single_file.path()).await, vec!["my_package"]);
+    }
+
+    #[tokio::test]
+    async fn import_prefix_replaces_the_package_name() {
+        let td = TempDir::new().unwrap();
+        let root = td.path();
+        let api = "@mod_fn\ndef greet(name: str) -> str:\n    return name\n";
+
+        make_project(root);
+        write(
+            &root.join("pyproject.toml"),
+            "[project]\nname = \"my-package\"\nversion = \"0.1.0\"\n\n[tool.py2binmod]\nimport-prefix = \"vendor.pkg\"\n",
+        );
+        write(&root.join("my_package/__init__.py"), api);
+        write(&root.join("my_package/models/api.py"), api);
+
+        assert_eq!(import_paths(root).await, vec!["vendor.pkg", "vendor.pkg.models.api"]);
+    }
+}
@@ -0,0 +1,227 @@
+use std::{collections::{BTreeMap, BTreeSet, HashSet}, fs::read_dir, path::{Path, PathBuf}};
+
+
+/// Normalize a distribution name so that `Foo.Bar`, `foo-bar` and `foo_bar` compare equal.
+pub fn normalize_name(name: &str) -> String {
+    name
+        .split(['-', '_', '.'])
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase()
+}
+
+/// The normalized distribution name of a PEP 508 requirement such as
+/// `requests[socks]>=2.31; python_version > "3.8"`.
+pub fn requirement_name(requirement: &str) -> String {
+    normalize_name(
+        requirement
+            .split(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | '.')))
+            .next()
+            .unwrap_or_default(),
+    )
+}
+
+/// Normalized names of the distributions installed in any of `site_packages_dirs`.
+pub fn installed_distributions(site_packages_dirs: &[PathBuf]) -> HashSet<String> {
+    site_packages_dirs
+        .iter()
+        .flat_map(read_dir)
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name();
+            let file_name = file_name.to_str()?;
+            let stem = file_name
+                .strip_suffix(".dist-info")
+                .or_else(|| file_name.strip_suffix(".egg-info"))?;
+
+            // `{name}-{version}`, where the name itself never contains a dash.
+            stem.split('-').next().map(normalize_name)
+        })
+        .collect()
+}
+
+/// Requirements of each optional-dependency group that are not installed,
+/// as `(extra, requirement)` pairs.
+pub fn missing_extras(
+    site_packages_dirs: &[PathBuf],
+    optional_dependencies: &BTreeMap<String, Vec<String>>,
+) -> Vec<(String, String)> {
+    let installed = installed_distributions(site_packages_dirs);
+
+    optional_dependencies
+        .iter()
+        .flat_map(|(extra, requirements)| requirements
+            .iter()
+            .filter(|r| !installed.contains(&requirement_name(r)))
+            .map(move |r| (extra.clone(), r.clone()))
+        )
+        .collect()
+}
+
+/// The package directory or single-file module named `name` in `site_packages_dir`.
+pub fn locate_package(site_packages_dir: &Path, name: &str) -> Option<PathBuf> {
+    let dir = site_packages_dir.join(name);
+    let file = site_packages_dir.join(format!("{}.py", name));
+
+    if dir.is_dir() {
+        Some(dir)
+    } else if file.is_file() {
+        Some(file)
+    } else {
+        None
+    }
+}
+
+/// Every `.py` file under `path`, or `path` itself when it is a file, sorted.
+pub fn python_files(path: &Path) -> Vec<PathBuf> {
+    if path.is_file() {
+        return vec![path.to_path_buf()];
+    }
+
+    let mut files = Vec::new();
+    let mut stack = vec![path.to_path_buf()];
+
+    while let Some(dir) = stack.pop() {
+        for entry in read_dir(&dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+
+            if path.is_dir() && path.file_name().is_some_and(|n| n != "__pycache__") {
+                stack.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "py") {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+/// Total size in bytes of the files under `path`, or of `path` itself when it
+/// is a file.
+pub fn disk_size(path: &Path) -> u64 {
+    if path.is_file() {
+        return path.metadata().map(|m| m.len()).unwrap_or(0);
+    }
+
+    read_dir(path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| disk_size(&entry.path()))
+        .sum()
+}
+
+/// The top-level packages and single-file modules installed in any of
+/// `site_packages_dirs`, with their size on disk. Distribution metadata and
+/// bytecode caches are left out.
+pub fn installed_packages(site_packages_dirs: &[PathBuf]) -> BTreeMap<String, u64> {
+    let mut packages = BTreeMap::new();
+
+    for entry in site_packages_dirs.iter().flat_map(read_dir).flatten().flatten() {
+        let path = entry.path();
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+
+        let name = if path.is_dir() {
+            // `*.dist-info`, `*.egg-info` and `*.data` aren't importable.
+            if file_name.contains('.') || file_name == "__pycache__" || python_files(&path).is_empty() {
+                continue;
+            }
+            file_name
+        } else {
+            match file_name.strip_suffix(".py") {
+                Some(stem) => stem,
+                None => continue,
+            }
+        };
+
+        *packages.entry(name.to_string()).or_insert(0) += disk_size(&path);
+    }
+
+    packages
+}
+
+/// Installed packages of at least `min_size` bytes that aren't in `imported`,
+/// largest first.
+pub fn unused_packages(
+    site_packages_dirs: &[PathBuf],
+    imported: &BTreeSet<String>,
+    min_size: u64,
+) -> Vec<(String, u64)> {
+    let mut unused = installed_packages(site_packages_dirs)
+        .into_iter()
+        .filter(|(name, size)| *size >= min_size && !imported.contains(name))
+        .collect::<Vec<_>>();
+
+    unused.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    unused
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn requirement_names_are_normalized() {
+        assert_eq!(requirement_name("PyYAML>=6"), "pyyaml");
+        assert_eq!(requirement_name("requests[socks]>=2.31"), "requests");
+        assert_eq!(requirement_name("typing_extensions; python_version < '3.11'"), "typing-extensions");
+        assert_eq!(requirement_name("zope.interface"), "zope-interface");
+    }
+
+    #[test]
+    fn reports_extras_missing_from_site_packages() {
+        let td = TempDir::new().unwrap();
+        fs::create_dir_all(td.path().join("PyYAML-6.0.1.dist-info")).unwrap();
+        fs::create_dir_all(td.path().join("typing_extensions-4.12.0.dist-info")).unwrap();
+
+        let optional_dependencies = BTreeMap::from([
+            ("yaml".to_string(), vec!["pyyaml>=6".to_string()]),
+            ("http".to_string(), vec!["requests>=2".to_string(), "typing-extensions".to_string()]),
+        ]);
+
+        assert_eq!(
+            missing_extras(&[td.path().to_path_buf()], &optional_dependencies),
+            vec![("http".to_string(), "requests>=2".to_string())],
+        );
+    }
+
+    #[test]
+    fn lists_large_packages_that_are_not_imported() {
+        let td = TempDir::new().unwrap();
+        let site_packages = td.path().to_path_buf();
+        let write = |path: &str, size: usize| {
+            let path = site_packages.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "#".repeat(size)).unwrap();
+        };
+
+        write("requests/__init__.py", 3000);
+        write("numpy/__init__.py", 1000);
+        write("numpy/core/_multiarray.so", 9000);
+        write("pandas/__init__.py", 4000);
+        write("six.py", 2000);
+        write("tiny/__init__.py", 10);
+        write("numpy-2.1.0.dist-info/RECORD", 5000);
+        write("numpy/__pycache__/__init__.cpython-311.pyc", 500);
+        write("distutils-precedence.pth", 100);
+
+        let imported = BTreeSet::from(["requests".to_string()]);
+
+        assert_eq!(
+            unused_packages(&[site_packages], &imported, 1000),
+            vec![
+                ("numpy".to_string(), 10500),
+                ("pandas".to_string(), 4000),
+                ("six".to_string(), 2000),
+            ],
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use std::path::Path;
+
+use crate::parser::error::{ParserError, ParserResult};
+
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// The encoding declared by a PEP 263 `coding` comment on the first or second
+/// line, lowercased.
+pub fn coding_declaration(source: &[u8]) -> Option<String> {
+    let mut lines = source.split(|b| *b == b'\n').take(2).peekable();
+
+    // The second line only counts when the first is a comment or blank.
+    let first_is_comment = lines
+        .peek()
+        .map(|line| String::from_utf8_lossy(line).trim().is_empty() || line.trim_ascii_start().starts_with(b"#"))
+        .unwrap_or(false);
+
+    lines
+        .take(if first_is_comment { 2 } else { 1 })
+        .filter_map(|line| {
+            let line = String::from_utf8_lossy(line);
+            let comment = line.trim_start().strip_prefix('#')?;
+            let (_, rest) = comment.split_once("coding")?;
+            let name = rest
+                .strip_prefix([':', '='])?
+                .trim_start_matches([' ', '\t'])
+                .split(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+                .next()?;
+
+            (!name.is_empty()).then(|| name.to_lowercase())
+        })
+        .next()
+}
+
+/// Decode a Python source file: UTF-8 with an optional BOM, or Latin-1 when
+/// declared so by a coding comment. Anything else is a
+/// [`ParserError::UndecodableSource`].
+pub fn decode_source(path: &Path, source: &[u8]) -> ParserResult<String> {
+    if let Some(source) = source.strip_prefix(UTF8_BOM) {
+        return String::from_utf8(source.to_vec())
+            .map_err(|_| undecodable(path, "utf-8"));
+    }
+
+    match coding_declaration(source).as_deref().map(|c| c.replace('_', "-")) {
+        None => String::from_utf8(source.to_vec()).map_err(|_| undecodable(path, "utf-8")),
+        Some(coding) => match coding.as_str() {
+            "utf-8" | "utf8" | "ascii" | "us-ascii" => String::from_utf8(source.to_vec())
+                .map_err(|_| undecodable(path, &coding)),
+            "latin-1" | "latin1" | "iso-8859-1" | "iso8859-1" | "l1" => Ok(source.iter().map(|b| *b as char).collect()),
+            _ => Err(undecodable(path, &coding)),
+        },
+    }
+}
+
+fn undecodable(path: &Path, encoding: &str) -> ParserError {
+    ParserError::UndecodableSource {
+        file: path.to_path_buf(),
+        encoding: encoding.to_string(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_coding_declarations() {
+        assert_eq!(coding_declaration(b"# -*- coding: latin-1 -*-\nx = 1\n").as_deref(), Some("latin-1"));
+        assert_eq!(coding_declaration(b"#!/usr/bin/env python\n# vim: set fileencoding=UTF-8 :\n").as_deref(), Some("utf-8"));
+        assert_eq!(coding_declaration(b"x = 1\n# coding: latin-1\n"), None);
+        assert_eq!(coding_declaration(b"x = 1\n"), None);
+    }
+
+    #[test]
+    fn strips_the_bom() {
+        assert_eq!(decode_source(Path::new("api.py"), b"\xEF\xBB\xBFx = 1\n").unwrap(), "x = 1\n");
+    }
+
+    #[test]
+    fn decodes_declared_latin1() {
+        assert_eq!(
+            decode_source(Path::new("api.py"), b"# -*- coding: latin-1 -*-\nname = '\xE9'\n").unwrap(),
+            "# -*- coding: latin-1 -*-\nname = '\u{e9}'\n",
+        );
+    }
+
+    #[test]
+    fn names_the_file_and_encoding_it_cant_decode() {
+        let err = decode_source(Path::new("legacy.py"), b"name = '\xE9'\n").unwrap_err();
+
+        assert!(matches!(&err, ParserError::UndecodableSource { encoding, .. } if encoding == "utf-8"));
+        assert!(err.to_string().contains("legacy.py"), "{err}");
+
+        let err = decode_source(Path::new("legacy.py"), b"# coding: cp1252\nname = '\x80'\n").unwrap_err();
+
+        assert!(matches!(&err, ParserError::UndecodableSource { encoding, .. } if encoding == "cp1252"));
+    }
+}
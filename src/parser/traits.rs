@@ -1,11 +1,17 @@
+use std::collections::HashSet;
 use anyhow::{anyhow, Error};
 use ruff_python_ast::{self as ast};
+use ruff_python_parser::parse_expression;
 
-use crate::types::{
-    ModuleFunction,
-    HostFunction,
-    Parameter,
-    ParameterType,
+use crate::{
+    parser::error::ParserError,
+    types::{
+        ModuleFunction,
+        HostFunction,
+        Parameter,
+        ParameterKind,
+        ParameterType,
+    },
 };
 
 pub trait TryFromAst: Sized {
@@ -15,6 +21,129 @@ pub trait TryFromAst: Sized {
     fn try_from_ast(expr: &Self::Expr) -> Result<Self, Self::Error>;
 }
 
+/// Raised for `Callable` annotations, before the function and parameter
+/// they belong to are known.
+#[derive(Debug, thiserror::Error)]
+#[error("Callable annotations are not supported, Python callables can't be passed across the wasm boundary")]
+pub struct CallableAnnotation;
+
+/// Raised for dicts keyed by anything that doesn't serialize as a string, as
+/// JSON object keys must. Holds the key annotation.
+#[derive(Debug, thiserror::Error)]
+#[error("Unsupported dict key type '{0}', JSON object keys must be strings")]
+pub struct NonStringMapKey(pub ParameterType);
+
+/// Raised for sets of items the generated `HashSet` can't hold, as they
+/// aren't `Eq` and `Hash` in Rust. Holds the item annotation.
+#[derive(Debug, thiserror::Error)]
+#[error("Unsupported set item type '{0}', set items must be hashable")]
+pub struct UnhashableSetItem(pub ParameterType);
+
+/// Whether the Rust type generated for `item` can be a `HashSet` item.
+fn is_hashable(item: &ParameterType) -> bool {
+    match item {
+        ParameterType::String
+        | ParameterType::Integer
+        | ParameterType::Boolean
+        | ParameterType::Uuid
+        | ParameterType::Path
+        | ParameterType::None => true,
+        ParameterType::List(inner) | ParameterType::Optional(inner) => is_hashable(inner),
+        ParameterType::Tuple(inner) => inner.iter().all(|t| is_hashable(t)),
+        _ => false,
+    }
+}
+
+/// Name the function and parameter of a [`CallableAnnotation`],
+/// [`NonStringMapKey`] or [`UnhashableSetItem`] error.
+fn locate_annotation_error(error: Error, function: &str, parameter: impl FnOnce() -> String) -> Error {
+    if error.is::<CallableAnnotation>() {
+        ParserError::UnsupportedCallable {
+            function: function.to_string(),
+            parameter: parameter(),
+        }
+        .into()
+    } else if let Some(NonStringMapKey(key)) = error.downcast_ref() {
+        ParserError::UnsupportedMapKey {
+            function: function.to_string(),
+            parameter: parameter(),
+            key: key.to_string(),
+        }
+        .into()
+    } else if let Some(UnhashableSetItem(item)) = error.downcast_ref() {
+        ParserError::UnhashableSetItem {
+            function: function.to_string(),
+            parameter: parameter(),
+            item: item.to_string(),
+        }
+        .into()
+    } else {
+        error
+    }
+}
+
+/// Parse a function's return annotation.
+fn parse_return_type(func: &ast::StmtFunctionDef) -> Result<ParameterType, Error> {
+    ParameterType::try_from_ast(
+        func.returns
+            .as_deref()
+            .ok_or_else(|| ParserError::ReturnMissingTypeAnnotation {
+                file: None,
+                function: func.name.to_string(),
+            })?,
+    )
+    .map_err(|e| locate_annotation_error(e, &func.name, || "return value".to_string()))
+}
+
+/// Parse a function's parameters in declaration order, keeping track of which
+/// are positional-only (before `/`), keyword-only (after `*`) or variadic.
+/// Parameters must be named uniquely, as the generated Rust function binds
+/// each of them.
+fn parse_parameters(function: &str, parameters: &ast::Parameters) -> Result<Vec<Parameter>, Error> {
+    let mut seen = HashSet::new();
+
+    if let Some(duplicate) = parameters
+        .iter()
+        .map(|p| p.name().as_str())
+        .find(|name| !seen.insert(*name))
+    {
+        return Err(ParserError::DuplicateParameter {
+            function: function.to_string(),
+            parameter: duplicate.to_string(),
+        }
+        .into());
+    }
+
+    parameters.posonlyargs
+        .iter()
+        .map(|p| (&p.parameter, ParameterKind::PositionalOnly))
+        .chain(parameters.args.iter().map(|p| (&p.parameter, ParameterKind::PositionalOrKeyword)))
+        .chain(parameters.vararg.as_deref().map(|p| (p, ParameterKind::VarPositional)))
+        .chain(parameters.kwonlyargs.iter().map(|p| (&p.parameter, ParameterKind::KeywordOnly)))
+        .chain(parameters.kwarg.as_deref().map(|p| (p, ParameterKind::VarKeyword)))
+        .map(|(p, kind)| {
+            let prefix = match kind {
+                ParameterKind::VarPositional => "*",
+                ParameterKind::VarKeyword => "**",
+                _ => "",
+            };
+
+            if p.annotation().is_none() {
+                return Err(ParserError::ParameterMissingTypeAnnotation {
+                    file: None,
+                    function: function.to_string(),
+                    parameter: format!("{}{}", prefix, p.name()),
+                }
+                .into());
+            }
+
+            Parameter::try_from_ast(p)
+                .map(|param| Parameter { kind, ..param })
+                .map_err(|e| locate_annotation_error(e, function, || format!("parameter '{}'", p.name())))
+        })
+        .collect()
+}
+
 impl TryFromAst for ModuleFunction {
     type Expr = ast::StmtFunctionDef;
     type Error = Error;
@@ -33,15 +162,8 @@ impl TryFromAst for ModuleFunction {
         Ok(ModuleFunction {
             name: expr.name.to_string(),
             docstring,
-            parameters: expr.parameters
-                .iter()
-                .map(|arg| Parameter::try_from_ast(arg.as_parameter()))
-                .collect::<Result<Vec<Parameter>, Error>>()?,
-            return_type: ParameterType::try_from_ast(
-                expr.returns
-                    .as_deref()
-                    .ok_or_else(|| anyhow!("Missing return type annotation for function {}", expr.name))?,
-            )?,
+            parameters: parse_parameters(&expr.name, &expr.parameters)?,
+            return_type: parse_return_type(expr)?,
         })
     }
 }
@@ -53,15 +175,8 @@ impl TryFromAst for HostFunction {
     fn try_from_ast(expr: &Self::Expr) -> Result<Self, Self::Error> {
         Ok(HostFunction {
             name: expr.name.to_string(),
-            parameters: expr.parameters
-                .iter()
-                .map(|arg| Parameter::try_from_ast(arg.as_parameter()))
-                .collect::<Result<Vec<Parameter>, Error>>()?,
-            return_type: ParameterType::try_from_ast(
-                expr.returns
-                    .as_deref()
-                    .ok_or_else(|| anyhow!("Missing return type annotation for host function {}", expr.name))?,
-            )?,
+            parameters: parse_parameters(&expr.name, &expr.parameters)?,
+            return_type: parse_return_type(expr)?,
         })
     }
 }
@@ -78,7 +193,8 @@ impl TryFromAst for Parameter {
                     .annotation()
                     .as_deref()
                     .ok_or_else(|| anyhow!("Missing type annotation for parameter {}", expr.name()))?,
-            )?
+            )?,
+            kind: ParameterKind::default(),
         })
     }
 }
@@ -94,7 +210,11 @@ impl TryFromAst for ParameterType {
                 "float" | "builtins.float" => "float",
                 "str" | "builtins.str" => "str",
                 "bool" | "builtins.bool" => "bool",
+                "complex" | "builtins.complex" => "complex",
+                "UUID" | "uuid.UUID" => "UUID",
+                "Path" | "PurePath" | "pathlib.Path" | "pathlib.PurePath" => "Path",
                 "None" | "NoneType" => "None",
+                "Any" | "typing.Any" => "Any",
                 other => other,
             }
         }
@@ -149,14 +269,22 @@ impl TryFromAst for ParameterType {
         }
 
         match expr {
-            // Primitive literals
-            ast::Expr::Name(n) => match normalize_ident(n.id.as_str()) {
-                "int" => Ok(ParameterType::Integer),
-                "float" => Ok(ParameterType::Float),
-                "str" => Ok(ParameterType::String),
-                "bool" => Ok(ParameterType::Boolean),
-                "None" => Ok(ParameterType::None),
-                _ => Ok(ParameterType::Any),
+            // Primitive literals, bare or dotted (`uuid.UUID`)
+            ast::Expr::Name(_) | ast::Expr::Attribute(_) => match parse_name(expr)
+                .as_deref()
+                .map(normalize_ident)
+            {
+                Some("int") => Ok(ParameterType::Integer),
+                Some("float") => Ok(ParameterType::Float),
+                Some("str") => Ok(ParameterType::String),
+                Some("bool") => Ok(ParameterType::Boolean),
+                Some("complex") => Ok(ParameterType::Complex),
+                Some("UUID") => Ok(ParameterType::Uuid),
+                Some("Path") => Ok(ParameterType::Path),
+                Some("None") => Ok(ParameterType::None),
+                Some("Any") => Ok(ParameterType::Any),
+                Some("Callable" | "typing.Callable" | "collections.abc.Callable") => Err(CallableAnnotation.into()),
+                name => Ok(ParameterType::Unrecognized(name.unwrap_or("?").to_string())),
             },
 
             // Optional and Union types
@@ -176,22 +304,49 @@ impl TryFromAst for ParameterType {
                     .replace("collections.abc.", "");
 
                 match base_normalized.as_str() {
-                    // list[T]
-                    "list" | "List" => Ok(ParameterType::List(
+                    // list[T], Sequence[T], Iterable[T], ...
+                    "list" | "List" | "Sequence" | "MutableSequence" | "Iterable" => Ok(ParameterType::List(
                         Box::new(ParameterType::try_from_ast(
                             args.first()
                                 .ok_or_else(|| anyhow!("Missing type argument for List"))?,
                         )?)
                     )),
 
-                    // dict[K, V]
-                    "dict" | "Dict" | "Mapping" => {
+                    // set[T], AbstractSet[T], ...
+                    "set" | "Set" | "AbstractSet" => {
+                        let item_type = ParameterType::try_from_ast(
+                            args.first()
+                                .ok_or_else(|| anyhow!("Missing type argument for Set"))?,
+                        )?;
+
+                        if !is_hashable(&item_type) {
+                            return Err(UnhashableSetItem(item_type).into());
+                        }
+
+                        Ok(ParameterType::Set(Box::new(item_type)))
+                    },
+
+                    // dict[K, V], Mapping[K, V], ...
+                    "dict" | "Dict" | "Mapping" | "MutableMapping" => {
                         if args.len() != 2 {
                             return Err(anyhow!("Dict type annotation requires two type arguments"));
                         }
 
+                        // Keys that serialize as strings survive the JSON
+                        // round-trip, anything else can't be a map key.
+                        // Untyped keys may still name a struct, so they're
+                        // settled once structs are resolved.
+                        let key_type = match ParameterType::try_from_ast(args[0])? {
+                            key @ (ParameterType::String
+                            | ParameterType::Uuid
+                            | ParameterType::Path
+                            | ParameterType::Any
+                            | ParameterType::Unrecognized(_)) => key,
+                            key => return Err(NonStringMapKey(key).into()),
+                        };
+
                         Ok(ParameterType::Map {
-                            key_type: Box::new(ParameterType::try_from_ast(args[0])?),
+                            key_type: Box::new(key_type),
                             value_type: Box::new(ParameterType::try_from_ast(args[1])?),
                         })
                     },
@@ -207,6 +362,8 @@ impl TryFromAst for ParameterType {
                             .collect(),
                     )),
 
+                    "Callable" => Err(CallableAnnotation.into()),
+
                     "Optional" => Ok(ParameterType::Optional(
                         Box::new(ParameterType::try_from_ast(
                             args.first()
@@ -214,14 +371,345 @@ impl TryFromAst for ParameterType {
                         )?)
                     )),
 
-                    _ => Ok(ParameterType::Any)
+                    _ => Ok(ParameterType::Unrecognized(base))
                 }
             },
 
             // None literal
             ast::Expr::NoneLiteral(_) => Ok(ParameterType::None),
 
+            // Forward references, `"Config"` or `"list[Config]"`, are parsed
+            // like the annotation they quote. Struct names are resolved once
+            // the whole project has been analyzed, so the class may come later.
+            ast::Expr::StringLiteral(s) => {
+                let source = s.value.to_str();
+                let parsed = parse_expression(source.trim())
+                    .map_err(|e| anyhow!("Invalid forward reference '{}': {}", source, e))?;
+
+                ParameterType::try_from_ast(parsed.expr())
+            },
+
             _ => Err(anyhow!("Unsupported type annotation expression: {:?}", expr)),
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ruff_python_parser::parse_module;
+
+    fn parse(annotation: &str) -> ParameterType {
+        ParameterType::try_from_ast(parse_expression(annotation).unwrap().expr()).unwrap()
+    }
+
+    fn list_of_int() -> ParameterType {
+        ParameterType::List(Box::new(ParameterType::Integer))
+    }
+
+    fn set_of_int() -> ParameterType {
+        ParameterType::Set(Box::new(ParameterType::Integer))
+    }
+
+    fn map_of_str_int() -> ParameterType {
+        ParameterType::Map {
+            key_type: Box::new(ParameterType::String),
+            value_type: Box::new(ParameterType::Integer),
+        }
+    }
+
+    #[test]
+    fn sequence_abcs_map_to_list() {
+        for annotation in [
+            "Sequence[int]",
+            "MutableSequence[int]",
+            "Iterable[int]",
+            "typing.Sequence[int]",
+            "collections.abc.Iterable[int]",
+            "collections.abc.MutableSequence[int]",
+        ] {
+            assert_eq!(parse(annotation), list_of_int(), "{annotation}");
+        }
+    }
+
+    #[test]
+    fn mapping_abcs_map_to_map() {
+        for annotation in [
+            "Mapping[str, int]",
+            "MutableMapping[str, int]",
+            "typing.Mapping[str, int]",
+            "collections.abc.MutableMapping[str, int]",
+        ] {
+            assert_eq!(parse(annotation), map_of_str_int(), "{annotation}");
+        }
+    }
+
+    #[test]
+    fn set_abcs_map_to_set() {
+        for annotation in [
+            "set[int]",
+            "Set[int]",
+            "AbstractSet[int]",
+            "typing.AbstractSet[int]",
+            "collections.abc.Set[int]",
+        ] {
+            assert_eq!(parse(annotation), set_of_int(), "{annotation}");
+        }
+    }
+
+    #[test]
+    fn nested_optional_generics() {
+        let optional_list = ParameterType::Optional(Box::new(list_of_int()));
+        let nested = ParameterType::Map {
+            key_type: Box::new(ParameterType::String),
+            value_type: Box::new(optional_list.clone()),
+        };
+
+        for annotation in [
+            "dict[str, Optional[list[int]]]",
+            "dict[str, list[int] | None]",
+            "Mapping[str, typing.Optional[Sequence[int]]]",
+        ] {
+            assert_eq!(parse(annotation), nested, "{annotation}");
+        }
+
+        assert_eq!(
+            parse("Optional[list[dict[str, Optional[list[int]]]]]"),
+            ParameterType::Optional(Box::new(ParameterType::List(Box::new(nested)))),
+        );
+    }
+
+    #[test]
+    fn uuid_and_path_annotations() {
+        for annotation in ["UUID", "uuid.UUID"] {
+            assert_eq!(parse(annotation), ParameterType::Uuid, "{annotation}");
+        }
+        for annotation in ["Path", "pathlib.Path", "PurePath", "pathlib.PurePath"] {
+            assert_eq!(parse(annotation), ParameterType::Path, "{annotation}");
+        }
+        assert_eq!(parse("list[uuid.UUID]"), ParameterType::List(Box::new(ParameterType::Uuid)));
+    }
+
+    #[test]
+    fn explicit_any_differs_from_unrecognized() {
+        assert_eq!(parse("Any"), ParameterType::Any);
+        assert_eq!(parse("typing.Any"), ParameterType::Any);
+        assert_eq!(parse("MyModel"), ParameterType::Unrecognized("MyModel".into()));
+        assert_eq!(parse("list[MyModel]").unrecognized(), Some("MyModel"));
+        assert_eq!(parse("list[Any]").unrecognized(), None);
+    }
+
+    #[test]
+    fn complex_annotations() {
+        for annotation in ["complex", "builtins.complex"] {
+            assert_eq!(parse(annotation), ParameterType::Complex, "{annotation}");
+        }
+        assert_eq!(parse("list[complex]"), ParameterType::List(Box::new(ParameterType::Complex)));
+    }
+
+    #[test]
+    fn forward_references_parse_like_the_quoted_annotation() {
+        assert_eq!(parse("\"Config\""), ParameterType::Unrecognized("Config".into()));
+        assert_eq!(parse("list[\"Config\"]"), ParameterType::List(Box::new(ParameterType::Unrecognized("Config".into()))));
+        assert_eq!(parse("\"list[int]\""), list_of_int());
+        assert_eq!(parse("dict[str, \"int\"]"), map_of_str_int());
+        assert!(ParameterType::try_from_ast(parse_expression("\"list[\"").unwrap().expr()).is_err());
+    }
+
+    #[test]
+    fn parameters_keep_their_kind() {
+        let module = parse_module("def f(a: int, /, b: int, *, c: int) -> None: ...").unwrap();
+        let ast::Stmt::FunctionDef(func) = &module.suite()[0] else {
+            panic!("expected a function definition");
+        };
+
+        let kinds = ModuleFunction::try_from_ast(func)
+            .unwrap()
+            .parameters
+            .into_iter()
+            .map(|p| (p.name, p.kind))
+            .collect::<Vec<_>>();
+
+        assert_eq!(kinds, vec![
+            ("a".to_string(), ParameterKind::PositionalOnly),
+            ("b".to_string(), ParameterKind::PositionalOrKeyword),
+            ("c".to_string(), ParameterKind::KeywordOnly),
+        ]);
+    }
+
+    fn parse_function(source: &str) -> Result<ModuleFunction, Error> {
+        let module = parse_module(source).unwrap();
+        let ast::Stmt::FunctionDef(func) = &module.suite()[0] else {
+            panic!("expected a function definition");
+        };
+
+        ModuleFunction::try_from_ast(func)
+    }
+
+    #[test]
+    fn variadic_parameters_keep_their_item_type() {
+        let parameters = parse_function("def f(a: int, *args: str, **kwargs: float) -> None: ...")
+            .unwrap()
+            .parameters
+            .into_iter()
+            .map(|p| (p.name, p.type_hint, p.kind))
+            .collect::<Vec<_>>();
+
+        assert_eq!(parameters, vec![
+            ("a".to_string(), ParameterType::Integer, ParameterKind::PositionalOrKeyword),
+            ("args".to_string(), ParameterType::String, ParameterKind::VarPositional),
+            ("kwargs".to_string(), ParameterType::Float, ParameterKind::VarKeyword),
+        ]);
+    }
+
+    #[test]
+    fn unannotated_variadic_parameters_are_rejected() {
+        let err = parse_function("def f(*args) -> None: ...").unwrap_err();
+        assert!(err.to_string().contains("*args"), "{err}");
+
+        let err = parse_function("def f(**options) -> None: ...").unwrap_err();
+        assert!(err.to_string().contains("**options"), "{err}");
+    }
+
+    #[test]
+    fn callables_are_rejected_with_their_location() {
+        let err = parse_function("def apply(f: Callable[[int], str], x: int) -> str: ...").unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<ParserError>(),
+            Some(ParserError::UnsupportedCallable { function, parameter })
+                if function == "apply" && parameter == "parameter 'f'"
+        ));
+        assert!(err.to_string().contains("can't be passed across the wasm boundary"), "{err}");
+
+        let err = parse_function("def make() -> typing.Callable[[], None]: ...").unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<ParserError>(),
+            Some(ParserError::UnsupportedCallable { parameter, .. }) if parameter == "return value"
+        ));
+        assert!(ParameterType::try_from_ast(parse_expression("list[Callable]").unwrap().expr()).is_err());
+    }
+
+    #[test]
+    fn missing_annotations_name_the_function_and_parameter() {
+        for (source, missing) in [
+            ("def f(a: int, b) -> None: ...", "b"),
+            ("def f(a: int, *, b) -> None: ...", "b"),
+            ("def f(a: int, *args) -> None: ...", "*args"),
+            ("def f(a: int, **kwargs) -> None: ...", "**kwargs"),
+        ] {
+            let err = parse_function(source).unwrap_err();
+
+            assert!(matches!(
+                err.downcast_ref::<ParserError>(),
+                Some(ParserError::ParameterMissingTypeAnnotation { file: None, function, parameter })
+                    if function == "f" && parameter == missing
+            ), "{source}: {err}");
+        }
+
+        let err = parse_function("def f(a: int): ...").unwrap_err();
+
+        assert_eq!(err.to_string(), "'f' is missing a return type annotation");
+        assert!(matches!(
+            err.downcast_ref::<ParserError>(),
+            Some(ParserError::ReturnMissingTypeAnnotation { function, .. }) if function == "f"
+        ));
+    }
+
+    /// The function in `source` with its parameter `b` renamed to `a`. The
+    /// parser rejects a name declared twice itself, so duplicates reaching
+    /// the AST have to be built by hand.
+    fn duplicating_a(source: &str) -> ast::StmtFunctionDef {
+        let Some(ast::Stmt::FunctionDef(mut func)) = parse_module(source).unwrap().into_suite().into_iter().next() else {
+            panic!("expected a function definition");
+        };
+        let parameters: &mut ast::Parameters = &mut func.parameters;
+        let named = parameters.posonlyargs
+            .iter_mut()
+            .chain(&mut parameters.args)
+            .chain(&mut parameters.kwonlyargs)
+            .map(|p| &mut p.parameter)
+            .chain(parameters.vararg.as_deref_mut())
+            .chain(parameters.kwarg.as_deref_mut());
+
+        for parameter in named {
+            if parameter.name.as_str() == "b" {
+                parameter.name.id = "a".into();
+            }
+        }
+
+        func
+    }
+
+    #[test]
+    fn duplicate_parameters_are_rejected() {
+        for source in [
+            "def f(a: int, b: str) -> None: ...",
+            "def f(a: int, /, *, b: int) -> None: ...",
+            "def f(a: int, *b: int) -> None: ...",
+            "def f(a: int, **b: int) -> None: ...",
+        ] {
+            let err = ModuleFunction::try_from_ast(&duplicating_a(source)).unwrap_err();
+
+            assert!(matches!(
+                err.downcast_ref::<ParserError>(),
+                Some(ParserError::DuplicateParameter { function, parameter })
+                    if function == "f" && parameter == "a"
+            ), "{source}: {err}");
+        }
+
+        let err = HostFunction::try_from_ast(&duplicating_a("def g(a: int, c: int, b: int) -> int: ...")).unwrap_err();
+
+        assert_eq!(err.to_string(), "Parameter 'a' of 'g' is declared more than once");
+    }
+
+    #[test]
+    fn dicts_need_string_keys() {
+        assert_eq!(parse_function("def f(counts: dict[str, int]) -> None: ...").unwrap().parameters[0].type_hint, map_of_str_int());
+
+        let err = parse_function("def f(names: dict[int, str]) -> None: ...").unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<ParserError>(),
+            Some(ParserError::UnsupportedMapKey { function, parameter, key })
+                if function == "f" && parameter == "parameter 'names'" && key == "int"
+        ));
+
+        let err = parse_function("def f() -> list[Mapping[tuple[int, int], str]]: ...").unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<ParserError>(),
+            Some(ParserError::UnsupportedMapKey { parameter, key, .. })
+                if parameter == "return value" && key == "tuple[int, int]"
+        ));
+    }
+
+    #[test]
+    fn set_items_must_be_hashable() {
+        assert_eq!(
+            parse("set[tuple[str, int]]"),
+            ParameterType::Set(Box::new(ParameterType::Tuple(vec![
+                Box::new(ParameterType::String),
+                Box::new(ParameterType::Integer),
+            ]))),
+        );
+
+        for (source, item) in [
+            ("def f(xs: set[float]) -> None: ...", "float"),
+            ("def f(xs: set[Any]) -> None: ...", "Any"),
+            ("def f(xs: AbstractSet[list[complex]]) -> None: ...", "list[complex]"),
+        ] {
+            let err = parse_function(source).unwrap_err();
+
+            assert!(matches!(
+                err.downcast_ref::<ParserError>(),
+                Some(ParserError::UnhashableSetItem { function, parameter, item: found })
+                    if function == "f" && parameter == "parameter 'xs'" && found == item
+            ), "{source}: {err}");
+        }
+
+        assert_eq!(parse("frozenset[int]"), ParameterType::Unrecognized("frozenset".into()));
+    }
+}
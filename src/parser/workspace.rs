@@ -0,0 +1,82 @@
+use std::path::Path;
+
+use crate::{
+    parser::error::{ParserError, ParserResult},
+    types::Workspace,
+};
+
+
+/// Keys of `[workspace.package]` the generated manifest inherits when the
+/// workspace defines them. The description stays the module's own.
+const INHERITABLE_KEYS: &[&str] = &[
+    "version",
+    "edition",
+    "authors",
+    "license",
+    "homepage",
+    "repository",
+    "documentation",
+];
+
+/// Read the Cargo workspace rooted at `root`, which must hold a `Cargo.toml`
+/// with a `[workspace]` table.
+pub fn read_workspace(root: &Path) -> ParserResult<Workspace> {
+    let manifest_path = root.join("Cargo.toml");
+    let content = std::fs::read_to_string(&manifest_path)
+        .map_err(|_| ParserError::InvalidWorkspace(root.to_path_buf()))?;
+    let manifest: toml::Table = toml::from_str(&content)?;
+    let workspace = manifest
+        .get("workspace")
+        .and_then(|w| w.as_table())
+        .ok_or_else(|| ParserError::InvalidWorkspace(root.to_path_buf()))?;
+    let package = workspace
+        .get("package")
+        .and_then(|p| p.as_table());
+
+    Ok(Workspace {
+        root: root.canonicalize().unwrap_or_else(|_| root.to_path_buf()),
+        inherited: INHERITABLE_KEYS
+            .iter()
+            .filter(|key| package.is_some_and(|p| p.contains_key(**key)))
+            .map(|key| key.to_string())
+            .collect(),
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn inherits_the_workspace_package_keys() {
+        let td = TempDir::new().unwrap();
+        fs::write(
+            td.path().join("Cargo.toml"),
+            "[workspace]\nmembers = [\"modules/*\"]\n\n[workspace.package]\nversion = \"2.0.0\"\nedition = \"2024\"\nrust-version = \"1.91\"\n",
+        ).unwrap();
+
+        let workspace = read_workspace(td.path()).unwrap();
+
+        assert_eq!(workspace.inherited, vec!["version", "edition"]);
+    }
+
+    #[test]
+    fn rejects_missing_workspaces() {
+        let td = TempDir::new().unwrap();
+
+        assert!(matches!(
+            read_workspace(&td.path().join("missing")),
+            Err(ParserError::InvalidWorkspace(_)),
+        ));
+
+        fs::write(td.path().join("Cargo.toml"), "[package]\nname = \"app\"\nversion = \"0.1.0\"\n").unwrap();
+
+        assert!(matches!(
+            read_workspace(td.path()),
+            Err(ParserError::InvalidWorkspace(_)),
+        ));
+    }
+}
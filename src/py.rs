@@ -1,38 +1,144 @@
+use std::{
+    env,
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use tokio_util::sync::CancellationToken;
 use pyo3::{
     prelude::*,
+    create_exception,
     exceptions::{PyValueError, PyTypeError, PyRuntimeError},
 };
 
 use crate::{
     commands::{
-        BuildOptions, TranspileOptions,
-        build_project, transpile_project,
-    }, error::AppError,
+        BuildOptions, TranspileOptions, DoctorOptions, InitOptions, WatchOptions,
+        build_project, transpile_project, doctor_project, init_project, watch_project,
+    },
+    error::AppError,
+    ui::Verbosity,
 };
 
 
+// Each exception keeps the builtin base the errors were raised as before, so
+// existing `except ValueError` handlers still catch them.
+create_exception!(_py2binmod, Py2BinmodError, PyRuntimeError);
+create_exception!(_py2binmod, ParserError, PyValueError);
+create_exception!(_py2binmod, CompilerError, PyRuntimeError);
+create_exception!(_py2binmod, TemplateError, PyTypeError);
+
+/// Raised exceptions carry the error's [`AppError::code`] as a `code` attribute.
 impl From<AppError> for PyErr {
     fn from(err: AppError) -> PyErr {
-        match err {
-            AppError::CompilationError(e) => PyRuntimeError::new_err(e.to_string()),
-            AppError::ParserError(e) => PyValueError::new_err(e.to_string()),
-            AppError::TemplateError(e) => PyTypeError::new_err(e.to_string()),
-            AppError::GeneratorError(msg) => PyRuntimeError::new_err(msg),
-            AppError::IoError(e) => PyRuntimeError::new_err(e.to_string()),
-            AppError::UnknownError(e) => PyRuntimeError::new_err(e.to_string()),
-        }
+        let code = err.code();
+        let py_err = match err {
+            AppError::CompilationError(e) => CompilerError::new_err(e.to_string()),
+            AppError::ParserError(e) => ParserError::new_err(e.to_string()),
+            AppError::TemplateError(e) => TemplateError::new_err(e.to_string()),
+            AppError::GeneratorError(msg) => Py2BinmodError::new_err(msg),
+            AppError::IoError(e) => Py2BinmodError::new_err(e.to_string()),
+            AppError::UnknownError(e) => Py2BinmodError::new_err(e.to_string()),
+            AppError::Cancelled => Py2BinmodError::new_err("Cancelled"),
+        };
+
+        Python::attach(|py| match py_err.value(py).setattr("code", code) {
+            Ok(()) => py_err,
+            Err(e) => e,
+        })
     }
 }
 
 
-#[pyfunction(name = "transpile_command")]
-#[pyo3(signature = (project_dir, out_dir=None, stdout=false))]
-fn py_transpile_command(py: Python<'_>, project_dir: String, out_dir: Option<String>, stdout: bool) -> PyResult<Bound<'_, PyAny>> {
+fn parse_verbosity(verbosity: &str) -> PyResult<Verbosity> {
+    verbosity
+        .parse()
+        .map_err(PyValueError::new_err)
+}
+
+/// Overrides the number of worker threads of the runtime the bindings run on.
+const WORKER_THREADS_ENV: &str = "PY2BINMOD_WORKER_THREADS";
+
+/// Set once a command has touched the runtime, after which its configuration
+/// can no longer change.
+static RUNTIME_STARTED: AtomicBool = AtomicBool::new(false);
+
+fn runtime() -> &'static tokio::runtime::Runtime {
+    RUNTIME_STARTED.store(true, Ordering::SeqCst);
+    pyo3_async_runtimes::tokio::get_runtime()
+}
+
+/// Spawn a command on the shared tokio runtime as an asyncio awaitable.
+/// Cancelling the awaitable drops the command and cancels the token it was
+/// handed, which stops the work it passed to other tasks.
+fn spawn<F, T>(py: Python<'_>, command: impl FnOnce(CancellationToken) -> F) -> PyResult<Bound<'_, PyAny>>
+where
+    F: Future<Output = PyResult<T>> + Send + 'static,
+    T: for<'py> IntoPyObject<'py> + Send + 'static,
+{
+    runtime();
+    let token = CancellationToken::new();
+    let fut = command(token.clone());
+
     pyo3_async_runtimes::tokio::future_into_py(py, async move {
+        let guard = token.drop_guard();
+        let result = fut.await;
+        guard.disarm();
+        result
+    })
+}
+
+fn init_runtime(worker_threads: usize) -> PyResult<()> {
+    if worker_threads == 0 {
+        return Err(PyValueError::new_err("worker_threads must be at least 1"));
+    }
+
+    if RUNTIME_STARTED.load(Ordering::SeqCst) {
+        return Err(Py2BinmodError::new_err(
+            "The runtime is already running, configure it before running any command",
+        ));
+    }
+
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder
+        .enable_all()
+        .worker_threads(worker_threads);
+    pyo3_async_runtimes::tokio::init(builder);
+
+    Ok(())
+}
+
+/// Run a command to completion on the shared tokio runtime, with the GIL released.
+/// The runtime is separate from any asyncio loop, so this is safe to call while
+/// one is running, it only blocks the calling thread. Nothing can cancel it, so
+/// commands run this way get no cancellation token.
+fn block_on<F, T>(py: Python<'_>, fut: F) -> PyResult<T>
+where
+    F: Future<Output = PyResult<T>> + Send,
+    T: Send,
+{
+    py.detach(|| runtime().block_on(fut))
+}
+
+
+#[pyfunction(name = "transpile_command")]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (project_dir, out_dir=None, stdout=false, ignore=None, verbosity="normal", metadata=None, bundle=false, include=None, emit_cargo_lock=false, explain=false))]
+fn py_transpile_command<'py>(py: Python<'py>, project_dir: String, out_dir: Option<String>, stdout: bool, ignore: Option<Vec<String>>, verbosity: &str, metadata: Option<String>, bundle: bool, include: Option<Vec<String>>, emit_cargo_lock: bool, explain: bool) -> PyResult<Bound<'py, PyAny>> {
+    let verbosity = parse_verbosity(verbosity)?;
+
+    spawn(py, move |cancellation| async move {
         transpile_project(TranspileOptions {
             project_dir: project_dir,
             out_dir: out_dir,
             stdout: stdout,
+            ignore_patterns: ignore.unwrap_or_default(),
+            include_patterns: include.unwrap_or_default(),
+            metadata_path: metadata,
+            verbosity,
+            bundle,
+            emit_cargo_lock,
+            explain,
+            cancellation: Some(cancellation),
         })
         .await?;
 
@@ -41,13 +147,92 @@ fn py_transpile_command(py: Python<'_>, project_dir: String, out_dir: Option<Str
 }
 
 #[pyfunction(name = "build_command")]
-#[pyo3(signature = (project_dir, out_dir=None, release=false))]
-fn py_build_command(py: Python<'_>, project_dir: String, out_dir: Option<String>, release: bool) -> PyResult<Bound<'_, PyAny>> {
-    pyo3_async_runtimes::tokio::future_into_py(py, async move {
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (project_dir, out_dir=None, release=false, ignore=None, offline=false, locked=false, profile=None, jobs=None, verbosity="normal", force=false, metadata=None, rustflags=None, dry_run=false, assume_target=false, include=None, retries=0, retry_backoff_ms=1000, explain=false))]
+fn py_build_command<'py>(py: Python<'py>, project_dir: String, out_dir: Option<String>, release: bool, ignore: Option<Vec<String>>, offline: bool, locked: bool, profile: Option<String>, jobs: Option<usize>, verbosity: &str, force: bool, metadata: Option<String>, rustflags: Option<Vec<String>>, dry_run: bool, assume_target: bool, include: Option<Vec<String>>, retries: usize, retry_backoff_ms: u64, explain: bool) -> PyResult<Bound<'py, PyAny>> {
+    let verbosity = parse_verbosity(verbosity)?;
+
+    spawn(py, move |cancellation| async move {
         build_project(BuildOptions {
             project_dir: project_dir,
             out_dir: out_dir,
             release: release,
+            profile,
+            offline,
+            locked,
+            jobs,
+            ignore_patterns: ignore.unwrap_or_default(),
+            include_patterns: include.unwrap_or_default(),
+            metadata_path: metadata,
+            verbosity,
+            force,
+            rustflags: rustflags.unwrap_or_default(),
+            dry_run,
+            assume_target,
+            retries,
+            retry_backoff_ms,
+            explain,
+            cancellation: Some(cancellation),
+        })
+        .await?;
+
+        Ok(())
+    })
+}
+
+#[pyfunction(name = "watch_command")]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (project_dir, out_dir=None, release=false, debounce_ms=300, ignore=None, metadata=None, include=None, verbosity="normal"))]
+fn py_watch_command<'py>(py: Python<'py>, project_dir: String, out_dir: Option<String>, release: bool, debounce_ms: u64, ignore: Option<Vec<String>>, metadata: Option<String>, include: Option<Vec<String>>, verbosity: &str) -> PyResult<Bound<'py, PyAny>> {
+    let verbosity = parse_verbosity(verbosity)?;
+
+    spawn(py, |_| async move {
+        watch_project(WatchOptions {
+            project_dir,
+            out_dir,
+            release,
+            debounce_ms,
+            ignore_patterns: ignore.unwrap_or_default(),
+            include_patterns: include.unwrap_or_default(),
+            metadata_path: metadata,
+            verbosity,
+        })
+        .await?;
+
+        Ok(())
+    })
+}
+
+#[pyfunction(name = "doctor_command")]
+#[pyo3(signature = (project_dir, verbosity="normal"))]
+fn py_doctor_command<'py>(py: Python<'py>, project_dir: String, verbosity: &str) -> PyResult<Bound<'py, PyAny>> {
+    let verbosity = parse_verbosity(verbosity)?;
+
+    spawn(py, |_| async move {
+        let report = doctor_project(DoctorOptions {
+            project_dir,
+            verbosity,
+        })
+        .await?;
+
+        Ok(
+            report.failures
+                .into_iter()
+                .map(|failure| (failure.check.name().to_string(), failure.message))
+                .collect::<Vec<(String, String)>>()
+        )
+    })
+}
+
+#[pyfunction(name = "init_command")]
+#[pyo3(signature = (project_dir, verbosity="normal"))]
+fn py_init_command<'py>(py: Python<'py>, project_dir: String, verbosity: &str) -> PyResult<Bound<'py, PyAny>> {
+    let verbosity = parse_verbosity(verbosity)?;
+
+    spawn(py, |_| async move {
+        init_project(InitOptions {
+            project_dir,
+            verbosity,
         })
         .await?;
 
@@ -55,12 +240,137 @@ fn py_build_command(py: Python<'_>, project_dir: String, out_dir: Option<String>
     })
 }
 
+#[pyfunction(name = "transpile_command_sync")]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (project_dir, out_dir=None, stdout=false, ignore=None, verbosity="normal", metadata=None, bundle=false, include=None, emit_cargo_lock=false, explain=false))]
+fn py_transpile_command_sync(py: Python<'_>, project_dir: String, out_dir: Option<String>, stdout: bool, ignore: Option<Vec<String>>, verbosity: &str, metadata: Option<String>, bundle: bool, include: Option<Vec<String>>, emit_cargo_lock: bool, explain: bool) -> PyResult<()> {
+    let verbosity = parse_verbosity(verbosity)?;
+
+    block_on(py, async move {
+        transpile_project(TranspileOptions {
+            project_dir,
+            out_dir,
+            stdout,
+            ignore_patterns: ignore.unwrap_or_default(),
+            include_patterns: include.unwrap_or_default(),
+            metadata_path: metadata,
+            verbosity,
+            bundle,
+            emit_cargo_lock,
+            explain,
+            cancellation: None,
+        })
+        .await?;
+
+        Ok(())
+    })
+}
+
+#[pyfunction(name = "build_command_sync")]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (project_dir, out_dir=None, release=false, ignore=None, offline=false, locked=false, profile=None, jobs=None, verbosity="normal", force=false, metadata=None, rustflags=None, dry_run=false, assume_target=false, include=None, retries=0, retry_backoff_ms=1000, explain=false))]
+fn py_build_command_sync(py: Python<'_>, project_dir: String, out_dir: Option<String>, release: bool, ignore: Option<Vec<String>>, offline: bool, locked: bool, profile: Option<String>, jobs: Option<usize>, verbosity: &str, force: bool, metadata: Option<String>, rustflags: Option<Vec<String>>, dry_run: bool, assume_target: bool, include: Option<Vec<String>>, retries: usize, retry_backoff_ms: u64, explain: bool) -> PyResult<()> {
+    let verbosity = parse_verbosity(verbosity)?;
+
+    block_on(py, async move {
+        build_project(BuildOptions {
+            project_dir,
+            out_dir,
+            release,
+            profile,
+            offline,
+            locked,
+            jobs,
+            ignore_patterns: ignore.unwrap_or_default(),
+            include_patterns: include.unwrap_or_default(),
+            metadata_path: metadata,
+            verbosity,
+            force,
+            rustflags: rustflags.unwrap_or_default(),
+            dry_run,
+            assume_target,
+            retries,
+            retry_backoff_ms,
+            explain,
+            cancellation: None,
+        })
+        .await?;
+
+        Ok(())
+    })
+}
+
+#[pyfunction(name = "doctor_command_sync")]
+#[pyo3(signature = (project_dir, verbosity="normal"))]
+fn py_doctor_command_sync(py: Python<'_>, project_dir: String, verbosity: &str) -> PyResult<Vec<(String, String)>> {
+    let verbosity = parse_verbosity(verbosity)?;
+
+    block_on(py, async move {
+        let report = doctor_project(DoctorOptions {
+            project_dir,
+            verbosity,
+        })
+        .await?;
+
+        Ok(
+            report.failures
+                .into_iter()
+                .map(|failure| (failure.check.name().to_string(), failure.message))
+                .collect()
+        )
+    })
+}
+
+#[pyfunction(name = "init_command_sync")]
+#[pyo3(signature = (project_dir, verbosity="normal"))]
+fn py_init_command_sync(py: Python<'_>, project_dir: String, verbosity: &str) -> PyResult<()> {
+    let verbosity = parse_verbosity(verbosity)?;
+
+    block_on(py, async move {
+        init_project(InitOptions {
+            project_dir,
+            verbosity,
+        })
+        .await?;
+
+        Ok(())
+    })
+}
+
+#[pyfunction(name = "configure_runtime")]
+#[pyo3(signature = (worker_threads))]
+fn py_configure_runtime(worker_threads: usize) -> PyResult<()> {
+    init_runtime(worker_threads)
+}
+
 /// Python bindings for py2binmod
 #[pymodule]
 #[pyo3(name = "_py2binmod")]
 fn py_py2binmod_module(py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
+    if let Some(worker_threads) = env::var(WORKER_THREADS_ENV).ok().filter(|v| !v.is_empty()) {
+        init_runtime(
+            worker_threads
+                .parse()
+                .map_err(|_| PyValueError::new_err(format!(
+                    "{WORKER_THREADS_ENV} must be a number of threads, got '{worker_threads}'",
+                )))?,
+        )?;
+    }
+
     m.add_function(wrap_pyfunction!(py_transpile_command, py)?)?;
     m.add_function(wrap_pyfunction!(py_build_command, py)?)?;
+    m.add_function(wrap_pyfunction!(py_watch_command, py)?)?;
+    m.add_function(wrap_pyfunction!(py_doctor_command, py)?)?;
+    m.add_function(wrap_pyfunction!(py_init_command, py)?)?;
+    m.add_function(wrap_pyfunction!(py_transpile_command_sync, py)?)?;
+    m.add_function(wrap_pyfunction!(py_build_command_sync, py)?)?;
+    m.add_function(wrap_pyfunction!(py_doctor_command_sync, py)?)?;
+    m.add_function(wrap_pyfunction!(py_init_command_sync, py)?)?;
+    m.add_function(wrap_pyfunction!(py_configure_runtime, py)?)?;
+    m.add("Py2BinmodError", py.get_type::<Py2BinmodError>())?;
+    m.add("ParserError", py.get_type::<ParserError>())?;
+    m.add("CompilerError", py.get_type::<CompilerError>())?;
+    m.add("TemplateError", py.get_type::<TemplateError>())?;
     m.add("__version__", env!("CARGO_PKG_VERSION"))?;
     Ok(())
 }
@@ -1,4 +1,4 @@
-use std::result::Result;
+use std::{path::PathBuf, result::Result};
 use thiserror::Error;
 
 
@@ -6,7 +6,26 @@ use thiserror::Error;
 pub enum TemplateError {
     #[error("Template render failed: {0}")]
     RenderFailed(String),
+    #[error(
+        "Generated invalid code for {}{}: {message}",
+        .destination.display(),
+        .function.as_ref().map(|f| format!(" in '{}'", f)).unwrap_or_default(),
+    )]
+    InvalidCode {
+        destination: PathBuf,
+        function: Option<String>,
+        message: String,
+    },
+}
+
+impl TemplateError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            TemplateError::RenderFailed(_) => "template.render_failed",
+            TemplateError::InvalidCode { .. } => "template.invalid_code",
+        }
+    }
 }
 
 
-pub type TemplateResult<T> = Result<T, TemplateError>;
\ No newline at end of file
+pub type TemplateResult<T> = Result<T, TemplateError>;
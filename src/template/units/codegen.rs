@@ -1,30 +1,46 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 use syn::parse2;
 use prettyplease::unparse;
-use proc_macro2::TokenStream;
+use proc_macro2::{TokenStream, TokenTree, Delimiter};
 
-use crate::{codegen::traits::CodeGenerator, template::{traits::TemplateUnit, error::TemplateResult, types::RenderedFile}};
+use crate::{
+    codegen::traits::CodeGenerator,
+    template::{traits::TemplateUnit, error::{TemplateError, TemplateResult}, types::RenderedFile},
+    ui::Printer,
+};
 
 
+/// Rewrites generated code, e.g. to add attributes or wrap functions.
+pub type TokenTransform = Arc<dyn Fn(TokenStream) -> TokenStream + Send + Sync>;
+
 pub struct CodegenUnit<G>
 where
     G: CodeGenerator,
 {
     pub destination: PathBuf,
     pub generator: G,
+    /// Applied to the generated tokens before they are formatted with
+    /// `prettyplease`, so its output has to parse as a file too.
+    pub transform: Option<TokenTransform>,
 }
 
 impl<G> CodegenUnit<G>
 where
     G: CodeGenerator,
 {
-    pub fn format_token_stream(&self, tokens: TokenStream) -> String {
-        unparse(
-            &parse2(tokens)
-                .expect("Failed to parse TokenStream into syn::File")
+    pub fn format_token_stream(&self, tokens: TokenStream) -> TemplateResult<String> {
+        let file = parse2::<syn::File>(tokens.clone())
+            .map_err(|e| TemplateError::InvalidCode {
+                destination: self.destination.clone(),
+                function: invalid_function(tokens),
+                message: e.to_string(),
+            })?;
+
+        Ok(
+            unparse(&file)
+                .replace("\r\n", "\n") // Normalize line endings to Unix style
+                .replace("\t", "    ") // Replace tabs with spaces for consistency
         )
-        .replace("\r\n", "\n") // Normalize line endings to Unix style
-        .replace("\t", "    ") // Replace tabs with spaces for consistency
     }
 }
 
@@ -32,12 +48,136 @@ impl<G> TemplateUnit for CodegenUnit<G>
 where
     G: CodeGenerator,
 {
+    /// Code that can't be formatted is written as is, so it can still be
+    /// inspected, and the failure is reported as a warning.
     fn render(&self) -> TemplateResult<Vec<RenderedFile>> {
+        let tokens = match &self.transform {
+            Some(transform) => transform(self.generator.generate()),
+            None => self.generator.generate(),
+        };
+        let content = self.format_token_stream(tokens.clone())
+            .unwrap_or_else(|err| {
+                Printer::warning(&format!("{}, writing it unformatted", err));
+                tokens.to_string()
+            });
+
         Ok(vec![
             RenderedFile {
                 path: self.destination.clone(),
-                content: self.format_token_stream(self.generator.generate()),
+                content,
             }
         ])
     }
-}
\ No newline at end of file
+}
+
+/// Name of the first top-level function that fails to parse on its own.
+/// Generated tokens all share the call site span, so the parse error itself
+/// can't point at the offending item.
+fn invalid_function(tokens: TokenStream) -> Option<String> {
+    let tokens = tokens.into_iter().collect::<Vec<_>>();
+    let mut items = Vec::new();
+    let mut start = 0;
+
+    // Items end with a `;` or a brace block that isn't part of an expression.
+    for (i, token) in tokens.iter().enumerate() {
+        let ends_item = match token {
+            TokenTree::Punct(p) => p.as_char() == ';',
+            TokenTree::Group(g) => g.delimiter() == Delimiter::Brace && !matches!(
+                tokens.get(i + 1),
+                Some(TokenTree::Punct(p)) if matches!(p.as_char(), ';' | '.' | ',' | '?')
+            ),
+            _ => false,
+        };
+
+        if ends_item {
+            items.push(&tokens[start..=i]);
+            start = i + 1;
+        }
+    }
+
+    items
+        .into_iter()
+        .find(|item| parse2::<syn::File>(item.iter().cloned().collect()).is_err())
+        .and_then(|item| item
+            .windows(2)
+            .find_map(|pair| match pair {
+                [TokenTree::Ident(keyword), TokenTree::Ident(name)] if keyword == "fn" => Some(name.to_string()),
+                _ => None,
+            })
+        )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::quote;
+
+    struct Malformed;
+
+    impl CodeGenerator for Malformed {
+        fn generate(&self) -> TokenStream {
+            quote! {
+                use std::fmt;
+
+                pub fn valid() -> i64 { 1 }
+
+                pub fn broken() -> i64 { let = 1; }
+
+                pub fn also_valid() {}
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_tokens_name_the_function() {
+        let unit = CodegenUnit { destination: "src/lib.rs".into(), generator: Malformed, transform: None };
+        let Err(err) = unit.format_token_stream(Malformed.generate()) else {
+            panic!("malformed tokens were formatted");
+        };
+
+        assert!(matches!(
+            &err,
+            TemplateError::InvalidCode { destination, function: Some(function), .. }
+                if destination == &PathBuf::from("src/lib.rs") && function == "broken"
+        ));
+        assert!(err.to_string().starts_with("Generated invalid code for src/lib.rs in 'broken': "));
+    }
+
+    struct Valid;
+
+    impl CodeGenerator for Valid {
+        fn generate(&self) -> TokenStream {
+            quote! {
+                pub fn answer() -> i64 { 42 }
+            }
+        }
+    }
+
+    #[test]
+    fn transform_runs_before_formatting() {
+        let files = CodegenUnit {
+            destination: "src/lib.rs".into(),
+            generator: Valid,
+            transform: Some(Arc::new(|tokens| quote! {
+                #[tracing::instrument]
+                #tokens
+            })),
+        }
+        .render()
+        .unwrap();
+
+        assert_eq!(files[0].content, "#[tracing::instrument]\npub fn answer() -> i64 {\n    42\n}\n");
+    }
+
+    #[test]
+    fn malformed_tokens_are_rendered_unformatted() {
+        let files = CodegenUnit { destination: "src/lib.rs".into(), generator: Malformed, transform: None }
+            .render()
+            .unwrap();
+
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, PathBuf::from("src/lib.rs"));
+        assert_eq!(files[0].content, Malformed.generate().to_string());
+    }
+}
@@ -0,0 +1,123 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use crate::{
+    parser::site_packages::{locate_package, python_files},
+    template::{traits::TemplateUnit, error::{TemplateError, TemplateResult}, types::RenderedFile},
+};
+
+
+/// Directory of the generated crate the selected packages are copied into.
+pub const FROZEN_PACKAGES_DIR: &str = "site-packages";
+
+/// Copies the Python sources of the selected site-packages into the generated
+/// crate so only those get frozen into the module.
+pub struct FrozenPackagesUnit {
+    pub site_packages_dirs: Vec<PathBuf>,
+    pub packages: Vec<String>,
+}
+
+impl TemplateUnit for FrozenPackagesUnit {
+    fn render(&self) -> TemplateResult<Vec<RenderedFile>> {
+        let mut files = Vec::new();
+
+        for package in &self.packages {
+            let (site_packages_dir, location) = self.site_packages_dirs
+                .iter()
+                .find_map(|dir| locate_package(dir, package).map(|location| (dir, location)))
+                .ok_or_else(|| TemplateError::RenderFailed(
+                    format!("package '{}' is not installed in {}", package, self.site_packages_dirs
+                        .iter()
+                        .map(|dir| dir.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "))
+                ))?;
+
+            for file in python_files(&location) {
+                let relative = file
+                    .strip_prefix(site_packages_dir)
+                    .unwrap_or(&file);
+
+                files.push(RenderedFile {
+                    path: Path::new(FROZEN_PACKAGES_DIR).join(relative),
+                    content: fs::read_to_string(&file)
+                        .map_err(|e| TemplateError::RenderFailed(format!("{}: {}", file.display(), e)))?,
+                });
+            }
+        }
+
+        Ok(files)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn copies_selected_packages() {
+        let td = TempDir::new().unwrap();
+        let site_packages = td.path();
+
+        for file in ["requests/__init__.py", "requests/api.py", "idna.py", "unused/__init__.py"] {
+            let path = site_packages.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, file).unwrap();
+        }
+
+        let files = FrozenPackagesUnit {
+            site_packages_dirs: vec![site_packages.to_path_buf()],
+            packages: vec!["requests".into(), "idna".into()],
+        }
+        .render()
+        .unwrap();
+
+        let paths = files.iter().map(|f| f.path.clone()).collect::<Vec<_>>();
+
+        assert_eq!(paths, vec![
+            PathBuf::from("site-packages/requests/__init__.py"),
+            PathBuf::from("site-packages/requests/api.py"),
+            PathBuf::from("site-packages/idna.py"),
+        ]);
+        assert_eq!(files[1].content, "requests/api.py");
+    }
+
+    #[test]
+    fn missing_package_fails() {
+        let td = TempDir::new().unwrap();
+
+        assert!(FrozenPackagesUnit {
+            site_packages_dirs: vec![td.path().to_path_buf()],
+            packages: vec!["missing".into()],
+        }
+        .render()
+        .is_err());
+    }
+
+    #[test]
+    fn copies_packages_from_every_site_packages_dir() {
+        let td = TempDir::new().unwrap();
+        let purelib = td.path().join("lib/python3.11/site-packages");
+        let platlib = td.path().join("lib64/python3.11/site-packages");
+
+        for file in [purelib.join("requests/__init__.py"), platlib.join("yaml/__init__.py")] {
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(&file, "").unwrap();
+        }
+
+        let files = FrozenPackagesUnit {
+            site_packages_dirs: vec![purelib, platlib],
+            packages: vec!["requests".into(), "yaml".into()],
+        }
+        .render()
+        .unwrap();
+
+        let paths = files.iter().map(|f| f.path.clone()).collect::<Vec<_>>();
+
+        assert_eq!(paths, vec![
+            PathBuf::from("site-packages/requests/__init__.py"),
+            PathBuf::from("site-packages/yaml/__init__.py"),
+        ]);
+    }
+}
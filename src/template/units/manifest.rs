@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+use serde::Serialize;
+
+use crate::{
+    types::{ProjectContext, Parameter, ParameterType},
+    template::{traits::TemplateUnit, error::{TemplateError, TemplateResult}, types::RenderedFile},
+};
+
+
+#[derive(Serialize)]
+pub struct SignatureManifest<'a> {
+    pub name: &'a str,
+    pub version: &'a str,
+    pub functions: Vec<FunctionSignature<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct FunctionSignature<'a> {
+    pub name: &'a str,
+    pub module: String,
+    pub docstring: Option<&'a str>,
+    pub parameters: &'a [Parameter],
+    pub return_type: &'a ParameterType,
+}
+
+/// Emits a JSON manifest of the functions exported by the generated module.
+pub struct SignatureManifestUnit {
+    pub destination: PathBuf,
+    pub context: ProjectContext,
+}
+
+impl SignatureManifestUnit {
+    pub fn manifest(&self) -> SignatureManifest<'_> {
+        SignatureManifest {
+            name: &self.context.metadata.name,
+            version: &self.context.metadata.version,
+            functions: self.context
+                .modules
+                .iter()
+                .flat_map(|module| {
+                    let import_path = self.context.import_path(module);
+
+                    module.module_functions
+                        .iter()
+                        .map(move |f| FunctionSignature {
+                            name: &f.name,
+                            module: import_path.clone(),
+                            docstring: f.docstring.as_deref(),
+                            parameters: &f.parameters,
+                            return_type: &f.return_type,
+                        })
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TemplateUnit for SignatureManifestUnit {
+    fn render(&self) -> TemplateResult<Vec<RenderedFile>> {
+        Ok(vec![
+            RenderedFile {
+                path: self.destination.clone(),
+                content: serde_json::to_string_pretty(&self.manifest())
+                    .map_err(|e| TemplateError::RenderFailed(e.to_string()))?,
+            }
+        ])
+    }
+}
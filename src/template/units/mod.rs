@@ -1,2 +1,4 @@
 pub mod jinja;
-pub mod codegen;
\ No newline at end of file
+pub mod codegen;
+pub mod manifest;
+pub mod frozen;
\ No newline at end of file
@@ -1,16 +1,150 @@
-use std::{path::{Path, PathBuf}, ops::{Deref, DerefMut}, vec::IntoIter};
+use std::{collections::{BTreeMap, HashSet}, fmt, path::{Path, PathBuf}, ops::{Deref, DerefMut}, vec::IntoIter};
 use serde::{Deserialize, Serialize};
 
+use crate::error::{AppError, AppResult};
+
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ProjectContext {
     pub venv_dir: PathBuf,
     pub site_packages_dir: PathBuf,
+    /// Where compiled packages are installed, when distinct from `site_packages_dir`.
+    pub platlib_dir: Option<PathBuf>,
     pub project_dir: PathBuf,
     pub module_root: PathBuf,
     pub module_name: String,
     pub metadata: ProjectMetadata,
     pub modules: Vec<Module>,
+    /// Site-packages to freeze into the module, or `None` to freeze all of them.
+    pub frozen_packages: Option<Vec<String>>,
+    /// The Cargo workspace the generated crate is a member of, standalone when `None`.
+    #[serde(default)]
+    pub workspace: Option<Workspace>,
+    /// Why the layout was resolved as it was, when parsed with `explain`.
+    #[serde(default)]
+    pub layout_trace: Vec<String>,
+    /// Layout hints that couldn't be followed.
+    #[serde(default)]
+    pub layout_warnings: Vec<String>,
+    /// Annotations that were loosened to something the boundary can carry.
+    #[serde(default)]
+    pub type_warnings: Vec<String>,
+}
+
+/// A Cargo workspace the generated crate is emitted into.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Workspace {
+    pub root: PathBuf,
+    /// Keys of `[workspace.package]` the crate's manifest inherits.
+    pub inherited: Vec<String>,
+}
+
+impl ProjectContext {
+    /// Check that the fields generation relies on are set and that the
+    /// directories it reads from exist.
+    pub fn validate(&self) -> AppResult<()> {
+        let required = [
+            ("module name", &self.module_name),
+            ("project name", &self.metadata.name),
+            ("project version", &self.metadata.version),
+        ];
+
+        for (field, value) in required {
+            if value.trim().is_empty() {
+                return Err(AppError::GeneratorError(format!("The {field} is empty")));
+            }
+        }
+
+        let directories = [
+            ("module root", Some(&self.module_root)),
+            ("site-packages directory", Some(&self.site_packages_dir)),
+            ("platlib directory", self.platlib_dir.as_ref()),
+        ];
+
+        for (field, dir) in directories {
+            if let Some(dir) = dir
+                && !dir.is_dir()
+            {
+                return Err(AppError::GeneratorError(format!(
+                    "The {field} '{}' does not exist",
+                    dir.display(),
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The directory the module is imported from. For a package that's the
+    /// directory containing it, for a single-file module the module root
+    /// already is the directory containing the `.py` file.
+    pub fn import_root(&self) -> &Path {
+        let single_file = !self.module_root.join("__init__.py").is_file()
+            && self.module_root.join(format!("{}.py", self.module_name)).is_file();
+
+        if single_file {
+            &self.module_root
+        } else {
+            self.module_root.parent().unwrap_or(&self.module_root)
+        }
+    }
+
+    /// Order modules by import path and their functions by name, so the
+    /// generated code doesn't depend on the order files were discovered in.
+    pub fn sort_modules(&mut self) {
+        let mut modules = std::mem::take(&mut self.modules)
+            .into_iter()
+            .map(|module| (self.import_path(&module), module))
+            .collect::<Vec<_>>();
+        modules.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        self.modules = modules
+            .into_iter()
+            .map(|(_, mut module)| {
+                module.module_functions.sort_by(|a, b| a.name.cmp(&b.name));
+                module
+            })
+            .collect();
+    }
+
+    /// Every directory packages are installed in, purelib first.
+    pub fn site_packages_dirs(&self) -> Vec<PathBuf> {
+        std::iter::once(self.site_packages_dir.clone())
+            .chain(self.platlib_dir.clone())
+            .collect()
+    }
+
+    /// The features gating the exported function `function`.
+    pub fn features_of(&self, function: &str) -> Vec<&str> {
+        self.metadata.py2binmod
+            .iter()
+            .flat_map(|c| c.features.iter())
+            .filter(|(_, functions)| functions.iter().any(|f| f == function))
+            .map(|(feature, _)| feature.as_str())
+            .collect()
+    }
+
+    /// The dotted path `module` is imported by: its path relative to the
+    /// import root, so `src/pkg/api.py` is `pkg.api` and a single-file module
+    /// is its own name. The package name is replaced by `[tool.py2binmod]
+    /// import-prefix` when set.
+    pub fn import_path(&self, module: &Module) -> String {
+        let path = module
+            .import_path(self.import_root())
+            .unwrap_or_else(|| self.module_name.clone());
+        let prefix = self.metadata.py2binmod
+            .as_ref()
+            .and_then(|c| c.import_prefix.as_deref());
+
+        match prefix {
+            Some(prefix) if path == self.module_name => prefix.to_string(),
+            Some(prefix) => match path.strip_prefix(&format!("{}.", self.module_name)) {
+                Some(submodule) => format!("{}.{}", prefix, submodule),
+                None => path,
+            },
+            None => path,
+        }
+    }
 }
 
 
@@ -21,15 +155,72 @@ pub struct ProjectMetadata {
     pub requires_python: Option<String>,
     pub description: Option<String>,
     pub authors: Vec<String>,
+    /// An SPDX license expression.
     pub license: Option<String>,
+    /// The license file, for projects that don't declare an SPDX expression.
+    pub license_file: Option<PathBuf>,
+    #[serde(default)]
+    pub urls: ProjectUrls,
+    pub optional_dependencies: BTreeMap<String, Vec<String>>,
     pub py2binmod: Option<Py2BinmodConfig>,
+    /// Declared metadata that couldn't be carried over as is.
+    #[serde(default)]
+    pub warnings: Vec<String>,
 }
 
-#[derive(Clone, Deserialize, Serialize, Debug)]
+/// The `[project.urls]` cargo has manifest fields for.
+#[derive(Clone, Deserialize, Serialize, Debug, Default, PartialEq)]
+pub struct ProjectUrls {
+    pub homepage: Option<String>,
+    pub repository: Option<String>,
+    pub documentation: Option<String>,
+}
+
+#[derive(Clone, Deserialize, Serialize, Debug, Default)]
 pub struct Py2BinmodConfig {
     pub venv: Option<PathBuf>,
+    /// Python interpreter asked for its site-packages, in place of scanning `venv`.
+    pub interpreter: Option<PathBuf>,
     pub module_root: Option<PathBuf>,
     pub module: Option<String>,
+    pub rustpython_version: Option<String>,
+    pub mdk_version: Option<String>,
+    pub ignore: Vec<String>,
+    /// Globs limiting which module files are analyzed.
+    pub include: Vec<String>,
+    pub stack_size: Option<u64>,
+    pub recursion_limit: Option<usize>,
+    pub freeze: Option<Vec<String>>,
+    pub freeze_imported: bool,
+    /// Standard library modules registered in the interpreter, all of them
+    /// when unset. The modules they import are included too, along with those
+    /// the interpreter and the generated glue need, see
+    /// [`stdlib_closure`](crate::codegen::stdlib::stdlib_closure).
+    pub stdlib: Option<Vec<String>>,
+    pub strict: bool,
+    pub generate_tests: bool,
+    /// Environment variables set for cargo on top of the inherited ones.
+    pub build_env: BTreeMap<String, String>,
+    /// Cargo target dir, relative to the project. May be shared between projects.
+    pub target_dir: Option<PathBuf>,
+    /// Cargo features of the generated crate, each gating the listed exported
+    /// functions.
+    pub features: BTreeMap<String, Vec<String>>,
+    /// Extra rustc flags, e.g. `["-C", "opt-level=z"]`.
+    pub rustflags: Vec<String>,
+    /// The dotted name the package is imported by in generated code, for
+    /// packages importable under another name than their directory's.
+    pub import_prefix: Option<String>,
+    /// Root of the Cargo workspace the generated crate joins, relative to the project.
+    pub workspace_root: Option<PathBuf>,
+    /// Resolve the dependencies into a tracked `Cargo.lock` when transpiling.
+    pub emit_cargo_lock: bool,
+    /// The `[lib] crate-type` of the generated crate, `["cdylib"]` when unset.
+    pub crate_types: Option<Vec<String>>,
+    /// Also look for a venv among the `.tox/<env>` environments.
+    pub discover_tox: bool,
+    /// Fall back to the pyenv interpreter named by `.python-version`.
+    pub use_python_version: bool,
 }
 
 #[derive(Clone, Deserialize, Serialize, Debug)]
@@ -38,6 +229,9 @@ pub struct Module {
     pub file_path: PathBuf,
     pub module_functions: ModuleFunctions,
     pub host_functions: Option<HostFunctions>,
+    /// `NamedTuple` and `TypedDict` classes defined in the module.
+    #[serde(default)]
+    pub structs: Vec<StructDef>,
 }
 
 impl Module {
@@ -45,7 +239,7 @@ impl Module {
         let relative_path = self
             .file_path
             .strip_prefix(module_root)
-            .unwrap();
+            .ok()?;
 
         let mut components = relative_path
             .components()
@@ -71,6 +265,34 @@ impl Module {
 }
 
 
+/// A `typing.NamedTuple` or `typing.TypedDict`, generated as a serde struct.
+#[derive(Clone, Deserialize, Serialize, Debug, PartialEq)]
+pub struct StructDef {
+    pub name: String,
+    #[serde(default)]
+    pub kind: StructKind,
+    pub fields: Vec<StructField>,
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize, Debug, Default, PartialEq, Eq)]
+pub enum StructKind {
+    /// Instantiated from its fields when passed to Python.
+    #[default]
+    NamedTuple,
+    /// A plain dict on the Python side.
+    TypedDict,
+}
+
+#[derive(Clone, Deserialize, Serialize, Debug, PartialEq)]
+pub struct StructField {
+    pub name: String,
+    pub type_hint: ParameterType,
+    /// Whether the field may be left out, because it has a default on the
+    /// Python side or isn't required in a `TypedDict`.
+    pub has_default: bool,
+}
+
+
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct ModuleFunction {
     pub name: String,
@@ -79,6 +301,19 @@ pub struct ModuleFunction {
     pub return_type: ParameterType,
 }
 
+/// Renders as a Python signature, e.g. `greet(name: str) -> str`.
+impl fmt::Display for ModuleFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parameters = self.parameters
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        write!(f, "{}({}) -> {}", self.name, parameters, self.return_type)
+    }
+}
+
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct ModuleFunctions(pub Vec<ModuleFunction>);
 
@@ -213,6 +448,33 @@ impl Default for HostFunctions {
 pub struct Parameter {
     pub name: String,
     pub type_hint: ParameterType,
+    #[serde(default)]
+    pub kind: ParameterKind,
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefix = match self.kind {
+            ParameterKind::VarPositional => "*",
+            ParameterKind::VarKeyword => "**",
+            _ => "",
+        };
+
+        write!(f, "{}{}: {}", prefix, self.name, self.type_hint)
+    }
+}
+
+/// How a parameter can be passed, following Python's `/` and `*` markers.
+#[derive(Clone, Copy, Deserialize, Serialize, Debug, Default, PartialEq, Eq)]
+pub enum ParameterKind {
+    PositionalOnly,
+    #[default]
+    PositionalOrKeyword,
+    KeywordOnly,
+    /// `*args`, the type hint is the type of each item.
+    VarPositional,
+    /// `**kwargs`, the type hint is the type of each value.
+    VarKeyword,
 }
 
 #[derive(Clone, Deserialize, Serialize, Debug, PartialEq)]
@@ -221,7 +483,14 @@ pub enum ParameterType {
     Integer,
     Float,
     Boolean,
+    Uuid,
+    Path,
+    /// Crosses the boundary as `{ "re": f64, "im": f64 }`.
+    Complex,
+    /// A `NamedTuple` or `TypedDict` defined in the project, by name.
+    Struct(String),
     List(Box<ParameterType>),
+    Set(Box<ParameterType>),
     Tuple(Vec<Box<ParameterType>>),
     Map {
         key_type: Box<ParameterType>,
@@ -230,4 +499,86 @@ pub enum ParameterType {
     Optional(Box<ParameterType>),
     None,
     Any,
+    /// An annotation that didn't map to a supported type, generated like
+    /// `Any` but rejected in strict mode. Holds the annotation's name.
+    Unrecognized(String),
+}
+
+impl ParameterType {
+    /// Turn unrecognized annotations naming one of `structs` into references to it.
+    pub fn resolve_structs(&mut self, structs: &HashSet<String>) {
+        match self {
+            ParameterType::Unrecognized(name) if structs.contains(name.as_str()) => {
+                *self = ParameterType::Struct(name.clone());
+            },
+            ParameterType::List(inner)
+            | ParameterType::Set(inner)
+            | ParameterType::Optional(inner) => inner.resolve_structs(structs),
+            ParameterType::Tuple(items) => items.iter_mut().for_each(|t| t.resolve_structs(structs)),
+            ParameterType::Map { key_type, value_type } => {
+                key_type.resolve_structs(structs);
+                value_type.resolve_structs(structs);
+            },
+            _ => {},
+        }
+    }
+
+    /// The key types of every dict in this type, including nested ones.
+    pub fn map_keys_mut(&mut self) -> Vec<&mut ParameterType> {
+        match self {
+            ParameterType::List(inner)
+            | ParameterType::Set(inner)
+            | ParameterType::Optional(inner) => inner.map_keys_mut(),
+            ParameterType::Tuple(items) => items.iter_mut().flat_map(|t| t.map_keys_mut()).collect(),
+            ParameterType::Map { key_type, value_type } => {
+                let mut keys = vec![key_type.as_mut()];
+                keys.extend(value_type.map_keys_mut());
+                keys
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    /// The first unrecognized annotation in this type, including nested ones.
+    pub fn unrecognized(&self) -> Option<&str> {
+        match self {
+            ParameterType::Unrecognized(name) => Some(name),
+            ParameterType::List(inner)
+            | ParameterType::Set(inner)
+            | ParameterType::Optional(inner) => inner.unrecognized(),
+            ParameterType::Tuple(items) => items.iter().find_map(|t| t.unrecognized()),
+            ParameterType::Map { key_type, value_type } => key_type
+                .unrecognized()
+                .or_else(|| value_type.unrecognized()),
+            _ => None,
+        }
+    }
+}
+
+/// Renders as the equivalent Python annotation.
+impl fmt::Display for ParameterType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParameterType::String => write!(f, "str"),
+            ParameterType::Integer => write!(f, "int"),
+            ParameterType::Float => write!(f, "float"),
+            ParameterType::Boolean => write!(f, "bool"),
+            ParameterType::Uuid => write!(f, "UUID"),
+            ParameterType::Path => write!(f, "Path"),
+            ParameterType::Complex => write!(f, "complex"),
+            ParameterType::Struct(name) => write!(f, "{}", name),
+            ParameterType::List(item) => write!(f, "list[{}]", item),
+            ParameterType::Set(item) => write!(f, "set[{}]", item),
+            ParameterType::Tuple(items) => write!(
+                f,
+                "tuple[{}]",
+                items.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(", "),
+            ),
+            ParameterType::Map { key_type, value_type } => write!(f, "dict[{}, {}]", key_type, value_type),
+            ParameterType::Optional(inner) => write!(f, "Optional[{}]", inner),
+            ParameterType::None => write!(f, "None"),
+            ParameterType::Any => write!(f, "Any"),
+            ParameterType::Unrecognized(name) => write!(f, "{}", name),
+        }
+    }
 }
@@ -1,9 +1,18 @@
-use std::{time::Duration, future::Future, path::Path, fmt::Display, sync::{Arc, Mutex}, io};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+    future::Future,
+    path::Path,
+    fmt::Display,
+    str::FromStr,
+    sync::{Arc, Mutex, atomic::{AtomicU8, Ordering}},
+    io,
+};
 use async_trait::async_trait;
-use console::{style, StyledObject, Term};
+use console::{style, truncate_str, StyledObject, Term};
 use indicatif::{ProgressBar, ProgressStyle};
 use syntect::parsing::{SyntaxSet, SyntaxReference};
-use syntect::highlighting::{Theme as SyntectTheme, ThemeSet as SyntectThemeSet, Style as SyntectStyle};
+use syntect::highlighting::{Theme as SyntectTheme, ThemeSet as SyntectThemeSet};
 use syntect::easy::HighlightLines;
 use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
 use once_cell::sync::Lazy;
@@ -12,11 +21,101 @@ use crate::compiler::traits::OutputSink;
 
 static SYNTAX_SET: Lazy<SyntaxSet> = Lazy::new(|| SyntaxSet::load_defaults_newlines());
 static THEME_SET: Lazy<SyntectThemeSet> = Lazy::new(|| SyntectThemeSet::load_defaults());
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+
+/// How much output the UI produces. The `Printer` is static, so this is a
+/// process-wide setting: every command sets it from its options on entry, and
+/// commands run concurrently in one process print at the verbosity of the
+/// last one started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Verbosity {
+    /// Only warnings, errors and results.
+    Quiet,
+    #[default]
+    Normal,
+    /// Also stream the full compiler output and warn about unused
+    /// site-packages.
+    Verbose,
+}
+
+impl Verbosity {
+    pub fn current() -> Self {
+        match VERBOSITY.load(Ordering::Relaxed) {
+            0 => Verbosity::Quiet,
+            2 => Verbosity::Verbose,
+            _ => Verbosity::Normal,
+        }
+    }
+
+    pub fn set(verbosity: Self) {
+        VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+    }
+
+    /// Whether a message of the given kind is printed at this verbosity.
+    pub fn shows(self, kind: MessageKind) -> bool {
+        match kind {
+            MessageKind::Section | MessageKind::Info => self != Verbosity::Quiet,
+            MessageKind::Success | MessageKind::Warning | MessageKind::Error => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    Section,
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl FromStr for Verbosity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "quiet" => Ok(Verbosity::Quiet),
+            "normal" => Ok(Verbosity::Normal),
+            "verbose" => Ok(Verbosity::Verbose),
+            other => Err(format!("Unknown verbosity '{}', expected quiet, normal or verbose", other)),
+        }
+    }
+}
 
 
+/// Names the syntect theme generated code is highlighted with.
+pub const THEME_ENV: &str = "PY2BINMOD_THEME";
+/// Used unless the terminal is known to be dark or a theme is requested.
+pub const DEFAULT_THEME: &str = "InspiredGitHub";
+pub const DARK_THEME: &str = "base16-ocean.dark";
+
 pub struct Syntax;
 
 impl Syntax {
+    /// The requested theme if it exists, otherwise a theme matching the
+    /// terminal background, which is read from `COLORFGBG` (`"15;0"` is light
+    /// text on black). A requested theme that doesn't exist falls back to the
+    /// default.
+    pub fn theme_name(requested: Option<&str>, colorfgbg: Option<&str>) -> &'static str {
+        if let Some(requested) = requested {
+            return THEME_SET
+                .themes
+                .get_key_value(requested)
+                .map(|(name, _)| name.as_str())
+                .unwrap_or(DEFAULT_THEME);
+        }
+
+        // Colors 0-6 and 8 are the dark ones of the 16 color palette.
+        let dark = colorfgbg
+            .and_then(|value| value.rsplit(';').next())
+            .and_then(|background| background.parse::<u8>().ok())
+            .is_some_and(|background| background < 7 || background == 8);
+
+        if dark { DARK_THEME } else { DEFAULT_THEME }
+    }
+
+
     pub fn get_theme(name: &str) -> SyntectTheme {
         THEME_SET
             .themes
@@ -33,24 +132,59 @@ impl Syntax {
     }
 
     pub fn highlight(code: &str, syntax: &SyntaxReference, theme: &SyntectTheme) -> String {
+        let mut highlighted = Vec::new();
+
+        Self::highlight_to(code, syntax, theme, &mut highlighted)
+            .expect("writing to a Vec can't fail");
+
+        String::from_utf8_lossy(&highlighted).into_owned()
+    }
+
+    /// Write the highlighted `code` to `writer` one line at a time, returning
+    /// the number of lines written. Lines syntect fails on are written plain.
+    pub fn highlight_to<W: io::Write>(
+        code: &str,
+        syntax: &SyntaxReference,
+        theme: &SyntectTheme,
+        writer: &mut W,
+    ) -> io::Result<usize> {
         let mut h = HighlightLines::new(syntax, theme);
-        let mut highlighted = String::new();
+        let mut count = 0;
 
         for (i, line) in LinesWithEndings::from(code).enumerate() {
-            let ranges: Vec<(SyntectStyle, &str)> = h.highlight_line(line, &SYNTAX_SET).unwrap();
-            let escaped = as_24_bit_terminal_escaped(&ranges[..], false);
-            highlighted.push_str(&format!("{:>4} | {}", i + 1, escaped));
+            match h.highlight_line(line, &SYNTAX_SET) {
+                Ok(ranges) => {
+                    write!(writer, "{:>4} | {}", i + 1, as_24_bit_terminal_escaped(&ranges[..], false))?;
+                },
+                Err(_) => write!(writer, "{:>4} | {}", i + 1, line)?,
+            }
+            count += 1;
         }
 
-        highlighted
+        Ok(count)
     }
 
     pub fn code(code: &str, path: &Path) -> String {
-        Self::highlight(
+        let mut highlighted = Vec::new();
+
+        Self::write_code(code, path, &mut highlighted)
+            .expect("writing to a Vec can't fail");
+
+        String::from_utf8_lossy(&highlighted).into_owned()
+    }
+
+    /// Highlight `code` by the syntax of `path` with the configured theme,
+    /// streaming it to `writer`.
+    pub fn write_code<W: io::Write>(code: &str, path: &Path, writer: &mut W) -> io::Result<usize> {
+        Self::highlight_to(
             code,
             Self::get_syntax(path)
                 .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text()),
-            &Self::get_theme("InspiredGitHub"),
+            &Self::get_theme(Self::theme_name(
+                std::env::var(THEME_ENV).ok().filter(|theme| !theme.is_empty()).as_deref(),
+                std::env::var("COLORFGBG").ok().as_deref(),
+            )),
+            writer,
         )
     }
 }
@@ -87,12 +221,18 @@ impl Style {
 pub struct Printer;
 
 impl Printer {
+    fn print(kind: MessageKind, line: String) {
+        if Verbosity::current().shows(kind) {
+            println!("{}", line);
+        }
+    }
+
     pub fn render_section(title: &str) -> String {
         format!("{} {}", Style::header("▶"), Style::header(title))
     }
 
     pub fn section(title: &str) {
-        println!("{}", Self::render_section(title));
+        Self::print(MessageKind::Section, Self::render_section(title));
     }
 
     pub fn render_subsection(title: &str) -> String {
@@ -100,7 +240,7 @@ impl Printer {
     }
 
     pub fn subsection(title: &str) {
-        println!("{}", Self::render_subsection(title));
+        Self::print(MessageKind::Section, Self::render_subsection(title));
     }
 
     pub fn render_info(message: &str) -> String {
@@ -108,7 +248,7 @@ impl Printer {
     }
 
     pub fn info(message: &str) {
-        println!("{}", Self::render_info(message));
+        Self::print(MessageKind::Info, Self::render_info(message));
     }
 
     pub fn render_success(message: &str) -> String {
@@ -116,7 +256,7 @@ impl Printer {
     }
 
     pub fn success(message: &str) {
-        println!("{}", Self::render_success(message));
+        Self::print(MessageKind::Success, Self::render_success(message));
     }
 
     pub fn render_warning(message: &str) -> String {
@@ -124,7 +264,7 @@ impl Printer {
     }
 
     pub fn warning(message: &str) {
-        println!("{}", Self::render_warning(message));
+        Self::print(MessageKind::Warning, Self::render_warning(message));
     }
 
     pub fn render_error(message: &str) -> String {
@@ -132,7 +272,7 @@ impl Printer {
     }
 
     pub fn error(message: &str) {
-        println!("{}", Self::render_error(message));
+        Self::print(MessageKind::Error, Self::render_error(message));
     }
 }
 
@@ -216,12 +356,66 @@ impl Progress {
     pub fn finish(&self, message: impl Display) {
         self.bar.finish_with_message(format!("{}", message));
     }
+
+    pub fn clear(&self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+/// Minimum time between two redraws of a [`LogPanel`]. Lines arriving in
+/// between are drawn together by the next one.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Decides when a panel redraws, so that a flood of output redraws it at
+/// most once per interval instead of once per line.
+struct RedrawThrottle {
+    interval: Duration,
+    last: Option<Instant>,
+    /// Lines arrived since the last redraw.
+    pending: bool,
+}
+
+impl RedrawThrottle {
+    fn new(interval: Duration) -> Self {
+        Self { interval, last: None, pending: false }
+    }
+
+    fn due(&self, now: Instant) -> bool {
+        self.last.is_none_or(|last| now.duration_since(last) >= self.interval)
+    }
+
+    /// Whether a line arriving at `now` is drawn right away, otherwise it is
+    /// left for the next redraw.
+    fn line(&mut self, now: Instant) -> bool {
+        if !self.due(now) {
+            self.pending = true;
+            return false;
+        }
+
+        self.last = Some(now);
+        self.pending = false;
+        true
+    }
+
+    /// Whether lines left pending are due to be drawn at `now`.
+    fn flush(&mut self, now: Instant) -> bool {
+        if !self.pending || !self.due(now) {
+            return false;
+        }
+
+        self.last = Some(now);
+        self.pending = false;
+        true
+    }
 }
 
 pub struct LogPanel {
     title: String,
     height: usize,
-    buffer: Mutex<Vec<LogLine>>,
+    buffer: Mutex<VecDeque<LogLine>>,
+    /// Every line appended, the buffer only keeps the visible ones.
+    log: Mutex<Vec<LogLine>>,
+    throttle: Mutex<RedrawThrottle>,
     term: Term,
 }
 
@@ -243,7 +437,9 @@ impl LogPanel {
         let this = Self {
             title: format!("{}", title),
             height,
-            buffer: Mutex::new(Vec::new()),
+            buffer: Mutex::new(VecDeque::new()),
+            log: Mutex::new(Vec::new()),
+            throttle: Mutex::new(RedrawThrottle::new(REDRAW_INTERVAL)),
             term,
         };
 
@@ -263,14 +459,37 @@ impl LogPanel {
     }
 
     fn push(&self, kind: LogKind, text: impl Into<String>) -> io::Result<()> {
+        let line = LogLine { kind, text: text.into() };
+        self.log.lock().unwrap().push(line.clone());
+
         let mut buffer = self.buffer.lock().unwrap();
-        buffer.push(LogLine { kind, text: text.into() });
+        buffer.push_back(line);
 
         if buffer.len() > self.height {
-            buffer.remove(0);
+            buffer.pop_front();
         }
 
-        self.redraw(&buffer)
+        if self.throttle.lock().unwrap().line(Instant::now()) {
+            self.redraw(buffer.make_contiguous())?;
+        }
+
+        Ok(())
+    }
+
+    /// Draw the lines held back since the last redraw, once it is due.
+    pub fn flush(&self) -> io::Result<()> {
+        let mut buffer = self.buffer.lock().unwrap();
+
+        if self.throttle.lock().unwrap().flush(Instant::now()) {
+            self.redraw(buffer.make_contiguous())?;
+        }
+
+        Ok(())
+    }
+
+    /// Every line appended so far.
+    pub fn log(&self) -> Vec<LogLine> {
+        self.log.lock().unwrap().clone()
     }
 
     pub fn append_stdout(&self, text: impl Into<String>) -> io::Result<()> {
@@ -281,18 +500,38 @@ impl LogPanel {
         self.push(LogKind::Stderr, text)
     }
 
-    fn redraw(&self, buffer: &Vec<LogLine>) -> io::Result<()> {
+    /// Usable width of a panel line. One column is kept free so that a line
+    /// filling the terminal never wraps and shifts the panel's cursor math.
+    fn width(&self) -> usize {
+        self.term
+            .size_checked()
+            .map(|(_, columns)| columns as usize)
+            .unwrap_or(80)
+            .saturating_sub(1)
+            .max(1)
+    }
+
+    /// Truncate a line to `width` visible columns, ignoring ANSI escapes.
+    pub fn fit_line(text: &str, width: usize) -> String {
+        truncate_str(text, width, "…").into_owned()
+    }
+
+    fn redraw(&self, buffer: &[LogLine]) -> io::Result<()> {
+        let width = self.width();
+
         self.term.move_cursor_up(self.height)?;
 
         for line in buffer {
             self.term.clear_line()?;
 
+            let text = Self::fit_line(&line.text, width);
+
             match line.kind {
                 LogKind::Stdout => {
-                    println!("{}", style(&line.text).color256(245));
+                    println!("{}", style(text).color256(245));
                 }
                 LogKind::Stderr => {
-                    println!("{}", style(&line.text).color256(245));
+                    println!("{}", style(text).color256(245));
                 }
             }
         }
@@ -321,10 +560,10 @@ impl LogPanel {
         let buffer = self.buffer.lock().unwrap();
         self.redraw(
             &buffer
-                .clone()
-                .into_iter()
+                .iter()
                 .filter(|line| matches!(line.kind, LogKind::Stderr))
-                .collect()
+                .cloned()
+                .collect::<Vec<_>>()
         )?;
 
         Ok(())
@@ -345,8 +584,21 @@ impl LogPanel {
         FA: Display,
     {
         let panel = Arc::new(LogPanel::new(title, height));
-        
-        match func(panel.clone()).await {
+        let work = func(panel.clone());
+        tokio::pin!(work);
+
+        // Lines held back by the throttle are drawn even when no more follow.
+        let mut ticker = tokio::time::interval(REDRAW_INTERVAL);
+        let result = loop {
+            tokio::select! {
+                result = &mut work => break result,
+                _ = ticker.tick() => {
+                    let _ = panel.flush();
+                }
+            }
+        };
+
+        match result {
             Ok(result) => {
                 panel.finish_success(
                     finish
@@ -380,6 +632,20 @@ impl OutputSink for LogPanel {
     }
 }
 
+/// Prints every line of output as it arrives, for verbose builds.
+pub struct StreamOutputSink;
+
+#[async_trait]
+impl OutputSink for StreamOutputSink {
+    async fn stdout(&self, line: &str) {
+        println!("{}", style(line).color256(245));
+    }
+
+    async fn stderr(&self, line: &str) {
+        eprintln!("{}", style(line).color256(245));
+    }
+}
+
 #[async_trait]
 impl OutputSink for Arc<LogPanel> {
     async fn stdout(&self, line: &str) {
@@ -389,4 +655,106 @@ impl OutputSink for Arc<LogPanel> {
     async fn stderr(&self, line: &str) {
         (**self).stderr(line).await
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use console::measure_text_width;
+
+    #[test]
+    fn theme_follows_request_then_background() {
+        assert_eq!(Syntax::theme_name(Some("Solarized (dark)"), Some("0;15")), "Solarized (dark)");
+        assert_eq!(Syntax::theme_name(Some("no-such-theme"), Some("15;0")), DEFAULT_THEME);
+        assert_eq!(Syntax::theme_name(None, Some("15;0")), DARK_THEME);
+        assert_eq!(Syntax::theme_name(None, Some("15;default;8")), DARK_THEME);
+        assert_eq!(Syntax::theme_name(None, Some("0;15")), DEFAULT_THEME);
+        assert_eq!(Syntax::theme_name(None, None), DEFAULT_THEME);
+    }
+
+    #[test]
+    fn highlighting_large_files_streams_every_line() {
+        let code = (0..5_000)
+            .map(|i| format!("pub fn f{i}(x: i64) -> i64 {{ x + {i} }} // \"{}\"\n", "é".repeat(i % 7)))
+            .collect::<String>();
+        let mut out = Vec::new();
+
+        let count = Syntax::write_code(&code, Path::new("src/lib.rs"), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert_eq!(count, 5_000);
+        assert_eq!(out.lines().count(), 5_000);
+        assert!(out.lines().last().unwrap().starts_with("5000 | "));
+    }
+
+    #[test]
+    fn fit_line_truncates_long_lines() {
+        let line = "warning: unused variable `x` ".repeat(10);
+        let fitted = LogPanel::fit_line(&line, 40);
+
+        assert_eq!(measure_text_width(&fitted), 40);
+        assert!(fitted.ends_with('…'));
+    }
+
+    #[test]
+    fn fit_line_keeps_short_lines() {
+        assert_eq!(LogPanel::fit_line("Compiling foo v0.1.0", 40), "Compiling foo v0.1.0");
+    }
+
+    #[test]
+    fn fit_line_ignores_ansi_escapes() {
+        let line = format!("{}", style("error[E0308]: mismatched types ".repeat(5)).force_styling(true).red());
+        let fitted = LogPanel::fit_line(&line, 30);
+
+        assert!(measure_text_width(&fitted) <= 30);
+        assert!(fitted.contains("\u{1b}["));
+    }
+
+    #[test]
+    fn floods_of_lines_redraw_at_most_once_per_interval() {
+        let mut throttle = RedrawThrottle::new(Duration::from_millis(50));
+        let start = Instant::now();
+        let mut redraws = 0;
+
+        // A second's worth of output, a line every 10µs.
+        for i in 0..100_000 {
+            if throttle.line(start + Duration::from_micros(i * 10)) {
+                redraws += 1;
+            }
+        }
+
+        assert!(redraws <= 21, "{redraws} redraws");
+        assert!(throttle.pending);
+    }
+
+    #[test]
+    fn held_back_lines_are_flushed_once() {
+        let mut throttle = RedrawThrottle::new(Duration::from_millis(50));
+        let start = Instant::now();
+
+        assert!(throttle.line(start));
+        assert!(!throttle.line(start + Duration::from_millis(10)));
+        assert!(!throttle.flush(start + Duration::from_millis(20)));
+        assert!(throttle.flush(start + Duration::from_millis(60)));
+        assert!(!throttle.flush(start + Duration::from_millis(120)));
+        assert!(throttle.line(start + Duration::from_millis(130)));
+    }
+
+    #[test]
+    fn quiet_hides_info_but_not_errors() {
+        assert!(!Verbosity::Quiet.shows(MessageKind::Info));
+        assert!(!Verbosity::Quiet.shows(MessageKind::Section));
+        assert!(Verbosity::Quiet.shows(MessageKind::Warning));
+        assert!(Verbosity::Quiet.shows(MessageKind::Error));
+        assert!(Verbosity::Normal.shows(MessageKind::Info));
+        assert!(Verbosity::Verbose.shows(MessageKind::Info));
+    }
+
+    #[test]
+    fn parses_verbosity() {
+        assert_eq!("quiet".parse::<Verbosity>(), Ok(Verbosity::Quiet));
+        assert_eq!("verbose".parse::<Verbosity>(), Ok(Verbosity::Verbose));
+        assert!("loud".parse::<Verbosity>().is_err());
+    }
+}
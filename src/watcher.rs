@@ -0,0 +1,254 @@
+use std::{future::Future, path::{Path, PathBuf}, time::Duration};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::{sync::mpsc::{UnboundedReceiver, unbounded_channel}, time::timeout};
+
+use crate::{
+    parser::file_walker::traits::FileIgnoreStrategy,
+    ui::Printer,
+};
+
+
+/// Watches a directory for changes to Python source files.
+pub struct ProjectWatcher {
+    // Dropping the watcher stops the notifications.
+    _watcher: RecommendedWatcher,
+    changes: UnboundedReceiver<PathBuf>,
+}
+
+impl ProjectWatcher {
+    pub fn new(
+        root: &Path,
+        ignore_strategy: Box<dyn FileIgnoreStrategy + Send + Sync>,
+    ) -> notify::Result<Self> {
+        let (tx, changes) = unbounded_channel();
+        let watch_root = root.to_path_buf();
+
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else { return };
+
+            for path in event.paths {
+                if is_relevant(&path, &watch_root, ignore_strategy.as_ref()) {
+                    let _ = tx.send(path);
+                }
+            }
+        })?;
+
+        watcher.watch(root, RecursiveMode::Recursive)?;
+
+        Ok(Self { _watcher: watcher, changes })
+    }
+
+    /// Rebuild on every debounced batch of changes, see [`watch_loop`].
+    pub async fn watch<F, Fut, E>(self, quiet: Duration, rebuild: F)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+        E: std::fmt::Display,
+    {
+        let Self { _watcher, changes } = self;
+
+        watch_loop(changes, quiet, rebuild).await
+    }
+}
+
+/// Whether a changed path is a Python source file outside of any ignored directory.
+pub fn is_relevant(path: &Path, root: &Path, ignore_strategy: &dyn FileIgnoreStrategy) -> bool {
+    path.extension().is_some_and(|ext| ext == "py")
+        && !path
+            .ancestors()
+            .take_while(|p| p.starts_with(root) && *p != root)
+            .any(|p| ignore_strategy.should_ignore(p))
+}
+
+/// Wait for the next change, then keep collecting changes until none arrive for
+/// `quiet`. Returns `None` once the channel is closed.
+pub async fn debounce(changes: &mut UnboundedReceiver<PathBuf>, quiet: Duration) -> Option<Vec<PathBuf>> {
+    let mut paths = vec![changes.recv().await?];
+
+    while let Ok(Some(path)) = timeout(quiet, changes.recv()).await {
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+
+    Some(paths)
+}
+
+/// Run `rebuild` once and again after every debounced batch of changes. A batch
+/// arriving while a rebuild is still running cancels it before starting over,
+/// changes still settling when it finishes start the next one right away.
+/// Returns once the change channel is closed.
+pub async fn watch_loop<F, Fut, E>(
+    mut changes: UnboundedReceiver<PathBuf>,
+    quiet: Duration,
+    mut rebuild: F,
+)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), E>>,
+    E: std::fmt::Display,
+{
+    loop {
+        let build = rebuild();
+        tokio::pin!(build);
+
+        // Changes are buffered outside of the select, so those received while
+        // the build runs survive it finishing.
+        let mut pending = Vec::new();
+
+        loop {
+            tokio::select! {
+                result = &mut build => {
+                    match result {
+                        Ok(()) if pending.is_empty() => Printer::success("waiting for changes"),
+                        Ok(()) => {},
+                        Err(e) => Printer::error(&format!("{}", e)),
+                    }
+
+                    break;
+                },
+                path = changes.recv() => match path {
+                    Some(path) if !pending.contains(&path) => pending.push(path),
+                    Some(_) => {},
+                    None => return,
+                },
+                _ = tokio::time::sleep(quiet), if !pending.is_empty() => {
+                    Printer::warning("changes detected, cancelling the current build");
+                    break;
+                },
+            }
+        }
+
+        if pending.is_empty() {
+            match debounce(&mut changes, quiet).await {
+                Some(paths) => pending = paths,
+                None => break,
+            }
+        }
+
+        Printer::info(&format!("{} file(s) changed, rebuilding", pending.len()));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+    use crate::parser::file_walker::default::DefaultFileIgnoreStrategy;
+
+    #[test]
+    fn only_python_files_outside_ignored_dirs_are_relevant() {
+        let root = Path::new("/project/my_package");
+        let strategy = DefaultFileIgnoreStrategy::new();
+
+        assert!(is_relevant(&root.join("api.py"), root, &strategy));
+        assert!(is_relevant(&root.join("nested/api.py"), root, &strategy));
+        assert!(!is_relevant(&root.join("README.md"), root, &strategy));
+        assert!(!is_relevant(&root.join("__pycache__/api.py"), root, &strategy));
+        assert!(!is_relevant(&root.join(".venv/lib/site.py"), root, &strategy));
+    }
+
+    #[tokio::test]
+    async fn debounce_collects_a_burst_of_changes() {
+        let (tx, mut rx) = unbounded_channel();
+
+        tx.send(PathBuf::from("a.py")).unwrap();
+        tx.send(PathBuf::from("b.py")).unwrap();
+        tx.send(PathBuf::from("a.py")).unwrap();
+        drop(tx);
+
+        let paths = debounce(&mut rx, Duration::from_millis(10)).await.unwrap();
+
+        assert_eq!(paths, vec![PathBuf::from("a.py"), PathBuf::from("b.py")]);
+        assert!(debounce(&mut rx, Duration::from_millis(10)).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn change_triggers_rebuild() {
+        let (tx, rx) = unbounded_channel();
+        let builds = Arc::new(AtomicUsize::new(0));
+
+        let loop_handle = tokio::spawn({
+            let builds = builds.clone();
+            watch_loop(rx, Duration::from_millis(10), move || {
+                let builds = builds.clone();
+                async move {
+                    builds.fetch_add(1, Ordering::SeqCst);
+                    Ok::<_, String>(())
+                }
+            })
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        tx.send(PathBuf::from("api.py")).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(tx);
+        loop_handle.await.unwrap();
+
+        assert_eq!(builds.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn change_cancels_in_flight_rebuild() {
+        let (tx, rx) = unbounded_channel();
+        let started = Arc::new(AtomicUsize::new(0));
+        let finished = Arc::new(AtomicUsize::new(0));
+
+        let loop_handle = tokio::spawn({
+            let started = started.clone();
+            let finished = finished.clone();
+            watch_loop(rx, Duration::from_millis(10), move || {
+                let started = started.clone();
+                let finished = finished.clone();
+                async move {
+                    // The first build hangs until it is cancelled.
+                    if started.fetch_add(1, Ordering::SeqCst) == 0 {
+                        tokio::time::sleep(Duration::from_secs(60)).await;
+                    }
+                    finished.fetch_add(1, Ordering::SeqCst);
+                    Ok::<_, String>(())
+                }
+            })
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        tx.send(PathBuf::from("api.py")).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(tx);
+        loop_handle.await.unwrap();
+
+        assert_eq!(started.load(Ordering::SeqCst), 2);
+        assert_eq!(finished.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn change_during_a_finishing_build_triggers_rebuild() {
+        let (tx, rx) = unbounded_channel();
+        let started = Arc::new(AtomicUsize::new(0));
+
+        let loop_handle = tokio::spawn({
+            let started = started.clone();
+            // The quiet period outlasts the first build, so the change is
+            // still settling when it finishes.
+            watch_loop(rx, Duration::from_millis(500), move || {
+                let started = started.clone();
+                async move {
+                    if started.fetch_add(1, Ordering::SeqCst) == 0 {
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                    }
+                    Ok::<_, String>(())
+                }
+            })
+        });
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        tx.send(PathBuf::from("api.py")).unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert_eq!(started.load(Ordering::SeqCst), 2);
+
+        drop(tx);
+        loop_handle.await.unwrap();
+    }
+}
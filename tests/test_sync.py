@@ -0,0 +1,168 @@
+import asyncio
+import os
+import subprocess
+import sys
+from pathlib import Path
+
+import pytest
+
+from py2binmod.core import (
+    CompilerError,
+    ParserError,
+    Py2BinmodError,
+    build_command_sync,
+    configure_runtime,
+    doctor_command_sync,
+    init_command_sync,
+    transpile_command,
+    transpile_command_sync,
+)
+
+
+def make_project(root: Path) -> Path:
+    (root / "pyproject.toml").write_text(
+        '[project]\nname = "my-package"\nversion = "0.1.0"\n'
+    )
+    (root / "my_package").mkdir()
+    (root / "my_package" / "__init__.py").write_text(
+        "@mod_fn\ndef greet(name: str) -> str:\n    return name\n"
+    )
+    (root / "venv" / "lib" / "python3.11" / "site-packages").mkdir(parents=True)
+    return root
+
+
+def test_transpile_command_sync(tmp_path: Path) -> None:
+    project = make_project(tmp_path / "project")
+    out_dir = tmp_path / "out"
+
+    assert transpile_command_sync(str(project), out_dir=str(out_dir)) is None
+    assert (out_dir / "src" / "lib.rs").is_file()
+
+
+def test_sync_matches_async(tmp_path: Path) -> None:
+    project = make_project(tmp_path / "project")
+
+    transpile_command_sync(str(project), out_dir=str(tmp_path / "sync"))
+    asyncio.run(transpile_command(str(project), out_dir=str(tmp_path / "async")))
+
+    assert (tmp_path / "sync" / "src" / "lib.rs").read_text() == (
+        tmp_path / "async" / "src" / "lib.rs"
+    ).read_text()
+
+
+def test_sync_inside_running_loop(tmp_path: Path) -> None:
+    project = make_project(tmp_path / "project")
+
+    async def main() -> None:
+        transpile_command_sync(str(project), out_dir=str(tmp_path / "out"))
+
+    asyncio.run(main())
+
+    assert (tmp_path / "out" / "src" / "lib.rs").is_file()
+
+
+def test_init_command_sync(tmp_path: Path) -> None:
+    project = make_project(tmp_path)
+
+    init_command_sync(str(project))
+
+    assert "[tool.py2binmod]" in (project / "pyproject.toml").read_text()
+
+
+def test_doctor_command_sync_reports_failures(tmp_path: Path) -> None:
+    failures = doctor_command_sync(str(tmp_path))
+
+    assert "metadata" in [name for name, _ in failures]
+
+
+def test_sync_errors_raise(tmp_path: Path) -> None:
+    with pytest.raises(ValueError):
+        transpile_command_sync(str(tmp_path / "missing"), out_dir=str(tmp_path / "out"))
+
+
+def test_parser_errors_carry_a_code(tmp_path: Path) -> None:
+    with pytest.raises(ParserError) as missing_dir:
+        transpile_command_sync(str(tmp_path / "missing"))
+    assert missing_dir.value.code == "parser.invalid_project_dir"
+
+    project = make_project(tmp_path / "project")
+    with pytest.raises(ParserError) as bad_ignore:
+        transpile_command_sync(str(project), ignore=["[unclosed"])
+    assert bad_ignore.value.code == "parser.invalid_ignore_pattern"
+
+    with pytest.raises(ParserError) as bad_include:
+        transpile_command_sync(str(project), include=["[unclosed"])
+    assert bad_include.value.code == "parser.invalid_include_pattern"
+
+    (project / "my_package" / "__init__.py").write_text("def helper() -> None:\n    pass\n")
+    with pytest.raises(ParserError) as no_exports:
+        transpile_command_sync(str(project))
+    assert no_exports.value.code == "parser.no_exported_functions"
+
+    (project / "pyproject.toml").unlink()
+    with pytest.raises(ParserError) as no_metadata:
+        transpile_command_sync(str(project))
+    assert no_metadata.value.code == "parser.missing_project_metadata"
+
+
+def test_compiler_errors_carry_a_code(tmp_path: Path) -> None:
+    project = make_project(tmp_path / "project")
+
+    with pytest.raises(CompilerError) as invalid_jobs:
+        build_command_sync(str(project), jobs=0)
+    assert invalid_jobs.value.code == "compiler.invalid_jobs"
+    assert isinstance(invalid_jobs.value, RuntimeError)
+
+
+def run_python(code: str, **env: str) -> subprocess.CompletedProcess[str]:
+    # The runtime can only be configured before its first use, so each check
+    # runs in a fresh interpreter.
+    return subprocess.run(
+        [sys.executable, "-c", code],
+        capture_output=True,
+        text=True,
+        env={**os.environ, **env},
+    )
+
+
+def test_commands_run_after_configuring_threads(tmp_path: Path) -> None:
+    project = make_project(tmp_path / "project")
+    out_dir = tmp_path / "out"
+
+    result = run_python(
+        "import asyncio\n"
+        "from py2binmod.core import configure_runtime, transpile_command, transpile_command_sync\n"
+        "configure_runtime(2)\n"
+        f"transpile_command_sync({str(project)!r}, out_dir={str(out_dir / 'sync')!r})\n"
+        f"asyncio.run(transpile_command({str(project)!r}, out_dir={str(out_dir / 'async')!r}))\n"
+    )
+
+    assert result.returncode == 0, result.stderr
+    assert (out_dir / "sync" / "src" / "lib.rs").is_file()
+    assert (out_dir / "async" / "src" / "lib.rs").is_file()
+
+
+def test_worker_threads_from_env(tmp_path: Path) -> None:
+    project = make_project(tmp_path / "project")
+
+    result = run_python(
+        "from py2binmod.core import transpile_command_sync\n"
+        f"transpile_command_sync({str(project)!r}, out_dir={str(tmp_path / 'out')!r})\n",
+        PY2BINMOD_WORKER_THREADS="1",
+    )
+
+    assert result.returncode == 0, result.stderr
+    assert (tmp_path / "out" / "src" / "lib.rs").is_file()
+
+    invalid = run_python("import py2binmod.core", PY2BINMOD_WORKER_THREADS="many")
+    assert "PY2BINMOD_WORKER_THREADS" in invalid.stderr
+
+
+def test_configure_runtime_after_start_raises(tmp_path: Path) -> None:
+    transpile_command_sync(str(make_project(tmp_path / "project")), out_dir=str(tmp_path / "out"))
+
+    with pytest.raises(Py2BinmodError):
+        configure_runtime(2)
+
+    with pytest.raises(ValueError):
+        configure_runtime(0)